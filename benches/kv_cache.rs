@@ -0,0 +1,81 @@
+//! Decode-time cost with and without the KV cache
+//!
+//! "full_recompute" reruns the transformer over the whole context for every
+//! new token (the pre-cache path); "kv_cache" prefills once and then does one
+//! cached step per token.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use markovian_thinker::{EmbeddingLayer, KvCache, ModelConfig, Transformer};
+
+fn bench_config() -> ModelConfig {
+    ModelConfig {
+        vocab_size: 2048,
+        embed_dim: 128,
+        num_heads: 4,
+        num_kv_heads: 4,
+        head_dim: 32,
+        num_layers: 4,
+        intermediate_size: 384,
+        max_seq_len: 512,
+        ..ModelConfig::default()
+    }
+}
+
+fn argmax(logits: &[f32]) -> usize {
+    logits
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(b.1))
+        .map(|(i, _)| i)
+        .unwrap()
+}
+
+fn bench_decode(c: &mut Criterion) {
+    let config = bench_config();
+    let model = Transformer::random(&config, 0).unwrap();
+    let embedding = EmbeddingLayer::new(config.vocab_size, config.embed_dim);
+    let prompt: Vec<usize> = (0..32).map(|i| (i * 37) % config.vocab_size).collect();
+
+    let mut group = c.benchmark_group("decode");
+    group.sample_size(10);
+
+    for &new_tokens in &[16usize, 64] {
+        group.bench_with_input(
+            BenchmarkId::new("full_recompute", new_tokens),
+            &new_tokens,
+            |b, &n| {
+                b.iter(|| {
+                    let mut tokens = prompt.clone();
+                    for _ in 0..n {
+                        let logits = model.forward(&embedding, &tokens).unwrap();
+                        tokens.push(argmax(&logits));
+                    }
+                    black_box(tokens)
+                });
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("kv_cache", new_tokens),
+            &new_tokens,
+            |b, &n| {
+                b.iter(|| {
+                    let mut cache = KvCache::new(&config, config.max_seq_len);
+                    let mut tokens = prompt.clone();
+                    let mut logits = model.prefill(&embedding, &tokens, &mut cache).unwrap();
+                    for _ in 0..n {
+                        let next = argmax(&logits);
+                        tokens.push(next);
+                        logits = model.forward_cached(&embedding, &[next], &mut cache).unwrap();
+                    }
+                    black_box(tokens)
+                });
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_decode);
+criterion_main!(benches);
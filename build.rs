@@ -1,7 +1,10 @@
 // build.rs - Compile CUDA kernels to PTX
 
+#[cfg(feature = "gpu")]
 use std::env;
+#[cfg(feature = "gpu")]
 use std::path::PathBuf;
+#[cfg(feature = "gpu")]
 use std::process::Command;
 
 fn main() {
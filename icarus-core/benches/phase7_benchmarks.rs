@@ -3,11 +3,11 @@
 // expert-guided prompts, and attention compression
 
 use criterion::{black_box, criterion_group, criterion_main, Criterion, BenchmarkId};
-use markovian_thinker::*;
+use icarus_core::markovian::*;
 
 // Benchmark event emission overhead
 fn bench_event_emission(c: &mut Criterion) {
-    use markovian_thinker::chunk_manager::ChunkManager;
+    use icarus_core::markovian::chunk_manager::ChunkManager;
     
     let mut config = StateConfig::default();
     config.enable_event_driven = true;
@@ -55,7 +55,7 @@ fn bench_intelligent_carryover(c: &mut Criterion) {
 
 // Benchmark expert selection
 fn bench_expert_selection(c: &mut Criterion) {
-    use markovian_thinker::experts::{ExpertGating, ExpertConfig};
+    use icarus_core::markovian::experts::{ExpertGating, ExpertConfig};
     
     let config = ExpertConfig::default();
     let gating = ExpertGating::new(config);
@@ -78,7 +78,7 @@ fn bench_expert_selection(c: &mut Criterion) {
 
 // Benchmark attention compression
 fn bench_attention_compression(c: &mut Criterion) {
-    use markovian_thinker::attention::{SlidingWindowAttention, AttentionConfig};
+    use icarus_core::markovian::attention::{SlidingWindowAttention, AttentionConfig};
     
     let config = AttentionConfig::default();
     let attention = SlidingWindowAttention::new(config);
@@ -97,8 +97,8 @@ fn bench_attention_compression(c: &mut Criterion) {
 
 // Benchmark causal trace recording
 fn bench_causal_trace(c: &mut Criterion) {
-    use markovian_thinker::causal_trace::CausalTrace;
-    use markovian_thinker::events::{ReasoningEvent, ReasoningLevel};
+    use icarus_core::markovian::causal_trace::CausalTrace;
+    use icarus_core::markovian::events::{ReasoningEvent, ReasoningLevel};
     
     let session_id = uuid::Uuid::new_v4();
     let mut trace = CausalTrace::new(session_id);
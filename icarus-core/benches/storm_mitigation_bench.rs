@@ -2,7 +2,7 @@
 // Measures overhead and throughput of Phase 6 features
 
 use criterion::{black_box, criterion_group, criterion_main, Criterion, BenchmarkId};
-use markovian_thinker::*;
+use icarus_core::markovian::*;
 
 fn benchmark_rate_limiter(c: &mut Criterion) {
     let mut group = c.benchmark_group("rate_limiter");
@@ -39,7 +39,8 @@ fn benchmark_circuit_breaker(c: &mut Criterion) {
                 let breaker = CircuitBreaker::new(CircuitBreakerConfig {
                     failure_threshold: thresh,
                     success_threshold: 2,
-                    timeout: std::time::Duration::from_secs(30),
+                    recovery_timeout: std::time::Duration::from_secs(30),
+                    ..Default::default()
                 });
 
                 b.iter(|| {
@@ -172,7 +173,7 @@ fn benchmark_concept_crystallization(c: &mut Criterion) {
                         "cubic-8" => LatticeType::Hypercubic(8),
                         _ => LatticeType::E8,
                     },
-                    enable_crystallization: true,
+                    max_concepts: 10000,
                     similarity_threshold: 0.9,
                 };
 
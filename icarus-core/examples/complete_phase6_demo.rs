@@ -5,7 +5,7 @@
 // 3. Concept space querying
 // 4. Real-time metrics monitoring
 
-use markovian_thinker::{
+use icarus_core::markovian::{
     concept_space::{Concept, ConceptSpace, ConceptSpaceConfig},
     events::{ReasoningEvent, ReasoningLevel},
     lattice::LatticeType,
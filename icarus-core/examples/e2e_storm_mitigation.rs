@@ -1,7 +1,7 @@
 // End-to-End Integration Test: Storm Mitigation in Reasoning Loop
 // Tests the full Phase 6 system with storm mitigation protecting a multi-chunk session
 
-use markovian_thinker::*;
+use icarus_core::markovian::*;
 
 fn main() {
     println!("=== End-to-End Storm Mitigation Integration Test ===\n");
@@ -1,7 +1,7 @@
 // Markovian Thinker: Full Integration Example
 // Demonstrates GPT-OSS + Icarus TIC features working together
 
-use markovian_thinker::*;
+use icarus_core::markovian::*;
 
 fn main() {
     println!("=== Markovian Thinker: Full Integration Demo ===\n");
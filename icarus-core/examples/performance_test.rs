@@ -1,7 +1,7 @@
 // Simple Performance Test for Storm Mitigation
 // Measures overhead without external dependencies
 
-use markovian_thinker::*;
+use icarus_core::markovian::*;
 use std::time::Instant;
 
 fn main() {
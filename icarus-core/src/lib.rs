@@ -25,6 +25,7 @@ pub mod mcp;
 pub mod vulkan_renderer;
 pub mod tic;  // TIC (Topological Information Crystallography) substrate
 pub mod learning;  // Knowledge distillation & skill acquisition
+pub mod markovian;  // Delethink chunk-based reasoning (state, chunk loop, sessions)
 
 // Re-export core types
 pub use config::IcarusConfig;
@@ -37,6 +38,10 @@ pub use mcp::IcarusMCPServer;
 pub use vulkan_renderer::{VulkanRenderer, CognitiveVisualization};
 pub use tic::TICSubstrate;
 pub use learning::{Skill, SkillLibrary, SkillDomain, Interaction, StrategyExtractor};
+pub use markovian::{
+    ChunkGenerator, ChunkManager, MarkovianState, ReasoningTrace, SessionManager, StateConfig,
+    StormMitigation, TerminationReason,
+};
 
 use anyhow::Result;
 use std::sync::Arc;
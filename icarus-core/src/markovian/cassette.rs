@@ -0,0 +1,315 @@
+// Markovian Thinker: Record/Replay Chunk Generators
+// Cassettes pin every (prompt, max_tokens) → (output, tokens) exchange of a
+// chunk loop to JSONL so the loop can be replayed deterministically without
+// a model. A replayed prompt that differs from the recording is an error,
+// which makes changes to carryover extraction or prompt building visible.
+
+use crate::markovian::chunk_manager::ChunkGenerator;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// One recorded chunk exchange (one line of a cassette)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CassetteEntry {
+    /// Position in the recording (0-indexed)
+    pub index: usize,
+    /// Model that produced the output
+    pub model: String,
+    pub prompt: String,
+    pub max_tokens: usize,
+    pub output: String,
+    pub tokens: usize,
+}
+
+/// Load all entries from a JSONL cassette
+pub fn load_cassette<P: AsRef<Path>>(path: P) -> Result<Vec<CassetteEntry>> {
+    let path = path.as_ref();
+    let file = File::open(path)
+        .with_context(|| format!("Failed to open cassette {}", path.display()))?;
+
+    let mut entries = Vec::new();
+    for (line_no, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: CassetteEntry = serde_json::from_str(&line).with_context(|| {
+            format!("Invalid cassette entry at {}:{}", path.display(), line_no + 1)
+        })?;
+        entries.push(entry);
+    }
+
+    Ok(entries)
+}
+
+/// Wraps a generator and appends every exchange to a cassette
+pub struct RecordingGenerator<G: ChunkGenerator> {
+    inner: G,
+    path: PathBuf,
+    file: Mutex<File>,
+    recorded: Mutex<usize>,
+}
+
+impl<G: ChunkGenerator> RecordingGenerator<G> {
+    /// Start a new recording at `path` (truncates an existing cassette)
+    pub fn new<P: AsRef<Path>>(inner: G, path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&path)
+            .with_context(|| format!("Failed to create cassette {}", path.display()))?;
+
+        Ok(Self {
+            inner,
+            path,
+            file: Mutex::new(file),
+            recorded: Mutex::new(0),
+        })
+    }
+
+    /// Cassette being written
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Number of exchanges recorded so far
+    pub fn recorded(&self) -> usize {
+        *self.recorded.lock().unwrap()
+    }
+
+    /// Unwrap the inner generator
+    pub fn into_inner(self) -> G {
+        self.inner
+    }
+}
+
+#[async_trait::async_trait]
+impl<G: ChunkGenerator + Send + Sync> ChunkGenerator for RecordingGenerator<G> {
+    async fn generate(&self, prompt: &str, max_tokens: usize) -> Result<(String, usize)> {
+        let (output, tokens) = self.inner.generate(prompt, max_tokens).await?;
+
+        let mut recorded = self.recorded.lock().unwrap();
+        let entry = CassetteEntry {
+            index: *recorded,
+            model: self.inner.model_name().to_string(),
+            prompt: prompt.to_string(),
+            max_tokens,
+            output: output.clone(),
+            tokens,
+        };
+
+        // Written line by line so an interrupted run still leaves a usable prefix
+        let mut file = self.file.lock().unwrap();
+        writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+        file.flush()?;
+        *recorded += 1;
+
+        Ok((output, tokens))
+    }
+
+    fn model_name(&self) -> &str {
+        self.inner.model_name()
+    }
+}
+
+/// Serves recorded exchanges back in order, rejecting divergent prompts
+pub struct ReplayGenerator {
+    entries: Vec<CassetteEntry>,
+    cursor: Mutex<usize>,
+    model_name: String,
+}
+
+impl ReplayGenerator {
+    /// Replay a cassette file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(Self::from_entries(load_cassette(path)?))
+    }
+
+    /// Replay in-memory entries
+    pub fn from_entries(entries: Vec<CassetteEntry>) -> Self {
+        let model_name = entries
+            .first()
+            .map(|e| e.model.clone())
+            .unwrap_or_else(|| "replay".to_string());
+
+        Self {
+            entries,
+            cursor: Mutex::new(0),
+            model_name,
+        }
+    }
+
+    /// Exchanges not yet served
+    pub fn remaining(&self) -> usize {
+        self.entries.len() - *self.cursor.lock().unwrap()
+    }
+
+    /// True once every recorded exchange has been served
+    pub fn is_exhausted(&self) -> bool {
+        self.remaining() == 0
+    }
+
+    /// Describe where `actual` first departs from `expected`
+    fn describe_divergence(expected: &str, actual: &str) -> String {
+        let offset = expected
+            .char_indices()
+            .zip(actual.chars())
+            .find(|((_, e), a)| e != a)
+            .map(|((i, _), _)| i)
+            .unwrap_or_else(|| expected.len().min(actual.len()));
+
+        let snippet = |s: &str| -> String { s[offset..].chars().take(40).collect() };
+        format!(
+            "first difference at byte {}: expected {:?}, got {:?}",
+            offset,
+            snippet(expected),
+            snippet(actual)
+        )
+    }
+}
+
+#[async_trait::async_trait]
+impl ChunkGenerator for ReplayGenerator {
+    async fn generate(&self, prompt: &str, max_tokens: usize) -> Result<(String, usize)> {
+        let mut cursor = self.cursor.lock().unwrap();
+        let entry = self.entries.get(*cursor).ok_or_else(|| {
+            anyhow::anyhow!(
+                "Cassette exhausted: chunk {} requested but only {} recorded",
+                *cursor + 1,
+                self.entries.len()
+            )
+        })?;
+
+        if entry.prompt != prompt {
+            anyhow::bail!(
+                "Prompt for chunk {} diverges from cassette ({})",
+                entry.index + 1,
+                Self::describe_divergence(&entry.prompt, prompt)
+            );
+        }
+
+        if entry.max_tokens != max_tokens {
+            anyhow::bail!(
+                "max_tokens for chunk {} diverges from cassette: expected {}, got {}",
+                entry.index + 1,
+                entry.max_tokens,
+                max_tokens
+            );
+        }
+
+        *cursor += 1;
+        Ok((entry.output.clone(), entry.tokens))
+    }
+
+    fn model_name(&self) -> &str {
+        &self.model_name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::markovian::chunk_manager::ChunkManager;
+    use crate::markovian::state::StateConfig;
+    use crate::markovian::trace::TerminationReason;
+
+    struct ScriptedGenerator {
+        outputs: Vec<(&'static str, usize)>,
+        index: Mutex<usize>,
+    }
+
+    #[async_trait::async_trait]
+    impl ChunkGenerator for ScriptedGenerator {
+        async fn generate(&self, _prompt: &str, _max_tokens: usize) -> Result<(String, usize)> {
+            let mut idx = self.index.lock().unwrap();
+            let (text, tokens) = self.outputs[*idx];
+            *idx += 1;
+            Ok((text.to_string(), tokens))
+        }
+
+        fn model_name(&self) -> &str {
+            "scripted"
+        }
+    }
+
+    fn scripted() -> ScriptedGenerator {
+        ScriptedGenerator {
+            outputs: vec![
+                ("First we note that 6 groups of 7 are needed.", 30),
+                ("Adding 7 six times gives 42. #### 42", 20),
+            ],
+            index: Mutex::new(0),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_record_then_replay_reproduces_trace() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("run.jsonl");
+        let config = StateConfig::new(100, 50, 5).unwrap();
+
+        let recorder = RecordingGenerator::new(scripted(), &path).unwrap();
+        let recorded = ChunkManager::new(config.clone())
+            .generate_trace("What is 6*7?".to_string(), &recorder)
+            .await
+            .unwrap();
+        assert_eq!(recorder.recorded(), 2);
+
+        let replay = ReplayGenerator::load(&path).unwrap();
+        assert_eq!(replay.model_name(), "scripted");
+        let replayed = ChunkManager::new(config)
+            .generate_trace("What is 6*7?".to_string(), &replay)
+            .await
+            .unwrap();
+
+        assert!(replay.is_exhausted());
+        assert_eq!(replayed.solution, recorded.solution);
+        assert_eq!(replayed.termination_reason, TerminationReason::SolutionFound);
+        let prompts = |t: &crate::markovian::trace::ReasoningTrace| {
+            t.chunks.iter().map(|c| c.prompt.clone()).collect::<Vec<_>>()
+        };
+        assert_eq!(prompts(&replayed), prompts(&recorded));
+    }
+
+    #[tokio::test]
+    async fn test_replay_rejects_divergent_prompt() {
+        let replay = ReplayGenerator::from_entries(vec![CassetteEntry {
+            index: 0,
+            model: "m".to_string(),
+            prompt: "Problem: 2+2\nCarryover: none".to_string(),
+            max_tokens: 10,
+            output: "4".to_string(),
+            tokens: 1,
+        }]);
+
+        let err = replay
+            .generate("Problem: 2+2\nCarryover: some", 10)
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("diverges"));
+        assert!(err.contains("byte 24"));
+
+        // A divergent request doesn't consume the entry
+        assert_eq!(replay.remaining(), 1);
+        assert!(replay.generate("Problem: 2+2\nCarryover: none", 11).await.is_err());
+        assert!(replay.generate("Problem: 2+2\nCarryover: none", 10).await.is_ok());
+        assert!(replay.generate("anything", 10).await.is_err());
+    }
+
+    #[test]
+    fn test_load_cassette_reports_bad_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bad.jsonl");
+        std::fs::write(&path, "{\"index\":0}\n").unwrap();
+
+        let err = load_cassette(&path).unwrap_err().to_string();
+        assert!(err.contains("bad.jsonl:1"));
+    }
+}
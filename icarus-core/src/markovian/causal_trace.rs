@@ -1,7 +1,7 @@
 // Markovian Thinker: Causal Set Trace Management
 // Partially ordered set of reasoning events inspired by Icarus TIC and causal set theory
 
-use crate::events::{ReasoningEvent, ReasoningLevel};
+use crate::markovian::events::{ReasoningEvent, ReasoningLevel};
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet, VecDeque};
 use uuid::Uuid;
@@ -1,11 +1,11 @@
 // Markovian Thinker: Chunk Manager
 // Orchestrates chunk-based reasoning with bounded context
 
-use crate::causal_trace::CausalTrace;
-use crate::event_queue::EventQueue;
-use crate::events::{EventWithMetadata, ReasoningEvent, ReasoningLevel};
-use crate::state::{ChunkHistory, ChunkRecord, MarkovianState, StateConfig};
-use crate::trace::{ReasoningTrace, TerminationReason};
+use crate::markovian::causal_trace::CausalTrace;
+use crate::markovian::event_queue::EventQueue;
+use crate::markovian::events::{EventWithMetadata, ReasoningEvent, ReasoningLevel};
+use crate::markovian::state::{ChunkHistory, ChunkRecord, MarkovianState, StateConfig};
+use crate::markovian::trace::{ReasoningTrace, TerminationReason};
 use anyhow::Result;
 use std::time::Instant;
 use uuid::Uuid;
@@ -94,8 +94,8 @@ impl ChunkManager {
             self.config.carryover_size
         );
 
-        // Main generation loop
-        while state.should_continue() {
+        // Main generation loop; MarkovianState::update decides when to stop
+        loop {
             let chunk_start = Instant::now();
 
             // Build prompt for this chunk
@@ -188,38 +188,25 @@ impl ChunkManager {
 
             // Update state for next iteration
             match state.update(&output, tokens) {
+                Ok(info) if info.should_terminate => {
+                    // Termination condition reached
+                    tracing::info!("Termination: {:?} | {}", info.reason, state.summary());
+
+                    let solution = info.solution.or_else(|| Self::extract_solution(&output));
+                    trace.complete(solution, info.reason);
+                    return Ok(trace);
+                }
                 Ok(_) => {
                     // Continue to next chunk
                     continue;
                 }
                 Err(reason) => {
-                    // Termination condition reached
-                    tracing::info!("Termination: {}", reason);
-
-                    let termination = if reason.contains("budget") {
-                        TerminationReason::TokenBudgetExceeded
-                    } else if reason.contains("iterations") {
-                        TerminationReason::MaxIterations
-                    } else {
-                        TerminationReason::Error(reason)
-                    };
-
-                    let solution = Self::extract_solution(&output);
-                    trace.complete(solution, termination);
+                    tracing::error!("State update failed: {}", reason);
+                    trace.complete(None, TerminationReason::Error(reason));
                     return Ok(trace);
                 }
             }
         }
-
-        // Should not reach here, but handle gracefully
-        let solution = if let Some(last_chunk) = history.chunks().back() {
-            Self::extract_solution(&last_chunk.output)
-        } else {
-            None
-        };
-
-        trace.complete(solution, TerminationReason::MaxIterations);
-        Ok(trace)
     }
 
     /// Check if output contains termination markers
@@ -432,6 +419,44 @@ mod tests {
         assert_eq!(trace.solution, Some("123".to_string()));
     }
 
+    #[tokio::test]
+    async fn test_chunk_manager_runs_all_iterations() {
+        let config = StateConfig::new(100, 50, 3).unwrap();
+        let mut manager = ChunkManager::new(config);
+
+        let generator = MockGenerator::new(vec![
+            ("Still working on it".to_string(), 10),
+        ]);
+
+        let trace = manager
+            .generate_trace("Problem".to_string(), &generator)
+            .await
+            .unwrap();
+
+        // The final iteration's chunk is generated before stopping
+        assert_eq!(trace.chunks.len(), 3);
+        assert_eq!(trace.termination_reason, TerminationReason::MaxIterations);
+    }
+
+    #[tokio::test]
+    async fn test_chunk_manager_token_budget() {
+        // Budget: 100 + 4 * (100 - 50) = 300 tokens
+        let config = StateConfig::new(100, 50, 5).unwrap();
+        let mut manager = ChunkManager::new(config);
+
+        let generator = MockGenerator::new(vec![
+            ("Long chunk".to_string(), 160),
+        ]);
+
+        let trace = manager
+            .generate_trace("Problem".to_string(), &generator)
+            .await
+            .unwrap();
+
+        assert_eq!(trace.chunks.len(), 2);
+        assert_eq!(trace.termination_reason, TerminationReason::TokenBudgetExceeded);
+    }
+
     #[tokio::test]
     async fn test_termination_markers() {
         assert!(ChunkManager::has_termination_marker("Answer: [EOS]"));
@@ -1,7 +1,7 @@
 // Markovian Thinker: Concept Space with Crystallographic Lattices
 // High-level API for concept representation, similarity, and composition
 
-use crate::lattice::{
+use crate::markovian::lattice::{
     create_generator, LatticeGenerator, LatticePoint, LatticeType,
 };
 use serde::{Deserialize, Serialize};
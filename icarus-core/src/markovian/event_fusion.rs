@@ -1,7 +1,7 @@
 // Markovian Thinker: Event Fusion for Storm Mitigation
 // Deduplicates and merges similar pending events
 
-use crate::events::{EventWithMetadata, ReasoningEvent};
+use crate::markovian::events::{EventWithMetadata, ReasoningEvent};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -253,7 +253,7 @@ pub struct FusionStats {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::events::{CognitiveTimestamp, ReasoningLevel};
+    use crate::markovian::events::{CognitiveTimestamp, ReasoningLevel};
     use uuid::Uuid;
 
     fn make_chunk_request(_session_id: &str, prompt: &str, priority: f32) -> EventWithMetadata {
@@ -1,7 +1,7 @@
 // Markovian Thinker: Priority Event Queue with Momentum
 // Lock-free concurrent queue inspired by Icarus TIC
 
-use crate::events::{EventWithMetadata, ReasoningEvent};
+use crate::markovian::events::{EventWithMetadata, ReasoningEvent};
 use std::cmp::Ordering;
 use std::collections::BinaryHeap;
 use std::sync::{Arc, Mutex};
@@ -300,7 +300,7 @@ impl Default for SessionEventQueue {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::events::{ReasoningEvent, ReasoningLevel};
+    use crate::markovian::events::{ReasoningEvent, ReasoningLevel};
 
     #[test]
     fn test_event_queue_basic() {
@@ -342,9 +342,9 @@ mod tests {
         // Insert high priority event
         let high_pri = ReasoningEvent::TerminationCheck {
             session_id,
-            current_state: Box::new(crate::state::MarkovianState::new(
+            current_state: Box::new(crate::markovian::state::MarkovianState::new(
                 "Test".to_string(),
-                crate::state::StateConfig::default(),
+                crate::markovian::state::StateConfig::default(),
             )),
             timestamp: 1001,
         };
@@ -389,9 +389,9 @@ mod tests {
         // High-priority should still succeed
         let high_pri = ReasoningEvent::TerminationCheck {
             session_id,
-            current_state: Box::new(crate::state::MarkovianState::new(
+            current_state: Box::new(crate::markovian::state::MarkovianState::new(
                 "Test".to_string(),
-                crate::state::StateConfig::default(),
+                crate::markovian::state::StateConfig::default(),
             )),
             timestamp: 1003,
         };
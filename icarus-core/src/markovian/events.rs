@@ -1,8 +1,8 @@
 // Markovian Thinker: Event-Driven Reasoning System
 // Inspired by Icarus TIC's event-driven architecture
 
-use crate::state::MarkovianState;
-use crate::types::VerificationResult;
+use crate::markovian::state::MarkovianState;
+use crate::markovian::types::VerificationResult;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
@@ -316,7 +316,7 @@ mod tests {
             session_id,
             current_state: Box::new(MarkovianState::new(
                 "Test".to_string(),
-                crate::state::StateConfig::default(),
+                crate::markovian::state::StateConfig::default(),
             )),
             timestamp: 1001,
         };
@@ -261,7 +261,7 @@ impl ReasoningExpert for CodeExpert {
         if text.len() <= approx_chars {
             text.to_string()
         } else {
-            let start_pos = text.len() - approx_chars;
+            let start_pos = text.ceil_char_boundary(text.len() - approx_chars);
             text[start_pos..].trim().to_string()
         }
     }
@@ -332,7 +332,7 @@ impl ReasoningExpert for TextExpert {
         }
 
         // Find paragraph or sentence boundary
-        let start_pos = text.len() - approx_chars;
+        let start_pos = text.ceil_char_boundary(text.len() - approx_chars);
         let search_slice = &text[start_pos..];
 
         // Look for paragraph break
@@ -472,7 +472,7 @@ impl ExpertGating {
         if text.len() <= approx_chars {
             text.to_string()
         } else {
-            let start_pos = text.len() - approx_chars;
+            let start_pos = text.ceil_char_boundary(text.len() - approx_chars);
             text[start_pos..].trim().to_string()
         }
     }
@@ -0,0 +1,138 @@
+// Markovian Thinker: Local Inference Chunk Generator
+// Runs the chunk loop fully offline on markovian_thinker's InferenceModel
+
+use crate::markovian::chunk_manager::ChunkGenerator;
+use crate::markovian::state::StateConfig;
+use anyhow::Result;
+use markovian_thinker::{GenerationConfig, InferenceModel};
+use std::sync::Arc;
+
+/// ChunkGenerator over the in-crate InferenceModel
+pub struct InferenceModelGenerator {
+    model: Arc<InferenceModel>,
+    /// Per-chunk token cap (StateConfig::chunk_size)
+    chunk_size: usize,
+    /// Sampling controls; max_new_tokens is set per chunk
+    generation: GenerationConfig,
+    model_name: String,
+}
+
+impl InferenceModelGenerator {
+    /// Create a generator capped at `config.chunk_size` tokens per chunk
+    pub fn new(model: Arc<InferenceModel>, config: &StateConfig) -> Self {
+        Self {
+            model,
+            chunk_size: config.chunk_size,
+            generation: GenerationConfig::default(),
+            model_name: "markovian-thinker-local".to_string(),
+        }
+    }
+
+    /// Override the name reported in traces
+    pub fn with_model_name(mut self, name: impl Into<String>) -> Self {
+        self.model_name = name.into();
+        self
+    }
+
+    /// Sample chunks with these controls instead of greedy decoding
+    pub fn with_generation_config(mut self, generation: GenerationConfig) -> Self {
+        self.generation = generation;
+        self
+    }
+
+    /// Underlying model
+    pub fn model(&self) -> &Arc<InferenceModel> {
+        &self.model
+    }
+}
+
+#[async_trait::async_trait]
+impl ChunkGenerator for InferenceModelGenerator {
+    async fn generate(&self, prompt: &str, max_tokens: usize) -> Result<(String, usize)> {
+        let generation = GenerationConfig {
+            max_new_tokens: max_tokens.min(self.chunk_size),
+            ..self.generation.clone()
+        };
+        // Forward passes are CPU-bound; keep them off the async workers
+        let (model, prompt) = (self.model.clone(), prompt.to_string());
+        let output = tokio::task::spawn_blocking(move || {
+            tokio::runtime::Handle::current().block_on(model.generate_with_config(&prompt, &generation))
+        })
+        .await??;
+        let tokens = self.model.tokenizer().count_tokens(&output.text);
+
+        Ok((output.text, tokens))
+    }
+
+    fn model_name(&self) -> &str {
+        &self.model_name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::markovian::chunk_manager::ChunkManager;
+    use crate::markovian::trace::TerminationReason;
+    use markovian_thinker::ModelConfig;
+
+    fn small_model() -> Arc<InferenceModel> {
+        // Full tokenizer vocabulary over a tiny transformer
+        let config = ModelConfig {
+            embed_dim: 16,
+            num_heads: 2,
+            num_kv_heads: 2,
+            head_dim: 8,
+            num_layers: 1,
+            intermediate_size: 32,
+            ..ModelConfig::default()
+        };
+        Arc::new(InferenceModel::new(config, ()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_chunk_size_caps_generation() {
+        let config = StateConfig::new(4, 2, 3).unwrap();
+        let generator = InferenceModelGenerator::new(small_model(), &config);
+
+        let (text, tokens) = generator.generate("Hello", 100).await.unwrap();
+        assert!(!text.is_empty());
+        assert!(tokens <= 4);
+        assert_eq!(tokens, generator.model().tokenizer().count_tokens(&text));
+    }
+
+    #[tokio::test]
+    async fn test_seeded_generation_config_is_reproducible() {
+        let config = StateConfig::new(6, 2, 3).unwrap();
+        let model = small_model();
+        let generator = |seed| {
+            InferenceModelGenerator::new(model.clone(), &config)
+                .with_generation_config(GenerationConfig::sampled(0, 1.0, seed))
+        };
+
+        let first = generator(9).generate("Think step by step", 6).await.unwrap();
+        assert_eq!(first, generator(9).generate("Think step by step", 6).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_runs_chunk_loop_offline() {
+        let config = StateConfig::new(8, 4, 3).unwrap();
+        let generator =
+            InferenceModelGenerator::new(small_model(), &config).with_model_name("tiny");
+        let mut manager = ChunkManager::new(config);
+
+        let trace = manager
+            .generate_trace("Count upwards".to_string(), &generator)
+            .await
+            .unwrap();
+
+        assert!(trace.is_complete());
+        assert_eq!(trace.metadata.model, "tiny");
+        assert!(!trace.chunks.is_empty());
+        assert!(!matches!(trace.termination_reason, TerminationReason::Error(_)));
+        assert_eq!(
+            trace.total_tokens,
+            trace.chunks.iter().map(|c| c.tokens).sum::<usize>()
+        );
+    }
+}
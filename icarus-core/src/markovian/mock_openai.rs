@@ -0,0 +1,245 @@
+// Markovian Thinker: Mock OpenAI Server
+// In-process stub of the OpenAI HTTP API so the HTTP chunk generator can be
+// exercised offline. Serves scripted responses and records every request.
+
+use anyhow::Result;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, Server, StatusCode};
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::sync::oneshot;
+
+/// One scripted reply
+#[derive(Debug, Clone)]
+pub enum MockResponse {
+    /// A successful completion with the given usage.completion_tokens
+    Completion {
+        text: String,
+        completion_tokens: usize,
+    },
+    /// An error status with a JSON error body
+    Status(u16),
+}
+
+impl MockResponse {
+    pub fn completion(text: impl Into<String>, completion_tokens: usize) -> Self {
+        Self::Completion {
+            text: text.into(),
+            completion_tokens,
+        }
+    }
+
+    pub fn status(code: u16) -> Self {
+        Self::Status(code)
+    }
+}
+
+/// A request the server received
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    pub path: String,
+    pub authorization: Option<String>,
+    pub body: Value,
+}
+
+#[derive(Default)]
+struct MockState {
+    /// Replies in order; the last one repeats once the script runs out
+    script: Mutex<VecDeque<MockResponse>>,
+    requests: Mutex<Vec<MockRequest>>,
+}
+
+impl MockState {
+    fn next_response(&self) -> MockResponse {
+        let mut script = self.script.lock().unwrap();
+        if script.len() > 1 {
+            script.pop_front().unwrap()
+        } else {
+            script
+                .front()
+                .cloned()
+                .unwrap_or_else(|| MockResponse::completion("", 0))
+        }
+    }
+}
+
+/// Stub server bound to an ephemeral localhost port; shuts down on drop
+pub struct MockOpenAiServer {
+    addr: SocketAddr,
+    state: Arc<MockState>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl MockOpenAiServer {
+    /// Start serving `script` (must be called inside a Tokio runtime)
+    pub async fn start(script: Vec<MockResponse>) -> Result<Self> {
+        let state = Arc::new(MockState {
+            script: Mutex::new(script.into()),
+            requests: Mutex::new(Vec::new()),
+        });
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        listener.set_nonblocking(true)?;
+        let addr = listener.local_addr()?;
+
+        let service_state = state.clone();
+        let make_svc = make_service_fn(move |_conn| {
+            let state = service_state.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req| Self::handle(state.clone(), req)))
+            }
+        });
+
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+        let server = Server::from_tcp(listener)?
+            .serve(make_svc)
+            .with_graceful_shutdown(async {
+                let _ = shutdown_rx.await;
+            });
+
+        tokio::spawn(async move {
+            if let Err(e) = server.await {
+                tracing::error!("Mock OpenAI server error: {}", e);
+            }
+        });
+
+        Ok(Self {
+            addr,
+            state,
+            shutdown: Some(shutdown_tx),
+        })
+    }
+
+    /// Base URL to hand to the generator, e.g. "http://127.0.0.1:PORT/v1"
+    pub fn base_url(&self) -> String {
+        format!("http://{}/v1", self.addr)
+    }
+
+    /// Requests received so far
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.state.requests.lock().unwrap().clone()
+    }
+
+    async fn handle(state: Arc<MockState>, req: Request<Body>) -> Result<Response<Body>, Infallible> {
+        let path = req.uri().path().to_string();
+        let authorization = req
+            .headers()
+            .get(hyper::header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+
+        let bytes = hyper::body::to_bytes(req.into_body()).await.unwrap_or_default();
+        let body: Value = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+
+        let is_chat = match path.as_str() {
+            "/v1/chat/completions" => true,
+            "/v1/completions" => false,
+            _ => {
+                return Ok(Self::json_response(
+                    StatusCode::NOT_FOUND,
+                    json!({"error": {"message": format!("no route for {}", path)}}),
+                ));
+            }
+        };
+
+        let model = body["model"].as_str().unwrap_or("mock").to_string();
+        let prompt_tokens = body.to_string().split_whitespace().count();
+        state.requests.lock().unwrap().push(MockRequest {
+            path,
+            authorization,
+            body,
+        });
+
+        let response = match state.next_response() {
+            MockResponse::Status(code) => {
+                let status = StatusCode::from_u16(code).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+                Self::json_response(
+                    status,
+                    json!({"error": {"message": format!("mock status {}", code)}}),
+                )
+            }
+            MockResponse::Completion {
+                text,
+                completion_tokens,
+            } => {
+                let choice = if is_chat {
+                    json!({
+                        "index": 0,
+                        "message": {"role": "assistant", "content": text},
+                        "finish_reason": "stop",
+                    })
+                } else {
+                    json!({"index": 0, "text": text, "finish_reason": "stop"})
+                };
+
+                Self::json_response(
+                    StatusCode::OK,
+                    json!({
+                        "id": "mock-completion",
+                        "object": if is_chat { "chat.completion" } else { "text_completion" },
+                        "model": model,
+                        "choices": [choice],
+                        "usage": {
+                            "prompt_tokens": prompt_tokens,
+                            "completion_tokens": completion_tokens,
+                            "total_tokens": prompt_tokens + completion_tokens,
+                        },
+                    }),
+                )
+            }
+        };
+
+        Ok(response)
+    }
+
+    fn json_response(status: StatusCode, body: Value) -> Response<Body> {
+        Response::builder()
+            .status(status)
+            .header(hyper::header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+}
+
+impl Drop for MockOpenAiServer {
+    fn drop(&mut self) {
+        if let Some(tx) = self.shutdown.take() {
+            let _ = tx.send(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_unknown_route_returns_404() {
+        let server = MockOpenAiServer::start(vec![]).await.unwrap();
+        let response = reqwest::Client::new()
+            .post(format!("{}/embeddings", server.base_url()))
+            .json(&json!({}))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status().as_u16(), 404);
+        assert!(server.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_last_scripted_response_repeats() {
+        let state = MockState::default();
+        state.script.lock().unwrap().extend([
+            MockResponse::status(500),
+            MockResponse::completion("done", 1),
+        ]);
+
+        assert!(matches!(state.next_response(), MockResponse::Status(500)));
+        assert!(matches!(state.next_response(), MockResponse::Completion { .. }));
+        assert!(matches!(state.next_response(), MockResponse::Completion { .. }));
+    }
+}
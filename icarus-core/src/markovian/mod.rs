@@ -0,0 +1,70 @@
+// Markovian Reasoning: Delethink chunk-based reasoning inside Icarus
+//
+// Bounded-state reasoning from "The Markovian Thinker" (arXiv:2510.06557):
+// each chunk sees only the query plus a fixed-size carryover, so cost grows
+// linearly with the number of chunks. Around the core loop sit the GPT-OSS
+// enhancements (experts, attention, sampling) and the TIC-inspired event
+// system (event queue, causal trace, storm mitigation, concept space).
+
+// Core chunk loop
+pub mod state;
+pub mod chunk_manager;
+pub mod session_manager;
+pub mod trace;
+pub mod parser;
+pub mod prompts;
+pub mod types;
+
+// GPT-OSS enhancements
+pub mod experts;
+pub mod attention;
+pub mod sampling_strategies;
+pub mod monte_carlo;
+
+// Event-driven reasoning (Icarus TIC)
+pub mod events;
+pub mod event_queue;
+pub mod causal_trace;
+pub mod storm_mitigation;
+pub mod rate_limit;
+pub mod circuit_breaker;
+pub mod event_fusion;
+pub mod concept_space;
+pub mod lattice;
+
+// Integrations
+pub mod h2ce_adapter;
+pub mod todo_bridge;
+
+// Chunk generator backends
+pub mod openai_generator;
+pub mod inference_generator;
+pub mod cassette;
+pub mod mock_openai;
+
+pub use state::{ChunkHistory, ChunkRecord, MarkovianState, StateConfig, TerminationInfo};
+pub use chunk_manager::{ChunkGenerator, ChunkManager};
+pub use session_manager::{ReasoningSession, SessionInfo, SessionManager};
+pub use trace::{ReasoningTrace, TerminationReason, TraceChunk, TraceDataset};
+pub use parser::{parse_chunk_output, ParsedChunk};
+pub use prompts::{generate_legacy_prompt, generate_prompt, generate_verification_prompt};
+pub use types::{ReasoningDomain, SessionMetadata, VerificationResult, VerificationStatus};
+pub use experts::{ExpertConfig, ExpertGating, ExpertType};
+pub use attention::{AttentionConfig, SlidingWindowAttention};
+pub use sampling_strategies::{SamplingConfig, SamplingStrategy};
+pub use monte_carlo::{MonteCarloConfig, MonteCarloSampler};
+pub use events::{CognitiveTimestamp, EventWithMetadata, ReasoningEvent, ReasoningLevel};
+pub use event_queue::EventQueue;
+pub use causal_trace::CausalTrace;
+pub use storm_mitigation::{MitigationDecision, StormMitigation, StormMitigationConfig};
+pub use rate_limit::{RateLimitConfig, RateLimiter};
+pub use circuit_breaker::{CircuitBreaker, CircuitBreakerConfig, CircuitState};
+pub use event_fusion::{EventFusion, EventFusionConfig};
+pub use concept_space::{Concept, ConceptSpace, ConceptSpaceConfig};
+pub use lattice::{LatticePoint, LatticeType};
+pub use h2ce_adapter::{H2CEAdapter, H2CEConfig};
+pub use todo_bridge::{TodoBridge, TodoList};
+pub use openai_generator::{OpenAiApi, OpenAiGenerator, OpenAiGeneratorConfig, Usage};
+pub use inference_generator::InferenceModelGenerator;
+pub use cassette::{load_cassette, CassetteEntry, RecordingGenerator, ReplayGenerator};
+pub use mock_openai::{MockOpenAiServer, MockRequest, MockResponse};
@@ -0,0 +1,438 @@
+// Markovian Thinker: OpenAI-Compatible Chunk Generator
+// Drives the chunk loop against any server speaking the OpenAI HTTP API
+// (vLLM, llama.cpp server, LM Studio, OpenAI itself)
+
+use crate::markovian::chunk_manager::ChunkGenerator;
+use anyhow::Result;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+/// Which OpenAI endpoint to call
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OpenAiApi {
+    /// POST {base_url}/chat/completions with a messages array
+    ChatCompletions,
+    /// POST {base_url}/completions with a raw prompt
+    Completions,
+}
+
+/// Configuration for an OpenAI-compatible backend
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpenAiGeneratorConfig {
+    /// Base URL including the version prefix, e.g. "http://localhost:8000/v1"
+    pub base_url: String,
+
+    /// Model name sent with every request
+    pub model: String,
+
+    /// Environment variable holding the API key (None = no Authorization header)
+    pub api_key_env: Option<String>,
+
+    /// Endpoint flavour
+    pub api: OpenAiApi,
+
+    /// Optional system message (chat endpoint only)
+    pub system_prompt: Option<String>,
+
+    /// Sampling temperature
+    pub temperature: f32,
+
+    /// TCP connect timeout
+    pub connect_timeout: Duration,
+
+    /// Whole-request timeout (a chunk can take a while on local servers)
+    pub request_timeout: Duration,
+
+    /// Retries after the first attempt for transient failures
+    pub max_retries: u32,
+
+    /// Backoff before the first retry, doubled on each further retry
+    pub initial_backoff: Duration,
+
+    /// Upper bound on any single backoff (including Retry-After)
+    pub max_backoff: Duration,
+}
+
+impl Default for OpenAiGeneratorConfig {
+    fn default() -> Self {
+        Self {
+            base_url: "http://localhost:8000/v1".to_string(),
+            model: "default".to_string(),
+            api_key_env: Some("OPENAI_API_KEY".to_string()),
+            api: OpenAiApi::ChatCompletions,
+            system_prompt: None,
+            temperature: 0.7,
+            connect_timeout: Duration::from_secs(10),
+            request_timeout: Duration::from_secs(300),
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(8),
+        }
+    }
+}
+
+impl OpenAiGeneratorConfig {
+    /// Config for a server at `base_url` serving `model`, other settings default
+    pub fn new(base_url: impl Into<String>, model: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into(),
+            model: model.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Backoff before retry number `attempt` (1-based)
+    fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 1u32 << (attempt.saturating_sub(1)).min(16);
+        self.initial_backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+}
+
+/// Token accounting reported by the server
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Usage {
+    #[serde(default)]
+    pub prompt_tokens: usize,
+    #[serde(default)]
+    pub completion_tokens: usize,
+    #[serde(default)]
+    pub total_tokens: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct CompletionResponse {
+    choices: Vec<Choice>,
+    usage: Option<Usage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Choice {
+    /// Set by /completions
+    text: Option<String>,
+    /// Set by /chat/completions
+    message: Option<ChatMessage>,
+    finish_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatMessage {
+    content: Option<String>,
+}
+
+/// Why a single attempt failed
+enum AttemptError {
+    /// Worth retrying (connection problems, timeouts, 429, 5xx)
+    Transient {
+        error: anyhow::Error,
+        retry_after: Option<Duration>,
+    },
+    /// Retrying won't help (bad request, auth, malformed body)
+    Fatal(anyhow::Error),
+}
+
+/// ChunkGenerator backed by an OpenAI-compatible HTTP endpoint
+pub struct OpenAiGenerator {
+    config: OpenAiGeneratorConfig,
+    client: reqwest::Client,
+    api_key: Option<String>,
+}
+
+impl OpenAiGenerator {
+    /// Create a generator; the API key is read from `api_key_env` once, here
+    pub fn new(config: OpenAiGeneratorConfig) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .connect_timeout(config.connect_timeout)
+            .timeout(config.request_timeout)
+            .build()?;
+
+        let api_key = config
+            .api_key_env
+            .as_deref()
+            .and_then(|var| std::env::var(var).ok())
+            .filter(|key| !key.is_empty());
+
+        Ok(Self {
+            config,
+            client,
+            api_key,
+        })
+    }
+
+    /// Current configuration
+    pub fn config(&self) -> &OpenAiGeneratorConfig {
+        &self.config
+    }
+
+    /// Generate a completion and return the text with the server's usage report
+    pub async fn complete(&self, prompt: &str, max_tokens: usize) -> Result<(String, Usage)> {
+        let url = self.endpoint_url();
+        let body = self.request_body(prompt, max_tokens);
+        let mut attempt = 0;
+
+        loop {
+            match self.attempt(&url, &body).await {
+                Ok(result) => return Ok(result),
+                Err(AttemptError::Fatal(e)) => return Err(e),
+                Err(AttemptError::Transient { error, retry_after }) => {
+                    attempt += 1;
+                    if attempt > self.config.max_retries {
+                        return Err(error.context(format!(
+                            "giving up after {} attempts",
+                            attempt
+                        )));
+                    }
+
+                    let delay = retry_after
+                        .map(|d| d.min(self.config.max_backoff))
+                        .unwrap_or_else(|| self.config.backoff(attempt));
+                    tracing::warn!(
+                        "Chunk request failed ({}), retry {}/{} in {:?}",
+                        error,
+                        attempt,
+                        self.config.max_retries,
+                        delay
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+            }
+        }
+    }
+
+    fn endpoint_url(&self) -> String {
+        let base = self.config.base_url.trim_end_matches('/');
+        match self.config.api {
+            OpenAiApi::ChatCompletions => format!("{}/chat/completions", base),
+            OpenAiApi::Completions => format!("{}/completions", base),
+        }
+    }
+
+    fn request_body(&self, prompt: &str, max_tokens: usize) -> Value {
+        match self.config.api {
+            OpenAiApi::ChatCompletions => {
+                let mut messages = Vec::new();
+                if let Some(system) = &self.config.system_prompt {
+                    messages.push(json!({"role": "system", "content": system}));
+                }
+                messages.push(json!({"role": "user", "content": prompt}));
+
+                json!({
+                    "model": self.config.model,
+                    "messages": messages,
+                    "max_tokens": max_tokens,
+                    "temperature": self.config.temperature,
+                    "stream": false,
+                })
+            }
+            OpenAiApi::Completions => json!({
+                "model": self.config.model,
+                "prompt": prompt,
+                "max_tokens": max_tokens,
+                "temperature": self.config.temperature,
+                "stream": false,
+            }),
+        }
+    }
+
+    async fn attempt(&self, url: &str, body: &Value) -> Result<(String, Usage), AttemptError> {
+        let mut request = self.client.post(url).json(body);
+        if let Some(key) = &self.api_key {
+            request = request.bearer_auth(key);
+        }
+
+        let response = request.send().await.map_err(|e| {
+            let error = anyhow::anyhow!("request to {} failed: {}", url, e);
+            if e.is_connect() || e.is_timeout() || e.is_request() {
+                AttemptError::Transient {
+                    error,
+                    retry_after: None,
+                }
+            } else {
+                AttemptError::Fatal(error)
+            }
+        })?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let retry_after = response
+                .headers()
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .map(Duration::from_secs);
+            let text = response.text().await.unwrap_or_default();
+            let error = anyhow::anyhow!("{} returned {}: {}", url, status, text.trim());
+
+            return if status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
+                Err(AttemptError::Transient { error, retry_after })
+            } else {
+                Err(AttemptError::Fatal(error))
+            };
+        }
+
+        let parsed: CompletionResponse = response.json().await.map_err(|e| {
+            AttemptError::Fatal(anyhow::anyhow!("invalid completion response: {}", e))
+        })?;
+
+        Self::parse_response(parsed).map_err(AttemptError::Fatal)
+    }
+
+    fn parse_response(response: CompletionResponse) -> Result<(String, Usage)> {
+        let choice = response
+            .choices
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("completion response has no choices"))?;
+
+        let text = choice
+            .text
+            .or_else(|| choice.message.and_then(|m| m.content))
+            .unwrap_or_default();
+
+        if let Some(reason) = &choice.finish_reason {
+            tracing::debug!("Chunk finished: {}", reason);
+        }
+
+        let usage = response.usage.ok_or_else(|| {
+            anyhow::anyhow!("completion response has no usage; enable usage reporting on the server")
+        })?;
+
+        Ok((text, usage))
+    }
+}
+
+#[async_trait::async_trait]
+impl ChunkGenerator for OpenAiGenerator {
+    async fn generate(&self, prompt: &str, max_tokens: usize) -> Result<(String, usize)> {
+        let (text, usage) = self.complete(prompt, max_tokens).await?;
+        Ok((text, usage.completion_tokens))
+    }
+
+    fn model_name(&self) -> &str {
+        &self.config.model
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::markovian::mock_openai::{MockOpenAiServer, MockResponse};
+
+    fn test_config(base_url: String, api: OpenAiApi) -> OpenAiGeneratorConfig {
+        OpenAiGeneratorConfig {
+            api,
+            api_key_env: None,
+            initial_backoff: Duration::from_millis(5),
+            max_backoff: Duration::from_millis(20),
+            ..OpenAiGeneratorConfig::new(base_url, "test-model")
+        }
+    }
+
+    #[test]
+    fn test_backoff_doubles_and_caps() {
+        let config = OpenAiGeneratorConfig {
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(350),
+            ..Default::default()
+        };
+
+        assert_eq!(config.backoff(1), Duration::from_millis(100));
+        assert_eq!(config.backoff(2), Duration::from_millis(200));
+        assert_eq!(config.backoff(3), Duration::from_millis(350));
+    }
+
+    #[tokio::test]
+    async fn test_chat_completion_uses_usage() {
+        let server = MockOpenAiServer::start(vec![MockResponse::completion("Step 1 done", 7)])
+            .await
+            .unwrap();
+        let generator =
+            OpenAiGenerator::new(test_config(server.base_url(), OpenAiApi::ChatCompletions)).unwrap();
+
+        let (text, tokens) = generator.generate("Solve it", 64).await.unwrap();
+        assert_eq!(text, "Step 1 done");
+        assert_eq!(tokens, 7);
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].path, "/v1/chat/completions");
+        assert_eq!(requests[0].body["model"], "test-model");
+        assert_eq!(requests[0].body["max_tokens"], 64);
+        assert_eq!(requests[0].body["messages"][0]["content"], "Solve it");
+        assert!(requests[0].authorization.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_completions_endpoint() {
+        let server = MockOpenAiServer::start(vec![MockResponse::completion("#### 4", 3)])
+            .await
+            .unwrap();
+        let generator =
+            OpenAiGenerator::new(test_config(server.base_url(), OpenAiApi::Completions)).unwrap();
+
+        let (text, tokens) = generator.generate("2+2=", 16).await.unwrap();
+        assert_eq!(text, "#### 4");
+        assert_eq!(tokens, 3);
+
+        let requests = server.requests();
+        assert_eq!(requests[0].path, "/v1/completions");
+        assert_eq!(requests[0].body["prompt"], "2+2=");
+    }
+
+    #[tokio::test]
+    async fn test_retries_transient_errors() {
+        let server = MockOpenAiServer::start(vec![
+            MockResponse::status(503),
+            MockResponse::status(429),
+            MockResponse::completion("ok", 1),
+        ])
+        .await
+        .unwrap();
+        let generator =
+            OpenAiGenerator::new(test_config(server.base_url(), OpenAiApi::ChatCompletions)).unwrap();
+
+        let (text, _) = generator.generate("prompt", 8).await.unwrap();
+        assert_eq!(text, "ok");
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_retries() {
+        let server = MockOpenAiServer::start(vec![MockResponse::status(500)]).await.unwrap();
+        let mut config = test_config(server.base_url(), OpenAiApi::ChatCompletions);
+        config.max_retries = 2;
+        let generator = OpenAiGenerator::new(config).unwrap();
+
+        assert!(generator.generate("prompt", 8).await.is_err());
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_client_errors_are_not_retried() {
+        let server = MockOpenAiServer::start(vec![MockResponse::status(400)]).await.unwrap();
+        let generator =
+            OpenAiGenerator::new(test_config(server.base_url(), OpenAiApi::ChatCompletions)).unwrap();
+
+        assert!(generator.generate("prompt", 8).await.is_err());
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_api_key_from_env() {
+        std::env::set_var("ICARUS_TEST_OPENAI_KEY", "sk-test");
+        let server = MockOpenAiServer::start(vec![MockResponse::completion("hi", 1)])
+            .await
+            .unwrap();
+        let mut config = test_config(server.base_url(), OpenAiApi::ChatCompletions);
+        config.api_key_env = Some("ICARUS_TEST_OPENAI_KEY".to_string());
+        let generator = OpenAiGenerator::new(config).unwrap();
+
+        generator.generate("prompt", 8).await.unwrap();
+        assert_eq!(
+            server.requests()[0].authorization.as_deref(),
+            Some("Bearer sk-test")
+        );
+    }
+}
@@ -1,7 +1,7 @@
 // Parser for structured LLM output with verification sections
 // Extracts [REASONING], [VERIFICATION], and [CARRYOVER] blocks
 
-use crate::types::*;
+use crate::markovian::types::*;
 use regex::Regex;
 
 /// Parsed chunk with separated sections
@@ -1,9 +1,22 @@
 // Enhanced prompt generation with integrated verification system
 // Prompts guide LLM through self-verification and structured output
 
-use crate::experts::ExpertGating;
-use crate::state::MarkovianState;
-use crate::types::ReasoningDomain;
+use crate::markovian::experts::ExpertGating;
+use crate::markovian::state::MarkovianState;
+use crate::markovian::types::ReasoningDomain;
+
+/// Self-verification checklist, shared by chunk and verification prompts
+const VERIFICATION_CHECKLIST: &str = "   ✓ Logical Consistency: Are there contradictions?
+   ✓ Completeness: Are assumptions stated?
+   ✓ Accuracy: Are facts/calculations correct?
+   ✓ Relevance: Does this advance toward solution?";
+
+/// `[VERIFICATION]` block in the format `parse_chunk_output` reads
+const VERIFICATION_FORMAT: &str = "   [VERIFICATION]
+   Status: PASS | FAIL | UNCERTAIN
+   Confidence: 0.XX (0.0-1.0)
+   Issues: [list any problems, or \"None\"]
+   Key Concepts: [main ideas this chunk establishes]";
 
 /// Generate enhanced prompt with verification instructions
 pub fn generate_prompt(state: &MarkovianState, domain: Option<&ReasoningDomain>) -> String {
@@ -30,7 +43,7 @@ pub fn generate_prompt(state: &MarkovianState, domain: Option<&ReasoningDomain>)
         if !experts.is_empty() {
             let expert = experts[0]; // Use top expert
             let expert_guidance = match expert.expert_type() {
-                crate::experts::ExpertType::MathReasoning => {
+                crate::markovian::experts::ExpertType::MathReasoning => {
                     "\nEXPERT GUIDANCE (Mathematical):
 - Use clear mathematical notation
 - Show each calculation step explicitly
@@ -39,7 +52,7 @@ pub fn generate_prompt(state: &MarkovianState, domain: Option<&ReasoningDomain>)
 - Verify calculations numerically when possible
 "
                 }
-                crate::experts::ExpertType::CodeGeneration => {
+                crate::markovian::experts::ExpertType::CodeGeneration => {
                     "\nEXPERT GUIDANCE (Code):
 - Use proper syntax highlighting with ```language blocks
 - Include comments explaining complex logic
@@ -48,7 +61,7 @@ pub fn generate_prompt(state: &MarkovianState, domain: Option<&ReasoningDomain>)
 - Mark solution with // SOLUTION or # SOLUTION comment
 "
                 }
-                crate::experts::ExpertType::TextualReasoning => {
+                crate::markovian::experts::ExpertType::TextualReasoning => {
                     "\nEXPERT GUIDANCE (Text Analysis):
 - Structure arguments clearly with numbered points
 - Use evidence and citations when relevant
@@ -57,7 +70,7 @@ pub fn generate_prompt(state: &MarkovianState, domain: Option<&ReasoningDomain>)
 - Mark final conclusion with [CONCLUSION]
 "
                 }
-                crate::experts::ExpertType::VisualReasoning => {
+                crate::markovian::experts::ExpertType::VisualReasoning => {
                     "\nEXPERT GUIDANCE (Visual):
 - Describe visual elements systematically
 - Use spatial relationships (left, right, above, below)
@@ -65,7 +78,7 @@ pub fn generate_prompt(state: &MarkovianState, domain: Option<&ReasoningDomain>)
 - Provide structured descriptions
 "
                 }
-                crate::experts::ExpertType::Mixed => {
+                crate::markovian::experts::ExpertType::Mixed => {
                     "\nEXPERT GUIDANCE (Mixed Domain):
 - Integrate multiple reasoning approaches
 - Clearly separate different types of reasoning
@@ -95,10 +108,7 @@ INSTRUCTIONS:
 2. VERIFICATION PHASE:
    After reasoning, perform rigorous self-verification:
 
-   ✓ Logical Consistency: Are there contradictions?
-   ✓ Completeness: Are assumptions stated?
-   ✓ Accuracy: Are facts/calculations correct?
-   ✓ Relevance: Does this advance toward solution?
+{}
 
    If verification FAILS, regenerate your reasoning with corrections.
 
@@ -108,11 +118,7 @@ INSTRUCTIONS:
    [REASONING]
    ... your detailed reasoning here ...
 
-   [VERIFICATION]
-   Status: PASS | FAIL | UNCERTAIN
-   Confidence: 0.XX (0.0-1.0)
-   Issues: [list any problems, or \"None\"]
-   Key Concepts: [main ideas this chunk establishes]
+{}
 
    [CARRYOVER]
    ... essential context for next chunk (top {} most important points) ...
@@ -125,10 +131,42 @@ Begin reasoning now:",
         domain_instructions,
         expert_instructions,
         state.config.chunk_size,
+        VERIFICATION_CHECKLIST,
+        VERIFICATION_FORMAT,
         state.config.carryover_size / 100 // Rough estimate: ~100 tokens per point
     )
 }
 
+/// Generate a standalone verification prompt for finished reasoning, using the
+/// domain's strategy as the standard to check against
+pub fn generate_verification_prompt(problem: &str, reasoning: &str, domain: &ReasoningDomain) -> String {
+    format!(
+        "Problem: {}\n\nReasoning to verify:\n{}\n{}
+
+INSTRUCTIONS:
+
+1. VERIFICATION PHASE:
+   Do not continue the reasoning. Check it step by step against the
+   domain strategy above and this checklist:
+
+{}
+
+2. OUTPUT FORMAT:
+   Structure your response exactly as:
+
+{}
+
+   If the reasoning reaches a correct solution, add [SOLUTION] followed by it.
+
+Begin verification now:",
+        problem,
+        reasoning.trim(),
+        domain.strategy_instructions(),
+        VERIFICATION_CHECKLIST,
+        VERIFICATION_FORMAT
+    )
+}
+
 /// Generate legacy prompt without verification (for backward compatibility)
 pub fn generate_legacy_prompt(state: &MarkovianState) -> String {
     format!(
@@ -146,7 +184,7 @@ pub fn generate_legacy_prompt(state: &MarkovianState) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::state::StateConfig;
+    use crate::markovian::state::StateConfig;
 
     #[test]
     fn test_generate_prompt_includes_verification() {
@@ -200,6 +238,24 @@ mod tests {
         assert!(!prompt.contains("(This is the first chunk)"));
     }
 
+    #[test]
+    fn test_verification_prompt_per_domain() {
+        let prompt = generate_verification_prompt(
+            "Prove that 2 + 2 = 4",
+            "2 + 2 = 4 by definition",
+            &ReasoningDomain::Mathematical,
+        );
+
+        assert!(prompt.contains("2 + 2 = 4 by definition"));
+        assert!(prompt.contains("MATHEMATICAL STRATEGY"));
+        assert!(prompt.contains(VERIFICATION_CHECKLIST));
+        assert!(prompt.contains("[VERIFICATION]"));
+        assert!(!prompt.contains("[CARRYOVER]"));
+
+        let general = generate_verification_prompt("p", "r", &ReasoningDomain::General);
+        assert!(!general.contains("STRATEGY"));
+    }
+
     #[test]
     fn test_prompt_first_chunk() {
         let config = StateConfig::default();
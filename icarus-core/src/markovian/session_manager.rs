@@ -1,11 +1,11 @@
 // Session Manager for Markovian Reasoning
 // Manages the lifecycle of reasoning sessions
 
-use crate::causal_trace::CausalTrace;
-use crate::concept_space::{ConceptSpace, ConceptSpaceConfig};
-use crate::state::{MarkovianState, StateConfig};
-use crate::storm_mitigation::StormMitigation;
-use crate::trace::{ReasoningTrace, TerminationReason};
+use crate::markovian::causal_trace::CausalTrace;
+use crate::markovian::concept_space::{ConceptSpace, ConceptSpaceConfig};
+use crate::markovian::state::{MarkovianState, StateConfig};
+use crate::markovian::storm_mitigation::StormMitigation;
+use crate::markovian::trace::{ReasoningTrace, TerminationReason};
 use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
 use std::collections::HashMap;
@@ -264,7 +264,7 @@ impl SessionManager {
     pub async fn check_storm_mitigation(
         &self,
         session_id: Uuid,
-    ) -> Result<crate::storm_mitigation::MitigationDecision> {
+    ) -> Result<crate::markovian::storm_mitigation::MitigationDecision> {
         let mut mitigations = self.storm_mitigations.lock().await;
 
         let mitigation = mitigations
@@ -300,7 +300,7 @@ impl SessionManager {
     pub async fn get_storm_stats(
         &self,
         session_id: Uuid,
-    ) -> Result<crate::storm_mitigation::StormMitigationStats> {
+    ) -> Result<crate::markovian::storm_mitigation::StormMitigationStats> {
         let mitigations = self.storm_mitigations.lock().await;
 
         let mitigation = mitigations
@@ -326,7 +326,7 @@ impl SessionManager {
         session_id: Uuid,
         embedding: Vec<f32>,
         k: usize,
-    ) -> Result<Vec<crate::concept_space::Concept>> {
+    ) -> Result<Vec<crate::markovian::concept_space::Concept>> {
         let spaces = self.concept_spaces.lock().await;
 
         let space = spaces
@@ -344,7 +344,7 @@ impl SessionManager {
     pub async fn get_concept_stats(
         &self,
         session_id: Uuid,
-    ) -> Result<crate::concept_space::ConceptSpaceStatistics> {
+    ) -> Result<crate::markovian::concept_space::ConceptSpaceStatistics> {
         let spaces = self.concept_spaces.lock().await;
 
         let space = spaces
@@ -1,15 +1,15 @@
 // Markovian Thinker: State Management
 // Implements bounded-state reasoning with carryover between chunks
 
-use crate::attention::AttentionConfig;
-use crate::concept_space::ConceptSpaceConfig;
-use crate::experts::ExpertConfig;
-use crate::h2ce_adapter::H2CEConfig;
-use crate::parser;
-use crate::sampling_strategies::SamplingConfig;
-use crate::storm_mitigation::StormMitigationConfig;
-use crate::trace::TerminationReason;
-use crate::types::{ReasoningDomain, SessionMetadata, VerificationResult};
+use crate::markovian::attention::AttentionConfig;
+use crate::markovian::concept_space::ConceptSpaceConfig;
+use crate::markovian::experts::ExpertConfig;
+use crate::markovian::h2ce_adapter::H2CEConfig;
+use crate::markovian::parser;
+use crate::markovian::sampling_strategies::SamplingConfig;
+use crate::markovian::storm_mitigation::StormMitigationConfig;
+use crate::markovian::trace::TerminationReason;
+use crate::markovian::types::{ReasoningDomain, SessionMetadata, VerificationResult};
 use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
 
@@ -403,7 +403,7 @@ impl MarkovianState {
             text.to_string()
         } else {
             // Take last N characters, but start at word boundary
-            let start_pos = text.len().saturating_sub(approx_chars);
+            let start_pos = text.ceil_char_boundary(text.len().saturating_sub(approx_chars));
 
             // Find word boundary
             let boundary = text[start_pos..]
@@ -492,8 +492,8 @@ impl MarkovianState {
 
     /// Attention-based carryover compression
     /// Uses attention scoring to keep most important tokens when exceeding limits
-    fn compress_with_attention(text: &str, target_tokens: usize, config: &crate::attention::AttentionConfig) -> String {
-        use crate::attention::SlidingWindowAttention;
+    fn compress_with_attention(text: &str, target_tokens: usize, config: &crate::markovian::attention::AttentionConfig) -> String {
+        use crate::markovian::attention::SlidingWindowAttention;
 
         let attention = SlidingWindowAttention::new(config.clone());
         attention.select_important(text, target_tokens)
@@ -676,6 +676,15 @@ mod tests {
         assert!(text.ends_with(&carryover.trim()));
     }
 
+    #[test]
+    fn test_extract_carryover_multibyte() {
+        // The byte cut-off lands inside a 3-byte character
+        let text = "résumé 生成 of the reasoning so far";
+
+        let carryover = MarkovianState::extract_carryover(text, 7);
+        assert!(text.ends_with(&carryover));
+    }
+
     #[test]
     fn test_chunk_history() {
         let mut history = ChunkHistory::new(3);
@@ -1,10 +1,10 @@
 // Markovian Thinker: Event Storm Mitigation System
 // Orchestrates rate limiting, circuit breaking, and event fusion
 
-use crate::circuit_breaker::{CircuitBreaker, CircuitBreakerConfig, CircuitState};
-use crate::event_fusion::{EventFusion, EventFusionConfig};
-use crate::events::EventWithMetadata;
-use crate::rate_limit::{RateLimiter, RateLimitConfig};
+use crate::markovian::circuit_breaker::{CircuitBreaker, CircuitBreakerConfig, CircuitState};
+use crate::markovian::event_fusion::{EventFusion, EventFusionConfig};
+use crate::markovian::events::EventWithMetadata;
+use crate::markovian::rate_limit::{RateLimiter, RateLimitConfig};
 use serde::{Deserialize, Serialize};
 
 /// Storm mitigation configuration
@@ -271,8 +271,8 @@ impl StormMetrics {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StormMitigationStats {
     pub circuit_state: CircuitState,
-    pub rate_limiter_stats: crate::rate_limit::RateLimiterStats,
-    pub circuit_breaker_stats: crate::circuit_breaker::CircuitBreakerStats,
+    pub rate_limiter_stats: crate::markovian::rate_limit::RateLimiterStats,
+    pub circuit_breaker_stats: crate::markovian::circuit_breaker::CircuitBreakerStats,
     pub metrics: StormMetrics,
 }
 
@@ -1,10 +1,11 @@
 // Enhanced types for CRV-inspired verification system
 // Provides domain detection, verification results, and reasoning metadata
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 /// Reasoning domain detection for specialized strategies
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum ReasoningDomain {
     Debugging,
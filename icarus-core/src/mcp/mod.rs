@@ -1,10 +1,12 @@
 // MCP Server Module for Icarus
 // Exposes Icarus cognitive capabilities via Model Context Protocol
 
-pub mod protocol;
+pub mod prompts;
+pub mod resources;
 pub mod server;
-pub mod stdio;
+pub mod tools;
 
-pub use protocol::*;
+pub use mcp_framework::{protocol, stdio};
+pub use mcp_framework::protocol::*;
+pub use mcp_framework::{McpServer, PromptHandler, PromptRouter, RequestContext, StdioHandler, ToolHandler, ToolRouter};
 pub use server::IcarusMCPServer;
-pub use stdio::StdioHandler;
@@ -0,0 +1,160 @@
+// Icarus MCP Prompts
+// The Delethink templates the Markovian engine uses, for clients that drive the
+// chunk loop themselves: a first chunk, a continuation from carryover and a
+// per-domain verification pass
+
+use super::server::IcarusMCPServer;
+use crate::markovian::{generate_prompt, generate_verification_prompt, MarkovianState, ReasoningDomain, StateConfig};
+use anyhow::Result;
+use mcp_framework::{PromptHandler, PromptMessage, PromptRouter};
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+/// All prompts, in the order `prompts/list` reports them
+pub fn router() -> PromptRouter<IcarusMCPServer> {
+    PromptRouter::new()
+        .with(FirstChunk)
+        .with(ContinueChunk)
+        .with(Verify)
+}
+
+/// State for rendering a chunk prompt; carryover is half the chunk size
+fn chunk_state(problem: String, chunk_size: Option<String>) -> Result<MarkovianState> {
+    let config = match chunk_size {
+        Some(chunk_size) => {
+            let chunk_size: usize = chunk_size.trim().parse()
+                .map_err(|_| anyhow::anyhow!("chunk_size must be a number, got '{}'", chunk_size))?;
+            let defaults = StateConfig::default();
+            StateConfig::new(chunk_size, chunk_size / 2, defaults.max_iterations)
+                .map_err(|e| anyhow::anyhow!(e))?
+        }
+        None => StateConfig::default(),
+    };
+    Ok(MarkovianState::new(problem, config))
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct FirstChunkArgs {
+    /// The problem to reason about
+    pub problem: String,
+    /// Reasoning domain: debugging, architecture, mathematical, logical or general (default: detected from the problem)
+    pub domain: Option<ReasoningDomain>,
+    /// Maximum tokens per chunk (default: 8192)
+    pub chunk_size: Option<String>,
+}
+
+struct FirstChunk;
+
+impl PromptHandler<IcarusMCPServer> for FirstChunk {
+    type Args = FirstChunkArgs;
+    const NAME: &'static str = "delethink_first_chunk";
+    const DESCRIPTION: &'static str = "First chunk of Delethink reasoning with the domain strategy and the [REASONING]/[VERIFICATION]/[CARRYOVER] output format. Continue with delethink_continue_chunk, passing the [CARRYOVER] section, until the output contains [SOLUTION] or [DONE].";
+
+    fn get(&self, _server: &IcarusMCPServer, args: FirstChunkArgs) -> Result<Vec<PromptMessage>> {
+        let state = chunk_state(args.problem, args.chunk_size)?;
+        let domain = args.domain.or_else(|| state.domain.clone());
+        Ok(vec![PromptMessage::user(generate_prompt(&state, domain.as_ref()))])
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ContinueChunkArgs {
+    /// The problem being reasoned about
+    pub problem: String,
+    /// The [CARRYOVER] section of the previous chunk
+    pub carryover: String,
+    /// Reasoning domain: debugging, architecture, mathematical, logical or general (default: detected from the problem)
+    pub domain: Option<ReasoningDomain>,
+    /// Maximum tokens per chunk (default: 8192)
+    pub chunk_size: Option<String>,
+}
+
+struct ContinueChunk;
+
+impl PromptHandler<IcarusMCPServer> for ContinueChunk {
+    type Args = ContinueChunkArgs;
+    const NAME: &'static str = "delethink_continue_chunk";
+    const DESCRIPTION: &'static str = "Later chunk of Delethink reasoning: the problem plus the carryover of the previous chunk, with the same strategy and output format as the first chunk.";
+
+    fn get(&self, _server: &IcarusMCPServer, args: ContinueChunkArgs) -> Result<Vec<PromptMessage>> {
+        let mut state = chunk_state(args.problem, args.chunk_size)?;
+        state.carryover = args.carryover.trim().to_string();
+        if state.carryover.is_empty() {
+            anyhow::bail!("carryover is empty; use delethink_first_chunk for the first chunk");
+        }
+        let domain = args.domain.or_else(|| state.domain.clone());
+        Ok(vec![PromptMessage::user(generate_prompt(&state, domain.as_ref()))])
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct VerifyArgs {
+    /// The problem that was reasoned about
+    pub problem: String,
+    /// The reasoning (and answer) to verify
+    pub reasoning: String,
+    /// Reasoning domain: debugging, architecture, mathematical, logical or general (default: detected from the problem and reasoning)
+    pub domain: Option<ReasoningDomain>,
+}
+
+struct Verify;
+
+impl PromptHandler<IcarusMCPServer> for Verify {
+    type Args = VerifyArgs;
+    const NAME: &'static str = "delethink_verify";
+    const DESCRIPTION: &'static str = "Verify finished reasoning against the domain's strategy and the self-verification checklist; answers with a [VERIFICATION] block.";
+
+    fn get(&self, _server: &IcarusMCPServer, args: VerifyArgs) -> Result<Vec<PromptMessage>> {
+        let domain = args.domain
+            .unwrap_or_else(|| ReasoningDomain::detect(&args.problem, Some(&args.reasoning)));
+        Ok(vec![PromptMessage::user(generate_verification_prompt(&args.problem, &args.reasoning, &domain))])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mcp_framework::GetPromptParams;
+    use std::collections::HashMap;
+
+    fn get(name: &str, arguments: &[(&str, &str)]) -> Result<String> {
+        let params = GetPromptParams {
+            name: name.to_string(),
+            arguments: arguments.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect::<HashMap<_, _>>(),
+        };
+        let result = router().get(&IcarusMCPServer::new(), params)?;
+        Ok(result.messages[0].content.as_text().unwrap().to_string())
+    }
+
+    #[test]
+    fn test_prompts_render_engine_templates() {
+        let names: Vec<String> = router().list().into_iter().map(|p| p.name).collect();
+        assert_eq!(names, ["delethink_first_chunk", "delethink_continue_chunk", "delethink_verify"]);
+
+        let first = get("delethink_first_chunk", &[("problem", "Fix the crash on startup")]).unwrap();
+        assert!(first.contains("(This is the first chunk)"));
+        assert!(first.contains("DEBUGGING STRATEGY"));
+        assert!(first.contains("up to 8192 tokens"));
+
+        let next = get("delethink_continue_chunk", &[
+            ("problem", "Fix the crash on startup"),
+            ("carryover", "The config file is missing"),
+            ("domain", "architecture"),
+            ("chunk_size", "1024"),
+        ]).unwrap();
+        assert!(next.contains("Previous Context: The config file is missing"));
+        assert!(next.contains("ARCHITECTURE STRATEGY"));
+        assert!(next.contains("up to 1024 tokens"));
+
+        let verify = get("delethink_verify", &[("problem", "Prove x = 1"), ("reasoning", "x - 1 = 0")]).unwrap();
+        assert!(verify.contains("MATHEMATICAL STRATEGY"));
+        assert!(verify.contains("x - 1 = 0"));
+    }
+
+    #[test]
+    fn test_invalid_arguments() {
+        assert!(get("delethink_verify", &[("problem", "p"), ("reasoning", "r"), ("domain", "poetry")]).is_err());
+        assert!(get("delethink_first_chunk", &[("problem", "p"), ("chunk_size", "big")]).is_err());
+        assert!(get("delethink_continue_chunk", &[("problem", "p"), ("carryover", " ")]).is_err());
+    }
+}
@@ -0,0 +1,26 @@
+// Icarus MCP Resources
+// Read-only views of the cognitive system, one `ResourceHandler` each
+
+use super::server::IcarusMCPServer;
+use anyhow::Result;
+use async_trait::async_trait;
+use mcp_framework::{ResourceHandler, ResourceRouter, UriVars};
+use serde_json::Value;
+
+/// All resources, in the order `resources/list` reports them
+pub fn router() -> ResourceRouter<IcarusMCPServer> {
+    ResourceRouter::new().with(Status)
+}
+
+struct Status;
+
+#[async_trait]
+impl ResourceHandler<IcarusMCPServer> for Status {
+    const URI: &'static str = "icarus://status";
+    const NAME: &'static str = "Icarus status";
+    const DESCRIPTION: &'static str = "Overall status of the Icarus cognitive system: agent states and memory levels.";
+
+    async fn read(&self, server: &IcarusMCPServer, _vars: &UriVars) -> Result<Value> {
+        Ok(server.status().await)
+    }
+}
@@ -2,9 +2,13 @@
 
 use super::protocol::*;
 use super::stdio::StdioHandler;
-use crate::{AgentSystem, MemoryHierarchy, NeuralCore, WorldModel, IcarusCore, IcarusConfig};
+use super::{prompts, resources, tools};
+use crate::IcarusCore;
 use anyhow::Result;
+use async_trait::async_trait;
+use mcp_framework::{McpServer, PromptRouter, RequestContext, ResourceRouter, ToolRouter};
 use serde_json::json;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
@@ -15,7 +19,13 @@ pub struct IcarusMCPServer {
     /// Icarus core instance (optional - created on first use)
     icarus: Arc<RwLock<Option<IcarusCore>>>,
     /// Server initialized flag
-    initialized: bool,
+    initialized: AtomicBool,
+    /// Registered tools
+    tools: ToolRouter<IcarusMCPServer>,
+    /// Registered resources
+    resources: ResourceRouter<IcarusMCPServer>,
+    /// Registered prompts
+    prompts: PromptRouter<IcarusMCPServer>,
 }
 
 impl IcarusMCPServer {
@@ -27,7 +37,10 @@ impl IcarusMCPServer {
                 version: env!("CARGO_PKG_VERSION").to_string(),
             },
             icarus: Arc::new(RwLock::new(None)),
-            initialized: false,
+            initialized: AtomicBool::new(false),
+            tools: tools::router(),
+            resources: resources::router(),
+            prompts: prompts::router(),
         }
     }
 
@@ -40,254 +53,20 @@ impl IcarusMCPServer {
     }
 
     /// Run server with stdio
-    pub async fn run_with_stdio(mut self, stdio: Arc<StdioHandler>) -> Result<()> {
+    pub async fn run_with_stdio(self, stdio: Arc<StdioHandler>) -> Result<()> {
         tracing::info!("🧠 Icarus Cognitive System MCP Server");
         tracing::info!("Version: {}", self.server_info.version);
         tracing::info!("Waiting for initialize...");
 
-        loop {
-            let request = match stdio.recv_request().await {
-                Some(req) => req,
-                None => {
-                    tracing::info!("Stdio closed, server exiting");
-                    break;
-                }
-            };
-
-            tracing::debug!("Received request: {} (id: {:?})", request.method, request.id);
-
-            let is_notification = request.id.is_none();
-            let response = self.handle_request(request).await;
-
-            if !is_notification {
-                stdio.send_response(response)?;
-            }
-        }
+        mcp_framework::serve(Arc::new(self), stdio).await?;
 
+        tracing::info!("Stdio closed, server exiting");
         Ok(())
     }
 
-    /// Handle incoming MCP request
-    async fn handle_request(&mut self, request: JsonRpcRequest) -> JsonRpcResponse {
-        match request.method.as_str() {
-            "initialize" => self.handle_initialize(request),
-            "initialized" => JsonRpcResponse {
-                jsonrpc: "2.0".to_string(),
-                id: None,
-                result: None,
-                error: None,
-            },
-            "tools/list" => self.handle_list_tools(request),
-            "tools/call" => self.handle_call_tool(request).await,
-            "resources/list" => self.handle_list_resources(request),
-            method => {
-                tracing::warn!("Unknown method: {}", method);
-                JsonRpcResponse::error(
-                    request.id,
-                    JsonRpcError::method_not_found(method),
-                )
-            }
-        }
-    }
-
-    /// Handle initialize request
-    fn handle_initialize(&mut self, request: JsonRpcRequest) -> JsonRpcResponse {
-        tracing::info!("Handling initialize request");
-        self.initialized = true;
-
-        let result = InitializeResult {
-            protocol_version: "2024-11-05".to_string(),
-            capabilities: ServerCapabilities {
-                tools: Some(ToolsCapability {
-                    list_changed: Some(false),
-                }),
-                resources: Some(ResourcesCapability {
-                    subscribe: Some(false),
-                    list_changed: Some(false),
-                }),
-                prompts: None,
-            },
-            server_info: self.server_info.clone(),
-        };
-
-        JsonRpcResponse::success(request.id, serde_json::to_value(result).unwrap())
-    }
-
-    /// Handle tools/list request
-    fn handle_list_tools(&self, request: JsonRpcRequest) -> JsonRpcResponse {
-        let tools = vec![
-            Tool {
-                name: "icarus_query_status".to_string(),
-                description: "Query the overall status of the Icarus cognitive system including uptime, agent states, memory usage, and event statistics.".to_string(),
-                input_schema: json!({
-                    "type": "object",
-                    "properties": {},
-                    "required": []
-                }),
-            },
-            Tool {
-                name: "icarus_query_agents".to_string(),
-                description: "Query detailed status of Icarus agents (Perception, WorldModel, Planning, Memory, Action, Learning).".to_string(),
-                input_schema: json!({
-                    "type": "object",
-                    "properties": {
-                        "agent_type": {
-                            "type": "string",
-                            "description": "Specific agent to query (optional). Options: perception, world_model, planning, memory, action, learning",
-                            "enum": ["perception", "world_model", "planning", "memory", "action", "learning"]
-                        }
-                    },
-                    "required": []
-                }),
-            },
-            Tool {
-                name: "icarus_send_event".to_string(),
-                description: "Send an event to the Icarus event bus for agent processing.".to_string(),
-                input_schema: json!({
-                    "type": "object",
-                    "properties": {
-                        "event_type": {
-                            "type": "string",
-                            "description": "Type of event to send"
-                        },
-                        "data": {
-                            "type": "object",
-                            "description": "Event data payload"
-                        }
-                    },
-                    "required": ["event_type"]
-                }),
-            },
-            Tool {
-                name: "icarus_query_memory".to_string(),
-                description: "Query Icarus hierarchical memory (working, short-term, long-term, episodic).".to_string(),
-                input_schema: json!({
-                    "type": "object",
-                    "properties": {
-                        "level": {
-                            "type": "string",
-                            "description": "Memory level to query",
-                            "enum": ["working", "short_term", "long_term", "episodic", "all"]
-                        },
-                        "query": {
-                            "type": "string",
-                            "description": "Optional semantic search query"
-                        },
-                        "limit": {
-                            "type": "integer",
-                            "description": "Maximum results to return",
-                            "default": 10
-                        }
-                    },
-                    "required": ["level"]
-                }),
-            },
-            Tool {
-                name: "icarus_query_world_model".to_string(),
-                description: "Query the current state of Icarus's world model and get predictions.".to_string(),
-                input_schema: json!({
-                    "type": "object",
-                    "properties": {
-                        "include_predictions": {
-                            "type": "boolean",
-                            "description": "Include future state predictions",
-                            "default": false
-                        },
-                        "prediction_steps": {
-                            "type": "integer",
-                            "description": "Number of prediction steps (if predictions enabled)",
-                            "default": 5
-                        }
-                    },
-                    "required": []
-                }),
-            },
-            Tool {
-                name: "icarus_execute_action".to_string(),
-                description: "Request Icarus to execute an action via the Action agent.".to_string(),
-                input_schema: json!({
-                    "type": "object",
-                    "properties": {
-                        "action_type": {
-                            "type": "string",
-                            "description": "Type of action to execute"
-                        },
-                        "parameters": {
-                            "type": "object",
-                            "description": "Action parameters"
-                        }
-                    },
-                    "required": ["action_type"]
-                }),
-            },
-            Tool {
-                name: "icarus_neural_state".to_string(),
-                description: "Query the neural core state (SSM, Liquid, RNN layers).".to_string(),
-                input_schema: json!({
-                    "type": "object",
-                    "properties": {
-                        "include_hidden_state": {
-                            "type": "boolean",
-                            "description": "Include detailed hidden state activations",
-                            "default": false
-                        }
-                    },
-                    "required": []
-                }),
-            },
-        ];
-
-        let result = ListToolsResult { tools };
-        JsonRpcResponse::success(request.id, serde_json::to_value(result).unwrap())
-    }
-
-    /// Handle tools/call request
-    async fn handle_call_tool(&self, request: JsonRpcRequest) -> JsonRpcResponse {
-        let params: CallToolParams = match request.params.as_ref().and_then(|p| serde_json::from_value(p.clone()).ok()) {
-            Some(p) => p,
-            None => {
-                return JsonRpcResponse::error(
-                    request.id,
-                    JsonRpcError::invalid_params("Missing or invalid params"),
-                );
-            }
-        };
-
-        let result = match params.name.as_str() {
-            "icarus_query_status" => self.handle_query_status(params.arguments).await,
-            "icarus_query_agents" => self.handle_query_agents(params.arguments).await,
-            "icarus_send_event" => self.handle_send_event(params.arguments).await,
-            "icarus_query_memory" => self.handle_query_memory(params.arguments).await,
-            "icarus_query_world_model" => self.handle_query_world_model(params.arguments).await,
-            "icarus_execute_action" => self.handle_execute_action(params.arguments).await,
-            "icarus_neural_state" => self.handle_neural_state(params.arguments).await,
-            _ => CallToolResult {
-                content: vec![Content::Text {
-                    text: format!("Unknown tool: {}", params.name),
-                }],
-                is_error: Some(true),
-            },
-        };
-
-        JsonRpcResponse::success(request.id, serde_json::to_value(result).unwrap())
-    }
-
-    /// Handle resources/list request
-    fn handle_list_resources(&self, request: JsonRpcRequest) -> JsonRpcResponse {
-        let result = ListResourcesResult {
-            resources: vec![],
-        };
-        JsonRpcResponse::success(request.id, serde_json::to_value(result).unwrap())
-    }
-
-    // ========================================================================
-    // Tool Implementations
-    // ========================================================================
-
-    async fn handle_query_status(&self, _args: serde_json::Value) -> CallToolResult {
-        let icarus_lock = self.icarus.read().await;
-
-        let status = if let Some(ref core) = *icarus_lock {
+    /// Overall status of the cognitive system (agents and memory levels)
+    pub async fn status(&self) -> serde_json::Value {
+        if self.icarus.read().await.is_some() {
             // Icarus is initialized - return real status
             json!({
                 "running": true,
@@ -329,73 +108,55 @@ impl IcarusMCPServer {
                 },
                 "note": "Call icarus_initialize to start the cognitive system."
             })
-        };
-
-        CallToolResult {
-            content: vec![Content::Text {
-                text: serde_json::to_string_pretty(&status).unwrap(),
-            }],
-            is_error: Some(false),
-        }
-    }
-
-    async fn handle_query_agents(&self, _args: serde_json::Value) -> CallToolResult {
-        // TODO: Implement actual agent query
-        CallToolResult {
-            content: vec![Content::Text {
-                text: "Agent query not yet implemented. Building agent intelligence...".to_string(),
-            }],
-            is_error: Some(false),
-        }
-    }
-
-    async fn handle_send_event(&self, _args: serde_json::Value) -> CallToolResult {
-        // TODO: Implement event sending
-        CallToolResult {
-            content: vec![Content::Text {
-                text: "Event sending not yet implemented.".to_string(),
-            }],
-            is_error: Some(false),
         }
     }
 
-    async fn handle_query_memory(&self, _args: serde_json::Value) -> CallToolResult {
-        // TODO: Implement memory query
-        CallToolResult {
-            content: vec![Content::Text {
-                text: "Memory query not yet implemented. Integrating vector database...".to_string(),
-            }],
-            is_error: Some(false),
-        }
-    }
+    /// Handle initialize request
+    fn handle_initialize(&self, request: JsonRpcRequest) -> JsonRpcResponse {
+        tracing::info!("Handling initialize request");
+        self.initialized.store(true, Ordering::Relaxed);
 
-    async fn handle_query_world_model(&self, _args: serde_json::Value) -> CallToolResult {
-        // TODO: Implement world model query
-        CallToolResult {
-            content: vec![Content::Text {
-                text: "World model query not yet implemented.".to_string(),
-            }],
-            is_error: Some(false),
-        }
-    }
+        let result = InitializeResult {
+            protocol_version: "2024-11-05".to_string(),
+            capabilities: ServerCapabilities {
+                tools: Some(ToolsCapability {
+                    list_changed: Some(false),
+                }),
+                resources: Some(ResourcesCapability {
+                    subscribe: Some(false),
+                    list_changed: Some(false),
+                }),
+                prompts: Some(PromptsCapability {
+                    list_changed: Some(false),
+                }),
+            },
+            server_info: self.server_info.clone(),
+        };
 
-    async fn handle_execute_action(&self, _args: serde_json::Value) -> CallToolResult {
-        // TODO: Implement action execution
-        CallToolResult {
-            content: vec![Content::Text {
-                text: "Action execution not yet implemented.".to_string(),
-            }],
-            is_error: Some(false),
-        }
+        JsonRpcResponse::success(request.id, serde_json::to_value(result).unwrap())
     }
+}
 
-    async fn handle_neural_state(&self, _args: serde_json::Value) -> CallToolResult {
-        // TODO: Implement neural state query
-        CallToolResult {
-            content: vec![Content::Text {
-                text: "Neural state query not yet implemented. Building neural core...".to_string(),
-            }],
-            is_error: Some(false),
+#[async_trait]
+impl McpServer for IcarusMCPServer {
+    /// Handle incoming MCP request
+    async fn handle_request(&self, request: JsonRpcRequest, ctx: RequestContext) -> JsonRpcResponse {
+        match request.method.as_str() {
+            "initialize" => self.handle_initialize(request),
+            "tools/list" => self.tools.handle_list(request),
+            "tools/call" => self.tools.handle_call(self, request, &ctx).await,
+            "resources/list" => self.resources.handle_list(self, request),
+            "resources/templates/list" => self.resources.handle_templates_list(request),
+            "resources/read" => self.resources.handle_read(self, request).await,
+            "prompts/list" => self.prompts.handle_list(request),
+            "prompts/get" => self.prompts.handle_get(self, request),
+            method => {
+                tracing::warn!("Unknown method: {}", method);
+                JsonRpcResponse::error(
+                    request.id,
+                    JsonRpcError::method_not_found(method),
+                )
+            }
         }
     }
 }
@@ -0,0 +1,217 @@
+// Icarus MCP Tools
+// One `ToolHandler` per tool; params structs double as input schemas
+
+use super::server::IcarusMCPServer;
+use anyhow::Result;
+use async_trait::async_trait;
+use mcp_framework::{NoParams, RequestContext, ToolHandler, ToolRouter};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::Value;
+
+/// All tools, in the order `tools/list` reports them
+pub fn router() -> ToolRouter<IcarusMCPServer> {
+    ToolRouter::new()
+        .with(QueryStatus)
+        .with(QueryAgents)
+        .with(SendEvent)
+        .with(QueryMemory)
+        .with(QueryWorldModel)
+        .with(ExecuteAction)
+        .with(NeuralState)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AgentKind {
+    Perception,
+    WorldModel,
+    Planning,
+    Memory,
+    Action,
+    Learning,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MemoryLevel {
+    Working,
+    ShortTerm,
+    LongTerm,
+    Episodic,
+    All,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct QueryAgentsParams {
+    /// Specific agent to query (default: all agents)
+    #[serde(default)]
+    pub agent_type: Option<AgentKind>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct SendEventParams {
+    /// Type of event to send
+    pub event_type: String,
+    /// Event data payload
+    #[serde(default)]
+    pub data: Option<serde_json::Map<String, Value>>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct QueryMemoryParams {
+    /// Memory level to query
+    pub level: MemoryLevel,
+    /// Optional semantic search query
+    #[serde(default)]
+    pub query: Option<String>,
+    /// Maximum results to return
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+}
+
+fn default_limit() -> usize { 10 }
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct QueryWorldModelParams {
+    /// Include future state predictions
+    #[serde(default)]
+    pub include_predictions: bool,
+    /// Number of prediction steps (if predictions enabled)
+    #[serde(default = "default_prediction_steps")]
+    pub prediction_steps: usize,
+}
+
+fn default_prediction_steps() -> usize { 5 }
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ExecuteActionParams {
+    /// Type of action to execute
+    pub action_type: String,
+    /// Action parameters
+    #[serde(default)]
+    pub parameters: Option<serde_json::Map<String, Value>>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct NeuralStateParams {
+    /// Include detailed hidden state activations
+    #[serde(default)]
+    pub include_hidden_state: bool,
+}
+
+struct QueryStatus;
+
+#[async_trait]
+impl ToolHandler<IcarusMCPServer> for QueryStatus {
+    type Params = NoParams;
+    const NAME: &'static str = "icarus_query_status";
+    const DESCRIPTION: &'static str = "Query the overall status of the Icarus cognitive system including uptime, agent states, memory usage, and event statistics.";
+
+    async fn call(&self, server: &IcarusMCPServer, _params: NoParams, _ctx: &RequestContext) -> Result<Value> {
+        Ok(server.status().await)
+    }
+}
+
+struct QueryAgents;
+
+#[async_trait]
+impl ToolHandler<IcarusMCPServer> for QueryAgents {
+    type Params = QueryAgentsParams;
+    const NAME: &'static str = "icarus_query_agents";
+    const DESCRIPTION: &'static str = "Query detailed status of Icarus agents (Perception, WorldModel, Planning, Memory, Action, Learning).";
+
+    async fn call(&self, _server: &IcarusMCPServer, _params: QueryAgentsParams, _ctx: &RequestContext) -> Result<Value> {
+        // TODO: Implement actual agent query
+        Ok("Agent query not yet implemented. Building agent intelligence...".into())
+    }
+}
+
+struct SendEvent;
+
+#[async_trait]
+impl ToolHandler<IcarusMCPServer> for SendEvent {
+    type Params = SendEventParams;
+    const NAME: &'static str = "icarus_send_event";
+    const DESCRIPTION: &'static str = "Send an event to the Icarus event bus for agent processing.";
+
+    async fn call(&self, _server: &IcarusMCPServer, _params: SendEventParams, _ctx: &RequestContext) -> Result<Value> {
+        // TODO: Implement event sending
+        Ok("Event sending not yet implemented.".into())
+    }
+}
+
+struct QueryMemory;
+
+#[async_trait]
+impl ToolHandler<IcarusMCPServer> for QueryMemory {
+    type Params = QueryMemoryParams;
+    const NAME: &'static str = "icarus_query_memory";
+    const DESCRIPTION: &'static str = "Query Icarus hierarchical memory (working, short-term, long-term, episodic).";
+
+    async fn call(&self, _server: &IcarusMCPServer, _params: QueryMemoryParams, _ctx: &RequestContext) -> Result<Value> {
+        // TODO: Implement memory query
+        Ok("Memory query not yet implemented. Integrating vector database...".into())
+    }
+}
+
+struct QueryWorldModel;
+
+#[async_trait]
+impl ToolHandler<IcarusMCPServer> for QueryWorldModel {
+    type Params = QueryWorldModelParams;
+    const NAME: &'static str = "icarus_query_world_model";
+    const DESCRIPTION: &'static str = "Query the current state of Icarus's world model and get predictions.";
+
+    async fn call(&self, _server: &IcarusMCPServer, _params: QueryWorldModelParams, _ctx: &RequestContext) -> Result<Value> {
+        // TODO: Implement world model query
+        Ok("World model query not yet implemented.".into())
+    }
+}
+
+struct ExecuteAction;
+
+#[async_trait]
+impl ToolHandler<IcarusMCPServer> for ExecuteAction {
+    type Params = ExecuteActionParams;
+    const NAME: &'static str = "icarus_execute_action";
+    const DESCRIPTION: &'static str = "Request Icarus to execute an action via the Action agent.";
+
+    async fn call(&self, _server: &IcarusMCPServer, _params: ExecuteActionParams, _ctx: &RequestContext) -> Result<Value> {
+        // TODO: Implement action execution
+        Ok("Action execution not yet implemented.".into())
+    }
+}
+
+struct NeuralState;
+
+#[async_trait]
+impl ToolHandler<IcarusMCPServer> for NeuralState {
+    type Params = NeuralStateParams;
+    const NAME: &'static str = "icarus_neural_state";
+    const DESCRIPTION: &'static str = "Query the neural core state (SSM, Liquid, RNN layers).";
+
+    async fn call(&self, _server: &IcarusMCPServer, _params: NeuralStateParams, _ctx: &RequestContext) -> Result<Value> {
+        // TODO: Implement neural state query
+        Ok("Neural state query not yet implemented. Building neural core...".into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_enum_params_in_schema() {
+        let tools = router().list();
+        assert_eq!(tools.len(), 7);
+
+        let memory = tools.iter().find(|t| t.name == "icarus_query_memory").unwrap();
+        assert_eq!(memory.input_schema["required"], json!(["level"]));
+        assert_eq!(
+            memory.input_schema["properties"]["level"]["enum"],
+            json!(["working", "short_term", "long_term", "episodic", "all"])
+        );
+    }
+}
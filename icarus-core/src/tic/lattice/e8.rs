@@ -209,7 +209,7 @@ mod tests {
         // Type 1: 112 roots (±1, ±1, 0, 0, 0, 0, 0, 0) permutations
         let type1_count = roots.iter().filter(|r| r.iter().filter(|&&x| x != 0).count() == 2).count();
         // Type 2: 128 roots (all ±1 with even parity)
-        let type2_count = roots.iter().filter(|r| r.iter().all(|&&x| x.abs() == 1)).count();
+        let type2_count = roots.iter().filter(|r| r.iter().all(|&x| x.abs() == 1)).count();
 
         println!("Type 1 roots: {}", type1_count);
         println!("Type 2 roots: {}", type2_count);
@@ -1,7 +1,7 @@
 // Integration Tests for Markovian Thinker Phase 6
 // Tests storm mitigation integration with session manager
 
-use markovian_thinker::*;
+use icarus_core::markovian::*;
 
 #[tokio::test]
 async fn test_session_manager_with_storm_mitigation() {
@@ -173,7 +173,7 @@ fn create_test_event(prompt: &str, priority: f32) -> EventWithMetadata {
 
 #[tokio::test]
 async fn test_causal_trace_integration() {
-    use markovian_thinker::session_manager::SessionManager;
+    use icarus_core::markovian::session_manager::SessionManager;
 
     let manager = SessionManager::new();
     let mut config = StateConfig::default();
@@ -202,7 +202,7 @@ async fn test_causal_trace_integration() {
 
 #[tokio::test]
 async fn test_causal_trace_not_enabled() {
-    use markovian_thinker::session_manager::SessionManager;
+    use icarus_core::markovian::session_manager::SessionManager;
 
     let manager = SessionManager::new();
     let mut config = StateConfig::default();
@@ -220,7 +220,7 @@ async fn test_causal_trace_not_enabled() {
 
 #[tokio::test]
 async fn test_concept_space_integration() {
-    use markovian_thinker::session_manager::SessionManager;
+    use icarus_core::markovian::session_manager::SessionManager;
 
     let manager = SessionManager::new();
     let config = StateConfig::default(); // Concept space always created
@@ -259,7 +259,7 @@ async fn test_concept_space_integration() {
 
 #[tokio::test]
 async fn test_event_driven_chunk_processing() {
-    use markovian_thinker::chunk_manager::{ChunkGenerator, ChunkManager};
+    use icarus_core::markovian::chunk_manager::{ChunkGenerator, ChunkManager};
 
     // Mock generator
     struct MockGen;
@@ -312,7 +312,7 @@ async fn test_event_driven_chunk_processing() {
 
 #[tokio::test]
 async fn test_event_driven_disabled_by_default() {
-    use markovian_thinker::chunk_manager::ChunkManager;
+    use icarus_core::markovian::chunk_manager::ChunkManager;
 
     let config = StateConfig::default();
     let manager = ChunkManager::new(config);
@@ -324,7 +324,7 @@ async fn test_event_driven_disabled_by_default() {
 
 #[tokio::test]
 async fn test_causal_dependencies_tracked() {
-    use markovian_thinker::chunk_manager::{ChunkGenerator, ChunkManager};
+    use icarus_core::markovian::chunk_manager::{ChunkGenerator, ChunkManager};
 
     // Mock generator that produces 3 chunks
     struct MockGen {
@@ -400,7 +400,7 @@ async fn test_causal_dependencies_tracked() {
 
 #[tokio::test]
 async fn test_causal_trace_first_chunk_no_predecessors() {
-    use markovian_thinker::chunk_manager::{ChunkGenerator, ChunkManager};
+    use icarus_core::markovian::chunk_manager::{ChunkGenerator, ChunkManager};
 
     struct MockGen;
 
@@ -442,7 +442,7 @@ async fn test_causal_trace_first_chunk_no_predecessors() {
 
 #[tokio::test]
 async fn test_intelligent_carryover_enabled() {
-    use markovian_thinker::chunk_manager::{ChunkGenerator, ChunkManager};
+    use icarus_core::markovian::chunk_manager::{ChunkGenerator, ChunkManager};
 
     // Mock generator that produces different themed chunks
     struct ThemeMockGen {
@@ -498,8 +498,8 @@ async fn test_intelligent_carryover_enabled() {
 
 #[tokio::test]
 async fn test_attention_compression() {
-    use markovian_thinker::chunk_manager::{ChunkGenerator, ChunkManager};
-    use markovian_thinker::attention::AttentionConfig;
+    use icarus_core::markovian::chunk_manager::{ChunkGenerator, ChunkManager};
+    use icarus_core::markovian::attention::AttentionConfig;
 
     // Mock generator that produces long chunks
     struct LongChunkGen {
@@ -561,8 +561,8 @@ async fn test_attention_compression() {
 
 #[tokio::test]
 async fn test_batch_session_creation() {
-    use markovian_thinker::session_manager::SessionManager;
-    use markovian_thinker::state::StateConfig;
+    use icarus_core::markovian::session_manager::SessionManager;
+    use icarus_core::markovian::state::StateConfig;
 
     // Use a fresh manager for this test
     let manager = SessionManager::new();
@@ -606,3 +606,60 @@ async fn test_batch_session_creation() {
     println!("Batch session creation test completed - created {} sessions", session_ids.len());
 }
 
+#[tokio::test]
+async fn test_chunk_manager_with_openai_backend() {
+    use icarus_core::markovian::chunk_manager::ChunkManager;
+
+    // Two chunks of reasoning served by the in-process stub server
+    let server = MockOpenAiServer::start(vec![
+        MockResponse::completion("Let me compute 6*7 step by step", 40),
+        MockResponse::completion("6*7 = 42 #### 42", 12),
+    ])
+    .await
+    .unwrap();
+
+    let mut generator_config = OpenAiGeneratorConfig::new(server.base_url(), "stub-model");
+    generator_config.api_key_env = None;
+    let generator = OpenAiGenerator::new(generator_config).unwrap();
+
+    let config = StateConfig::new(100, 50, 5).unwrap();
+    let mut manager = ChunkManager::new(config);
+    let trace = manager
+        .generate_trace("What is 6*7?".to_string(), &generator)
+        .await
+        .unwrap();
+
+    assert_eq!(trace.chunks.len(), 2);
+    assert_eq!(trace.total_tokens, 52);
+    assert_eq!(trace.solution, Some("42".to_string()));
+    assert_eq!(trace.termination_reason, TerminationReason::SolutionFound);
+
+    // Each chunk request is capped at the configured chunk size
+    let requests = server.requests();
+    assert_eq!(requests.len(), 2);
+    assert!(requests.iter().all(|r| r.body["max_tokens"] == 100));
+}
+
+#[tokio::test]
+async fn test_replay_pinned_cassette() {
+    // Pins prompt building, carryover extraction, termination and solution
+    // parsing: any change to those makes the replayed prompts diverge
+    let cassette = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/train_catchup.jsonl");
+    let replay = ReplayGenerator::load(cassette).unwrap();
+
+    let config = StateConfig::new(100, 20, 5).unwrap();
+    let mut manager = ChunkManager::new(config);
+    let trace = manager
+        .generate_trace(
+            "When does the second train catch up with the first?".to_string(),
+            &replay,
+        )
+        .await
+        .unwrap();
+
+    assert!(replay.is_exhausted(), "{} recorded chunks unused", replay.remaining());
+    assert_eq!(trace.termination_reason, TerminationReason::SolutionFound);
+    assert_eq!(trace.solution, Some("6pm".to_string()));
+    assert_eq!(trace.chunks.len(), 3);
+    assert_eq!(trace.total_tokens, 146);
+}
@@ -0,0 +1,124 @@
+// MCP Request Context
+// What a handler gets besides the request itself: a `Progress` reporter for
+// the `progressToken` the client may have put in the request's `_meta`, and a
+// `CancellationToken` that `serve` fires on `notifications/cancelled`.
+
+use crate::protocol::*;
+use crate::stdio::Notifier;
+use anyhow::Result;
+
+pub use tokio_util::sync::CancellationToken;
+
+/// Sends `notifications/progress` for one request, if the client asked for it
+///
+/// Cloneable and `'static`, so it can be moved into blocking tasks.
+#[derive(Clone, Default)]
+pub struct Progress {
+    target: Option<(ProgressToken, Notifier)>,
+}
+
+impl Progress {
+    pub fn new(token: ProgressToken, notifier: Notifier) -> Self {
+        Self {
+            target: Some((token, notifier)),
+        }
+    }
+
+    /// Whether the client asked for progress
+    pub fn is_requested(&self) -> bool {
+        self.target.is_some()
+    }
+
+    /// Report `progress` (of `total`, if known); `progress` must increase
+    /// between calls. Does nothing if the client did not ask for progress.
+    pub fn report(&self, progress: usize, total: Option<usize>, message: impl Into<String>) {
+        let Some((token, notifier)) = &self.target else { return };
+
+        let params = ProgressParams {
+            progress_token: token.clone(),
+            progress: progress as f64,
+            total: total.map(|total| total as f64),
+            message: Some(message.into()),
+        };
+        let params = serde_json::to_value(params).unwrap_or_default();
+        if let Err(e) = notifier.send_notification("notifications/progress", Some(params)) {
+            tracing::debug!("Dropping progress notification: {}", e);
+        }
+    }
+}
+
+/// Progress and cancellation of the request being handled
+#[derive(Clone, Default)]
+pub struct RequestContext {
+    pub progress: Progress,
+    pub cancel: CancellationToken,
+}
+
+impl RequestContext {
+    pub fn new(progress: Progress, cancel: CancellationToken) -> Self {
+        Self { progress, cancel }
+    }
+
+    /// Context of `request`: progress goes to its `_meta.progressToken`, if any
+    pub fn for_request(request: &JsonRpcRequest, notifier: Notifier) -> Self {
+        let token = request
+            .params
+            .as_ref()
+            .and_then(|params| params.get("_meta"))
+            .and_then(|meta| meta.get("progressToken"))
+            .and_then(|token| serde_json::from_value::<ProgressToken>(token.clone()).ok());
+
+        Self {
+            progress: token.map(|token| Progress::new(token, notifier)).unwrap_or_default(),
+            cancel: CancellationToken::new(),
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    /// Fail once the request is cancelled, for checks between steps of work
+    pub fn check_cancelled(&self) -> Result<()> {
+        if self.is_cancelled() {
+            anyhow::bail!("Request cancelled");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stdio::StdioHandler;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_progress_follows_the_request_token() {
+        let ((server, _), (client, _)) = StdioHandler::in_memory_pair();
+
+        let request = JsonRpcRequest::new(
+            Some(RequestId::Number(1)),
+            "tools/call".to_string(),
+            Some(json!({"name": "t", "_meta": {"progressToken": "abc"}})),
+        );
+        let ctx = RequestContext::for_request(&request, server.notifier());
+        assert!(ctx.progress.is_requested());
+        ctx.progress.report(1, Some(4), "Chunk 1/4");
+
+        let notification = client.recv_notification().await.unwrap();
+        assert_eq!(notification.method, "notifications/progress");
+        assert_eq!(
+            notification.params,
+            Some(json!({"progressToken": "abc", "progress": 1.0, "total": 4.0, "message": "Chunk 1/4"}))
+        );
+
+        // Without a token nothing is sent
+        let request = JsonRpcRequest::new(Some(RequestId::Number(2)), "tools/call".to_string(), None);
+        let ctx = RequestContext::for_request(&request, server.notifier());
+        assert!(!ctx.progress.is_requested());
+        ctx.check_cancelled().unwrap();
+        ctx.cancel.cancel();
+        assert!(ctx.check_cancelled().is_err());
+    }
+}
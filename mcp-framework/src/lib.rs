@@ -0,0 +1,27 @@
+//! Model Context Protocol framework shared by markovian-thinker and icarus-core
+//!
+//! - `protocol`: JSON-RPC 2.0 and MCP message types
+//! - `stdio`: a full-duplex JSON-RPC session over stdio (or any byte stream)
+//! - `tool`: the `ToolHandler` trait and the `ToolRouter` that lists and calls tools
+//! - `resource`: the `ResourceHandler` trait and the `ResourceRouter` that lists,
+//!   reads and tracks subscriptions to resources
+//! - `prompt`: the `PromptHandler` trait and the `PromptRouter` that lists and
+//!   renders prompt templates
+//! - `context`: per-request progress reporting and cancellation
+//! - `server`: the `McpServer` trait and the `serve` loop
+
+pub mod prompt;
+pub mod context;
+pub mod protocol;
+pub mod resource;
+pub mod server;
+pub mod stdio;
+pub mod tool;
+
+pub use context::{CancellationToken, Progress, RequestContext};
+pub use prompt::{prompt_arguments, PromptHandler, PromptRouter};
+pub use protocol::*;
+pub use resource::{match_uri, ResourceHandler, ResourceRouter, UriVars};
+pub use server::{serve, McpServer};
+pub use stdio::{Notifier, StdioHandler};
+pub use tool::{input_schema, NoParams, ToolHandler, ToolRouter};
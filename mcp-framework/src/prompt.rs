@@ -0,0 +1,229 @@
+// MCP Prompts
+// A prompt is a `PromptHandler`: a name, a description, a typed arguments
+// struct (whose fields become the prompt's arguments) and a render function
+// producing the messages. `PromptRouter` answers prompts/list and prompts/get.
+
+use crate::protocol::*;
+use crate::tool::input_schema;
+use anyhow::Result;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+/// One MCP prompt template, rendered with the server state `S`
+pub trait PromptHandler<S>: Send + Sync + 'static {
+    /// Arguments; MCP passes them as strings, so fields should be `String`
+    /// or `Option<String>`. Doc comments become argument descriptions.
+    type Args: DeserializeOwned + JsonSchema;
+
+    const NAME: &'static str;
+    const DESCRIPTION: &'static str;
+
+    /// Render the prompt
+    fn get(&self, state: &S, args: Self::Args) -> Result<Vec<PromptMessage>>;
+}
+
+/// Prompt arguments from the JSON schema of an arguments struct
+pub fn prompt_arguments<A: JsonSchema>() -> Vec<PromptArgument> {
+    let schema = input_schema::<A>();
+    let required: Vec<&str> = schema["required"]
+        .as_array()
+        .map(|names| names.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default();
+
+    schema["properties"]
+        .as_object()
+        .map(|properties| {
+            properties
+                .iter()
+                .map(|(name, property)| PromptArgument {
+                    name: name.clone(),
+                    description: property["description"].as_str().map(str::to_string),
+                    required: required.contains(&name.as_str()),
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Type-erased `PromptHandler`
+trait RegisteredPrompt<S>: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn definition(&self) -> Prompt;
+    fn get(&self, state: &S, arguments: Value) -> Result<Vec<PromptMessage>>;
+}
+
+struct Registered<T>(T);
+
+impl<S, T: PromptHandler<S>> RegisteredPrompt<S> for Registered<T> {
+    fn name(&self) -> &'static str {
+        T::NAME
+    }
+
+    fn definition(&self) -> Prompt {
+        Prompt {
+            name: T::NAME.to_string(),
+            description: Some(T::DESCRIPTION.to_string()),
+            arguments: prompt_arguments::<T::Args>(),
+        }
+    }
+
+    fn get(&self, state: &S, arguments: Value) -> Result<Vec<PromptMessage>> {
+        let args = serde_json::from_value(arguments)
+            .map_err(|e| anyhow::anyhow!("Invalid arguments for {}: {}", T::NAME, e))?;
+        self.0.get(state, args)
+    }
+}
+
+/// Registered prompts of a server with state `S`, in registration order
+pub struct PromptRouter<S> {
+    prompts: Vec<Box<dyn RegisteredPrompt<S>>>,
+}
+
+impl<S: 'static> PromptRouter<S> {
+    pub fn new() -> Self {
+        Self { prompts: Vec::new() }
+    }
+
+    /// Add a prompt
+    ///
+    /// # Panics
+    /// If a prompt with the same name is already registered
+    pub fn register<T: PromptHandler<S>>(&mut self, prompt: T) -> &mut Self {
+        assert!(!self.contains(T::NAME), "Prompt {} registered twice", T::NAME);
+        self.prompts.push(Box::new(Registered(prompt)));
+        self
+    }
+
+    /// Builder form of `register`
+    pub fn with<T: PromptHandler<S>>(mut self, prompt: T) -> Self {
+        self.register(prompt);
+        self
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.prompts.iter().any(|prompt| prompt.name() == name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.prompts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.prompts.is_empty()
+    }
+
+    /// Definitions for `prompts/list`
+    pub fn list(&self) -> Vec<Prompt> {
+        self.prompts.iter().map(|prompt| prompt.definition()).collect()
+    }
+
+    /// Render a prompt
+    pub fn get(&self, state: &S, params: GetPromptParams) -> Result<GetPromptResult> {
+        let prompt = self
+            .prompts
+            .iter()
+            .find(|prompt| prompt.name() == params.name)
+            .ok_or_else(|| anyhow::anyhow!("Unknown prompt: {}", params.name))?;
+
+        let arguments = serde_json::to_value(params.arguments)?;
+        Ok(GetPromptResult {
+            description: prompt.definition().description,
+            messages: prompt.get(state, arguments)?,
+        })
+    }
+
+    /// Answer a `prompts/list` request
+    pub fn handle_list(&self, request: JsonRpcRequest) -> JsonRpcResponse {
+        let result = ListPromptsResult { prompts: self.list() };
+        JsonRpcResponse::success(request.id, serde_json::to_value(result).unwrap())
+    }
+
+    /// Answer a `prompts/get` request; unknown prompts and bad arguments are
+    /// invalid params
+    pub fn handle_get(&self, state: &S, request: JsonRpcRequest) -> JsonRpcResponse {
+        let params = match request.params.clone().map(serde_json::from_value::<GetPromptParams>) {
+            Some(Ok(params)) => params,
+            Some(Err(e)) => {
+                return JsonRpcResponse::error(request.id, JsonRpcError::invalid_params(&e.to_string()));
+            }
+            None => {
+                return JsonRpcResponse::error(request.id, JsonRpcError::invalid_params("Missing params"));
+            }
+        };
+
+        match self.get(state, params) {
+            Ok(result) => JsonRpcResponse::success(request.id, serde_json::to_value(result).unwrap()),
+            Err(e) => JsonRpcResponse::error(request.id, JsonRpcError::invalid_params(&e.to_string())),
+        }
+    }
+}
+
+impl<S: 'static> Default for PromptRouter<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use serde_json::json;
+
+    struct Greeter {
+        greeting: &'static str,
+    }
+
+    #[derive(Deserialize, JsonSchema)]
+    struct GreetArgs {
+        /// Who to greet
+        name: String,
+        /// Trailing punctuation
+        punctuation: Option<String>,
+    }
+
+    struct Greet;
+
+    impl PromptHandler<Greeter> for Greet {
+        type Args = GreetArgs;
+        const NAME: &'static str = "greet";
+        const DESCRIPTION: &'static str = "Greet someone.";
+
+        fn get(&self, state: &Greeter, args: GreetArgs) -> Result<Vec<PromptMessage>> {
+            let punctuation = args.punctuation.unwrap_or_else(|| "!".to_string());
+            Ok(vec![PromptMessage::user(format!("{}, {}{}", state.greeting, args.name, punctuation))])
+        }
+    }
+
+    #[test]
+    fn test_arguments_from_schema() {
+        let router = PromptRouter::new().with(Greet);
+        let prompts = router.list();
+        assert_eq!(prompts.len(), 1);
+
+        let arguments = &prompts[0].arguments;
+        assert_eq!(arguments.len(), 2);
+        let name = arguments.iter().find(|a| a.name == "name").unwrap();
+        assert!(name.required);
+        assert_eq!(name.description.as_deref(), Some("Who to greet"));
+        assert!(!arguments.iter().find(|a| a.name == "punctuation").unwrap().required);
+    }
+
+    #[test]
+    fn test_handle_get() {
+        let router = PromptRouter::new().with(Greet);
+        let state = Greeter { greeting: "Hello" };
+        let get = |params: Value| {
+            router.handle_get(&state, JsonRpcRequest::new(Some(RequestId::Number(1)), "prompts/get".to_string(), Some(params)))
+        };
+
+        let response = get(json!({"name": "greet", "arguments": {"name": "Ada"}}));
+        let result: GetPromptResult = serde_json::from_value(response.result.unwrap()).unwrap();
+        assert_eq!(result.messages[0].content.as_text(), Some("Hello, Ada!"));
+
+        // Missing required arguments and unknown prompts are invalid params
+        assert_eq!(get(json!({"name": "greet"})).error.unwrap().code, -32602);
+        assert_eq!(get(json!({"name": "missing"})).error.unwrap().code, -32602);
+    }
+}
@@ -0,0 +1,391 @@
+// MCP Resources
+// A resource is a `ResourceHandler`: a URI (or a URI template such as
+// `app://sessions/{id}`), a name and an async read. `ResourceRouter` answers
+// resources/list, resources/templates/list and resources/read, and tracks the
+// URIs the client subscribed to so updates can be announced.
+
+use crate::protocol::*;
+use crate::stdio::StdioHandler;
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::sync::RwLock;
+
+/// Values of a URI template's placeholders, by name
+pub type UriVars = HashMap<String, String>;
+
+/// One resource, or a family of resources under a URI template
+#[async_trait]
+pub trait ResourceHandler<S: Send + Sync>: Send + Sync + 'static {
+    /// URI, or a template whose `{name}` placeholders match text without '/'
+    const URI: &'static str;
+    const NAME: &'static str;
+    const DESCRIPTION: &'static str;
+    const MIME_TYPE: &'static str = "application/json";
+
+    /// Current members of a templated resource, for `resources/list`
+    /// (resources with a plain URI are listed without this)
+    fn instances(&self, _state: &S) -> Vec<Resource> {
+        Vec::new()
+    }
+
+    /// Read the resource; strings are returned verbatim, anything else as
+    /// pretty-printed JSON
+    async fn read(&self, state: &S, vars: &UriVars) -> Result<Value>;
+}
+
+/// Type-erased `ResourceHandler`
+#[async_trait]
+trait RegisteredResource<S>: Send + Sync {
+    fn uri(&self) -> &'static str;
+    fn list(&self, state: &S) -> Vec<Resource>;
+    fn template(&self) -> Option<ResourceTemplate>;
+    fn mime_type(&self) -> &'static str;
+    async fn read(&self, state: &S, vars: &UriVars) -> Result<Value>;
+}
+
+struct Registered<T>(T);
+
+#[async_trait]
+impl<S, T> RegisteredResource<S> for Registered<T>
+where
+    S: Send + Sync,
+    T: ResourceHandler<S>,
+{
+    fn uri(&self) -> &'static str {
+        T::URI
+    }
+
+    fn list(&self, state: &S) -> Vec<Resource> {
+        if is_template(T::URI) {
+            return self.0.instances(state);
+        }
+        vec![Resource {
+            uri: T::URI.to_string(),
+            name: T::NAME.to_string(),
+            description: Some(T::DESCRIPTION.to_string()),
+            mime_type: Some(T::MIME_TYPE.to_string()),
+        }]
+    }
+
+    fn template(&self) -> Option<ResourceTemplate> {
+        is_template(T::URI).then(|| ResourceTemplate {
+            uri_template: T::URI.to_string(),
+            name: T::NAME.to_string(),
+            description: Some(T::DESCRIPTION.to_string()),
+            mime_type: Some(T::MIME_TYPE.to_string()),
+        })
+    }
+
+    fn mime_type(&self) -> &'static str {
+        T::MIME_TYPE
+    }
+
+    async fn read(&self, state: &S, vars: &UriVars) -> Result<Value> {
+        self.0.read(state, vars).await
+    }
+}
+
+fn is_template(uri: &str) -> bool {
+    uri.contains('{')
+}
+
+/// Match `uri` against a URI or URI template, returning the placeholder values
+pub fn match_uri(template: &str, uri: &str) -> Option<UriVars> {
+    let mut vars = UriVars::new();
+    let mut pattern = template;
+    let mut rest = uri;
+
+    while let Some(start) = pattern.find('{') {
+        rest = rest.strip_prefix(&pattern[..start])?;
+        let end = start + pattern[start..].find('}')?;
+        let name = &pattern[start + 1..end];
+        pattern = &pattern[end + 1..];
+
+        // The value runs up to the literal text that follows the placeholder
+        let literal = pattern.find('{').map_or(pattern, |next| &pattern[..next]);
+        let len = if literal.is_empty() { rest.len() } else { rest.find(literal)? };
+        let value = &rest[..len];
+        if value.is_empty() || value.contains('/') {
+            return None;
+        }
+        vars.insert(name.to_string(), value.to_string());
+        rest = &rest[len..];
+    }
+
+    (rest == pattern).then_some(vars)
+}
+
+/// Registered resources of a server with state `S`, and the client's subscriptions
+pub struct ResourceRouter<S> {
+    resources: Vec<Box<dyn RegisteredResource<S>>>,
+    subscriptions: RwLock<HashSet<String>>,
+}
+
+impl<S: Send + Sync + 'static> ResourceRouter<S> {
+    pub fn new() -> Self {
+        Self {
+            resources: Vec::new(),
+            subscriptions: RwLock::new(HashSet::new()),
+        }
+    }
+
+    /// Add a resource
+    ///
+    /// # Panics
+    /// If a resource with the same URI is already registered
+    pub fn register<T: ResourceHandler<S>>(&mut self, resource: T) -> &mut Self {
+        assert!(
+            self.resources.iter().all(|r| r.uri() != T::URI),
+            "Resource {} registered twice",
+            T::URI
+        );
+        self.resources.push(Box::new(Registered(resource)));
+        self
+    }
+
+    /// Builder form of `register`
+    pub fn with<T: ResourceHandler<S>>(mut self, resource: T) -> Self {
+        self.register(resource);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.resources.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.resources.is_empty()
+    }
+
+    /// Resources for `resources/list`, including current members of templates
+    pub fn list(&self, state: &S) -> Vec<Resource> {
+        self.resources.iter().flat_map(|r| r.list(state)).collect()
+    }
+
+    /// URI templates for `resources/templates/list`
+    pub fn templates(&self) -> Vec<ResourceTemplate> {
+        self.resources.iter().filter_map(|r| r.template()).collect()
+    }
+
+    fn find(&self, uri: &str) -> Option<(&dyn RegisteredResource<S>, UriVars)> {
+        self.resources
+            .iter()
+            .find_map(|r| match_uri(r.uri(), uri).map(|vars| (r.as_ref(), vars)))
+    }
+
+    /// True if some registered resource (or template) matches `uri`
+    pub fn contains(&self, uri: &str) -> bool {
+        self.find(uri).is_some()
+    }
+
+    /// Read a resource; None if no resource matches `uri`
+    pub async fn read(&self, state: &S, uri: &str) -> Option<Result<ReadResourceResult>> {
+        let (resource, vars) = self.find(uri)?;
+        let result = resource.read(state, &vars).await.map(|value| {
+            let text = match value {
+                Value::String(text) => text,
+                value => serde_json::to_string_pretty(&value).unwrap_or_default(),
+            };
+            ReadResourceResult {
+                contents: vec![ResourceContents {
+                    uri: uri.to_string(),
+                    mime_type: Some(resource.mime_type().to_string()),
+                    content: Content::text(text),
+                }],
+            }
+        });
+        Some(result)
+    }
+
+    pub fn is_subscribed(&self, uri: &str) -> bool {
+        self.subscriptions.read().map(|s| s.contains(uri)).unwrap_or(false)
+    }
+
+    /// Send `notifications/resources/updated` for `uri` if the client subscribed to it
+    pub fn notify_updated(&self, session: &StdioHandler, uri: &str) -> Result<()> {
+        if !self.is_subscribed(uri) {
+            return Ok(());
+        }
+        let params = serde_json::to_value(ResourceUriParams { uri: uri.to_string() })?;
+        session.send_notification("notifications/resources/updated", Some(params))
+    }
+
+    /// Send `notifications/resources/list_changed` (e.g. after a templated resource gains a member)
+    pub fn notify_list_changed(&self, session: &StdioHandler) -> Result<()> {
+        session.send_notification("notifications/resources/list_changed", None)
+    }
+
+    /// Answer a `resources/list` request
+    pub fn handle_list(&self, state: &S, request: JsonRpcRequest) -> JsonRpcResponse {
+        let result = ListResourcesResult { resources: self.list(state) };
+        JsonRpcResponse::success(request.id, serde_json::to_value(result).unwrap())
+    }
+
+    /// Answer a `resources/templates/list` request
+    pub fn handle_templates_list(&self, request: JsonRpcRequest) -> JsonRpcResponse {
+        let result = ListResourceTemplatesResult { resource_templates: self.templates() };
+        JsonRpcResponse::success(request.id, serde_json::to_value(result).unwrap())
+    }
+
+    /// Answer a `resources/read` request
+    pub async fn handle_read(&self, state: &S, request: JsonRpcRequest) -> JsonRpcResponse {
+        let params: ReadResourceParams = match parse_params(&request) {
+            Ok(params) => params,
+            Err(error) => return JsonRpcResponse::error(request.id, error),
+        };
+
+        match self.read(state, &params.uri).await {
+            Some(Ok(result)) => JsonRpcResponse::success(request.id, serde_json::to_value(result).unwrap()),
+            Some(Err(e)) => JsonRpcResponse::error(request.id, JsonRpcError::internal_error(&e.to_string())),
+            None => JsonRpcResponse::error(request.id, JsonRpcError::resource_not_found(&params.uri)),
+        }
+    }
+
+    /// Answer a `resources/subscribe` request
+    pub fn handle_subscribe(&self, request: JsonRpcRequest) -> JsonRpcResponse {
+        let params: ResourceUriParams = match parse_params(&request) {
+            Ok(params) => params,
+            Err(error) => return JsonRpcResponse::error(request.id, error),
+        };
+        if !self.contains(&params.uri) {
+            return JsonRpcResponse::error(request.id, JsonRpcError::resource_not_found(&params.uri));
+        }
+
+        tracing::debug!("Subscribed to {}", params.uri);
+        if let Ok(mut subscriptions) = self.subscriptions.write() {
+            subscriptions.insert(params.uri);
+        }
+        JsonRpcResponse::success(request.id, serde_json::json!({}))
+    }
+
+    /// Answer a `resources/unsubscribe` request
+    pub fn handle_unsubscribe(&self, request: JsonRpcRequest) -> JsonRpcResponse {
+        let params: ResourceUriParams = match parse_params(&request) {
+            Ok(params) => params,
+            Err(error) => return JsonRpcResponse::error(request.id, error),
+        };
+
+        if let Ok(mut subscriptions) = self.subscriptions.write() {
+            subscriptions.remove(&params.uri);
+        }
+        JsonRpcResponse::success(request.id, serde_json::json!({}))
+    }
+}
+
+impl<S: Send + Sync + 'static> Default for ResourceRouter<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn parse_params<P: DeserializeOwned>(request: &JsonRpcRequest) -> Result<P, JsonRpcError> {
+    let params = request.params.clone().ok_or_else(|| JsonRpcError::invalid_params("Missing params"))?;
+    serde_json::from_value(params).map_err(|e| JsonRpcError::invalid_params(&e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    struct Notes {
+        notes: Vec<String>,
+    }
+
+    struct Count;
+
+    #[async_trait]
+    impl ResourceHandler<Notes> for Count {
+        const URI: &'static str = "notes://count";
+        const NAME: &'static str = "Note count";
+        const DESCRIPTION: &'static str = "Number of notes.";
+
+        async fn read(&self, state: &Notes, _vars: &UriVars) -> Result<Value> {
+            Ok(json!({ "count": state.notes.len() }))
+        }
+    }
+
+    struct Note;
+
+    #[async_trait]
+    impl ResourceHandler<Notes> for Note {
+        const URI: &'static str = "notes://notes/{index}/text";
+        const NAME: &'static str = "Note";
+        const DESCRIPTION: &'static str = "Text of one note.";
+        const MIME_TYPE: &'static str = "text/plain";
+
+        fn instances(&self, state: &Notes) -> Vec<Resource> {
+            (0..state.notes.len())
+                .map(|i| Resource {
+                    uri: format!("notes://notes/{}/text", i),
+                    name: format!("Note {}", i),
+                    description: None,
+                    mime_type: Some(Self::MIME_TYPE.to_string()),
+                })
+                .collect()
+        }
+
+        async fn read(&self, state: &Notes, vars: &UriVars) -> Result<Value> {
+            let index: usize = vars["index"].parse()?;
+            let note = state.notes.get(index).ok_or_else(|| anyhow::anyhow!("No note {}", index))?;
+            Ok(Value::String(note.clone()))
+        }
+    }
+
+    fn request(method: &str, params: Value) -> JsonRpcRequest {
+        JsonRpcRequest::new(Some(RequestId::Number(1)), method.to_string(), Some(params))
+    }
+
+    #[test]
+    fn test_match_uri() {
+        let vars = match_uri("app://sessions/{id}/trace", "app://sessions/abc-1/trace").unwrap();
+        assert_eq!(vars["id"], "abc-1");
+        assert!(match_uri("app://sessions/{id}/trace", "app://sessions/a/b/trace").is_none());
+        assert!(match_uri("app://sessions/{id}/trace", "app://sessions//trace").is_none());
+        assert!(match_uri("app://stats", "app://stats").unwrap().is_empty());
+        assert!(match_uri("app://stats", "app://stats/more").is_none());
+        assert_eq!(match_uri("app://{a}.{b}", "app://x.y").unwrap()["b"], "y");
+    }
+
+    #[tokio::test]
+    async fn test_list_and_read() {
+        let router = ResourceRouter::new().with(Count).with(Note);
+        let state = Notes { notes: vec!["first".to_string(), "second".to_string()] };
+
+        let uris: Vec<String> = router.list(&state).into_iter().map(|r| r.uri).collect();
+        assert_eq!(uris, ["notes://count", "notes://notes/0/text", "notes://notes/1/text"]);
+        assert_eq!(router.templates()[0].uri_template, "notes://notes/{index}/text");
+
+        let result = router.read(&state, "notes://notes/1/text").await.unwrap().unwrap();
+        assert_eq!(result.contents[0].content.as_text(), Some("second"));
+        assert_eq!(result.contents[0].mime_type.as_deref(), Some("text/plain"));
+
+        let response = router.handle_read(&state, request("resources/read", json!({"uri": "notes://notes/7/text"}))).await;
+        assert_eq!(response.error.unwrap().code, -32603);
+        let response = router.handle_read(&state, request("resources/read", json!({"uri": "notes://other"}))).await;
+        assert_eq!(response.error.unwrap().code, -32002);
+    }
+
+    #[tokio::test]
+    async fn test_updates_only_reach_subscribers() {
+        let router: ResourceRouter<Notes> = ResourceRouter::new().with(Count).with(Note);
+        let ((server, _), (client, _)) = StdioHandler::in_memory_pair();
+
+        let response = router.handle_subscribe(request("resources/subscribe", json!({"uri": "notes://missing"})));
+        assert_eq!(response.error.unwrap().code, -32002);
+        let response = router.handle_subscribe(request("resources/subscribe", json!({"uri": "notes://count"})));
+        assert!(response.error.is_none());
+
+        router.notify_updated(&server, "notes://notes/0/text").unwrap();
+        router.notify_updated(&server, "notes://count").unwrap();
+        let notification = client.recv_notification().await.unwrap();
+        assert_eq!(notification.method, "notifications/resources/updated");
+        assert_eq!(notification.params.unwrap()["uri"], "notes://count");
+
+        router.handle_unsubscribe(request("resources/unsubscribe", json!({"uri": "notes://count"})));
+        assert!(!router.is_subscribed("notes://count"));
+    }
+}
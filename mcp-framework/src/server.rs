@@ -0,0 +1,196 @@
+// MCP Server Loop
+// A server answers requests and reacts to notifications; `serve` drives it
+// from a `StdioHandler` session. Dispatch itself takes a `JsonRpcRequest`
+// and returns a `JsonRpcResponse`, so it does not depend on the transport.
+// Each request runs on its own task, so a slow tool call doesn't hold up
+// other requests, and `notifications/cancelled` can abort it meanwhile.
+
+use crate::context::{CancellationToken, RequestContext};
+use crate::protocol::*;
+use crate::stdio::StdioHandler;
+use anyhow::Result;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::task::JoinSet;
+
+/// Request and notification handling of an MCP server
+#[async_trait]
+pub trait McpServer: Send + Sync {
+    /// Answer a request (every request that is not cancelled gets exactly
+    /// one response); `ctx` carries its progress token and cancellation
+    async fn handle_request(&self, request: JsonRpcRequest, ctx: RequestContext) -> JsonRpcResponse;
+
+    /// React to a notification (never answered)
+    fn handle_notification(&self, notification: JsonRpcRequest) {
+        match notification.method.as_str() {
+            // "initialized" is the pre-2024 name
+            "notifications/initialized" | "initialized" => tracing::info!("Client initialized"),
+            // Requests in flight are cancelled by `serve`; this one already finished
+            "notifications/cancelled" => tracing::debug!("Ignoring cancellation of a finished request"),
+            method => tracing::debug!("Ignoring notification: {}", method),
+        }
+    }
+}
+
+/// Answer requests and notifications from `session` until it closes
+///
+/// Requests are handled concurrently. Once the session closes, requests
+/// still in flight are answered before `serve` returns.
+pub async fn serve<S: McpServer + 'static>(server: Arc<S>, session: Arc<StdioHandler>) -> Result<()> {
+    let mut in_flight: HashMap<RequestId, CancellationToken> = HashMap::new();
+    let mut running = JoinSet::new();
+
+    loop {
+        // Notifications first, so they take effect in the order they were sent
+        tokio::select! {
+            biased;
+            Some(notification) = session.recv_notification() => {
+                match cancelled_request(&notification).and_then(|id| in_flight.get(&id).map(|cancel| (id, cancel))) {
+                    Some((id, cancel)) => {
+                        tracing::info!("Request {:?} cancelled by the client", id);
+                        cancel.cancel();
+                    }
+                    None => server.handle_notification(notification),
+                }
+            }
+            Some(finished) = running.join_next() => {
+                let (id, sent) = finished?;
+                in_flight.remove(&id);
+                sent?;
+            }
+            request = session.recv_request() => {
+                let Some(request) = request else { break };
+                tracing::debug!("Received request: {} (id: {:?})", request.method, request.id);
+
+                let Some(id) = request.id.clone() else { continue };
+                let ctx = RequestContext::for_request(&request, session.notifier());
+                in_flight.insert(id.clone(), ctx.cancel.clone());
+                running.spawn(handle_cancellable(server.clone(), session.clone(), id, request, ctx));
+            }
+        }
+    }
+
+    while let Some(finished) = running.join_next().await {
+        finished?.1?;
+    }
+    Ok(())
+}
+
+/// Answer `request` unless the client cancelled it. A cancelled handler is
+/// still driven to completion, so it can clean up once it sees its token
+/// fire, but gets no response. Returns the request's id and whether the
+/// response could be sent.
+async fn handle_cancellable<S: McpServer + ?Sized>(
+    server: Arc<S>,
+    session: Arc<StdioHandler>,
+    id: RequestId,
+    request: JsonRpcRequest,
+    ctx: RequestContext,
+) -> (RequestId, Result<()>) {
+    let cancel = ctx.cancel.clone();
+    let response = server.handle_request(request, ctx).await;
+    if cancel.is_cancelled() {
+        return (id, Ok(()));
+    }
+    (id, session.send_response(response))
+}
+
+/// Id of the request a `notifications/cancelled` refers to
+fn cancelled_request(notification: &JsonRpcRequest) -> Option<RequestId> {
+    if notification.method != "notifications/cancelled" {
+        return None;
+    }
+    let params = notification.params.clone()?;
+    serde_json::from_value::<CancelledParams>(params).ok().map(|params| params.request_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    #[derive(Default)]
+    struct Echo {
+        notifications: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl McpServer for Echo {
+        async fn handle_request(&self, request: JsonRpcRequest, ctx: RequestContext) -> JsonRpcResponse {
+            if request.method == "slow" {
+                // Runs until cancelled
+                ctx.cancel.cancelled().await;
+                self.notifications.lock().unwrap().push("slow cancelled".to_string());
+            }
+            JsonRpcResponse::success(request.id, json!(request.method))
+        }
+
+        fn handle_notification(&self, notification: JsonRpcRequest) {
+            self.notifications.lock().unwrap().push(notification.method);
+        }
+    }
+
+    /// `serve` an `Echo` on one end of an in-memory pair; returns the client end
+    fn spawn_echo() -> (Arc<Echo>, Arc<StdioHandler>, tokio::task::JoinHandle<Result<()>>) {
+        let ((server_session, _), (client, _)) = StdioHandler::in_memory_pair();
+        let server = Arc::new(Echo::default());
+        let running = tokio::spawn(serve(server.clone(), Arc::new(server_session)));
+        (server, Arc::new(client), running)
+    }
+
+    #[tokio::test]
+    async fn test_serve_answers_requests_but_not_notifications() {
+        let (server, client, running) = spawn_echo();
+
+        client.send_notification("notifications/initialized", None).unwrap();
+        assert_eq!(client.send_request("tools/list", None).await.unwrap(), json!("tools/list"));
+        assert_eq!(*server.notifications.lock().unwrap(), ["notifications/initialized"]);
+
+        running.abort();
+    }
+
+    #[tokio::test]
+    async fn test_ping_is_answered_during_a_slow_call() {
+        let (server, client, running) = spawn_echo();
+
+        let slow = {
+            let client = client.clone();
+            tokio::spawn(async move { client.send_request("slow", None).await })
+        };
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // The slow call is still running, but doesn't hold up other requests
+        let ping = client.send_request_with_timeout("ping", None, Duration::from_secs(1));
+        assert_eq!(ping.await.unwrap(), json!("ping"));
+        assert!(!slow.is_finished());
+        assert!(server.notifications.lock().unwrap().is_empty());
+
+        slow.abort();
+        running.abort();
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_request_gets_no_response() {
+        let (server, client, running) = spawn_echo();
+
+        let slow = {
+            let client = client.clone();
+            tokio::spawn(async move { client.send_request_with_timeout("slow", None, Duration::from_millis(300)).await })
+        };
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        // The client numbers its requests from 1
+        client.send_notification("notifications/cancelled", Some(json!({"requestId": 1}))).unwrap();
+
+        // The handler sees its token fire (before the client's own timeout
+        // cancels it again), but the cancelled request is never answered
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(*server.notifications.lock().unwrap(), ["slow cancelled"]);
+        assert!(slow.await.unwrap().unwrap_err().to_string().contains("timed out"));
+        assert_eq!(client.send_request("tools/list", None).await.unwrap(), json!("tools/list"));
+
+        running.abort();
+    }
+}
@@ -0,0 +1,489 @@
+// Stdio Communication for MCP
+// Full-duplex JSON-RPC session over newline-delimited messages: incoming
+// requests and notifications are queued for the server, responses are
+// routed to the server-initiated requests (e.g. sampling/createMessage,
+// roots/list) waiting for them, and either side can send notifications.
+
+use super::protocol::*;
+use anyhow::Result;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, MutexGuard, PoisonError};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::{mpsc, oneshot, Mutex};
+use tokio::task::JoinHandle;
+
+/// How long a server-initiated request waits for its response by default
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Buffer size of each direction of an in-memory transport pair
+const IN_MEMORY_BUFFER: usize = 64 * 1024;
+
+/// Server-initiated requests awaiting a response from the client, keyed by id
+///
+/// A std mutex, since it is only held for single map operations and must be
+/// usable from `Drop`.
+type PendingRequests = Arc<std::sync::Mutex<HashMap<RequestId, oneshot::Sender<JsonRpcResponse>>>>;
+
+fn lock_pending(pending: &PendingRequests) -> MutexGuard<'_, HashMap<RequestId, oneshot::Sender<JsonRpcResponse>>> {
+    pending.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// A server-initiated request its caller is still waiting for
+///
+/// If the caller stops waiting (timeout, or the future is dropped) before
+/// the response arrives, dropping this withdraws the request and sends
+/// `notifications/cancelled`, so the client can stop working on it.
+struct Outstanding<'a> {
+    pending: &'a PendingRequests,
+    notifier: Notifier,
+    id: RequestId,
+    reason: String,
+}
+
+impl Drop for Outstanding<'_> {
+    fn drop(&mut self) {
+        // Answered or connection closed: the reader already removed it
+        if lock_pending(self.pending).remove(&self.id).is_none() {
+            return;
+        }
+
+        let params = CancelledParams {
+            request_id: self.id.clone(),
+            reason: Some(std::mem::take(&mut self.reason)),
+        };
+        if let Err(e) = self.notifier.send_notification("notifications/cancelled", serde_json::to_value(params).ok()) {
+            tracing::debug!("Dropping cancellation of request {:?}: {}", self.id, e);
+        }
+    }
+}
+
+/// JSON-RPC session handler for MCP server communication
+pub struct StdioHandler {
+    /// Channel to send outgoing messages (responses, requests and notifications)
+    tx_outgoing: mpsc::UnboundedSender<JsonRpcMessage>,
+
+    /// Channel to receive incoming requests
+    rx_incoming: Arc<Mutex<mpsc::UnboundedReceiver<JsonRpcRequest>>>,
+
+    /// Channel to receive incoming notifications
+    rx_notifications: Arc<Mutex<mpsc::UnboundedReceiver<JsonRpcRequest>>>,
+
+    /// Outstanding server-initiated requests
+    pending: PendingRequests,
+
+    /// Next id for server-initiated requests
+    next_id: AtomicI64,
+
+    /// How long `send_request` waits for a response
+    request_timeout: Duration,
+}
+
+/// Cloneable sender of notifications on a `StdioHandler` session
+#[derive(Clone)]
+pub struct Notifier {
+    tx_outgoing: mpsc::UnboundedSender<JsonRpcMessage>,
+}
+
+impl Notifier {
+    /// Send a notification (no response expected)
+    pub fn send_notification(&self, method: &str, params: Option<Value>) -> Result<()> {
+        let notification = JsonRpcRequest::notification(method.to_string(), params);
+        self.tx_outgoing
+            .send(JsonRpcMessage::Request(notification))
+            .map_err(|_| anyhow::anyhow!("Failed to send {} notification (channel closed)", method))?;
+        Ok(())
+    }
+}
+
+impl StdioHandler {
+    /// Create new stdio handler
+    /// Returns (handler, reader_task_handle)
+    pub fn new() -> (Self, JoinHandle<()>) {
+        Self::with_transport(tokio::io::stdin(), tokio::io::stdout())
+    }
+
+    /// Create a handler reading messages from `reader` and writing them to `writer`
+    /// Returns (handler, reader_task_handle)
+    pub fn with_transport<R, W>(reader: R, writer: W) -> (Self, JoinHandle<()>)
+    where
+        R: AsyncRead + Unpin + Send + 'static,
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        let (tx_out, rx_out) = mpsc::unbounded_channel();
+        let (tx_in_req, rx_in_req) = mpsc::unbounded_channel();
+        let (tx_in_notif, rx_in_notif) = mpsc::unbounded_channel();
+        let pending: PendingRequests = Arc::default();
+
+        // Spawn writer task
+        let _writer_handle = tokio::spawn(Self::writer_task(writer, rx_out));
+
+        // Spawn reader task
+        let reader_handle = tokio::spawn(Self::reader_task(reader, tx_in_req, tx_in_notif, pending.clone()));
+
+        let handler = Self {
+            tx_outgoing: tx_out,
+            rx_incoming: Arc::new(Mutex::new(rx_in_req)),
+            rx_notifications: Arc::new(Mutex::new(rx_in_notif)),
+            pending,
+            next_id: AtomicI64::new(1),
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+        };
+
+        (handler, reader_handle)
+    }
+
+    /// Two handlers connected to each other in memory (server and client side)
+    pub fn in_memory_pair() -> ((Self, JoinHandle<()>), (Self, JoinHandle<()>)) {
+        let (a, b) = tokio::io::duplex(IN_MEMORY_BUFFER);
+        let (a_read, a_write) = tokio::io::split(a);
+        let (b_read, b_write) = tokio::io::split(b);
+        (Self::with_transport(a_read, a_write), Self::with_transport(b_read, b_write))
+    }
+
+    /// Set how long `send_request` waits for a response
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// Writer task: reads from channel, writes one message per line
+    async fn writer_task<W: AsyncWrite + Unpin>(mut writer: W, mut rx_out: mpsc::UnboundedReceiver<JsonRpcMessage>) {
+        while let Some(message) = rx_out.recv().await {
+            let json = match serde_json::to_string(&message) {
+                Ok(j) => j,
+                Err(e) => {
+                    tracing::error!("Failed to serialize message: {}", e);
+                    continue;
+                }
+            };
+
+            tracing::trace!("→ {}", json);
+
+            if let Err(e) = writer.write_all(json.as_bytes()).await {
+                tracing::error!("Failed to write message: {}", e);
+                break;
+            }
+
+            if let Err(e) = writer.write_all(b"\n").await {
+                tracing::error!("Failed to write newline: {}", e);
+                break;
+            }
+
+            if let Err(e) = writer.flush().await {
+                tracing::error!("Failed to flush output: {}", e);
+                break;
+            }
+        }
+
+        tracing::info!("Writer task exiting");
+    }
+
+    /// Reader task: reads lines, forwards requests and notifications, routes responses
+    async fn reader_task<R: AsyncRead + Unpin>(
+        reader: R,
+        tx_in_req: mpsc::UnboundedSender<JsonRpcRequest>,
+        tx_in_notif: mpsc::UnboundedSender<JsonRpcRequest>,
+        pending: PendingRequests,
+    ) {
+        let mut reader = BufReader::new(reader);
+        let mut line = String::new();
+
+        loop {
+            line.clear();
+
+            let bytes_read = match reader.read_line(&mut line).await {
+                Ok(n) => n,
+                Err(e) => {
+                    tracing::error!("Failed to read input: {}", e);
+                    break;
+                }
+            };
+
+            if bytes_read == 0 {
+                // EOF
+                tracing::info!("Input EOF, reader exiting");
+                break;
+            }
+
+            let line_trimmed = line.trim();
+            if line_trimmed.is_empty() {
+                continue;
+            }
+
+            tracing::trace!("← {}", line_trimmed);
+
+            // Requests and notifications carry a method; anything else is a
+            // response to one of our requests
+            match serde_json::from_str::<JsonRpcMessage>(line_trimmed) {
+                Ok(JsonRpcMessage::Request(request)) => {
+                    let tx = if request.id.is_some() { &tx_in_req } else { &tx_in_notif };
+                    if tx.send(request).is_err() {
+                        tracing::debug!("Dropping incoming message (receiver closed)");
+                    }
+                }
+                Ok(JsonRpcMessage::Response(response)) => {
+                    Self::route_response(&pending, response);
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to parse message: {}", e);
+                }
+            }
+        }
+
+        // Drop outstanding waiters so callers see the transport close
+        lock_pending(&pending).clear();
+
+        tracing::info!("Reader task exiting");
+    }
+
+    /// Deliver a response to the server-initiated request with the same id
+    fn route_response(pending: &PendingRequests, response: JsonRpcResponse) {
+        let waiter = match response.id.as_ref() {
+            Some(id) => lock_pending(pending).remove(id),
+            None => None,
+        };
+
+        match waiter {
+            Some(tx) => {
+                let _ = tx.send(response);
+            }
+            None => {
+                tracing::warn!("Dropping response with unknown id: {:?}", response.id);
+            }
+        }
+    }
+
+    /// Send a response (for incoming requests)
+    pub fn send_response(&self, response: JsonRpcResponse) -> Result<()> {
+        self.tx_outgoing
+            .send(JsonRpcMessage::Response(response))
+            .map_err(|_| anyhow::anyhow!("Failed to send response (channel closed)"))?;
+        Ok(())
+    }
+
+    /// Send a notification (no response expected)
+    pub fn send_notification(&self, method: &str, params: Option<Value>) -> Result<()> {
+        self.notifier().send_notification(method, params)
+    }
+
+    /// A handle that sends notifications on this session, for work that
+    /// outlives a borrow of the handler (e.g. progress from a blocking task)
+    pub fn notifier(&self) -> Notifier {
+        Notifier {
+            tx_outgoing: self.tx_outgoing.clone(),
+        }
+    }
+
+    /// Send a server-initiated request and wait for the client's result
+    pub async fn send_request(&self, method: &str, params: Option<Value>) -> Result<Value> {
+        self.send_request_with_timeout(method, params, self.request_timeout).await
+    }
+
+    /// Send a server-initiated request and wait up to `timeout` for the client's result
+    ///
+    /// The client is sent `notifications/cancelled` if the request times out
+    /// or the returned future is dropped before the response arrives.
+    pub async fn send_request_with_timeout(
+        &self,
+        method: &str,
+        params: Option<Value>,
+        timeout: Duration,
+    ) -> Result<Value> {
+        let id = RequestId::Number(self.next_id.fetch_add(1, Ordering::Relaxed));
+        let (tx, rx) = oneshot::channel();
+        lock_pending(&self.pending).insert(id.clone(), tx);
+        let mut outstanding = Outstanding {
+            pending: &self.pending,
+            notifier: self.notifier(),
+            id: id.clone(),
+            reason: "No longer needed".to_string(),
+        };
+
+        let request = JsonRpcRequest::new(Some(id), method.to_string(), params);
+        if self.tx_outgoing.send(JsonRpcMessage::Request(request)).is_err() {
+            anyhow::bail!("Failed to send {} request (channel closed)", method);
+        }
+
+        let response = match tokio::time::timeout(timeout, rx).await {
+            Ok(Ok(response)) => response,
+            Ok(Err(_)) => anyhow::bail!("Connection closed before {} response", method),
+            Err(_) => {
+                outstanding.reason = format!("Timed out after {:?}", timeout);
+                anyhow::bail!("{} request timed out after {:?}", method, timeout);
+            }
+        };
+
+        if let Some(error) = response.error {
+            anyhow::bail!("{} failed ({}): {}", method, error.code, error.message);
+        }
+
+        response
+            .result
+            .ok_or_else(|| anyhow::anyhow!("{} response has no result", method))
+    }
+
+    /// Receive the next incoming request
+    pub async fn recv_request(&self) -> Option<JsonRpcRequest> {
+        self.rx_incoming.lock().await.recv().await
+    }
+
+    /// Receive the next incoming notification
+    pub async fn recv_notification(&self) -> Option<JsonRpcRequest> {
+        self.rx_notifications.lock().await.recv().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_stdio_handler_creation() {
+        let (handler, _reader_handle) = StdioHandler::new();
+        // Just verify we can create the handler
+        drop(handler);
+    }
+
+    #[tokio::test]
+    async fn test_route_response_to_pending_request() {
+        let pending: PendingRequests = Arc::default();
+        let (tx, rx) = oneshot::channel();
+        lock_pending(&pending).insert(RequestId::Number(3), tx);
+
+        // Unknown ids are dropped without disturbing the waiter
+        StdioHandler::route_response(
+            &pending,
+            JsonRpcResponse::success(Some(RequestId::Number(4)), serde_json::json!("other")),
+        );
+        StdioHandler::route_response(
+            &pending,
+            JsonRpcResponse::success(Some(RequestId::Number(3)), serde_json::json!("ok")),
+        );
+
+        let response = rx.await.unwrap();
+        assert_eq!(response.result, Some(serde_json::json!("ok")));
+        assert!(lock_pending(&pending).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_requests_are_correlated_in_both_directions() {
+        let ((server, _), (client, _)) = StdioHandler::in_memory_pair();
+        let server = Arc::new(server);
+
+        // The client answers two requests in reverse order
+        let responder = tokio::spawn(async move {
+            let first = client.recv_request().await.unwrap();
+            let second = client.recv_request().await.unwrap();
+            assert_ne!(first.id, second.id);
+            for request in [second, first] {
+                client.send_response(JsonRpcResponse::success(request.id, json!(request.method))).unwrap();
+            }
+            client
+        });
+
+        let (roots, sampling) = tokio::join!(
+            server.send_request("roots/list", None),
+            server.send_request("sampling/createMessage", Some(json!({}))),
+        );
+        assert_eq!(roots.unwrap(), json!("roots/list"));
+        assert_eq!(sampling.unwrap(), json!("sampling/createMessage"));
+
+        // ...and can make requests of its own
+        let client = responder.await.unwrap();
+        let server_side = server.clone();
+        tokio::spawn(async move {
+            let request = server_side.recv_request().await.unwrap();
+            let error = JsonRpcError::method_not_found(&request.method);
+            server_side.send_response(JsonRpcResponse::error(request.id, error)).unwrap();
+        });
+        let error = client.send_request("unknown/method", None).await.unwrap_err();
+        assert!(error.to_string().contains("-32601"));
+    }
+
+    #[tokio::test]
+    async fn test_notifications_are_routed_separately() {
+        let ((server, _), (client, _)) = StdioHandler::in_memory_pair();
+
+        client.send_notification("notifications/initialized", None).unwrap();
+        client.send_notification("notifications/cancelled", Some(json!({"requestId": 1}))).unwrap();
+
+        let first = server.recv_notification().await.unwrap();
+        assert_eq!(first.method, "notifications/initialized");
+        assert!(first.id.is_none());
+        let second = server.recv_notification().await.unwrap();
+        assert_eq!(second.params, Some(json!({"requestId": 1})));
+
+        // Nothing was queued as a request
+        let no_request = tokio::time::timeout(Duration::from_millis(50), server.recv_request()).await;
+        assert!(no_request.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_request_timeout_and_closed_transport() {
+        let ((server, _), (client, client_reader)) = StdioHandler::in_memory_pair();
+
+        let error = server
+            .send_request_with_timeout("roots/list", None, Duration::from_millis(50))
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("timed out"));
+        assert!(lock_pending(&server.pending).is_empty());
+
+        // The client is told to give up, and a late response is dropped
+        let late = client.recv_request().await.unwrap();
+        let cancelled = client.recv_notification().await.unwrap();
+        assert_eq!(cancelled.method, "notifications/cancelled");
+        assert_eq!(cancelled.params.unwrap()["requestId"], json!(late.id));
+        client.send_response(JsonRpcResponse::success(late.id, json!([]))).unwrap();
+
+        // Waiters fail as soon as the peer goes away
+        let server = server.with_request_timeout(Duration::from_secs(10));
+        let pending = tokio::spawn(async move { server.send_request("roots/list", None).await });
+        let _ = client.recv_request().await.unwrap();
+        drop(client);
+        client_reader.abort();
+        let error = pending.await.unwrap().unwrap_err();
+        assert!(error.to_string().contains("Connection closed"));
+    }
+
+    #[tokio::test]
+    async fn test_abandoned_request_is_cancelled_on_the_client() {
+        let ((server, _), (client, _)) = StdioHandler::in_memory_pair();
+        let cancel = crate::context::CancellationToken::new();
+
+        // A chunk being sampled when its tool call is cancelled
+        let chunk = async {
+            tokio::select! {
+                result = server.send_request("sampling/createMessage", None) => result,
+                _ = cancel.cancelled() => anyhow::bail!("Cancelled before chunk 2"),
+            }
+        };
+        let client_side = async {
+            let request = client.recv_request().await.unwrap();
+            cancel.cancel();
+            request
+        };
+        let (result, request) = tokio::join!(chunk, client_side);
+        assert!(result.is_err());
+        assert!(lock_pending(&server.pending).is_empty());
+
+        let cancelled = client.recv_notification().await.unwrap();
+        assert_eq!(cancelled.method, "notifications/cancelled");
+        let params: CancelledParams = serde_json::from_value(cancelled.params.unwrap()).unwrap();
+        assert_eq!(Some(params.request_id), request.id);
+
+        // Answered requests are not cancelled
+        let answered = async {
+            let request = client.recv_request().await.unwrap();
+            client.send_response(JsonRpcResponse::success(request.id, json!("done"))).unwrap();
+        };
+        let (result, _) = tokio::join!(server.send_request("roots/list", None), answered);
+        assert_eq!(result.unwrap(), json!("done"));
+        let none = tokio::time::timeout(Duration::from_millis(50), client.recv_notification()).await;
+        assert!(none.is_err());
+    }
+}
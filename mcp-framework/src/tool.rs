@@ -0,0 +1,294 @@
+// MCP Tools
+// A tool is a `ToolHandler`: a name, a description, a typed params struct
+// (whose JSON schema becomes the tool's input schema) and an async call.
+// `ToolRouter` lists registered tools and dispatches `tools/call` to them.
+
+use crate::context::RequestContext;
+use crate::protocol::*;
+use anyhow::Result;
+use async_trait::async_trait;
+use schemars::gen::SchemaSettings;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::Value;
+
+/// One MCP tool, called with the server state `S`
+#[async_trait]
+pub trait ToolHandler<S: Send + Sync>: Send + Sync + 'static {
+    /// Arguments; doc comments and serde defaults show up in the input schema
+    type Params: DeserializeOwned + JsonSchema + Send;
+
+    const NAME: &'static str;
+    const DESCRIPTION: &'static str;
+
+    /// Run the tool; the value is returned to the client as text (strings
+    /// verbatim, anything else as pretty-printed JSON). Long-running tools
+    /// report through `ctx.progress` and stop once `ctx.cancel` fires.
+    async fn call(&self, state: &S, params: Self::Params, ctx: &RequestContext) -> Result<Value>;
+}
+
+/// Params of a tool that takes no arguments
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+pub struct NoParams {}
+
+/// JSON schema of a params struct, inlined and without the draft/title header
+pub fn input_schema<P: JsonSchema>() -> Value {
+    let settings = SchemaSettings::draft07().with(|s| {
+        s.option_add_null_type = false;
+        s.inline_subschemas = true;
+    });
+    let schema = settings.into_generator().into_root_schema_for::<P>();
+    let mut value = serde_json::to_value(schema).unwrap_or_default();
+    if let Some(object) = value.as_object_mut() {
+        object.remove("$schema");
+        object.remove("title");
+        object.remove("description");
+        if object.get("type") == Some(&Value::String("object".to_string())) {
+            object.entry("properties").or_insert_with(|| Value::Object(Default::default()));
+        }
+    }
+    value
+}
+
+/// Type-erased `ToolHandler`
+#[async_trait]
+trait RegisteredTool<S>: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn definition(&self) -> Tool;
+    async fn call(&self, state: &S, arguments: Value, ctx: &RequestContext) -> Result<Value>;
+}
+
+struct Registered<T>(T);
+
+#[async_trait]
+impl<S, T> RegisteredTool<S> for Registered<T>
+where
+    S: Send + Sync,
+    T: ToolHandler<S>,
+{
+    fn name(&self) -> &'static str {
+        T::NAME
+    }
+
+    fn definition(&self) -> Tool {
+        Tool {
+            name: T::NAME.to_string(),
+            description: T::DESCRIPTION.to_string(),
+            input_schema: input_schema::<T::Params>(),
+        }
+    }
+
+    async fn call(&self, state: &S, arguments: Value, ctx: &RequestContext) -> Result<Value> {
+        // Clients may omit the arguments of tools without required params
+        let arguments = if arguments.is_null() { Value::Object(Default::default()) } else { arguments };
+        let params = serde_json::from_value(arguments)
+            .map_err(|e| anyhow::anyhow!("Invalid arguments for {}: {}", T::NAME, e))?;
+        self.0.call(state, params, ctx).await
+    }
+}
+
+/// Registered tools of a server with state `S`, in registration order
+pub struct ToolRouter<S> {
+    tools: Vec<Box<dyn RegisteredTool<S>>>,
+}
+
+impl<S: Send + Sync + 'static> ToolRouter<S> {
+    pub fn new() -> Self {
+        Self { tools: Vec::new() }
+    }
+
+    /// Add a tool
+    ///
+    /// # Panics
+    /// If a tool with the same name is already registered
+    pub fn register<T: ToolHandler<S>>(&mut self, tool: T) -> &mut Self {
+        assert!(!self.contains(T::NAME), "Tool {} registered twice", T::NAME);
+        self.tools.push(Box::new(Registered(tool)));
+        self
+    }
+
+    /// Builder form of `register`
+    pub fn with<T: ToolHandler<S>>(mut self, tool: T) -> Self {
+        self.register(tool);
+        self
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.tools.iter().any(|tool| tool.name() == name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.tools.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tools.is_empty()
+    }
+
+    /// Definitions for `tools/list`
+    pub fn list(&self) -> Vec<Tool> {
+        self.tools.iter().map(|tool| tool.definition()).collect()
+    }
+
+    /// Run a tool; failures (including unknown tools and bad arguments) are
+    /// reported in the result with `is_error`, as MCP expects
+    pub async fn call(&self, state: &S, params: CallToolParams, ctx: &RequestContext) -> CallToolResult {
+        tracing::debug!("Calling tool: {}", params.name);
+
+        let result = match self.tools.iter().find(|tool| tool.name() == params.name) {
+            Some(tool) => tool.call(state, params.arguments, ctx).await,
+            None => Err(anyhow::anyhow!("Unknown tool: {}", params.name)),
+        };
+
+        match result {
+            Ok(value) => {
+                let text = match value {
+                    Value::String(text) => text,
+                    value => serde_json::to_string_pretty(&value).unwrap_or_default(),
+                };
+                CallToolResult {
+                    content: vec![Content::text(text)],
+                    is_error: Some(false),
+                }
+            }
+            Err(e) => CallToolResult {
+                content: vec![Content::text(format!("Error: {}", e))],
+                is_error: Some(true),
+            },
+        }
+    }
+
+    /// Answer a `tools/list` request
+    pub fn handle_list(&self, request: JsonRpcRequest) -> JsonRpcResponse {
+        let result = ListToolsResult { tools: self.list() };
+        JsonRpcResponse::success(request.id, serde_json::to_value(result).unwrap())
+    }
+
+    /// Answer a `tools/call` request
+    pub async fn handle_call(&self, state: &S, request: JsonRpcRequest, ctx: &RequestContext) -> JsonRpcResponse {
+        let params = match request.params.clone().map(serde_json::from_value::<CallToolParams>) {
+            Some(Ok(params)) => params,
+            Some(Err(e)) => {
+                return JsonRpcResponse::error(request.id, JsonRpcError::invalid_params(&e.to_string()));
+            }
+            None => {
+                return JsonRpcResponse::error(request.id, JsonRpcError::invalid_params("Missing params"));
+            }
+        };
+
+        let result = self.call(state, params, ctx).await;
+        JsonRpcResponse::success(request.id, serde_json::to_value(result).unwrap())
+    }
+}
+
+impl<S: Send + Sync + 'static> Default for ToolRouter<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Serialize;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Default)]
+    struct Counter {
+        calls: AtomicUsize,
+    }
+
+    #[derive(Serialize, Deserialize, JsonSchema)]
+    struct AddParams {
+        /// Amount to add
+        amount: usize,
+        /// Label echoed back
+        #[serde(default = "default_label")]
+        label: String,
+    }
+
+    fn default_label() -> String { "total".to_string() }
+
+    struct Add;
+
+    #[async_trait]
+    impl ToolHandler<Counter> for Add {
+        type Params = AddParams;
+        const NAME: &'static str = "add";
+        const DESCRIPTION: &'static str = "Add to the counter.";
+
+        async fn call(&self, state: &Counter, params: AddParams, _ctx: &RequestContext) -> Result<Value> {
+            let total = state.calls.fetch_add(params.amount, Ordering::Relaxed) + params.amount;
+            Ok(json!({ "label": params.label, "total": total }))
+        }
+    }
+
+    struct Describe;
+
+    #[async_trait]
+    impl ToolHandler<Counter> for Describe {
+        type Params = NoParams;
+        const NAME: &'static str = "describe";
+        const DESCRIPTION: &'static str = "Describe the counter.";
+
+        async fn call(&self, state: &Counter, _params: NoParams, _ctx: &RequestContext) -> Result<Value> {
+            Ok(Value::String(format!("counter at {}", state.calls.load(Ordering::Relaxed))))
+        }
+    }
+
+    #[test]
+    fn test_input_schema_from_params() {
+        let router = ToolRouter::new().with(Add).with(Describe);
+        let tools = router.list();
+        assert_eq!(tools.iter().map(|t| t.name.as_str()).collect::<Vec<_>>(), ["add", "describe"]);
+
+        let schema = &tools[0].input_schema;
+        assert_eq!(schema["type"], "object");
+        assert_eq!(schema["required"], json!(["amount"]));
+        assert_eq!(schema["properties"]["amount"]["description"], "Amount to add");
+        assert_eq!(schema["properties"]["label"]["default"], "total");
+        assert!(schema.get("$schema").is_none());
+
+        assert_eq!(tools[1].input_schema, json!({"type": "object", "properties": {}}));
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_and_errors() {
+        let router = ToolRouter::new().with(Add).with(Describe);
+        let state = Counter::default();
+
+        let ctx = RequestContext::default();
+        let call = |name: &str, arguments: Value| CallToolParams { name: name.to_string(), arguments };
+        let result = router.call(&state, call("add", json!({"amount": 2})), &ctx).await;
+        assert_eq!(result.is_error, Some(false));
+        let value: Value = serde_json::from_str(result.content[0].as_text().unwrap()).unwrap();
+        assert_eq!(value, json!({"label": "total", "total": 2}));
+
+        // Strings are returned verbatim; missing arguments mean no arguments
+        let result = router.call(&state, call("describe", Value::Null), &ctx).await;
+        assert_eq!(result.content[0].as_text(), Some("counter at 2"));
+
+        let result = router.call(&state, call("add", json!({"amount": "two"})), &ctx).await;
+        assert_eq!(result.is_error, Some(true));
+        assert!(result.content[0].as_text().unwrap().contains("Invalid arguments for add"));
+
+        let result = router.call(&state, call("missing", json!({})), &ctx).await;
+        assert_eq!(result.content[0].as_text(), Some("Error: Unknown tool: missing"));
+    }
+
+    #[tokio::test]
+    async fn test_handle_call_rejects_malformed_params() {
+        let router = ToolRouter::new().with(Add);
+        let request = JsonRpcRequest::new(Some(RequestId::Number(1)), "tools/call".to_string(), None);
+        let response = router.handle_call(&Counter::default(), request, &RequestContext::default()).await;
+        assert_eq!(response.error.unwrap().code, -32602);
+    }
+
+    #[test]
+    #[should_panic(expected = "registered twice")]
+    fn test_duplicate_names_are_rejected() {
+        let _ = ToolRouter::new().with(Add).with(Add);
+    }
+}
@@ -1,23 +1,35 @@
 //! Embedding layer for converting tokens to/from vector representations
 
 use anyhow::Result;
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::borrow::Cow;
+
+use super::storage::{DType, Matrix};
 
 /// Embedding layer that converts token IDs to continuous vectors
+#[derive(Clone)]
 pub struct EmbeddingLayer {
     /// Vocabulary size
     vocab_size: usize,
     /// Embedding dimension
     embed_dim: usize,
-    /// Embedding weights [vocab_size, embed_dim]
-    /// In a real implementation, these would be loaded from a trained model
-    weights: Vec<f32>,
+    /// Embedding weights [vocab_size, embed_dim], in their storage dtype
+    weights: Matrix,
 }
 
 impl EmbeddingLayer {
     /// Create a new embedding layer with random initialization
     pub fn new(vocab_size: usize, embed_dim: usize) -> Self {
-        let mut rng = rand::thread_rng();
+        Self::random(&mut rand::thread_rng(), vocab_size, embed_dim)
+    }
+
+    /// Randomly initialised embedding layer (deterministic for a given seed)
+    pub fn seeded(vocab_size: usize, embed_dim: usize, seed: u64) -> Self {
+        Self::random(&mut StdRng::seed_from_u64(seed), vocab_size, embed_dim)
+    }
+
+    fn random(rng: &mut impl Rng, vocab_size: usize, embed_dim: usize) -> Self {
         let size = vocab_size * embed_dim;
         let weights: Vec<f32> = (0..size)
             .map(|_| rng.gen_range(-0.1..0.1))
@@ -26,6 +38,15 @@ impl EmbeddingLayer {
         Self {
             vocab_size,
             embed_dim,
+            weights: Matrix::from_f32(vocab_size, embed_dim, weights),
+        }
+    }
+
+    /// Embedding layer over a [vocab_size, embed_dim] matrix
+    pub fn from_matrix(weights: Matrix) -> Self {
+        Self {
+            vocab_size: weights.rows(),
+            embed_dim: weights.cols(),
             weights,
         }
     }
@@ -35,19 +56,17 @@ impl EmbeddingLayer {
         Self {
             vocab_size,
             embed_dim,
-            weights: vec![0.0; vocab_size * embed_dim],
+            weights: Matrix::from_f32(vocab_size, embed_dim, vec![0.0; vocab_size * embed_dim]),
         }
     }
 
     /// Embed a single token ID
-    pub fn embed_token(&self, token_id: usize) -> Result<&[f32]> {
+    pub fn embed_token(&self, token_id: usize) -> Result<Cow<'_, [f32]>> {
         if token_id >= self.vocab_size {
             anyhow::bail!("Token ID {} out of range (vocab size: {})", token_id, self.vocab_size);
         }
 
-        let start = token_id * self.embed_dim;
-        let end = start + self.embed_dim;
-        Ok(&self.weights[start..end])
+        Ok(self.weights.row(token_id))
     }
 
     /// Embed a sequence of tokens
@@ -57,7 +76,7 @@ impl EmbeddingLayer {
 
         for &token_id in token_ids {
             let embed = self.embed_token(token_id)?;
-            embeddings.extend_from_slice(embed);
+            embeddings.extend_from_slice(&embed);
         }
 
         Ok(embeddings)
@@ -120,7 +139,7 @@ impl EmbeddingLayer {
 
     /// Unembed a sequence of embeddings
     pub fn unembed_sequence(&self, embeddings: &[f32]) -> Result<Vec<usize>> {
-        if embeddings.len() % self.embed_dim != 0 {
+        if !embeddings.len().is_multiple_of(self.embed_dim) {
             anyhow::bail!("Embeddings length must be multiple of embed_dim");
         }
 
@@ -147,16 +166,26 @@ impl EmbeddingLayer {
         self.vocab_size
     }
 
-    /// Get mutable access to weights (for training)
+    /// Get mutable access to weights (for training); upcasts them to f32
     pub fn weights_mut(&mut self) -> &mut [f32] {
-        &mut self.weights
+        self.weights.values_mut()
+    }
+
+    /// Weights as f32 (a copy unless stored as f32)
+    pub fn weights(&self) -> Cow<'_, [f32]> {
+        self.weights.to_f32()
     }
 
-    /// Get immutable access to weights
-    pub fn weights(&self) -> &[f32] {
+    /// Weights in their storage dtype
+    pub fn matrix(&self) -> &Matrix {
         &self.weights
     }
 
+    /// Re-encode the weights in `dtype`
+    pub fn convert(&mut self, dtype: DType) {
+        self.weights.convert(dtype);
+    }
+
     /// Load weights from a flat array
     pub fn load_weights(&mut self, weights: Vec<f32>) -> Result<()> {
         if weights.len() != self.vocab_size * self.embed_dim {
@@ -166,7 +195,7 @@ impl EmbeddingLayer {
                 weights.len()
             );
         }
-        self.weights = weights;
+        self.weights = Matrix::from_f32(self.vocab_size, self.embed_dim, weights);
         Ok(())
     }
 }
@@ -213,7 +242,7 @@ mod tests {
 
         let token_id = 42;
         let embedding = embed_layer.embed_token(token_id).unwrap();
-        let recovered = embed_layer.unembed(embedding).unwrap();
+        let recovered = embed_layer.unembed(&embedding).unwrap();
 
         // With zeros, all tokens have same embedding, so any token is valid
         assert!(recovered < 1000);
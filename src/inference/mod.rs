@@ -5,7 +5,13 @@
 pub mod tokenizer;
 pub mod embeddings;
 pub mod model;
+pub mod transformer;
+pub mod sampling;
+pub mod storage;
 
-pub use tokenizer::Tokenizer;
+pub use tokenizer::{ChatMessage, ChatTemplate, Tokenizer};
 pub use embeddings::EmbeddingLayer;
 pub use model::{InferenceModel, ModelConfig};
+pub use transformer::{Activation, KvCache, NormType, ParamMut, Transformer, WeightInfo};
+pub use sampling::{FinishReason, GenerationConfig, GenerationOutput, TokenLogprob};
+pub use storage::{DType, MappedSafeTensors, Matrix, ObservedSource, TensorSource};
@@ -1,13 +1,20 @@
 //! Inference model for GPU-accelerated text generation
 
 use anyhow::Result;
-use std::sync::Arc;
+use std::collections::BTreeMap;
+use std::sync::{Arc, OnceLock};
 
 #[cfg(feature = "gpu")]
 use crate::gpu::{CudaContext, kernels::*};
 
 use super::tokenizer::Tokenizer;
 use super::embeddings::EmbeddingLayer;
+use super::transformer::{Activation, KvCache, NormType, ParamMut, Transformer, WeightInfo};
+use super::sampling::{FinishReason, GenerationConfig, GenerationOutput, Sampler, TokenLogprob};
+use super::storage::{DType, TensorSource};
+use crate::training::autograd::{Tape, Var};
+use crate::training::weight_loader::EMBEDDING_TENSOR_NAMES;
+use crate::training::{GgufMetadata, GgufValue, LoraAdapter, WeightFormat, WeightLoader};
 
 /// Model configuration
 #[derive(Debug, Clone)]
@@ -15,9 +22,28 @@ pub struct ModelConfig {
     pub vocab_size: usize,
     pub embed_dim: usize,
     pub num_heads: usize,
+    /// Key/value heads; fewer than `num_heads` means grouped-query attention
+    pub num_kv_heads: usize,
     pub head_dim: usize,
     pub num_layers: usize,
+    /// Hidden size of the MLP
+    pub intermediate_size: usize,
     pub max_seq_len: usize,
+    pub norm: NormType,
+    pub norm_eps: f32,
+    pub activation: Activation,
+    /// Base for rotary position embedding frequencies
+    pub rope_theta: f32,
+    /// Reuse the embedding table as the LM head
+    pub tie_word_embeddings: bool,
+    /// End-of-sequence token; generation stops when it is produced
+    pub eos_token_id: Option<usize>,
+    /// Storage dtype of the embedding table and linear weights; matmuls
+    /// dequantize on the fly and accumulate in f32
+    pub compute_dtype: DType,
+    /// Seed of the random weights the model starts with until real ones are
+    /// loaded, so outputs and losses before `load_weights` are reproducible
+    pub seed: u64,
 }
 
 impl Default for ModelConfig {
@@ -26,18 +52,165 @@ impl Default for ModelConfig {
             vocab_size: 100256, // cl100k_base vocab size
             embed_dim: 768,
             num_heads: 12,
+            num_kv_heads: 12,
             head_dim: 64,
             num_layers: 12,
+            intermediate_size: 3072,
             max_seq_len: 2048,
+            norm: NormType::RmsNorm,
+            norm_eps: 1e-5,
+            activation: Activation::SwiGlu,
+            rope_theta: 10000.0,
+            tie_word_embeddings: true,
+            // cl100k's <|endoftext|> (100257) lies outside the 100256-token vocab
+            eos_token_id: None,
+            compute_dtype: DType::F32,
+            seed: 0,
         }
     }
 }
 
+impl ModelConfig {
+    /// Check that the attention geometry is consistent
+    pub fn validate(&self) -> Result<()> {
+        if self.num_heads == 0 || self.num_kv_heads == 0 {
+            anyhow::bail!("num_heads and num_kv_heads must be > 0");
+        }
+        if !self.num_heads.is_multiple_of(self.num_kv_heads) {
+            anyhow::bail!(
+                "num_heads ({}) must be a multiple of num_kv_heads ({})",
+                self.num_heads,
+                self.num_kv_heads
+            );
+        }
+        if !self.head_dim.is_multiple_of(2) {
+            anyhow::bail!("head_dim ({}) must be even for rotary embeddings", self.head_dim);
+        }
+        Ok(())
+    }
+
+    /// Check that every id `tokenizer` produces has an embedding row
+    pub fn check_tokenizer(&self, tokenizer: &Tokenizer) -> Result<()> {
+        if tokenizer.vocab_size() > self.vocab_size {
+            anyhow::bail!(
+                "Tokenizer {} has {} tokens but the model's vocab_size is {}",
+                tokenizer.name(),
+                tokenizer.vocab_size(),
+                self.vocab_size
+            );
+        }
+        Ok(())
+    }
+
+    /// GGUF metadata describing this configuration as a llama model
+    pub fn to_gguf_metadata(&self) -> GgufMetadata {
+        let mut meta = GgufMetadata::default();
+        let u32_value = |v: usize| GgufValue::U32(v as u32);
+
+        meta.insert("general.architecture", GgufValue::String("llama".to_string()));
+        meta.insert("llama.context_length", u32_value(self.max_seq_len));
+        meta.insert("llama.embedding_length", u32_value(self.embed_dim));
+        meta.insert("llama.block_count", u32_value(self.num_layers));
+        meta.insert("llama.feed_forward_length", u32_value(self.intermediate_size));
+        meta.insert("llama.attention.head_count", u32_value(self.num_heads));
+        meta.insert("llama.attention.head_count_kv", u32_value(self.num_kv_heads));
+        meta.insert("llama.attention.key_length", u32_value(self.head_dim));
+        meta.insert("llama.attention.value_length", u32_value(self.head_dim));
+        meta.insert("llama.rope.freq_base", GgufValue::F32(self.rope_theta));
+        meta.insert("llama.vocab_size", u32_value(self.vocab_size));
+        let eps_key = match self.norm {
+            NormType::RmsNorm => "llama.attention.layer_norm_rms_epsilon",
+            NormType::LayerNorm => "llama.attention.layer_norm_epsilon",
+        };
+        meta.insert(eps_key, GgufValue::F32(self.norm_eps));
+        if let Some(eos) = self.eos_token_id {
+            meta.insert("tokenizer.ggml.eos_token_id", u32_value(eos));
+        }
+
+        meta
+    }
+
+    /// Derive the configuration of a GGUF file loaded into `loader`
+    pub fn from_gguf(loader: &WeightLoader) -> Result<Self> {
+        let meta = loader
+            .gguf_metadata()
+            .ok_or_else(|| anyhow::anyhow!("Weights were not loaded from a GGUF file"))?;
+        let arch = meta
+            .architecture()
+            .ok_or_else(|| anyhow::anyhow!("GGUF metadata has no general.architecture"))?;
+        let required = |key: &str| {
+            meta.arch_u64(key)
+                .map(|v| v as usize)
+                .ok_or_else(|| anyhow::anyhow!("GGUF metadata has no {}.{}", arch, key))
+        };
+
+        let embed_dim = required("embedding_length")?;
+        let num_heads = required("attention.head_count")?;
+        let num_kv_heads = meta
+            .arch_u64("attention.head_count_kv")
+            .map_or(num_heads, |v| v as usize);
+
+        let vocab_size = match meta.tokens() {
+            Some(tokens) => tokens.len(),
+            None => required("vocab_size")?,
+        };
+
+        let (norm, norm_eps) = match meta.arch_f32("attention.layer_norm_rms_epsilon") {
+            Some(eps) => (NormType::RmsNorm, eps),
+            None => (
+                NormType::LayerNorm,
+                meta.arch_f32("attention.layer_norm_epsilon").unwrap_or(1e-5),
+            ),
+        };
+
+        let activation = if loader.get_tensor("model.layers.0.mlp.gate_proj.weight").is_some() {
+            Activation::SwiGlu
+        } else {
+            Activation::Gelu
+        };
+
+        let config = Self {
+            vocab_size,
+            embed_dim,
+            num_heads,
+            num_kv_heads,
+            head_dim: meta
+                .arch_u64("attention.key_length")
+                .map_or(embed_dim / num_heads, |v| v as usize),
+            num_layers: required("block_count")?,
+            intermediate_size: required("feed_forward_length")?,
+            max_seq_len: required("context_length")?,
+            norm,
+            norm_eps,
+            activation,
+            rope_theta: meta.arch_f32("rope.freq_base").unwrap_or(10000.0),
+            tie_word_embeddings: loader.get_tensor("lm_head.weight").is_none(),
+            eos_token_id: meta.eos_token_id(),
+            compute_dtype: DType::F32,
+            seed: 0,
+        };
+        config.validate()?;
+
+        Ok(config)
+    }
+}
+
 /// Inference model for text generation
 pub struct InferenceModel {
     config: ModelConfig,
     tokenizer: Arc<Tokenizer>,
     embedding: Arc<EmbeddingLayer>,
+    transformer: Arc<Transformer>,
+
+    /// Loaded LoRA adapters by name
+    adapters: BTreeMap<String, LoraAdapter>,
+    /// Adapter used for generation and trained by `OnlineLearner`
+    active_adapter: Option<String>,
+    /// `transformer` with the active adapter merged in, built on first use
+    adapted: OnceLock<Arc<Transformer>>,
+
+    /// Cap on KV cache memory per sequence (None = limited by max_seq_len only)
+    kv_cache_limit: Option<usize>,
 
     #[cfg(feature = "gpu")]
     gpu_context: Option<Arc<CudaContext>>,
@@ -54,12 +227,20 @@ impl InferenceModel {
     #[cfg(feature = "gpu")]
     pub fn new(config: ModelConfig, gpu_context: Option<Arc<CudaContext>>) -> Result<Self> {
         let tokenizer = Arc::new(Tokenizer::new()?);
-        let embedding = Arc::new(EmbeddingLayer::new(config.vocab_size, config.embed_dim));
+        let mut embedding = EmbeddingLayer::seeded(config.vocab_size, config.embed_dim, config.seed);
+        embedding.convert(config.compute_dtype);
+        let embedding = Arc::new(embedding);
+        let transformer = Arc::new(Transformer::random(&config, config.seed)?);
 
         Ok(Self {
             config,
             tokenizer,
             embedding,
+            transformer,
+            adapters: BTreeMap::new(),
+            active_adapter: None,
+            adapted: OnceLock::new(),
+            kv_cache_limit: None,
             gpu_context,
         })
     }
@@ -68,64 +249,277 @@ impl InferenceModel {
     #[cfg(not(feature = "gpu"))]
     pub fn new(config: ModelConfig, _gpu_context: ()) -> Result<Self> {
         let tokenizer = Arc::new(Tokenizer::new()?);
-        let embedding = Arc::new(EmbeddingLayer::new(config.vocab_size, config.embed_dim));
+        let mut embedding = EmbeddingLayer::seeded(config.vocab_size, config.embed_dim, config.seed);
+        embedding.convert(config.compute_dtype);
+        let embedding = Arc::new(embedding);
+        let transformer = Arc::new(Transformer::random(&config, config.seed)?);
 
         Ok(Self {
             config,
             tokenizer,
             embedding,
+            transformer,
+            adapters: BTreeMap::new(),
+            active_adapter: None,
+            adapted: OnceLock::new(),
+            kv_cache_limit: None,
         })
     }
 
-    /// Generate text from a prompt
+    /// Load embeddings and, if present, transformer blocks from `loader`
+    ///
+    /// The file must match this model's `ModelConfig`; a file holding only an
+    /// embedding table keeps the current transformer blocks.
+    pub fn load_weights(&mut self, loader: &WeightLoader) -> Result<usize> {
+        self.load_from(loader)
+    }
+
+    /// `load_weights` from any tensor source
+    ///
+    /// Weights are stored in `compute_dtype`; from a `MappedSafeTensors`
+    /// file already in that dtype they are read in place without a copy.
+    pub fn load_from(&mut self, loader: &dyn TensorSource) -> Result<usize> {
+        let name = EMBEDDING_TENSOR_NAMES
+            .iter()
+            .find(|name| loader.tensor_len(name).is_some())
+            .ok_or_else(|| anyhow::anyhow!("No embedding weights found"))?;
+
+        let (vocab_size, embed_dim) = (self.config.vocab_size, self.config.embed_dim);
+        if loader.tensor_len(name) != Some(vocab_size * embed_dim) {
+            anyhow::bail!(
+                "Weight size mismatch: expected {}, got {}",
+                vocab_size * embed_dim,
+                loader.tensor_len(name).unwrap_or(0)
+            );
+        }
+        let embedding = EmbeddingLayer::from_matrix(loader.matrix(name, vocab_size, embed_dim, self.config.compute_dtype)?);
+        let mut loaded = vocab_size * embed_dim;
+
+        if Transformer::present_in(loader) {
+            let transformer = Transformer::from_source(&self.config, loader)?;
+            loaded += transformer.num_params();
+            self.transformer = Arc::new(transformer);
+            self.adapted = OnceLock::new();
+        }
+
+        self.embedding = Arc::new(embedding);
+        Ok(loaded)
+    }
+
+    /// All weights under HuggingFace names with their shapes, plus llama
+    /// GGUF metadata, ready for `WeightLoader::save_with_options`
+    pub fn export_weights(&self) -> Result<WeightLoader> {
+        let mut loader = WeightLoader::new(WeightFormat::SafeTensors);
+        loader.insert_tensor_with_shape(
+            "model.embed_tokens.weight".to_string(),
+            vec![self.config.vocab_size, self.config.embed_dim],
+            self.embedding.weights().into_owned(),
+        )?;
+        self.transformer.export(&mut loader)?;
+        loader.set_gguf_metadata(self.config.to_gguf_metadata());
+        Ok(loader)
+    }
+
+    /// Names, shapes and storage dtypes of the weights `export_weights`
+    /// writes, without copying them
+    pub fn describe_weights(&self) -> Vec<WeightInfo> {
+        let embedding = self.embedding.matrix();
+        let mut weights = vec![WeightInfo {
+            name: "model.embed_tokens.weight".to_string(),
+            shape: vec![embedding.rows(), embedding.cols()],
+            dtype: embedding.dtype(),
+        }];
+        weights.extend(self.transformer.describe());
+        weights
+    }
+
+    /// Cap KV cache memory per sequence; decoding stops when the cap is reached
+    pub fn set_kv_cache_limit(&mut self, max_bytes: Option<usize>) {
+        self.kv_cache_limit = max_bytes;
+    }
+
+    /// Empty KV cache sized for this model and its memory cap
+    pub fn new_kv_cache(&self) -> KvCache {
+        match self.kv_cache_limit {
+            Some(max_bytes) => KvCache::with_memory_limit(&self.config, max_bytes),
+            None => KvCache::new(&self.config, self.config.max_seq_len),
+        }
+    }
+
+    /// Greedily generate a continuation of a prompt (the prompt itself is not included)
     pub async fn generate(&self, prompt: &str, max_new_tokens: usize) -> Result<String> {
-        // Tokenize input
-        let input_tokens = self.tokenizer.encode_truncated(prompt, self.config.max_seq_len);
+        let output = self
+            .generate_with_config(prompt, &GenerationConfig::greedy(max_new_tokens))
+            .await?;
+        Ok(output.text)
+    }
 
-        // Generate tokens
-        let output_tokens = self.generate_tokens(&input_tokens, max_new_tokens).await?;
+    /// Generate a continuation with explicit sampling, stop and logprob controls
+    pub async fn generate_with_config(
+        &self,
+        prompt: &str,
+        config: &GenerationConfig,
+    ) -> Result<GenerationOutput> {
+        #[cfg(feature = "gpu")]
+        if let Some(ref gpu_ctx) = self.gpu_context {
+            let input_tokens = self.tokenizer.encode_truncated(prompt, self.config.max_seq_len);
+            return self.generate_tokens_gpu(&input_tokens, config, gpu_ctx).await;
+        }
 
-        // Decode to text
-        self.tokenizer.decode(&output_tokens)
+        let mut cache = self.new_kv_cache();
+        self.generate_with_cache(prompt, config, &mut cache).await
     }
 
-    /// Generate tokens from input token sequence
-    async fn generate_tokens(&self, input_tokens: &[usize], max_new_tokens: usize) -> Result<Vec<usize>> {
-        let mut tokens = input_tokens.to_vec();
+    /// Generate with a caller-owned KV cache that persists across calls
+    ///
+    /// Whatever prefix the cache shares with the new prompt is reused, so
+    /// consecutive Markovian chunks only recompute the carryover. Call
+    /// `KvCache::reset` when switching to an unrelated prompt.
+    pub async fn generate_with_cache(
+        &self,
+        prompt: &str,
+        config: &GenerationConfig,
+        cache: &mut KvCache,
+    ) -> Result<GenerationOutput> {
+        let input_tokens = self.tokenizer.encode_truncated(prompt, self.config.max_seq_len);
+        self.generate_tokens_cached(&input_tokens, config, cache)
+    }
 
-        for _ in 0..max_new_tokens {
-            if tokens.len() >= self.config.max_seq_len {
-                break;
+    /// Incremental CPU decoding: prefill once, then one cached step per token
+    fn generate_tokens_cached(
+        &self,
+        input_tokens: &[usize],
+        config: &GenerationConfig,
+        cache: &mut KvCache,
+    ) -> Result<GenerationOutput> {
+        config.validate()?;
+        let mut sampler = Sampler::new(config);
+        let mut context = input_tokens.to_vec();
+        let mut logprobs = Vec::new();
+
+        if config.max_new_tokens == 0 {
+            return Ok(self.finish_generation(Vec::new(), logprobs, FinishReason::Length, config));
+        }
+
+        let mut logits = self.inference_transformer().prefill(&self.embedding, &context, cache)?;
+
+        let finish_reason = loop {
+            if context.len() >= self.config.max_seq_len {
+                break FinishReason::ContextFull;
             }
 
-            // Get next token
-            let next_token = self.predict_next_token(&tokens).await?;
-            tokens.push(next_token);
+            let (next_token, logprob) = sampler.sample(&logits, &context);
+            context.push(next_token);
+            if config.logprobs {
+                logprobs.push(TokenLogprob {
+                    token: next_token,
+                    text: self.tokenizer.decode_piece(next_token, logprobs.is_empty()),
+                    logprob,
+                });
+            }
 
-            // Stop on end token (simplified - would check for actual EOS token)
-            if next_token == 0 {
-                break;
+            let generated = &context[input_tokens.len()..];
+            if let Some(reason) = self.stop_reason(generated, config) {
+                break reason;
+            }
+
+            if cache.remaining() == 0 {
+                tracing::warn!("KV cache full at {} tokens, stopping generation", cache.len());
+                break FinishReason::ContextFull;
+            }
+
+            logits = self.inference_transformer().forward_cached(&self.embedding, &[next_token], cache)?;
+        };
+
+        let generated = context.split_off(input_tokens.len());
+        Ok(self.finish_generation(generated, logprobs, finish_reason, config))
+    }
+
+    /// Whether generation should end after the latest token of `generated`
+    fn stop_reason(&self, generated: &[usize], config: &GenerationConfig) -> Option<FinishReason> {
+        let last = *generated.last()?;
+        if self.config.eos_token_id == Some(last) || config.stop_tokens.contains(&last) {
+            return Some(FinishReason::StopToken);
+        }
+
+        if !config.stop_strings.is_empty() {
+            let text = self.tokenizer.decode_lossy(generated);
+            if find_stop_string(&text, &config.stop_strings).is_some() {
+                return Some(FinishReason::StopString);
             }
         }
 
-        Ok(tokens)
+        if generated.len() >= config.max_new_tokens {
+            return Some(FinishReason::Length);
+        }
+
+        None
     }
 
-    /// Predict next token given context
-    #[cfg(feature = "gpu")]
-    async fn predict_next_token(&self, context: &[usize]) -> Result<usize> {
-        if let Some(ref gpu_ctx) = self.gpu_context {
-            // GPU inference path
-            self.predict_next_token_gpu(context, gpu_ctx).await
-        } else {
-            // CPU fallback
-            self.predict_next_token_cpu(context)
+    /// Decode generated tokens, dropping a terminating stop token and
+    /// anything from the first stop string onwards
+    fn finish_generation(
+        &self,
+        tokens: Vec<usize>,
+        logprobs: Vec<TokenLogprob>,
+        finish_reason: FinishReason,
+        config: &GenerationConfig,
+    ) -> GenerationOutput {
+        let text_tokens = match finish_reason {
+            FinishReason::StopToken => &tokens[..tokens.len() - 1],
+            _ => &tokens[..],
+        };
+
+        // May end mid-character when cut by length
+        let mut text = self.tokenizer.decode_lossy(text_tokens);
+        if let Some(cut) = find_stop_string(&text, &config.stop_strings) {
+            text.truncate(cut);
+        }
+
+        GenerationOutput {
+            text,
+            tokens,
+            logprobs: config.logprobs.then_some(logprobs),
+            finish_reason,
         }
     }
 
-    #[cfg(not(feature = "gpu"))]
-    async fn predict_next_token(&self, context: &[usize]) -> Result<usize> {
-        self.predict_next_token_cpu(context)
+    /// GPU decoding loop (recomputes the context each step)
+    ///
+    /// The GPU kernel path picks tokens greedily from the unembedding, so
+    /// sampling controls and logprobs are not applied here; stop conditions are.
+    #[cfg(feature = "gpu")]
+    async fn generate_tokens_gpu(
+        &self,
+        input_tokens: &[usize],
+        config: &GenerationConfig,
+        gpu_ctx: &Arc<CudaContext>,
+    ) -> Result<GenerationOutput> {
+        config.validate()?;
+        let mut context = input_tokens.to_vec();
+        let mut finish_reason = FinishReason::Length;
+
+        while config.max_new_tokens > 0 {
+            if context.len() >= self.config.max_seq_len {
+                finish_reason = FinishReason::ContextFull;
+                break;
+            }
+
+            let next_token = self.predict_next_token_gpu(&context, gpu_ctx).await?;
+            context.push(next_token);
+
+            if let Some(reason) = self.stop_reason(&context[input_tokens.len()..], config) {
+                finish_reason = reason;
+                break;
+            }
+        }
+
+        let generated = context.split_off(input_tokens.len());
+        let config = GenerationConfig {
+            logprobs: false,
+            ..config.clone()
+        };
+        Ok(self.finish_generation(generated, Vec::new(), finish_reason, &config))
     }
 
     /// GPU-accelerated next token prediction
@@ -182,19 +576,47 @@ impl InferenceModel {
         Ok(next_token)
     }
 
-    /// CPU fallback next token prediction
-    fn predict_next_token_cpu(&self, context: &[usize]) -> Result<usize> {
-        // Simple CPU inference (very basic)
-        // In practice, would run full model on CPU
+    /// Vocabulary logits for the token after `context`
+    pub fn forward(&self, context: &[usize]) -> Result<Vec<f32>> {
+        self.inference_transformer().forward(&self.embedding, context)
+    }
 
-        // For now, use a simple heuristic: cycle through vocabulary
-        let next_token = if context.is_empty() {
-            1 // Start token
-        } else {
-            (context.last().unwrap() + 1) % self.config.vocab_size
-        };
+    /// Vocabulary logits after every position of `context` (teacher forcing)
+    pub fn forward_all(&self, context: &[usize]) -> Result<Vec<Vec<f32>>> {
+        self.inference_transformer().forward_all(&self.embedding, context)
+    }
 
-        Ok(next_token)
+    /// Record a forward pass over `tokens` on `tape`; see `Transformer::record_with`
+    ///
+    /// With an active adapter only its matrices are parameters.
+    pub fn record_forward<'w>(&'w self, tape: &mut Tape<'w>, tokens: &[usize]) -> Result<Var> {
+        self.transformer
+            .record_with(tape, &self.embedding, tokens, self.active_adapter())
+    }
+
+    /// Trainable weights by name, as `record_forward` registers them
+    ///
+    /// These are the active adapter's matrices if there is one, else every
+    /// model weight. Weights still shared with an outstanding
+    /// `embedding()`/`transformer()` handle are copied first, so that handle
+    /// keeps the old values.
+    pub fn parameters_mut(&mut self) -> Vec<ParamMut<'_>> {
+        if let Some(name) = &self.active_adapter {
+            self.adapted = OnceLock::new();
+            if let Some(adapter) = self.adapters.get_mut(name) {
+                return adapter.parameters_mut();
+            }
+        }
+
+        let (rows, cols) = (self.config.vocab_size, self.config.embed_dim);
+        let mut params = vec![ParamMut {
+            name: "model.embed_tokens.weight".to_string(),
+            rows,
+            cols,
+            values: Arc::make_mut(&mut self.embedding).weights_mut(),
+        }];
+        params.extend(Arc::make_mut(&mut self.transformer).parameters_mut());
+        params
     }
 
     /// Encode text to tokens
@@ -217,6 +639,18 @@ impl InferenceModel {
         self.tokenizer.clone()
     }
 
+    /// Replace the tokenizer; its vocabulary must fit the model's
+    ///
+    /// The tokenizer's EOS token becomes the stop token if the config has none.
+    pub fn set_tokenizer(&mut self, tokenizer: Tokenizer) -> Result<()> {
+        self.config.check_tokenizer(&tokenizer)?;
+        if self.config.eos_token_id.is_none() {
+            self.config.eos_token_id = tokenizer.eos_token_id();
+        }
+        self.tokenizer = Arc::new(tokenizer);
+        Ok(())
+    }
+
     /// Get embedding layer
     pub fn embedding(&self) -> Arc<EmbeddingLayer> {
         self.embedding.clone()
@@ -226,42 +660,491 @@ impl InferenceModel {
     pub fn embedding_mut(&mut self) -> &mut Arc<EmbeddingLayer> {
         &mut self.embedding
     }
+
+    /// Get transformer blocks (without any adapter)
+    pub fn transformer(&self) -> Arc<Transformer> {
+        self.transformer.clone()
+    }
+
+    /// Transformer used for generation: the base one, or the base one with
+    /// the active adapter merged in
+    fn inference_transformer(&self) -> &Transformer {
+        match self.active_adapter() {
+            Some(adapter) => self.adapted.get_or_init(|| {
+                let mut adapted = (*self.transformer).clone();
+                adapted.merge_adapter(adapter);
+                Arc::new(adapted)
+            }),
+            None => &self.transformer,
+        }
+    }
+
+    /// Add (or replace) an adapter; it must only target linear weights of this model
+    pub fn insert_adapter(&mut self, adapter: LoraAdapter) -> Result<()> {
+        let weights = self.transformer.linear_weights();
+        for (name, layer) in adapter.layers() {
+            match weights.iter().find(|(weight, _, _)| weight == name) {
+                Some((_, out_features, in_features))
+                    if (*out_features, *in_features) == (layer.out_features, layer.in_features) => {}
+                Some((_, out_features, in_features)) => anyhow::bail!(
+                    "Adapter layer {} is [{}, {}] but the weight is [{}, {}]",
+                    name,
+                    layer.out_features,
+                    layer.in_features,
+                    out_features,
+                    in_features
+                ),
+                None => anyhow::bail!("Adapter targets unknown weight {}", name),
+            }
+        }
+
+        if self.active_adapter.as_deref() == Some(adapter.name()) {
+            self.adapted = OnceLock::new();
+        }
+        self.adapters.insert(adapter.name().to_string(), adapter);
+        Ok(())
+    }
+
+    /// Remove an adapter, deactivating it if it was active
+    pub fn remove_adapter(&mut self, name: &str) -> Result<LoraAdapter> {
+        let adapter = self
+            .adapters
+            .remove(name)
+            .ok_or_else(|| anyhow::anyhow!("No adapter named {}", name))?;
+        if self.active_adapter.as_deref() == Some(name) {
+            self.active_adapter = None;
+            self.adapted = OnceLock::new();
+        }
+        Ok(adapter)
+    }
+
+    /// Switch generation and training to adapter `name`, or back to the base model
+    pub fn set_active_adapter(&mut self, name: Option<&str>) -> Result<()> {
+        if let Some(name) = name {
+            if !self.adapters.contains_key(name) {
+                anyhow::bail!("No adapter named {}", name);
+            }
+        }
+        if self.active_adapter.as_deref() != name {
+            self.active_adapter = name.map(str::to_string);
+            self.adapted = OnceLock::new();
+        }
+        Ok(())
+    }
+
+    /// The adapter generation and training currently use
+    pub fn active_adapter(&self) -> Option<&LoraAdapter> {
+        self.active_adapter.as_ref().and_then(|name| self.adapters.get(name))
+    }
+
+    /// A loaded adapter by name
+    pub fn adapter(&self, name: &str) -> Option<&LoraAdapter> {
+        self.adapters.get(name)
+    }
+
+    /// Loaded adapters, ordered by name
+    pub fn adapters(&self) -> impl Iterator<Item = &LoraAdapter> {
+        self.adapters.values()
+    }
+
+    /// Fold adapter `name` into the base weights permanently and remove it
+    pub fn merge_adapter(&mut self, name: &str) -> Result<()> {
+        let adapter = self.remove_adapter(name)?;
+        Arc::make_mut(&mut self.transformer).merge_adapter(&adapter);
+        self.adapted = OnceLock::new();
+        Ok(())
+    }
+}
+
+/// Byte offset of the earliest stop string in `text`
+fn find_stop_string(text: &str, stop_strings: &[String]) -> Option<usize> {
+    stop_strings
+        .iter()
+        .filter(|stop| !stop.is_empty())
+        .filter_map(|stop| text.find(stop.as_str()))
+        .min()
+}
+
+#[cfg(test)]
+impl ModelConfig {
+    /// Full cl100k vocabulary over a one-layer transformer, so tests stay fast
+    pub(crate) fn tiny() -> Self {
+        Self {
+            embed_dim: 16,
+            num_heads: 2,
+            num_kv_heads: 1,
+            head_dim: 8,
+            num_layers: 1,
+            intermediate_size: 32,
+            ..Self::default()
+        }
+    }
+}
+
+#[cfg(test)]
+impl InferenceModel {
+    /// CPU model for tests, with or without the gpu feature
+    pub(crate) fn for_tests(config: ModelConfig) -> Self {
+        #[cfg(feature = "gpu")]
+        return Self::new(config, None).unwrap();
+        #[cfg(not(feature = "gpu"))]
+        Self::new(config, ()).unwrap()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Two layers without grouped-query attention
+    fn small_config() -> ModelConfig {
+        ModelConfig {
+            num_kv_heads: 2,
+            num_layers: 2,
+            ..ModelConfig::tiny()
+        }
+    }
+
     #[tokio::test]
     async fn test_model_creation() {
-        let config = ModelConfig::default();
-        #[cfg(feature = "gpu")]
-        let model = InferenceModel::new(config, None).unwrap();
-        #[cfg(not(feature = "gpu"))]
-        let model = InferenceModel::new(config, ()).unwrap();
+        let config = small_config();
+        let model = InferenceModel::for_tests(config);
 
         assert_eq!(model.config.vocab_size, 100256);
     }
 
     #[tokio::test]
     async fn test_generation() {
-        let config = ModelConfig::default();
-        #[cfg(feature = "gpu")]
-        let model = InferenceModel::new(config, None).unwrap();
-        #[cfg(not(feature = "gpu"))]
-        let model = InferenceModel::new(config, ()).unwrap();
+        let config = small_config();
+        let model = InferenceModel::for_tests(config);
 
         let result = model.generate("Hello", 5).await.unwrap();
         assert!(!result.is_empty());
+        assert!(!result.starts_with("Hello"));
+
+        // Decoded text need not re-encode to the same ids, so count the
+        // generated ones
+        let output = model.generate_with_config("Hello", &GenerationConfig::greedy(5)).await.unwrap();
+        assert_eq!(output.text, result);
+        assert!(output.tokens.len() <= 5);
+
+        // Greedy decoding over fixed weights is deterministic
+        assert_eq!(result, model.generate("Hello", 5).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_forward_logits() {
+        let config = small_config();
+        let model = InferenceModel::for_tests(config);
+
+        let logits = model.forward(&model.encode("Hello, world")).unwrap();
+        assert_eq!(logits.len(), 100256);
+        assert!(logits.iter().all(|l| l.is_finite()));
+    }
+
+    #[tokio::test]
+    async fn test_generate_with_persistent_cache() {
+        let config = small_config();
+        let model = InferenceModel::for_tests(config);
+
+        let config = GenerationConfig::greedy(4);
+        let mut cache = model.new_kv_cache();
+        let first = model.generate_with_cache("Problem: 2+2", &config, &mut cache).await.unwrap();
+        assert!(!cache.is_empty());
+
+        // A chunk sharing the query prefix matches a cold-cache run
+        let prompt = "Problem: 2+2\n\nCarryover";
+        let warm = model.generate_with_cache(prompt, &config, &mut cache).await.unwrap();
+        assert_eq!(warm.text, model.generate(prompt, 4).await.unwrap());
+        assert_eq!(first.text, model.generate("Problem: 2+2", 4).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_kv_cache_limit_stops_generation() {
+        let config = small_config();
+        let mut model = InferenceModel::for_tests(config.clone());
+
+        // Room for exactly 6 positions
+        model.set_kv_cache_limit(Some(6 * KvCache::bytes_per_token(&config)));
+        let prompt = model.encode("one two three");
+        let mut cache = model.new_kv_cache();
+        assert_eq!(cache.max_tokens(), 6);
+
+        let output = model
+            .generate_tokens_cached(&prompt, &GenerationConfig::greedy(50), &mut cache)
+            .unwrap();
+        assert_eq!(output.finish_reason, FinishReason::ContextFull);
+        assert!(prompt.len() + output.tokens.len() <= 7);
+        assert!(cache.len() <= 6);
+    }
+
+    #[tokio::test]
+    async fn test_seeded_sampling_is_reproducible() {
+        let config = small_config();
+        let model = InferenceModel::for_tests(config);
+
+        let generation = GenerationConfig {
+            logprobs: true,
+            ..GenerationConfig::sampled(6, 1.0, 42)
+        };
+        let first = model.generate_with_config("Once upon", &generation).await.unwrap();
+        let second = model.generate_with_config("Once upon", &generation).await.unwrap();
+
+        assert_eq!(first.tokens, second.tokens);
+        assert_eq!(first.finish_reason, FinishReason::Length);
+        let logprobs = first.logprobs.unwrap();
+        assert_eq!(logprobs.len(), 6);
+        assert!(logprobs.iter().all(|lp| lp.logprob <= 0.0));
+        assert_eq!(logprobs.iter().map(|lp| lp.token).collect::<Vec<_>>(), first.tokens);
+    }
+
+    #[tokio::test]
+    async fn test_stop_tokens_and_strings() {
+        let config = small_config();
+        let mut model = InferenceModel::for_tests(config);
+
+        let free = model.generate_with_config("Hello", &GenerationConfig::greedy(4)).await.unwrap();
+        assert!(free.logprobs.is_none());
+
+        // Stopping on the first greedy token not seen earlier ends generation there
+        let k = (1..free.tokens.len())
+            .find(|&i| !free.tokens[..i].contains(&free.tokens[i]))
+            .unwrap_or(0);
+        let stop = GenerationConfig {
+            stop_tokens: vec![free.tokens[k]],
+            ..GenerationConfig::greedy(4)
+        };
+        let stopped = model.generate_with_config("Hello", &stop).await.unwrap();
+        assert_eq!(stopped.finish_reason, FinishReason::StopToken);
+        assert_eq!(stopped.tokens, free.tokens[..=k]);
+        assert_eq!(stopped.text, model.tokenizer().decode_lossy(&free.tokens[..k]));
+
+        // Same via the model's EOS
+        model.config.eos_token_id = Some(free.tokens[k]);
+        let eos = model.generate_with_config("Hello", &GenerationConfig::greedy(4)).await.unwrap();
+        assert_eq!(eos.tokens, stopped.tokens);
+        model.config.eos_token_id = None;
+
+        // A stop string is cut from the returned text
+        let last = model.tokenizer().decode_lossy(&free.tokens[3..]);
+        let stop = GenerationConfig {
+            stop_strings: vec![last.clone()],
+            ..GenerationConfig::greedy(4)
+        };
+        let stopped = model.generate_with_config("Hello", &stop).await.unwrap();
+        assert_eq!(stopped.finish_reason, FinishReason::StopString);
+        assert!(!stopped.text.contains(&last));
+    }
+
+    #[tokio::test]
+    async fn test_gguf_model_matches_hf_weights() {
+        use crate::training::gguf::{tests::build_gguf, GgmlType};
+
+        let (vocab, dim, heads, ffn) = (32usize, 16usize, 2usize, 32usize);
+        let mut rng_state = 1u32;
+        let mut random = |n: usize| -> Vec<f32> {
+            (0..n)
+                .map(|_| {
+                    rng_state = rng_state.wrapping_mul(1664525).wrapping_add(1013904223);
+                    (rng_state >> 8) as f32 / (1u32 << 24) as f32 - 0.5
+                })
+                .collect()
+        };
+
+        // (GGUF name, row-major shape, values in HF layout)
+        let mut tensors = vec![
+            ("token_embd.weight".to_string(), vec![vocab, dim], random(vocab * dim)),
+            ("output_norm.weight".to_string(), vec![dim], random(dim)),
+        ];
+        for module in ["attn_norm", "ffn_norm"] {
+            tensors.push((format!("blk.0.{}.weight", module), vec![dim], random(dim)));
+        }
+        for (module, rows, cols) in [
+            ("attn_q", dim, dim),
+            ("attn_k", dim, dim),
+            ("attn_v", dim, dim),
+            ("attn_output", dim, dim),
+            ("ffn_gate", ffn, dim),
+            ("ffn_up", ffn, dim),
+            ("ffn_down", dim, ffn),
+        ] {
+            tensors.push((format!("blk.0.{}.weight", module), vec![rows, cols], random(rows * cols)));
+        }
+
+        let mut hf = WeightLoader::new(WeightFormat::Custom);
+        let mut gguf_tensors = Vec::new();
+        for (name, shape, values) in &tensors {
+            hf.insert_tensor(crate::training::gguf::hf_tensor_name(name), values.clone());
+
+            // llama.cpp interleaves rotary pairs in Q/K rows
+            let stored = if name.contains("attn_q") || name.contains("attn_k") {
+                let (head_dim, half) = (dim / heads, dim / heads / 2);
+                let mut out = vec![0.0; values.len()];
+                for row in 0..dim {
+                    let (head, r) = (row / head_dim, row % head_dim);
+                    let src = head * head_dim + (r % 2) * half + r / 2;
+                    out[row * dim..(row + 1) * dim].copy_from_slice(&values[src * dim..(src + 1) * dim]);
+                }
+                out
+            } else {
+                values.clone()
+            };
+            let dims = shape.iter().rev().map(|&d| d as u64).collect();
+            let bytes = stored.iter().flat_map(|v| v.to_le_bytes()).collect();
+            gguf_tensors.push((name.as_str(), dims, GgmlType::F32, bytes));
+        }
+
+        let bytes = build_gguf(
+            &[
+                ("general.architecture", GgufValue::String("llama".into())),
+                ("llama.context_length", GgufValue::U32(64)),
+                ("llama.embedding_length", GgufValue::U32(dim as u32)),
+                ("llama.block_count", GgufValue::U32(1)),
+                ("llama.feed_forward_length", GgufValue::U32(ffn as u32)),
+                ("llama.attention.head_count", GgufValue::U32(heads as u32)),
+                ("llama.attention.layer_norm_rms_epsilon", GgufValue::F32(1e-5)),
+                (
+                    "tokenizer.ggml.tokens",
+                    GgufValue::Array((0..vocab).map(|i| GgufValue::String(format!("t{}", i))).collect()),
+                ),
+                ("tokenizer.ggml.eos_token_id", GgufValue::U32(2)),
+            ],
+            &gguf_tensors,
+        );
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tiny-llama.gguf");
+        std::fs::write(&path, bytes).unwrap();
+
+        let mut gguf = WeightLoader::new(WeightFormat::GGUF);
+        gguf.load_from_file(&path).unwrap();
+        let config = ModelConfig::from_gguf(&gguf).unwrap();
+        assert_eq!((config.vocab_size, config.head_dim, config.num_layers), (vocab, 8, 1));
+        assert_eq!(config.max_seq_len, 64);
+        assert_eq!(config.eos_token_id, Some(2));
+        assert!(config.tie_word_embeddings);
+        assert_eq!(config.activation, Activation::SwiGlu);
+
+        let (mut from_gguf, mut from_hf) = (
+            InferenceModel::for_tests(config.clone()),
+            InferenceModel::for_tests(config),
+        );
+        from_gguf.load_weights(&gguf).unwrap();
+        from_hf.load_weights(&hf).unwrap();
+
+        let a = from_gguf.forward(&[1, 5, 9]).unwrap();
+        let b = from_hf.forward(&[1, 5, 9]).unwrap();
+        assert!(a.iter().zip(&b).all(|(x, y)| (x - y).abs() < 1e-5));
+    }
+
+    #[tokio::test]
+    async fn test_export_roundtrips_through_safetensors_and_gguf() {
+        use crate::training::SaveOptions;
+
+        let config = ModelConfig {
+            vocab_size: 64,
+            max_seq_len: 32,
+            tie_word_embeddings: false,
+            ..small_config()
+        };
+        let model = InferenceModel::for_tests(config);
+        let expected = model.forward(&[3, 1, 4, 1, 5]).unwrap();
+
+        let exported = model.export_weights().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        for file in ["model.safetensors", "model.gguf"] {
+            let path = dir.path().join(file);
+            let format = WeightFormat::from_path(&path).unwrap();
+            exported.save_with_options(&path, format, &SaveOptions::default()).unwrap();
+
+            let mut loader = WeightLoader::new(format);
+            loader.load_from_file(&path).unwrap();
+            let config = match format {
+                WeightFormat::GGUF => ModelConfig::from_gguf(&loader).unwrap(),
+                _ => model.config().clone(),
+            };
+            assert!(!config.tie_word_embeddings);
+
+            let mut reloaded = InferenceModel::for_tests(config);
+            reloaded.load_weights(&loader).unwrap();
+
+            let logits = reloaded.forward(&[3, 1, 4, 1, 5]).unwrap();
+            assert!(
+                logits.iter().zip(&expected).all(|(a, b)| (a - b).abs() < 1e-5),
+                "{} changed the model",
+                file
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reduced_precision_logits_match_f32() {
+        use crate::inference::MappedSafeTensors;
+        use crate::training::{SaveDtype, SaveOptions};
+
+        let config = ModelConfig {
+            vocab_size: 64,
+            max_seq_len: 32,
+            tie_word_embeddings: false,
+            ..small_config()
+        };
+        let model = InferenceModel::for_tests(config.clone());
+        let tokens = [3, 1, 4, 1, 5];
+        let expected = model.forward(&tokens).unwrap();
+
+        let exported = model.export_weights().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        for (save, dtype, tolerance) in [
+            (SaveDtype::F16, DType::F16, 1e-2),
+            (SaveDtype::BF16, DType::BF16, 5e-2),
+            (SaveDtype::Int8, DType::Int8, 5e-2),
+        ] {
+            let path = dir.path().join(format!("model-{}.safetensors", dtype.name()));
+            let options = SaveOptions {
+                dtype: save,
+                ..SaveOptions::default()
+            };
+            exported.save_with_options(&path, WeightFormat::SafeTensors, &options).unwrap();
+
+            let config = ModelConfig {
+                compute_dtype: dtype,
+                ..config.clone()
+            };
+            let mut reduced = InferenceModel::for_tests(config);
+            reduced.load_from(&MappedSafeTensors::open(&path).unwrap()).unwrap();
+
+            // Weights stay in the file's dtype and are read from the mapping
+            let embedding = reduced.embedding();
+            assert!(embedding.matrix().is_mapped());
+            assert_eq!(embedding.matrix().dtype(), dtype);
+            let transformer = reduced.transformer();
+            let q_proj = &transformer.layers()[0].q_proj.weight;
+            assert!(q_proj.is_mapped());
+            assert!(q_proj.storage_bytes() < q_proj.rows() * q_proj.cols() * 4);
+
+            let logits = reduced.forward(&tokens).unwrap();
+            let max_diff = logits.iter().zip(&expected).map(|(a, b)| (a - b).abs()).fold(0.0, f32::max);
+            assert!(max_diff < tolerance, "{} logits differ by {}", dtype.name(), max_diff);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_load_weights_embedding_only() {
+        let config = small_config();
+        let mut model = InferenceModel::for_tests(config.clone());
+
+        let weights = vec![0.5; config.vocab_size * config.embed_dim];
+        let mut loader = WeightLoader::new(crate::training::WeightFormat::Custom);
+        loader.insert_tensor("model.embed_tokens.weight".to_string(), weights);
+
+        let loaded = model.load_weights(&loader).unwrap();
+        assert_eq!(loaded, config.vocab_size * config.embed_dim);
+        assert!(model.embedding().weights().iter().all(|w| *w == 0.5));
     }
 
     #[tokio::test]
     async fn test_tokenization() {
-        let config = ModelConfig::default();
-        #[cfg(feature = "gpu")]
-        let model = InferenceModel::new(config, None).unwrap();
-        #[cfg(not(feature = "gpu"))]
-        let model = InferenceModel::new(config, ()).unwrap();
+        let config = small_config();
+        let model = InferenceModel::for_tests(config);
 
         let text = "Hello, world!";
         let tokens = model.encode(text);
@@ -269,4 +1152,17 @@ mod tests {
 
         assert_eq!(decoded, text);
     }
+
+    #[tokio::test]
+    async fn test_set_tokenizer_checks_vocab_size() {
+        let config = small_config();
+        let mut model = InferenceModel::for_tests(config);
+
+        let o200k = Tokenizer::tiktoken("o200k_base").unwrap();
+        assert!(model.set_tokenizer(o200k).is_err());
+        assert_eq!(model.tokenizer().name(), "cl100k_base");
+
+        model.set_tokenizer(Tokenizer::tiktoken("cl100k_base").unwrap()).unwrap();
+        assert_eq!(model.config().eos_token_id, Some(100257));
+    }
 }
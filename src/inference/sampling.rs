@@ -0,0 +1,345 @@
+//! Token sampling for generation
+//!
+//! `GenerationConfig` carries the decoding controls (temperature, top-k,
+//! top-p, min-p, repetition penalty), a seed, stop conditions and whether to
+//! report logprobs. `Sampler` turns a logit vector into the next token using
+//! the same pipeline as icarus-core's `SamplingStrategy`: repetition
+//! penalty, temperature, softmax, then top-k / top-p / min-p filtering and
+//! renormalisation.
+
+use anyhow::Result;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use super::transformer::softmax_in_place;
+
+/// Decoding controls for `InferenceModel::generate_with_config`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GenerationConfig {
+    pub max_new_tokens: usize,
+    /// Softmax temperature; 0.0 means greedy decoding
+    pub temperature: f32,
+    /// Keep only the k most likely tokens (None = disabled)
+    pub top_k: Option<usize>,
+    /// Keep the smallest set of tokens whose cumulative probability reaches p
+    pub top_p: Option<f32>,
+    /// Drop tokens whose probability is below this (0.0 = disabled)
+    pub min_p: f32,
+    /// > 1.0 discourages tokens already in the context
+    pub repetition_penalty: f32,
+    /// RNG seed; the same seed and prompt give the same output
+    pub seed: Option<u64>,
+    /// Token ids that end generation (in addition to the model's EOS)
+    pub stop_tokens: Vec<usize>,
+    /// Generation ends once the output contains any of these; the returned
+    /// text is cut before the match
+    pub stop_strings: Vec<String>,
+    /// Report the log probability of each generated token
+    pub logprobs: bool,
+}
+
+impl Default for GenerationConfig {
+    fn default() -> Self {
+        Self {
+            max_new_tokens: 256,
+            temperature: 0.0,
+            top_k: None,
+            top_p: None,
+            min_p: 0.0,
+            repetition_penalty: 1.0,
+            seed: None,
+            stop_tokens: Vec::new(),
+            stop_strings: Vec::new(),
+            logprobs: false,
+        }
+    }
+}
+
+impl GenerationConfig {
+    /// Greedy decoding of up to `max_new_tokens`
+    pub fn greedy(max_new_tokens: usize) -> Self {
+        Self {
+            max_new_tokens,
+            ..Default::default()
+        }
+    }
+
+    /// Temperature / top-k / top-p sampling with the given seed
+    pub fn sampled(max_new_tokens: usize, temperature: f32, seed: u64) -> Self {
+        Self {
+            max_new_tokens,
+            temperature,
+            top_k: Some(40),
+            top_p: Some(0.9),
+            seed: Some(seed),
+            ..Default::default()
+        }
+    }
+
+    /// Check that the controls are in range
+    pub fn validate(&self) -> Result<()> {
+        if self.temperature.is_nan() || self.temperature < 0.0 {
+            anyhow::bail!("temperature must be >= 0, got {}", self.temperature);
+        }
+        if self.top_k == Some(0) {
+            anyhow::bail!("top_k must be > 0");
+        }
+        if let Some(p) = self.top_p {
+            if !(p > 0.0 && p <= 1.0) {
+                anyhow::bail!("top_p must be in (0, 1], got {}", p);
+            }
+        }
+        if !(0.0..1.0).contains(&self.min_p) {
+            anyhow::bail!("min_p must be in [0, 1), got {}", self.min_p);
+        }
+        if self.repetition_penalty.is_nan() || self.repetition_penalty <= 0.0 {
+            anyhow::bail!("repetition_penalty must be > 0, got {}", self.repetition_penalty);
+        }
+        Ok(())
+    }
+}
+
+/// Why generation ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FinishReason {
+    /// Produced `max_new_tokens`
+    Length,
+    /// Sampled the model's EOS or a configured stop token
+    StopToken,
+    /// Output contained a stop string
+    StopString,
+    /// Ran out of context (max_seq_len or the KV cache memory cap)
+    ContextFull,
+}
+
+/// Log probability of one generated token
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TokenLogprob {
+    pub token: usize,
+    pub text: String,
+    /// Log probability of the token. Sampled tokens report it under the
+    /// temperature-scaled, filtered and renormalised distribution they were
+    /// drawn from (0.0 when filtering leaves one candidate); greedy tokens
+    /// (temperature 0) under the plain softmax of the logits, after the
+    /// repetition penalty.
+    pub logprob: f32,
+}
+
+/// Result of `InferenceModel::generate_with_config`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GenerationOutput {
+    /// Decoded continuation (stop string and stop tokens excluded)
+    pub text: String,
+    /// Generated token ids, including a terminating stop token
+    pub tokens: Vec<usize>,
+    /// Per-token logprobs when `GenerationConfig::logprobs` is set
+    pub logprobs: Option<Vec<TokenLogprob>>,
+    pub finish_reason: FinishReason,
+}
+
+/// Picks next tokens according to a `GenerationConfig`
+pub struct Sampler {
+    config: GenerationConfig,
+    rng: StdRng,
+}
+
+impl Sampler {
+    pub fn new(config: &GenerationConfig) -> Self {
+        let rng = match config.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+
+        Self {
+            config: config.clone(),
+            rng,
+        }
+    }
+
+    /// Choose the next token given the logits and the tokens so far;
+    /// returns the token and its log probability
+    pub fn sample(&mut self, logits: &[f32], previous_tokens: &[usize]) -> (usize, f32) {
+        let mut scores = logits.to_vec();
+        self.apply_repetition_penalty(&mut scores, previous_tokens);
+
+        if self.config.temperature == 0.0 {
+            let token = argmax(&scores);
+            softmax_in_place(&mut scores);
+            return (token, scores[token].ln());
+        }
+
+        for score in scores.iter_mut() {
+            *score /= self.config.temperature;
+        }
+        softmax_in_place(&mut scores);
+
+        let candidates = self.filter(&scores);
+        let total: f32 = candidates.iter().map(|&(_, p)| p).sum();
+
+        let mut target = self.rng.gen::<f32>() * total;
+        let mut chosen = candidates[candidates.len() - 1];
+        for &(token, prob) in &candidates {
+            if target < prob {
+                chosen = (token, prob);
+                break;
+            }
+            target -= prob;
+        }
+
+        (chosen.0, (chosen.1 / total).ln())
+    }
+
+    /// Divide positive / multiply negative logits of tokens already seen
+    fn apply_repetition_penalty(&self, scores: &mut [f32], previous_tokens: &[usize]) {
+        let penalty = self.config.repetition_penalty;
+        if (penalty - 1.0).abs() < 1e-6 {
+            return;
+        }
+
+        let mut seen = vec![false; scores.len()];
+        for &token in previous_tokens {
+            if token < scores.len() && !seen[token] {
+                seen[token] = true;
+                if scores[token] > 0.0 {
+                    scores[token] /= penalty;
+                } else {
+                    scores[token] *= penalty;
+                }
+            }
+        }
+    }
+
+    /// Top-k, top-p and min-p filtering; candidates sorted by probability,
+    /// never empty
+    fn filter(&self, probs: &[f32]) -> Vec<(usize, f32)> {
+        let mut candidates: Vec<(usize, f32)> = probs.iter().copied().enumerate().collect();
+        candidates.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        if let Some(k) = self.config.top_k {
+            candidates.truncate(k.max(1));
+        }
+
+        if let Some(p) = self.config.top_p {
+            let mut cumulative = 0.0;
+            let mut cutoff = candidates.len();
+            for (i, &(_, prob)) in candidates.iter().enumerate() {
+                cumulative += prob;
+                if cumulative >= p {
+                    cutoff = i + 1;
+                    break;
+                }
+            }
+            candidates.truncate(cutoff);
+        }
+
+        if self.config.min_p > 0.0 {
+            let keep = candidates
+                .iter()
+                .take_while(|&&(_, prob)| prob >= self.config.min_p)
+                .count();
+            candidates.truncate(keep.max(1));
+        }
+
+        candidates
+    }
+}
+
+/// Index of the largest logit
+pub(crate) fn argmax(logits: &[f32]) -> usize {
+    logits
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(b.1))
+        .map(|(token, _)| token)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn logits() -> Vec<f32> {
+        vec![1.0, 3.0, 2.0, 0.5, -1.0]
+    }
+
+    #[test]
+    fn test_greedy_picks_argmax() {
+        let mut sampler = Sampler::new(&GenerationConfig::greedy(1));
+        let (token, logprob) = sampler.sample(&logits(), &[]);
+        assert_eq!(token, 1);
+        assert!(logprob < 0.0);
+    }
+
+    #[test]
+    fn test_seed_reproducible() {
+        let config = GenerationConfig {
+            temperature: 1.5,
+            seed: Some(7),
+            ..Default::default()
+        };
+        let draw = |config: &GenerationConfig| {
+            let mut sampler = Sampler::new(config);
+            (0..32).map(|_| sampler.sample(&logits(), &[]).0).collect::<Vec<_>>()
+        };
+
+        let first = draw(&config);
+        assert_eq!(first, draw(&config));
+        // High temperature over 32 draws visits more than one token
+        assert!(first.iter().any(|&t| t != first[0]));
+    }
+
+    #[test]
+    fn test_top_k_and_min_p_restrict_candidates() {
+        let config = GenerationConfig {
+            temperature: 1.0,
+            top_k: Some(2),
+            seed: Some(1),
+            ..Default::default()
+        };
+        let mut sampler = Sampler::new(&config);
+        for _ in 0..50 {
+            let (token, _) = sampler.sample(&logits(), &[]);
+            assert!(token == 1 || token == 2);
+        }
+
+        let config = GenerationConfig {
+            temperature: 1.0,
+            min_p: 0.5,
+            seed: Some(1),
+            ..Default::default()
+        };
+        let mut sampler = Sampler::new(&config);
+        let (token, logprob) = sampler.sample(&logits(), &[]);
+        assert_eq!(token, 1);
+        assert_eq!(logprob, 0.0);
+    }
+
+    #[test]
+    fn test_top_p_keeps_nucleus() {
+        let sampler = Sampler::new(&GenerationConfig {
+            top_p: Some(0.8),
+            ..Default::default()
+        });
+        let kept = sampler.filter(&[0.5, 0.3, 0.15, 0.05]);
+        assert_eq!(kept.iter().map(|&(t, _)| t).collect::<Vec<_>>(), vec![0, 1]);
+    }
+
+    #[test]
+    fn test_repetition_penalty_changes_greedy_pick() {
+        let mut sampler = Sampler::new(&GenerationConfig {
+            repetition_penalty: 2.0,
+            ..GenerationConfig::greedy(1)
+        });
+        // 3.0 / 2 = 1.5 < 2.0, so token 2 wins once token 1 has been seen
+        assert_eq!(sampler.sample(&logits(), &[1, 1]).0, 2);
+    }
+
+    #[test]
+    fn test_validate_rejects_bad_controls() {
+        assert!(GenerationConfig::default().validate().is_ok());
+        assert!(GenerationConfig { temperature: -1.0, ..Default::default() }.validate().is_err());
+        assert!(GenerationConfig { top_p: Some(0.0), ..Default::default() }.validate().is_err());
+        assert!(GenerationConfig { top_k: Some(0), ..Default::default() }.validate().is_err());
+    }
+}
@@ -0,0 +1,554 @@
+//! Weight storage in F32, F16, BF16 or int8
+//!
+//! A `Matrix` keeps a row-major weight matrix in its storage dtype: f32
+//! values, raw little-endian F16/BF16 bytes, or int8 values with one absmax
+//! scale per row. Reduced-precision bytes are either owned or borrowed
+//! straight from a memory-mapped SafeTensors file (`MappedSafeTensors`), and
+//! the kernels (`row_dot`, `matvec`) dequantize one element at a time, so a
+//! model stored in F16 needs half the memory of its f32 form and an int8 one
+//! a quarter.
+//!
+//! Training works on f32: `values_mut` upcasts a matrix in place.
+
+use anyhow::{Context, Result};
+use memmap2::Mmap;
+use safetensors::{Dtype, SafeTensors};
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
+
+use crate::training::WeightLoader;
+
+/// Storage (and compute) dtype of weight matrices
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DType {
+    #[default]
+    F32,
+    F16,
+    BF16,
+    /// Symmetric int8 with one f32 scale per row
+    Int8,
+}
+
+impl DType {
+    /// Parse "f32", "f16", "bf16" or "int8"
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.to_lowercase().as_str() {
+            "f32" | "float32" => Ok(Self::F32),
+            "f16" | "float16" | "half" => Ok(Self::F16),
+            "bf16" | "bfloat16" => Ok(Self::BF16),
+            "int8" | "i8" | "q8" => Ok(Self::Int8),
+            other => anyhow::bail!("Unknown dtype: {} (expected f32, f16, bf16 or int8)", other),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::F32 => "f32",
+            Self::F16 => "f16",
+            Self::BF16 => "bf16",
+            Self::Int8 => "int8",
+        }
+    }
+}
+
+/// Raw tensor bytes, owned or inside a memory-mapped file
+#[derive(Clone)]
+enum Bytes {
+    Owned(Arc<[u8]>),
+    Mapped { map: Arc<Mmap>, offset: usize, len: usize },
+}
+
+impl Bytes {
+    fn as_slice(&self) -> &[u8] {
+        match self {
+            Self::Owned(bytes) => bytes,
+            Self::Mapped { map, offset, len } => &map[*offset..*offset + *len],
+        }
+    }
+}
+
+impl fmt::Debug for Bytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Owned(bytes) => write!(f, "Owned({} bytes)", bytes.len()),
+            Self::Mapped { len, .. } => write!(f, "Mapped({} bytes)", len),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Data {
+    F32(Vec<f32>),
+    F16(Bytes),
+    BF16(Bytes),
+    Int8 { values: Bytes, scales: Vec<f32> },
+}
+
+/// Row-major [rows, cols] weight matrix in its storage dtype
+#[derive(Debug, Clone)]
+pub struct Matrix {
+    rows: usize,
+    cols: usize,
+    data: Data,
+}
+
+impl Matrix {
+    pub fn from_f32(rows: usize, cols: usize, values: Vec<f32>) -> Self {
+        assert_eq!(values.len(), rows * cols, "matrix data does not match its shape");
+        Self {
+            rows,
+            cols,
+            data: Data::F32(values),
+        }
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    pub fn dtype(&self) -> DType {
+        match self.data {
+            Data::F32(_) => DType::F32,
+            Data::F16(_) => DType::F16,
+            Data::BF16(_) => DType::BF16,
+            Data::Int8 { .. } => DType::Int8,
+        }
+    }
+
+    /// Bytes held by the weights (mapped bytes included)
+    pub fn storage_bytes(&self) -> usize {
+        match &self.data {
+            Data::F32(values) => values.len() * 4,
+            Data::F16(bytes) | Data::BF16(bytes) => bytes.as_slice().len(),
+            Data::Int8 { values, scales } => values.as_slice().len() + scales.len() * 4,
+        }
+    }
+
+    /// True if the weights are read from a memory-mapped file
+    pub fn is_mapped(&self) -> bool {
+        match &self.data {
+            Data::F32(_) => false,
+            Data::F16(bytes) | Data::BF16(bytes) | Data::Int8 { values: bytes, .. } => {
+                matches!(bytes, Bytes::Mapped { .. })
+            }
+        }
+    }
+
+    /// Re-encode in `dtype` (int8 rounds each row to its absmax / 127)
+    pub fn convert(&mut self, dtype: DType) {
+        if self.dtype() == dtype {
+            return;
+        }
+        let values = self.to_f32().into_owned();
+        self.data = match dtype {
+            DType::F32 => Data::F32(values),
+            DType::F16 => Data::F16(owned(values.iter().flat_map(|&v| half::f16::from_f32(v).to_le_bytes()))),
+            DType::BF16 => Data::BF16(owned(values.iter().flat_map(|&v| half::bf16::from_f32(v).to_le_bytes()))),
+            DType::Int8 => {
+                let (quantized, scales) = quantize_rows(&values, self.cols);
+                Data::Int8 {
+                    values: owned(quantized.into_iter().map(|q| q as u8)),
+                    scales,
+                }
+            }
+        };
+    }
+
+    /// All values as f32 (borrowed when stored as f32)
+    pub fn to_f32(&self) -> Cow<'_, [f32]> {
+        match &self.data {
+            Data::F32(values) => Cow::Borrowed(values),
+            _ => Cow::Owned((0..self.rows).flat_map(|r| self.row(r).into_owned()).collect()),
+        }
+    }
+
+    /// Row `r` as f32
+    pub fn row(&self, r: usize) -> Cow<'_, [f32]> {
+        let (start, end) = (r * self.cols, (r + 1) * self.cols);
+        match &self.data {
+            Data::F32(values) => Cow::Borrowed(&values[start..end]),
+            Data::F16(bytes) => Cow::Owned(halves(&bytes.as_slice()[2 * start..2 * end]).map(f16_to_f32).collect()),
+            Data::BF16(bytes) => Cow::Owned(halves(&bytes.as_slice()[2 * start..2 * end]).map(bf16_to_f32).collect()),
+            Data::Int8 { values, scales } => Cow::Owned(
+                values.as_slice()[start..end]
+                    .iter()
+                    .map(|&q| q as i8 as f32 * scales[r])
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Dot product of row `r` with `x`, dequantizing on the fly
+    pub fn row_dot(&self, r: usize, x: &[f32]) -> f32 {
+        debug_assert_eq!(x.len(), self.cols);
+        let (start, end) = (r * self.cols, (r + 1) * self.cols);
+        match &self.data {
+            Data::F32(values) => values[start..end].iter().zip(x).map(|(w, x)| w * x).sum(),
+            Data::F16(bytes) => halves(&bytes.as_slice()[2 * start..2 * end])
+                .zip(x)
+                .map(|(w, x)| f16_to_f32(w) * x)
+                .sum(),
+            Data::BF16(bytes) => halves(&bytes.as_slice()[2 * start..2 * end])
+                .zip(x)
+                .map(|(w, x)| bf16_to_f32(w) * x)
+                .sum(),
+            Data::Int8 { values, scales } => {
+                let sum: f32 = values.as_slice()[start..end]
+                    .iter()
+                    .zip(x)
+                    .map(|(&q, x)| q as i8 as f32 * x)
+                    .sum();
+                sum * scales[r]
+            }
+        }
+    }
+
+    /// W x
+    pub fn matvec(&self, x: &[f32]) -> Vec<f32> {
+        (0..self.rows).map(|r| self.row_dot(r, x)).collect()
+    }
+
+    /// Mutable f32 values, upcasting (and unmapping) the matrix first
+    pub fn values_mut(&mut self) -> &mut [f32] {
+        self.convert(DType::F32);
+        match &mut self.data {
+            Data::F32(values) => values,
+            _ => unreachable!("converted to f32 above"),
+        }
+    }
+}
+
+/// Symmetric per-row int8 quantization: values and one scale per row
+pub fn quantize_rows(values: &[f32], cols: usize) -> (Vec<i8>, Vec<f32>) {
+    let mut quantized = Vec::with_capacity(values.len());
+    let mut scales = Vec::with_capacity(values.len() / cols.max(1));
+    for row in values.chunks(cols.max(1)) {
+        let absmax = row.iter().fold(0.0f32, |m, v| m.max(v.abs()));
+        let scale = absmax / 127.0;
+        let inv = if scale > 0.0 { 1.0 / scale } else { 0.0 };
+        quantized.extend(row.iter().map(|v| (v * inv).round().clamp(-127.0, 127.0) as i8));
+        scales.push(scale);
+    }
+    (quantized, scales)
+}
+
+fn owned(bytes: impl Iterator<Item = u8>) -> Bytes {
+    Bytes::Owned(bytes.collect::<Vec<u8>>().into())
+}
+
+fn halves(bytes: &[u8]) -> impl Iterator<Item = u16> + '_ {
+    bytes.chunks_exact(2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn f16_to_f32(bits: u16) -> f32 {
+    half::f16::from_bits(bits).to_f32()
+}
+
+fn bf16_to_f32(bits: u16) -> f32 {
+    half::bf16::from_bits(bits).to_f32()
+}
+
+/// Named tensors a model can be built from
+pub trait TensorSource {
+    /// Element count of `name`, or None if it is missing
+    fn tensor_len(&self, name: &str) -> Option<usize>;
+
+    /// `name` as f32 values (norms and biases)
+    fn vector(&self, name: &str) -> Result<Vec<f32>>;
+
+    /// `name` as a [rows, cols] matrix stored in `dtype`
+    fn matrix(&self, name: &str, rows: usize, cols: usize, dtype: DType) -> Result<Matrix>;
+}
+
+impl TensorSource for WeightLoader {
+    fn tensor_len(&self, name: &str) -> Option<usize> {
+        self.get_tensor(name).map(|t| t.len())
+    }
+
+    fn vector(&self, name: &str) -> Result<Vec<f32>> {
+        self.get_tensor(name)
+            .cloned()
+            .with_context(|| format!("Missing tensor {}", name))
+    }
+
+    fn matrix(&self, name: &str, rows: usize, cols: usize, dtype: DType) -> Result<Matrix> {
+        let mut matrix = Matrix::from_f32(rows, cols, self.vector(name)?);
+        matrix.convert(dtype);
+        Ok(matrix)
+    }
+}
+
+/// A `TensorSource` that reports every tensor read through it and refuses
+/// further reads once `cancel` fires, so loading a model can show progress
+/// and be aborted between tensors
+pub struct ObservedSource<'a, F: FnMut(usize)> {
+    inner: &'a dyn TensorSource,
+    cancel: &'a CancellationToken,
+    on_tensor: RefCell<F>,
+    read: Cell<usize>,
+}
+
+impl<'a, F: FnMut(usize)> ObservedSource<'a, F> {
+    /// `on_tensor` gets the number of tensors read so far
+    pub fn new(inner: &'a dyn TensorSource, cancel: &'a CancellationToken, on_tensor: F) -> Self {
+        Self {
+            inner,
+            cancel,
+            on_tensor: RefCell::new(on_tensor),
+            read: Cell::new(0),
+        }
+    }
+
+    fn observe<T>(&self, read: impl FnOnce() -> Result<T>) -> Result<T> {
+        if self.cancel.is_cancelled() {
+            anyhow::bail!("Weight loading cancelled after {} tensors", self.read.get());
+        }
+        let value = read()?;
+        self.read.set(self.read.get() + 1);
+        (self.on_tensor.borrow_mut())(self.read.get());
+        Ok(value)
+    }
+}
+
+impl<F: FnMut(usize)> TensorSource for ObservedSource<'_, F> {
+    fn tensor_len(&self, name: &str) -> Option<usize> {
+        self.inner.tensor_len(name)
+    }
+
+    fn vector(&self, name: &str) -> Result<Vec<f32>> {
+        self.observe(|| self.inner.vector(name))
+    }
+
+    fn matrix(&self, name: &str, rows: usize, cols: usize, dtype: DType) -> Result<Matrix> {
+        self.observe(|| self.inner.matrix(name, rows, cols, dtype))
+    }
+}
+
+#[derive(Debug)]
+struct MappedTensor {
+    dtype: Dtype,
+    shape: Vec<usize>,
+    offset: usize,
+    len: usize,
+}
+
+/// A SafeTensors file mapped into memory
+///
+/// Matrices whose file dtype is the requested one (F16, BF16, or I8 with a
+/// `{name}_scale` F32 tensor of per-row scales, as `SaveDtype::Int8`
+/// writes) borrow the mapped bytes without copying; others are converted.
+/// A mapped file must not be modified while a model reads from it;
+/// `WeightLoader` replaces files by renaming instead of rewriting them.
+pub struct MappedSafeTensors {
+    map: Arc<Mmap>,
+    tensors: HashMap<String, MappedTensor>,
+}
+
+impl MappedSafeTensors {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        // SAFETY: the mapping is read-only; see the type docs on modification
+        let map = Arc::new(unsafe { Mmap::map(&file)? });
+
+        let base = map.as_ptr() as usize;
+        let tensors = SafeTensors::deserialize(&map)
+            .with_context(|| format!("Failed to parse {}", path.display()))?
+            .tensors()
+            .into_iter()
+            .map(|(name, view)| {
+                let tensor = MappedTensor {
+                    dtype: view.dtype(),
+                    shape: view.shape().to_vec(),
+                    offset: view.data().as_ptr() as usize - base,
+                    len: view.data().len(),
+                };
+                (name, tensor)
+            })
+            .collect();
+
+        Ok(Self { map, tensors })
+    }
+
+    pub fn tensor_names(&self) -> Vec<String> {
+        self.tensors.keys().cloned().collect()
+    }
+
+    pub fn shape(&self, name: &str) -> Option<&[usize]> {
+        self.tensors.get(name).map(|t| t.shape.as_slice())
+    }
+
+    fn get(&self, name: &str) -> Result<&MappedTensor> {
+        self.tensors
+            .get(name)
+            .with_context(|| format!("Missing tensor {}", name))
+    }
+
+    fn bytes(&self, tensor: &MappedTensor) -> Bytes {
+        Bytes::Mapped {
+            map: self.map.clone(),
+            offset: tensor.offset,
+            len: tensor.len,
+        }
+    }
+
+    fn scales(&self, name: &str, rows: usize) -> Result<Vec<f32>> {
+        let scale_name = format!("{}_scale", name);
+        let scales = self.vector(&scale_name)?;
+        if scales.len() != rows {
+            anyhow::bail!("{} has {} scales, expected one per row ({})", scale_name, scales.len(), rows);
+        }
+        Ok(scales)
+    }
+}
+
+impl TensorSource for MappedSafeTensors {
+    fn tensor_len(&self, name: &str) -> Option<usize> {
+        self.shape(name).map(|shape| shape.iter().product())
+    }
+
+    fn vector(&self, name: &str) -> Result<Vec<f32>> {
+        let tensor = self.get(name)?;
+        let bytes = &self.map[tensor.offset..tensor.offset + tensor.len];
+        match tensor.dtype {
+            Dtype::F32 => Ok(bytes
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect()),
+            Dtype::F16 => Ok(halves(bytes).map(f16_to_f32).collect()),
+            Dtype::BF16 => Ok(halves(bytes).map(bf16_to_f32).collect()),
+            Dtype::I8 => {
+                let rows = tensor.shape.first().copied().unwrap_or(1);
+                let cols = self.tensor_len(name).unwrap_or(0) / rows.max(1);
+                Ok(self.matrix(name, rows, cols, DType::F32)?.to_f32().into_owned())
+            }
+            other => anyhow::bail!("Unsupported dtype {:?} for tensor {}", other, name),
+        }
+    }
+
+    fn matrix(&self, name: &str, rows: usize, cols: usize, dtype: DType) -> Result<Matrix> {
+        let tensor = self.get(name)?;
+        if self.tensor_len(name) != Some(rows * cols) {
+            anyhow::bail!("Tensor {} has shape {:?}, expected [{}, {}]", name, tensor.shape, rows, cols);
+        }
+        let data = match tensor.dtype {
+            Dtype::F16 => Data::F16(self.bytes(tensor)),
+            Dtype::BF16 => Data::BF16(self.bytes(tensor)),
+            Dtype::I8 => Data::Int8 {
+                values: self.bytes(tensor),
+                scales: self.scales(name, rows)?,
+            },
+            _ => Data::F32(self.vector(name)?),
+        };
+
+        let mut matrix = Matrix { rows, cols, data };
+        matrix.convert(dtype);
+        Ok(matrix)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::training::{SaveDtype, SaveOptions, WeightFormat};
+
+    fn sample(rows: usize, cols: usize) -> Vec<f32> {
+        (0..rows * cols).map(|i| ((i * 37 % 23) as f32 - 11.0) / 7.0).collect()
+    }
+
+    #[test]
+    fn test_matvec_matches_f32_within_tolerance() {
+        let values = sample(6, 10);
+        let x: Vec<f32> = (0..10).map(|i| (i as f32 - 4.5) / 3.0).collect();
+        let exact = Matrix::from_f32(6, 10, values.clone()).matvec(&x);
+
+        for (dtype, tolerance) in [(DType::F16, 1e-2), (DType::BF16, 5e-2), (DType::Int8, 1e-1)] {
+            let mut matrix = Matrix::from_f32(6, 10, values.clone());
+            matrix.convert(dtype);
+            assert_eq!(matrix.dtype(), dtype);
+            assert!(matrix.storage_bytes() < 6 * 10 * 4);
+
+            for (a, b) in matrix.matvec(&x).iter().zip(&exact) {
+                assert!((a - b).abs() < tolerance, "{}: {} vs {}", dtype.name(), a, b);
+            }
+            let upcast = matrix.values_mut().to_vec();
+            assert_eq!(upcast.len(), values.len());
+            assert_eq!(matrix.dtype(), DType::F32);
+        }
+    }
+
+    #[test]
+    fn test_quantize_rows_scales_each_row() {
+        let (quantized, scales) = quantize_rows(&[1.0, -0.5, 0.0, 0.0, 2.0, 4.0], 3);
+        assert_eq!(quantized, vec![127, -64, 0, 0, 64, 127]);
+        assert_eq!(scales, vec![1.0 / 127.0, 4.0 / 127.0]);
+    }
+
+    #[test]
+    fn test_mapped_safetensors_borrows_file_bytes() {
+        let dir = tempfile::tempdir().unwrap();
+        let values = sample(4, 8);
+        let mut loader = WeightLoader::new(WeightFormat::SafeTensors);
+        loader.insert_tensor_with_shape("w.weight".to_string(), vec![4, 8], values.clone()).unwrap();
+        loader.insert_tensor_with_shape("w.bias".to_string(), vec![4], vec![0.5; 4]).unwrap();
+
+        for (save, dtype) in [(SaveDtype::F16, DType::F16), (SaveDtype::BF16, DType::BF16), (SaveDtype::Int8, DType::Int8)] {
+            let path = dir.path().join(format!("{}.safetensors", dtype.name()));
+            let options = SaveOptions {
+                dtype: save,
+                ..SaveOptions::default()
+            };
+            loader.save_with_options(&path, WeightFormat::SafeTensors, &options).unwrap();
+
+            let mapped = MappedSafeTensors::open(&path).unwrap();
+            assert_eq!(mapped.tensor_len("w.weight"), Some(32));
+            let matrix = mapped.matrix("w.weight", 4, 8, dtype).unwrap();
+            assert!(matrix.is_mapped());
+            assert_eq!(matrix.dtype(), dtype);
+
+            // Asking for another dtype converts into owned memory
+            let upcast = mapped.matrix("w.weight", 4, 8, DType::F32).unwrap();
+            assert!(!upcast.is_mapped());
+            for (a, b) in upcast.to_f32().iter().zip(&values) {
+                assert!((a - b).abs() < 2e-2, "{}: {} vs {}", dtype.name(), a, b);
+            }
+            // 1-D tensors stay f32 for int8
+            assert_eq!(mapped.vector("w.bias").unwrap(), vec![0.5; 4]);
+
+            // WeightLoader dequantizes, applying (and dropping) int8 scales
+            let mut reloaded = WeightLoader::new(WeightFormat::SafeTensors);
+            reloaded.load_from_file(&path).unwrap();
+            assert_eq!(reloaded.tensor_names().len(), 2);
+            assert_eq!(reloaded.get_tensor("w.weight").unwrap(), &upcast.to_f32().to_vec());
+        }
+    }
+
+    #[test]
+    fn test_observed_source_counts_reads_until_cancelled() {
+        let mut loader = WeightLoader::new(WeightFormat::SafeTensors);
+        loader.insert_tensor_with_shape("w".to_string(), vec![2, 2], vec![1.0; 4]).unwrap();
+        loader.insert_tensor("b".to_string(), vec![0.5; 2]);
+
+        let cancel = CancellationToken::new();
+        let mut seen = Vec::new();
+        {
+            let observed = ObservedSource::new(&loader, &cancel, |read| seen.push(read));
+            assert_eq!(observed.tensor_len("w"), Some(4));
+            observed.matrix("w", 2, 2, DType::F32).unwrap();
+            observed.vector("b").unwrap();
+            cancel.cancel();
+            assert!(observed.vector("b").unwrap_err().to_string().contains("cancelled after 2 tensors"));
+        }
+        assert_eq!(seen, vec![1, 2]);
+    }
+}
@@ -0,0 +1,269 @@
+//! Byte-pair encoding over a vocabulary with merge priorities
+//!
+//! Two flavours share the merge loop:
+//! - byte-level (GPT-2, Llama 3, Qwen): text is split by a pre-tokenizer
+//!   regex and every byte is mapped to a printable character first
+//! - SentencePiece (Llama 2, Mistral): spaces become `▁`, and characters
+//!   missing from the vocabulary fall back to `<0xNN>` byte tokens
+//!
+//! Merge priority comes from an ordered merge list (HF `tokenizer.json`,
+//! GGUF `gpt2`) or from the score of the merged token (GGUF `llama`, as in
+//! llama.cpp's SentencePiece tokenizer).
+
+use anyhow::{Context, Result};
+use fancy_regex::Regex;
+use std::collections::HashMap;
+
+use super::{byte_fallback_ids, piece_bytes, sentencepiece_normalize};
+
+/// GPT-2's pre-tokenizer pattern
+pub(super) const GPT2_PATTERN: &str =
+    r"'s|'t|'re|'ve|'m|'ll|'d| ?\p{L}+| ?\p{N}+| ?[^\s\p{L}\p{N}]+|\s+(?!\S)|\s+";
+
+/// Llama 3's pre-tokenizer pattern (also used by Qwen 2)
+pub(super) const LLAMA3_PATTERN: &str = r"(?i:'s|'t|'re|'ve|'m|'ll|'d)|[^\r\n\p{L}\p{N}]?\p{L}+|\p{N}{1,3}| ?[^\s\p{L}\p{N}]+[\r\n]*|\s*[\r\n]+|\s+(?!\S)|\s+";
+
+/// How text becomes the symbols that are merged
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum BpeMode {
+    ByteLevel,
+    SentencePiece { add_prefix_space: bool },
+}
+
+/// Which adjacent pair merges first (lowest value wins)
+pub(super) enum Priority {
+    /// Position in the merge list
+    Ranks(HashMap<(String, String), usize>),
+    /// Score of the merged token, by token id (higher merges first)
+    Scores(Vec<f32>),
+}
+
+pub(super) struct BpeModel {
+    mode: BpeMode,
+    vocab: HashMap<String, usize>,
+    tokens: Vec<String>,
+    priority: Priority,
+    /// Pre-tokenizer for byte-level BPE
+    pattern: Option<Regex>,
+    /// Emit whole pre-tokenized words found in the vocabulary without merging (Llama 3)
+    ignore_merges: bool,
+    unk_id: Option<usize>,
+    /// Byte <-> printable character maps for byte-level BPE
+    byte_chars: Vec<char>,
+    char_bytes: HashMap<char, u8>,
+}
+
+impl BpeModel {
+    pub(super) fn new(mode: BpeMode, tokens: Vec<String>, priority: Priority) -> Self {
+        let vocab = tokens
+            .iter()
+            .enumerate()
+            .filter(|(_, token)| !token.is_empty())
+            .map(|(id, token)| (token.clone(), id))
+            .collect();
+        let byte_chars = bytes_to_chars();
+        let char_bytes = byte_chars.iter().enumerate().map(|(b, &c)| (c, b as u8)).collect();
+
+        Self {
+            mode,
+            vocab,
+            tokens,
+            priority,
+            pattern: None,
+            ignore_merges: false,
+            unk_id: None,
+            byte_chars,
+            char_bytes,
+        }
+    }
+
+    /// Split byte-level input with `pattern` before merging
+    pub(super) fn with_pattern(mut self, pattern: &str) -> Result<Self> {
+        let regex = Regex::new(pattern).with_context(|| format!("Invalid pre-tokenizer pattern {}", pattern))?;
+        self.pattern = Some(regex);
+        Ok(self)
+    }
+
+    pub(super) fn with_ignore_merges(mut self, ignore_merges: bool) -> Self {
+        self.ignore_merges = ignore_merges;
+        self
+    }
+
+    pub(super) fn with_unk(mut self, unk_id: Option<usize>) -> Self {
+        self.unk_id = unk_id;
+        self
+    }
+
+    /// Merge list in priority order
+    pub(super) fn ranks(merges: impl IntoIterator<Item = (String, String)>) -> Priority {
+        let mut ranks = HashMap::new();
+        for (rank, pair) in merges.into_iter().enumerate() {
+            ranks.entry(pair).or_insert(rank);
+        }
+        Priority::Ranks(ranks)
+    }
+
+    /// Split an "a b" merge line
+    pub(super) fn parse_merge(merge: &str) -> Result<(String, String)> {
+        let (a, b) = merge
+            .split_once(' ')
+            .with_context(|| format!("Invalid merge {:?}", merge))?;
+        Ok((a.to_string(), b.to_string()))
+    }
+
+    pub(super) fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub(super) fn mode(&self) -> BpeMode {
+        self.mode
+    }
+
+    /// Append the ids of `text`; `first` marks the start of the input
+    pub(super) fn encode(&self, text: &str, first: bool, out: &mut Vec<usize>) {
+        match self.mode {
+            BpeMode::ByteLevel => {
+                let words: Vec<&str> = match &self.pattern {
+                    Some(regex) => regex.find_iter(text).filter_map(|m| m.ok()).map(|m| m.as_str()).collect(),
+                    None => vec![text],
+                };
+                for word in words {
+                    let mapped: String = word.bytes().map(|b| self.byte_chars[b as usize]).collect();
+                    self.encode_word(&mapped, out);
+                }
+            }
+            BpeMode::SentencePiece { add_prefix_space } => {
+                let normalized = sentencepiece_normalize(text, add_prefix_space && first);
+                self.encode_word(&normalized, out);
+            }
+        }
+    }
+
+    fn encode_word(&self, word: &str, out: &mut Vec<usize>) {
+        if word.is_empty() {
+            return;
+        }
+        if self.ignore_merges {
+            if let Some(&id) = self.vocab.get(word) {
+                out.push(id);
+                return;
+            }
+        }
+
+        let mut symbols: Vec<String> = word.chars().map(String::from).collect();
+        loop {
+            let best = symbols
+                .windows(2)
+                .enumerate()
+                .filter_map(|(i, pair)| self.pair_priority(&pair[0], &pair[1]).map(|p| (i, p)))
+                .min_by(|(i, a), (j, b)| a.total_cmp(b).then(i.cmp(j)));
+            let Some((i, _)) = best else {
+                break;
+            };
+            let right = symbols.remove(i + 1);
+            symbols[i].push_str(&right);
+        }
+
+        for symbol in symbols {
+            match self.vocab.get(&symbol) {
+                Some(&id) => out.push(id),
+                None => match self.mode {
+                    BpeMode::SentencePiece { .. } => match byte_fallback_ids(&symbol, &self.vocab) {
+                        Some(ids) => out.extend(ids),
+                        None => out.extend(self.unk_id),
+                    },
+                    BpeMode::ByteLevel => out.extend(self.unk_id),
+                },
+            }
+        }
+    }
+
+    fn pair_priority(&self, a: &str, b: &str) -> Option<f64> {
+        match &self.priority {
+            Priority::Ranks(ranks) => ranks.get(&(a.to_string(), b.to_string())).map(|&r| r as f64),
+            Priority::Scores(scores) => {
+                let id = *self.vocab.get(&format!("{}{}", a, b))?;
+                Some(-(scores.get(id).copied().unwrap_or(0.0) as f64))
+            }
+        }
+    }
+
+    /// Bytes of token `id`, or None if it is not in the vocabulary
+    pub(super) fn token_bytes(&self, id: usize) -> Option<Vec<u8>> {
+        let token = self.tokens.get(id).filter(|t| !t.is_empty())?;
+        Some(match self.mode {
+            BpeMode::ByteLevel => {
+                let mut bytes = Vec::with_capacity(token.len());
+                for c in token.chars() {
+                    match self.char_bytes.get(&c) {
+                        Some(&b) => bytes.push(b),
+                        None => bytes.extend(c.to_string().bytes()),
+                    }
+                }
+                bytes
+            }
+            BpeMode::SentencePiece { .. } => piece_bytes(token),
+        })
+    }
+}
+
+/// GPT-2's reversible byte -> printable character mapping
+fn bytes_to_chars() -> Vec<char> {
+    let printable = |b: u8| matches!(b, b'!'..=b'~' | 0xA1..=0xAC | 0xAE..=0xFF);
+    let mut next = 256u32;
+    (0..=255u8)
+        .map(|b| {
+            if printable(b) {
+                b as char
+            } else {
+                let c = char::from_u32(next).unwrap();
+                next += 1;
+                c
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn byte_level() -> BpeModel {
+        // "Ġ" is the space byte; merges build "Ġlow" and "er"
+        let tokens = ["l", "o", "w", "e", "r", "Ġ", "lo", "low", "er", "Ġlow"]
+            .map(String::from)
+            .to_vec();
+        let merges = ["l o", "lo w", "e r", "Ġ low"].map(|m| BpeModel::parse_merge(m).unwrap());
+        let priority = BpeModel::ranks(merges);
+        BpeModel::new(BpeMode::ByteLevel, tokens, priority)
+            .with_pattern(GPT2_PATTERN)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_byte_level_merges_by_rank() {
+        let model = byte_level();
+        let mut ids = Vec::new();
+        model.encode("lower low", true, &mut ids);
+        assert_eq!(ids, vec![7, 8, 9]);
+
+        let bytes: Vec<u8> = ids.iter().flat_map(|&id| model.token_bytes(id).unwrap()).collect();
+        assert_eq!(bytes, b"lower low");
+    }
+
+    #[test]
+    fn test_sentencepiece_merges_by_score_with_byte_fallback() {
+        let mut tokens: Vec<String> = ["<unk>", "▁", "a", "b", "ab", "▁ab"].map(String::from).to_vec();
+        tokens.extend((0..=255u8).map(|b| format!("<0x{:02X}>", b)));
+        let scores = vec![0.0, -1.0, -1.0, -1.0, -0.5, -0.1];
+        let model = BpeModel::new(BpeMode::SentencePiece { add_prefix_space: true }, tokens, Priority::Scores(scores));
+
+        let mut ids = Vec::new();
+        model.encode("ab é", true, &mut ids);
+        // "▁ab", then "▁" and the two UTF-8 bytes of "é"
+        assert_eq!(ids, vec![5, 1, 6 + 0xC3, 6 + 0xA9]);
+
+        let bytes: Vec<u8> = ids.iter().flat_map(|&id| model.token_bytes(id).unwrap()).collect();
+        assert_eq!(String::from_utf8(bytes).unwrap(), " ab é");
+    }
+}
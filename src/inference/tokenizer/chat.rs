@@ -0,0 +1,222 @@
+//! Chat templates: rendering a conversation as the prompt a model was tuned on
+//!
+//! Models ship a Jinja template (`tokenizer.chat_template` in GGUF,
+//! `chat_template` in `tokenizer_config.json`). Rather than evaluating
+//! Jinja, `ChatTemplate::detect` recognizes the common families by their
+//! markers, as llama.cpp does, and renders them directly.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// One turn of a conversation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChatMessage {
+    /// "system", "user" or "assistant"
+    pub role: String,
+    pub content: String,
+}
+
+impl ChatMessage {
+    pub fn new(role: impl Into<String>, content: impl Into<String>) -> Self {
+        Self {
+            role: role.into(),
+            content: content.into(),
+        }
+    }
+}
+
+/// Prompt format for conversations
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChatTemplate {
+    /// "Role: content" lines, ending with "Assistant:" (models without a template)
+    #[default]
+    Plain,
+    /// `<|im_start|>role\n...<|im_end|>` (Qwen, Yi, OpenHermes)
+    ChatMl,
+    /// `[INST] <<SYS>>...<</SYS>> ... [/INST]` (Llama 2)
+    Llama2,
+    /// `<|start_header_id|>role<|end_header_id|>\n\n...<|eot_id|>` (Llama 3)
+    Llama3,
+    /// `[INST] ... [/INST]` with the system prompt in the first turn (Mistral)
+    Mistral,
+    /// `<start_of_turn>user\n...<end_of_turn>` (Gemma)
+    Gemma,
+}
+
+impl ChatTemplate {
+    /// Parse "plain", "chatml", "llama2", "llama3", "mistral" or "gemma"
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.to_lowercase().as_str() {
+            "plain" => Ok(Self::Plain),
+            "chatml" => Ok(Self::ChatMl),
+            "llama2" => Ok(Self::Llama2),
+            "llama3" => Ok(Self::Llama3),
+            "mistral" => Ok(Self::Mistral),
+            "gemma" => Ok(Self::Gemma),
+            other => anyhow::bail!(
+                "Unknown chat template: {} (expected plain, chatml, llama2, llama3, mistral or gemma)",
+                other
+            ),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Plain => "plain",
+            Self::ChatMl => "chatml",
+            Self::Llama2 => "llama2",
+            Self::Llama3 => "llama3",
+            Self::Mistral => "mistral",
+            Self::Gemma => "gemma",
+        }
+    }
+
+    /// Recognize a Jinja chat template by its markers (Plain if none match)
+    pub fn detect(template: &str) -> Self {
+        if template.contains("<|im_start|>") {
+            Self::ChatMl
+        } else if template.contains("<|start_header_id|>") {
+            Self::Llama3
+        } else if template.contains("<start_of_turn>") {
+            Self::Gemma
+        } else if template.contains("<<SYS>>") {
+            Self::Llama2
+        } else if template.contains("[INST]") {
+            Self::Mistral
+        } else {
+            Self::Plain
+        }
+    }
+
+    /// Render `messages`; with `add_generation_prompt` the text ends where
+    /// the assistant's reply begins
+    ///
+    /// `bos` and `eos` are the tokenizer's BOS/EOS texts (empty if it has none).
+    pub fn apply(&self, messages: &[ChatMessage], add_generation_prompt: bool, bos: &str, eos: &str) -> String {
+        let mut out = String::new();
+        match self {
+            Self::Plain => {
+                for message in messages {
+                    out.push_str(&format!("{}: {}\n", capitalize(&message.role), message.content));
+                }
+                if add_generation_prompt {
+                    out.push_str("Assistant:");
+                }
+            }
+            Self::ChatMl => {
+                for message in messages {
+                    out.push_str(&format!("<|im_start|>{}\n{}<|im_end|>\n", message.role, message.content));
+                }
+                if add_generation_prompt {
+                    out.push_str("<|im_start|>assistant\n");
+                }
+            }
+            Self::Llama3 => {
+                out.push_str(bos);
+                for message in messages {
+                    out.push_str(&format!(
+                        "<|start_header_id|>{}<|end_header_id|>\n\n{}<|eot_id|>",
+                        message.role, message.content
+                    ));
+                }
+                if add_generation_prompt {
+                    out.push_str("<|start_header_id|>assistant<|end_header_id|>\n\n");
+                }
+            }
+            Self::Llama2 | Self::Mistral => {
+                let (system, turns) = split_system(messages);
+                for (i, message) in turns.iter().enumerate() {
+                    if message.role == "assistant" {
+                        out.push_str(&format!(" {}{}", message.content, eos));
+                        continue;
+                    }
+                    let content = match (system, i, self) {
+                        (Some(system), 0, Self::Llama2) => {
+                            format!("<<SYS>>\n{}\n<</SYS>>\n\n{}", system, message.content)
+                        }
+                        (Some(system), 0, _) => format!("{}\n\n{}", system, message.content),
+                        _ => message.content.clone(),
+                    };
+                    out.push_str(&format!("{}[INST] {} [/INST]", bos, content));
+                }
+            }
+            Self::Gemma => {
+                out.push_str(bos);
+                let (system, turns) = split_system(messages);
+                for (i, message) in turns.iter().enumerate() {
+                    let role = if message.role == "assistant" { "model" } else { "user" };
+                    let content = match (system, i) {
+                        (Some(system), 0) => format!("{}\n\n{}", system, message.content),
+                        _ => message.content.clone(),
+                    };
+                    out.push_str(&format!("<start_of_turn>{}\n{}<end_of_turn>\n", role, content));
+                }
+                if add_generation_prompt {
+                    out.push_str("<start_of_turn>model\n");
+                }
+            }
+        }
+        out
+    }
+}
+
+/// A leading system message, and the turns after it
+fn split_system(messages: &[ChatMessage]) -> (Option<&str>, &[ChatMessage]) {
+    match messages.split_first() {
+        Some((first, rest)) if first.role == "system" => (Some(&first.content), rest),
+        _ => (None, messages),
+    }
+}
+
+fn capitalize(role: &str) -> String {
+    let mut chars = role.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn conversation() -> Vec<ChatMessage> {
+        vec![
+            ChatMessage::new("system", "Be brief."),
+            ChatMessage::new("user", "Hi"),
+            ChatMessage::new("assistant", "Hello"),
+            ChatMessage::new("user", "Bye"),
+        ]
+    }
+
+    #[test]
+    fn test_templates_render_known_formats() {
+        let messages = conversation();
+        assert_eq!(
+            ChatTemplate::Plain.apply(&messages, true, "", ""),
+            "System: Be brief.\nUser: Hi\nAssistant: Hello\nUser: Bye\nAssistant:"
+        );
+        assert_eq!(
+            ChatTemplate::ChatMl.apply(&messages[1..2], true, "", ""),
+            "<|im_start|>user\nHi<|im_end|>\n<|im_start|>assistant\n"
+        );
+        assert_eq!(
+            ChatTemplate::Llama2.apply(&messages, true, "<s>", "</s>"),
+            "<s>[INST] <<SYS>>\nBe brief.\n<</SYS>>\n\nHi [/INST] Hello</s><s>[INST] Bye [/INST]"
+        );
+        assert_eq!(
+            ChatTemplate::Gemma.apply(&messages[..2], true, "<bos>", ""),
+            "<bos><start_of_turn>user\nBe brief.\n\nHi<end_of_turn>\n<start_of_turn>model\n"
+        );
+    }
+
+    #[test]
+    fn test_detect_from_jinja_markers() {
+        let llama3 = "{% for message in messages %}<|start_header_id|>{{ message['role'] }}<|end_header_id|>";
+        assert_eq!(ChatTemplate::detect(llama3), ChatTemplate::Llama3);
+        assert_eq!(ChatTemplate::detect("{{ '<|im_start|>' + message['role'] }}"), ChatTemplate::ChatMl);
+        assert_eq!(ChatTemplate::detect("{{ '[INST] ' + content + ' [/INST]' }}"), ChatTemplate::Mistral);
+        assert_eq!(ChatTemplate::detect("{{ content }}"), ChatTemplate::Plain);
+        assert_eq!(ChatTemplate::parse("Llama3").unwrap().name(), "llama3");
+    }
+}
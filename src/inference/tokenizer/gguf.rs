@@ -0,0 +1,118 @@
+//! Vocabularies embedded in GGUF files (`tokenizer.ggml.*`)
+//!
+//! `gpt2` models are byte-level BPE with a merge list; `llama` models are
+//! SentencePiece with merges chosen by token score. Control and
+//! user-defined tokens are matched verbatim as special tokens.
+
+use anyhow::{Context, Result};
+
+use super::bpe::{BpeMode, BpeModel, Priority, GPT2_PATTERN, LLAMA3_PATTERN};
+use super::{Backend, ChatTemplate, Tokenizer};
+use crate::training::{GgufMetadata, GgufValue};
+
+/// `tokenizer.ggml.token_type` values
+const TOKEN_TYPE_CONTROL: u64 = 3;
+const TOKEN_TYPE_USER_DEFINED: u64 = 4;
+
+pub(super) fn from_metadata(meta: &GgufMetadata) -> Result<Tokenizer> {
+    let tokens: Vec<String> = meta
+        .tokens()
+        .context("GGUF file has no tokenizer.ggml.tokens")?
+        .into_iter()
+        .map(str::to_string)
+        .collect();
+    let token_types: Vec<u64> = array(meta, "tokenizer.ggml.token_type")
+        .map(|types| types.iter().map(|t| t.as_u64().unwrap_or(1)).collect())
+        .unwrap_or_default();
+
+    let model = match meta.tokenizer_model() {
+        Some("gpt2") => {
+            let merges = array(meta, "tokenizer.ggml.merges")
+                .context("GGUF gpt2 tokenizer has no tokenizer.ggml.merges")?
+                .iter()
+                .map(|m| BpeModel::parse_merge(m.as_str().unwrap_or_default()))
+                .collect::<Result<Vec<_>>>()?;
+            let pattern = match meta.get("tokenizer.ggml.pre").and_then(GgufValue::as_str) {
+                Some("llama-bpe" | "llama3" | "qwen2") => LLAMA3_PATTERN,
+                _ => GPT2_PATTERN,
+            };
+            BpeModel::new(BpeMode::ByteLevel, tokens.clone(), BpeModel::ranks(merges)).with_pattern(pattern)?
+        }
+        Some("llama") => {
+            let scores = array(meta, "tokenizer.ggml.scores")
+                .map(|scores| scores.iter().map(|s| s.as_f32().unwrap_or(0.0)).collect())
+                .unwrap_or_default();
+            let mode = BpeMode::SentencePiece { add_prefix_space: true };
+            BpeModel::new(mode, tokens.clone(), Priority::Scores(scores))
+        }
+        Some(other) => anyhow::bail!("Unsupported GGUF tokenizer model {} (expected gpt2 or llama)", other),
+        None => anyhow::bail!("GGUF file has no tokenizer.ggml.model"),
+    };
+    let unk_id = meta
+        .get("tokenizer.ggml.unknown_token_id")
+        .and_then(GgufValue::as_u64)
+        .map(|id| id as usize);
+    let model = model.with_unk(unk_id);
+
+    let name = meta
+        .get("general.name")
+        .and_then(GgufValue::as_str)
+        .unwrap_or("gguf");
+    let mut tokenizer = Tokenizer::from_backend(name, Backend::Bpe(model), tokens.len());
+    tokenizer.add_special_tokens(
+        tokens
+            .iter()
+            .enumerate()
+            .filter(|(id, _)| matches!(token_types.get(*id), Some(&(TOKEN_TYPE_CONTROL | TOKEN_TYPE_USER_DEFINED))))
+            .map(|(id, token)| (token.as_str(), id)),
+    )?;
+
+    tokenizer.bos_token_id = meta.bos_token_id();
+    tokenizer.eos_token_id = meta.eos_token_id();
+    if let Some(template) = meta.get("tokenizer.chat_template").and_then(GgufValue::as_str) {
+        tokenizer.chat_template = ChatTemplate::detect(template);
+    }
+
+    Ok(tokenizer)
+}
+
+fn array<'a>(meta: &'a GgufMetadata, key: &str) -> Option<&'a [GgufValue]> {
+    meta.get(key)?.as_array()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(values: &[&str]) -> GgufValue {
+        GgufValue::Array(values.iter().map(|v| GgufValue::String(v.to_string())).collect())
+    }
+
+    #[test]
+    fn test_llama_vocabulary_from_metadata() {
+        let mut meta = GgufMetadata::default();
+        meta.insert("tokenizer.ggml.model", GgufValue::String("llama".into()));
+        meta.insert("tokenizer.ggml.tokens", strings(&["<unk>", "<s>", "</s>", "▁", "h", "i", "hi", "▁hi"]));
+        meta.insert(
+            "tokenizer.ggml.scores",
+            GgufValue::Array([0.0, 0.0, 0.0, -1.0, -2.0, -2.0, -0.5, -0.2].map(GgufValue::F32).to_vec()),
+        );
+        meta.insert(
+            "tokenizer.ggml.token_type",
+            GgufValue::Array([2, 3, 3, 1, 1, 1, 1, 1].map(GgufValue::I32).to_vec()),
+        );
+        meta.insert("tokenizer.ggml.bos_token_id", GgufValue::U32(1));
+        meta.insert("tokenizer.ggml.eos_token_id", GgufValue::U32(2));
+        meta.insert("tokenizer.chat_template", GgufValue::String("[INST] <<SYS>>".into()));
+
+        let tokenizer = Tokenizer::from_gguf(&meta).unwrap();
+        assert_eq!(tokenizer.vocab_size(), 8);
+        assert_eq!(tokenizer.chat_template(), ChatTemplate::Llama2);
+
+        // The prefix space only goes at the start of the input
+        let tokens = tokenizer.encode("hi hi</s>");
+        assert_eq!(tokens, vec![7, 7, 2]);
+        assert_eq!(tokenizer.decode(&tokens).unwrap(), "hi hi</s>");
+        assert_eq!(tokenizer.encode("<s>hi hi"), vec![1, 6, 7]);
+    }
+}
@@ -0,0 +1,232 @@
+//! HuggingFace `tokenizer.json` (BPE and Unigram models)
+//!
+//! Only the parts that decide tokenization are read: the model, the added
+//! tokens, and whether the normalizer/pre-tokenizer are byte-level or
+//! SentencePiece-style. `tokenizer_config.json` adds the chat template and
+//! the BOS/EOS tokens.
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+
+use super::bpe::{BpeMode, BpeModel, GPT2_PATTERN};
+use super::unigram::UnigramModel;
+use super::{Backend, ChatTemplate, Tokenizer};
+
+pub(super) fn from_json(name: &str, json: &str, config: Option<&str>) -> Result<Tokenizer> {
+    let json: Value = serde_json::from_str(json).context("Invalid tokenizer.json")?;
+    let config: Option<Value> = config
+        .map(serde_json::from_str)
+        .transpose()
+        .context("Invalid tokenizer_config.json")?;
+
+    let model = &json["model"];
+    let added: Vec<(String, usize)> = json["added_tokens"]
+        .as_array()
+        .map(|tokens| {
+            tokens
+                .iter()
+                .filter_map(|t| Some((t["content"].as_str()?.to_string(), t["id"].as_u64()? as usize)))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let backend = match model["type"].as_str() {
+        Some("BPE") => Backend::Bpe(bpe_model(&json, model)?),
+        Some("Unigram") => Backend::Unigram(unigram_model(&json, model)?),
+        Some(other) => anyhow::bail!("Unsupported tokenizer model {} (expected BPE or Unigram)", other),
+        None => anyhow::bail!("tokenizer.json has no model type"),
+    };
+    let vocab_size = match &backend {
+        Backend::Bpe(model) => model.len(),
+        Backend::Unigram(model) => model.len(),
+        Backend::Tiktoken(_) => unreachable!("tokenizer.json is never tiktoken"),
+    };
+
+    let mut tokenizer = Tokenizer::from_backend(name, backend, vocab_size);
+    tokenizer.add_special_tokens(added.iter().map(|(content, id)| (content.as_str(), *id)))?;
+
+    let config_token = |key: &str| {
+        let value = config.as_ref().map(|c| &c[key])?;
+        value.as_str().or_else(|| value["content"].as_str()).map(str::to_string)
+    };
+    tokenizer.bos_token_id = config_token("bos_token").and_then(|t| tokenizer.token_id(&t));
+    tokenizer.eos_token_id = config_token("eos_token").and_then(|t| tokenizer.token_id(&t));
+
+    // A single template string, or named templates of which "default" is used
+    let template = config.as_ref().map(|c| &c["chat_template"]).and_then(|t| match t {
+        Value::String(template) => Some(template.as_str()),
+        Value::Array(named) => named
+            .iter()
+            .find(|t| t["name"] == "default")
+            .or(named.first())
+            .and_then(|t| t["template"].as_str()),
+        _ => None,
+    });
+    if let Some(template) = template {
+        tokenizer.chat_template = ChatTemplate::detect(template);
+    }
+
+    Ok(tokenizer)
+}
+
+fn bpe_model(json: &Value, model: &Value) -> Result<BpeModel> {
+    let vocab = model["vocab"].as_object().context("BPE model has no vocab")?;
+    let mut tokens = vec![String::new(); vocab.values().filter_map(Value::as_u64).max().map_or(0, |m| m as usize + 1)];
+    for (token, id) in vocab {
+        let id = id.as_u64().context("Invalid vocab id")? as usize;
+        tokens[id] = token.clone();
+    }
+    let unk_id = model["unk_token"].as_str().and_then(|unk| vocab.get(unk)?.as_u64()).map(|id| id as usize);
+
+    // Merges are "a b" strings, or [a, b] pairs in newer files
+    let merges = model["merges"]
+        .as_array()
+        .context("BPE model has no merges")?
+        .iter()
+        .map(|merge| match merge {
+            Value::String(merge) => BpeModel::parse_merge(merge),
+            Value::Array(pair) => match (pair.first().and_then(Value::as_str), pair.get(1).and_then(Value::as_str)) {
+                (Some(a), Some(b)) => Ok((a.to_string(), b.to_string())),
+                _ => anyhow::bail!("Invalid merge {}", merge),
+            },
+            _ => anyhow::bail!("Invalid merge {}", merge),
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let pre_tokenizer = &json["pre_tokenizer"];
+    let model = if has_type(pre_tokenizer, "ByteLevel") || has_type(&json["decoder"], "ByteLevel") {
+        let pattern = split_pattern(pre_tokenizer).unwrap_or(GPT2_PATTERN);
+        BpeModel::new(BpeMode::ByteLevel, tokens, BpeModel::ranks(merges)).with_pattern(pattern)?
+    } else {
+        let mode = BpeMode::SentencePiece {
+            add_prefix_space: adds_prefix_space(json),
+        };
+        BpeModel::new(mode, tokens, BpeModel::ranks(merges))
+    };
+
+    Ok(model
+        .with_ignore_merges(model_flag(json, "ignore_merges"))
+        .with_unk(unk_id))
+}
+
+fn unigram_model(json: &Value, model: &Value) -> Result<UnigramModel> {
+    let pieces = model["vocab"]
+        .as_array()
+        .context("Unigram model has no vocab")?
+        .iter()
+        .map(|entry| match (entry[0].as_str(), entry[1].as_f64()) {
+            (Some(piece), Some(score)) => Ok((piece.to_string(), score)),
+            _ => anyhow::bail!("Invalid Unigram vocab entry {}", entry),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let unk_id = model["unk_id"].as_u64().map(|id| id as usize);
+
+    Ok(UnigramModel::new(pieces, unk_id, adds_prefix_space(json)))
+}
+
+fn model_flag(json: &Value, key: &str) -> bool {
+    json["model"][key].as_bool().unwrap_or(false)
+}
+
+/// True if `value` or any nested normalizer/pre-tokenizer has type `kind`
+fn has_type(value: &Value, kind: &str) -> bool {
+    match value {
+        Value::Object(map) => map.get("type").and_then(Value::as_str) == Some(kind) || map.values().any(|v| has_type(v, kind)),
+        Value::Array(items) => items.iter().any(|v| has_type(v, kind)),
+        _ => false,
+    }
+}
+
+/// Regex of a `Split` pre-tokenizer, if there is one
+fn split_pattern(value: &Value) -> Option<&str> {
+    match value {
+        Value::Object(map) if map.get("type").and_then(Value::as_str) == Some("Split") => {
+            map.get("pattern")?.get("Regex")?.as_str()
+        }
+        Value::Object(map) => map.values().find_map(split_pattern),
+        Value::Array(items) => items.iter().find_map(split_pattern),
+        _ => None,
+    }
+}
+
+/// Whether SentencePiece-style input gets a leading `▁`: a `Prepend`
+/// normalizer (Llama 2), or a `Metaspace` pre-tokenizer that prepends
+fn adds_prefix_space(json: &Value) -> bool {
+    if has_type(&json["normalizer"], "Prepend") {
+        return true;
+    }
+    let metaspace = find_type(&json["pre_tokenizer"], "Metaspace");
+    metaspace.is_some_and(|m| match m["prepend_scheme"].as_str() {
+        Some(scheme) => scheme != "never",
+        None => m["add_prefix_space"].as_bool().unwrap_or(true),
+    })
+}
+
+fn find_type<'a>(value: &'a Value, kind: &str) -> Option<&'a Value> {
+    match value {
+        Value::Object(map) if map.get("type").and_then(Value::as_str) == Some(kind) => Some(value),
+        Value::Object(map) => map.values().find_map(|v| find_type(v, kind)),
+        Value::Array(items) => items.iter().find_map(|v| find_type(v, kind)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inference::tokenizer::ChatMessage;
+
+    #[test]
+    fn test_byte_level_tokenizer_json() {
+        let json = r#"{
+            "added_tokens": [
+                {"id": 6, "content": "<|im_start|>", "special": true},
+                {"id": 7, "content": "<|im_end|>", "special": true}
+            ],
+            "normalizer": null,
+            "pre_tokenizer": {"type": "Sequence", "pretokenizers": [
+                {"type": "Split", "pattern": {"Regex": " ?\\p{L}+|\\s+"}, "behavior": "Isolated"},
+                {"type": "ByteLevel", "add_prefix_space": false, "use_regex": false}
+            ]},
+            "decoder": {"type": "ByteLevel"},
+            "model": {"type": "BPE", "vocab": {"h": 0, "i": 1, "Ġ": 2, "hi": 3, "Ġhi": 4, "\n": 5},
+                      "merges": ["h i", ["Ġ", "hi"]]}
+        }"#;
+        let config = r#"{"eos_token": "<|im_end|>", "chat_template": "{{ '<|im_start|>' + message['role'] }}"}"#;
+        let tokenizer = Tokenizer::from_hf_json("test", json, Some(config)).unwrap();
+
+        assert_eq!(tokenizer.vocab_size(), 8);
+        assert_eq!(tokenizer.eos_token_id(), Some(7));
+        assert_eq!(tokenizer.chat_template(), ChatTemplate::ChatMl);
+
+        let tokens = tokenizer.encode("<|im_start|>hi hi<|im_end|>");
+        assert_eq!(tokens, vec![6, 3, 4, 7]);
+        assert_eq!(tokenizer.decode(&tokens).unwrap(), "<|im_start|>hi hi<|im_end|>");
+
+        let prompt = tokenizer.apply_chat_template(&[ChatMessage::new("user", "hi")], true);
+        assert_eq!(prompt, "<|im_start|>user\nhi<|im_end|>\n<|im_start|>assistant\n");
+    }
+
+    #[test]
+    fn test_unigram_tokenizer_json() {
+        let json = r#"{
+            "added_tokens": [{"id": 1, "content": "</s>", "special": true}],
+            "pre_tokenizer": {"type": "Metaspace", "replacement": "▁", "prepend_scheme": "always"},
+            "model": {"type": "Unigram", "unk_id": 0,
+                      "vocab": [["<unk>", 0.0], ["</s>", 0.0], ["▁a", -1.0], ["b", -2.0], ["▁ab", -1.5]]}
+        }"#;
+        let tokenizer = Tokenizer::from_hf_json("test", json, None).unwrap();
+
+        let tokens = tokenizer.encode("ab a</s>");
+        assert_eq!(tokens, vec![4, 2, 1]);
+        assert_eq!(tokenizer.decode(&tokens).unwrap(), "ab a</s>");
+
+        // Only the first piece drops its prefix space
+        let pieces: String = tokens
+            .iter()
+            .enumerate()
+            .map(|(i, &id)| tokenizer.decode_piece(id, i == 0))
+            .collect();
+        assert_eq!(pieces, "ab a</s>");
+    }
+}
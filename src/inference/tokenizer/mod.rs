@@ -0,0 +1,419 @@
+//! Tokenizer for text encoding/decoding
+//!
+//! `Tokenizer` runs one of three backends:
+//! - tiktoken encodings (`cl100k_base`, `o200k_base`)
+//! - BPE: byte-level (GPT-2, Llama 3, Qwen) or SentencePiece-style (Llama 2,
+//!   Mistral), from HuggingFace `tokenizer.json` or GGUF `gpt2`/`llama` vocabularies
+//! - SentencePiece Unigram, from `tokenizer.json`
+//!
+//! Special tokens (`<|endoftext|>`, `<s>`, `<|im_start|>`...) written in the
+//! text encode to their ids, and `apply_chat_template` renders conversations
+//! in the model's chat format.
+
+mod bpe;
+mod chat;
+mod gguf;
+mod hf;
+mod unigram;
+
+use aho_corasick::{AhoCorasick, MatchKind};
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::Path;
+use tiktoken_rs::CoreBPE;
+
+use bpe::{BpeMode, BpeModel};
+use unigram::UnigramModel;
+
+pub use chat::{ChatMessage, ChatTemplate};
+
+enum Backend {
+    Tiktoken(CoreBPE),
+    Bpe(BpeModel),
+    Unigram(UnigramModel),
+}
+
+/// Tokenizer for converting text to/from token IDs
+pub struct Tokenizer {
+    name: String,
+    backend: Backend,
+    vocab_size: usize,
+    /// Tokens matched verbatim in the text (not used by tiktoken, which
+    /// handles its own)
+    special_tokens: HashMap<String, usize>,
+    special_texts: HashMap<usize, String>,
+    /// `special_tokens` compiled for leftmost-longest matching
+    special_matcher: Option<AhoCorasick>,
+    bos_token_id: Option<usize>,
+    eos_token_id: Option<usize>,
+    chat_template: ChatTemplate,
+}
+
+impl Tokenizer {
+    /// Create a new tokenizer using cl100k_base (GPT-4 tokenizer)
+    pub fn new() -> Result<Self> {
+        Self::tiktoken("cl100k_base")
+    }
+
+    /// A tiktoken encoding: "cl100k_base" or "o200k_base"
+    pub fn tiktoken(encoding: &str) -> Result<Self> {
+        // Ordinary tokens only; special tokens have ids above these
+        let (bpe, vocab_size, eos) = match encoding {
+            "cl100k_base" => (tiktoken_rs::cl100k_base()?, 100256, 100257),
+            "o200k_base" => (tiktoken_rs::o200k_base()?, 199998, 199999),
+            other => anyhow::bail!("Unknown tiktoken encoding: {} (expected cl100k_base or o200k_base)", other),
+        };
+
+        let mut tokenizer = Self::from_backend(encoding, Backend::Tiktoken(bpe), vocab_size);
+        tokenizer.eos_token_id = Some(eos);
+        Ok(tokenizer)
+    }
+
+    /// Load a tiktoken encoding by name, a HuggingFace `tokenizer.json`, or
+    /// the vocabulary embedded in a `.gguf` file
+    pub fn load(spec: &str) -> Result<Self> {
+        let path = Path::new(spec);
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => Self::from_hf_file(path),
+            Some("gguf") => {
+                let file = crate::training::GgufFile::open(path)?;
+                Self::from_gguf(&file.metadata)
+            }
+            _ => Self::tiktoken(spec),
+        }
+    }
+
+    /// Load a HuggingFace `tokenizer.json`
+    ///
+    /// A `tokenizer_config.json` next to it supplies the chat template and
+    /// the BOS/EOS tokens.
+    pub fn from_hf_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let config_path = path.with_file_name("tokenizer_config.json");
+        let config = std::fs::read_to_string(&config_path).ok();
+
+        let name = path.file_stem().map_or("tokenizer".into(), |s| s.to_string_lossy().into_owned());
+        Self::from_hf_json(&name, &json, config.as_deref())
+    }
+
+    /// Build from `tokenizer.json` contents and, optionally, `tokenizer_config.json`
+    pub fn from_hf_json(name: &str, json: &str, config: Option<&str>) -> Result<Self> {
+        hf::from_json(name, json, config)
+    }
+
+    /// Build from the vocabulary in GGUF metadata (`tokenizer.ggml.*`)
+    pub fn from_gguf(metadata: &crate::training::GgufMetadata) -> Result<Self> {
+        gguf::from_metadata(metadata)
+    }
+
+    fn from_backend(name: &str, backend: Backend, vocab_size: usize) -> Self {
+        Self {
+            name: name.to_string(),
+            backend,
+            vocab_size,
+            special_tokens: HashMap::new(),
+            special_texts: HashMap::new(),
+            special_matcher: None,
+            bos_token_id: None,
+            eos_token_id: None,
+            chat_template: ChatTemplate::default(),
+        }
+    }
+
+    /// Match each text verbatim as its token id when encoding
+    fn add_special_tokens<'a>(&mut self, tokens: impl IntoIterator<Item = (&'a str, usize)>) -> Result<()> {
+        for (text, id) in tokens {
+            if text.is_empty() {
+                continue;
+            }
+            self.special_tokens.insert(text.to_string(), id);
+            self.special_texts.insert(id, text.to_string());
+            self.vocab_size = self.vocab_size.max(id + 1);
+        }
+
+        self.special_matcher = match self.special_tokens.is_empty() {
+            true => None,
+            false => Some(
+                AhoCorasick::builder()
+                    .match_kind(MatchKind::LeftmostLongest)
+                    .build(self.special_tokens.keys())
+                    .context("Failed to compile special tokens")?,
+            ),
+        };
+        Ok(())
+    }
+
+    /// Encode text to token IDs
+    pub fn encode(&self, text: &str) -> Vec<usize> {
+        let backend = match &self.backend {
+            Backend::Tiktoken(bpe) => return bpe.encode_with_special_tokens(text),
+            backend => backend,
+        };
+
+        let mut tokens = Vec::new();
+        let mut start = 0;
+        for (offset, special, id) in self.special_matches(text) {
+            self.encode_ordinary(backend, &text[start..offset], start == 0, &mut tokens);
+            tokens.push(id);
+            start = offset + special.len();
+        }
+        self.encode_ordinary(backend, &text[start..], start == 0, &mut tokens);
+        tokens
+    }
+
+    fn encode_ordinary(&self, backend: &Backend, text: &str, first: bool, out: &mut Vec<usize>) {
+        if text.is_empty() {
+            return;
+        }
+        match backend {
+            Backend::Tiktoken(bpe) => out.extend(bpe.encode_ordinary(text)),
+            Backend::Bpe(model) => model.encode(text, first, out),
+            Backend::Unigram(model) => model.encode(text, first, out),
+        }
+    }
+
+    /// Non-overlapping special tokens in `text`, leftmost and then longest first
+    fn special_matches<'a>(&self, text: &'a str) -> Vec<(usize, &'a str, usize)> {
+        let Some(matcher) = &self.special_matcher else {
+            return Vec::new();
+        };
+        matcher
+            .find_iter(text)
+            .map(|m| {
+                let special = &text[m.range()];
+                (m.start(), special, self.special_tokens[special])
+            })
+            .collect()
+    }
+
+    /// Bytes of `tokens`; unknown ids are an error unless `lossy`
+    ///
+    /// `first` marks `tokens[0]` as the start of the sequence, the only piece
+    /// that loses the space SentencePiece prepends to the input.
+    fn decode_bytes(&self, tokens: &[usize], lossy: bool, first: bool) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        for (i, &id) in tokens.iter().enumerate() {
+            if let Some(text) = self.special_texts.get(&id) {
+                bytes.extend(text.as_bytes());
+                continue;
+            }
+            let piece = match &self.backend {
+                Backend::Tiktoken(bpe) => Some(bpe._decode_native(&[id])),
+                Backend::Bpe(model) => model.token_bytes(id),
+                Backend::Unigram(model) => model.token_bytes(id),
+            };
+            match piece {
+                // The space SentencePiece prepends to the input is not part of the text
+                Some(piece) if first && i == 0 && self.adds_prefix_space() && piece.starts_with(b" ") => {
+                    bytes.extend(&piece[1..])
+                }
+                Some(piece) => bytes.extend(piece),
+                None if lossy => {}
+                None => anyhow::bail!("Decode error: unknown token id {}", id),
+            }
+        }
+        Ok(bytes)
+    }
+
+    fn adds_prefix_space(&self) -> bool {
+        match &self.backend {
+            Backend::Tiktoken(_) => false,
+            Backend::Bpe(model) => matches!(model.mode(), BpeMode::SentencePiece { add_prefix_space: true }),
+            Backend::Unigram(model) => model.add_prefix_space(),
+        }
+    }
+
+    /// Decode token IDs to text
+    pub fn decode(&self, tokens: &[usize]) -> Result<String> {
+        if let Backend::Tiktoken(bpe) = &self.backend {
+            return bpe.decode(tokens.to_vec()).map_err(|e| anyhow::anyhow!("Decode error: {}", e));
+        }
+        String::from_utf8(self.decode_bytes(tokens, false, true)?).map_err(|e| anyhow::anyhow!("Decode error: {}", e))
+    }
+
+    /// Decode token IDs, replacing invalid UTF-8 (e.g. a split multi-byte
+    /// character at the end of a generation) instead of failing
+    pub fn decode_lossy(&self, tokens: &[usize]) -> String {
+        let bytes = match &self.backend {
+            Backend::Tiktoken(bpe) => bpe._decode_native(tokens),
+            _ => self.decode_bytes(tokens, true, true).unwrap_or_default(),
+        };
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+
+    /// Text of one token of a sequence, lossy like `decode_lossy`
+    ///
+    /// Unless it is the `first` token, a SentencePiece piece keeps its
+    /// leading space, so the texts of consecutive tokens concatenate to the
+    /// decoded sequence.
+    pub fn decode_piece(&self, id: usize, first: bool) -> String {
+        let bytes = match &self.backend {
+            Backend::Tiktoken(bpe) => bpe._decode_native(&[id]),
+            _ => self.decode_bytes(&[id], true, first).unwrap_or_default(),
+        };
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+
+    /// Encode with truncation to max length
+    pub fn encode_truncated(&self, text: &str, max_length: usize) -> Vec<usize> {
+        let tokens = self.encode(text);
+        if tokens.len() > max_length {
+            tokens[..max_length].to_vec()
+        } else {
+            tokens
+        }
+    }
+
+    /// Encode with padding to exact length
+    pub fn encode_padded(&self, text: &str, length: usize, pad_token: usize) -> Vec<usize> {
+        let mut tokens = self.encode(text);
+
+        if tokens.len() > length {
+            tokens.truncate(length);
+        } else {
+            tokens.resize(length, pad_token);
+        }
+
+        tokens
+    }
+
+    /// Get vocabulary size
+    pub fn vocab_size(&self) -> usize {
+        self.vocab_size
+    }
+
+    /// Count tokens in text
+    pub fn count_tokens(&self, text: &str) -> usize {
+        self.encode(text).len()
+    }
+
+    /// Encoding name, or the file the tokenizer was loaded from
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Id of a special token such as "<|im_end|>"
+    pub fn token_id(&self, special: &str) -> Option<usize> {
+        match &self.backend {
+            Backend::Tiktoken(bpe) => match bpe.encode_with_special_tokens(special).as_slice() {
+                [id] => Some(*id),
+                _ => None,
+            },
+            _ => self.special_tokens.get(special).copied(),
+        }
+    }
+
+    pub fn bos_token_id(&self) -> Option<usize> {
+        self.bos_token_id
+    }
+
+    pub fn eos_token_id(&self) -> Option<usize> {
+        self.eos_token_id
+    }
+
+    pub fn chat_template(&self) -> ChatTemplate {
+        self.chat_template
+    }
+
+    pub fn set_chat_template(&mut self, template: ChatTemplate) {
+        self.chat_template = template;
+    }
+
+    /// Render a conversation with this tokenizer's chat template
+    pub fn apply_chat_template(&self, messages: &[ChatMessage], add_generation_prompt: bool) -> String {
+        let text = |id: Option<usize>| id.map(|id| self.decode_lossy(&[id])).unwrap_or_default();
+        self.chat_template.apply(
+            messages,
+            add_generation_prompt,
+            &text(self.bos_token_id),
+            &text(self.eos_token_id),
+        )
+    }
+}
+
+impl Default for Tokenizer {
+    fn default() -> Self {
+        Self::new().expect("Failed to initialize tokenizer")
+    }
+}
+
+/// SentencePiece normalization: spaces become `▁`, optionally with one in front
+fn sentencepiece_normalize(text: &str, add_prefix_space: bool) -> String {
+    let mut normalized = String::with_capacity(text.len() + 3);
+    if add_prefix_space {
+        normalized.push('▁');
+    }
+    normalized.push_str(&text.replace(' ', "▁"));
+    normalized
+}
+
+/// Bytes of a SentencePiece piece: `<0xNN>` byte tokens, `▁` as space
+fn piece_bytes(piece: &str) -> Vec<u8> {
+    match piece
+        .strip_prefix("<0x")
+        .and_then(|hex| hex.strip_suffix('>'))
+        .filter(|hex| hex.len() == 2)
+        .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+    {
+        Some(byte) => vec![byte],
+        None => piece.replace('▁', " ").into_bytes(),
+    }
+}
+
+/// `<0xNN>` byte-token ids for the UTF-8 bytes of `piece`, if all exist
+fn byte_fallback_ids(piece: &str, vocab: &HashMap<String, usize>) -> Option<Vec<usize>> {
+    piece
+        .bytes()
+        .map(|b| vocab.get(&format!("<0x{:02X}>", b)).copied())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenizer() {
+        let tokenizer = Tokenizer::new().unwrap();
+
+        let text = "Hello, world!";
+        let tokens = tokenizer.encode(text);
+        assert!(!tokens.is_empty());
+
+        let decoded = tokenizer.decode(&tokens).unwrap();
+        assert_eq!(decoded, text);
+    }
+
+    #[test]
+    fn test_truncation() {
+        let tokenizer = Tokenizer::new().unwrap();
+
+        let text = "This is a longer text that should be truncated to fit within the maximum token limit.";
+        let tokens = tokenizer.encode_truncated(text, 5);
+        assert_eq!(tokens.len(), 5);
+    }
+
+    #[test]
+    fn test_padding() {
+        let tokenizer = Tokenizer::new().unwrap();
+
+        let text = "Short";
+        let tokens = tokenizer.encode_padded(text, 10, 0);
+        assert_eq!(tokens.len(), 10);
+    }
+
+    #[test]
+    fn test_tiktoken_encodings_and_special_tokens() {
+        let o200k = Tokenizer::tiktoken("o200k_base").unwrap();
+        assert_eq!(o200k.vocab_size(), 199998);
+        let tokens = o200k.encode("Hello<|endoftext|>");
+        assert_eq!(tokens.last(), Some(&199999));
+        assert_eq!(o200k.token_id("<|endoftext|>"), o200k.eos_token_id());
+        assert_eq!(o200k.decode(&tokens).unwrap(), "Hello<|endoftext|>");
+
+        assert!(Tokenizer::load("cl100k_base").is_ok());
+        assert!(Tokenizer::tiktoken("p99k_base").is_err());
+    }
+}
@@ -0,0 +1,144 @@
+//! SentencePiece Unigram: the segmentation with the highest total score
+//!
+//! Viterbi over the characters of the normalized text (spaces as `▁`).
+//! Characters no piece covers fall back to `<0xNN>` byte tokens, or the
+//! unknown token.
+
+use std::collections::HashMap;
+
+use super::{byte_fallback_ids, piece_bytes, sentencepiece_normalize};
+
+/// Penalty below the lowest piece score for an unknown character
+const UNK_PENALTY: f64 = 10.0;
+
+pub(super) struct UnigramModel {
+    vocab: HashMap<String, usize>,
+    pieces: Vec<(String, f64)>,
+    unk_id: Option<usize>,
+    add_prefix_space: bool,
+    max_piece_chars: usize,
+    min_score: f64,
+}
+
+impl UnigramModel {
+    pub(super) fn new(pieces: Vec<(String, f64)>, unk_id: Option<usize>, add_prefix_space: bool) -> Self {
+        let vocab = pieces
+            .iter()
+            .enumerate()
+            .map(|(id, (piece, _))| (piece.clone(), id))
+            .collect();
+        let max_piece_chars = pieces.iter().map(|(p, _)| p.chars().count()).max().unwrap_or(1);
+        let min_score = pieces.iter().map(|(_, s)| *s).fold(0.0, f64::min);
+
+        Self {
+            vocab,
+            pieces,
+            unk_id,
+            add_prefix_space,
+            max_piece_chars,
+            min_score,
+        }
+    }
+
+    pub(super) fn len(&self) -> usize {
+        self.pieces.len()
+    }
+
+    pub(super) fn add_prefix_space(&self) -> bool {
+        self.add_prefix_space
+    }
+
+    /// Append the ids of `text`; `first` marks the start of the input
+    pub(super) fn encode(&self, text: &str, first: bool, out: &mut Vec<usize>) {
+        let normalized = sentencepiece_normalize(text, self.add_prefix_space && first);
+        let bounds: Vec<usize> = normalized
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain(std::iter::once(normalized.len()))
+            .collect();
+        let n = bounds.len() - 1;
+
+        // best[j] = (score, start char, piece id) of the best segmentation of chars 0..j
+        let mut best: Vec<Option<(f64, usize, Option<usize>)>> = vec![None; n + 1];
+        best[0] = Some((0.0, 0, None));
+        for start in 0..n {
+            let Some((score, _, _)) = best[start] else {
+                continue;
+            };
+            let mut matched = false;
+            for end in start + 1..=n.min(start + self.max_piece_chars) {
+                let piece = &normalized[bounds[start]..bounds[end]];
+                if let Some(&id) = self.vocab.get(piece) {
+                    matched |= end == start + 1;
+                    let candidate = score + self.pieces[id].1;
+                    if best[end].is_none_or(|(s, _, _)| candidate > s) {
+                        best[end] = Some((candidate, start, Some(id)));
+                    }
+                }
+            }
+            if !matched {
+                let candidate = score + self.min_score - UNK_PENALTY;
+                if best[start + 1].is_none_or(|(s, _, _)| candidate > s) {
+                    best[start + 1] = Some((candidate, start, None));
+                }
+            }
+        }
+
+        let mut segments = Vec::new();
+        let mut end = n;
+        while end > 0 {
+            let (_, start, id) = best[end].expect("every position is reachable");
+            segments.push((start, end, id));
+            end = start;
+        }
+        for (start, end, id) in segments.into_iter().rev() {
+            match id {
+                Some(id) => out.push(id),
+                None => {
+                    let piece = &normalized[bounds[start]..bounds[end]];
+                    match byte_fallback_ids(piece, &self.vocab) {
+                        Some(ids) => out.extend(ids),
+                        None => out.extend(self.unk_id),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Bytes of piece `id`, or None if it is not in the vocabulary
+    pub(super) fn token_bytes(&self, id: usize) -> Option<Vec<u8>> {
+        self.pieces.get(id).map(|(piece, _)| piece_bytes(piece))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_viterbi_picks_highest_scoring_segmentation() {
+        let pieces = [
+            ("<unk>", 0.0),
+            ("▁", -2.0),
+            ("▁h", -3.0),
+            ("ello", -2.0),
+            ("▁hello", -4.5),
+            ("h", -3.0),
+            ("e", -3.0),
+            ("l", -3.0),
+            ("o", -3.0),
+        ]
+        .map(|(p, s)| (p.to_string(), s))
+        .to_vec();
+        let model = UnigramModel::new(pieces, Some(0), true);
+
+        let mut ids = Vec::new();
+        model.encode("hello", true, &mut ids);
+        assert_eq!(ids, vec![4]);
+
+        // No piece covers "x" and there are no byte tokens
+        ids.clear();
+        model.encode("hex", false, &mut ids);
+        assert_eq!(ids, vec![5, 6, 0]);
+    }
+}
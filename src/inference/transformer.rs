@@ -0,0 +1,1356 @@
+//! Decoder-only transformer forward pass on CPU
+//!
+//! Llama-style architecture: pre-norm blocks (RMSNorm or LayerNorm), rotary
+//! position embeddings, multi-head attention with optional grouped KV heads,
+//! a SwiGLU or GELU MLP, and a tied or untied LM head. Token embeddings live
+//! in `EmbeddingLayer`, so training the embedding table also trains the tied
+//! head.
+//!
+//! All attention goes through a `KvCache`: decoding appends one token's keys
+//! and values per step instead of reprocessing the whole context.
+
+use anyhow::{Context, Result};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::borrow::Cow;
+
+use super::embeddings::EmbeddingLayer;
+use super::model::ModelConfig;
+use super::storage::{DType, Matrix, TensorSource};
+use crate::training::autograd::{Tape, Var};
+use crate::training::{LoraAdapter, WeightLoader};
+
+/// Normalization used before attention, before the MLP and at the output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NormType {
+    /// Root-mean-square norm with a learned scale (Llama, Mistral, Qwen)
+    RmsNorm,
+    /// Mean/variance norm with learned scale and bias (GPT-2, Pythia)
+    LayerNorm,
+}
+
+/// MLP activation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Activation {
+    /// down(silu(gate(x)) * up(x))
+    SwiGlu,
+    /// down(gelu(up(x)))
+    Gelu,
+}
+
+/// Mutable view of one trainable weight, as a row-major [rows, cols]
+/// matrix (vectors have one row)
+#[derive(Debug)]
+pub struct ParamMut<'a> {
+    pub name: String,
+    pub rows: usize,
+    pub cols: usize,
+    pub values: &'a mut [f32],
+}
+
+/// Name, shape and storage dtype of one weight, under the name `export`
+/// gives it
+#[derive(Debug, Clone, PartialEq)]
+pub struct WeightInfo {
+    pub name: String,
+    pub shape: Vec<usize>,
+    pub dtype: DType,
+}
+
+/// Dense layer with row-major weight [out_features, in_features]
+///
+/// The weight keeps its storage dtype (`ModelConfig::compute_dtype`);
+/// `forward` dequantizes it row by row.
+#[derive(Debug, Clone)]
+pub struct Linear {
+    pub weight: Matrix,
+    pub bias: Option<Vec<f32>>,
+    pub in_features: usize,
+    pub out_features: usize,
+}
+
+impl Linear {
+    /// Uniform init scaled by fan-in
+    fn random(rng: &mut StdRng, in_features: usize, out_features: usize, dtype: DType) -> Self {
+        let bound = 1.0 / (in_features as f32).sqrt();
+        let values = (0..in_features * out_features)
+            .map(|_| rng.gen_range(-bound..bound))
+            .collect();
+        let mut weight = Matrix::from_f32(out_features, in_features, values);
+        weight.convert(dtype);
+
+        Self {
+            weight,
+            bias: None,
+            in_features,
+            out_features,
+        }
+    }
+
+    /// Load `{prefix}.weight` and, if present, `{prefix}.bias`
+    fn from_loader(
+        loader: &dyn TensorSource,
+        prefix: &str,
+        in_features: usize,
+        out_features: usize,
+        dtype: DType,
+    ) -> Result<Self> {
+        let weight = take_matrix(loader, &format!("{}.weight", prefix), out_features, in_features, dtype)?;
+        let bias = optional_tensor(loader, &format!("{}.bias", prefix), out_features)?;
+
+        Ok(Self {
+            weight,
+            bias,
+            in_features,
+            out_features,
+        })
+    }
+
+    fn export(&self, loader: &mut WeightLoader, prefix: &str) -> Result<()> {
+        loader.insert_tensor_with_shape(
+            format!("{}.weight", prefix),
+            vec![self.out_features, self.in_features],
+            self.weight.to_f32().into_owned(),
+        )?;
+        if let Some(bias) = &self.bias {
+            loader.insert_tensor_with_shape(format!("{}.bias", prefix), vec![self.out_features], bias.clone())?;
+        }
+        Ok(())
+    }
+
+    fn describe(&self, prefix: &str, weights: &mut Vec<WeightInfo>) {
+        weights.push(WeightInfo {
+            name: format!("{}.weight", prefix),
+            shape: vec![self.out_features, self.in_features],
+            dtype: self.weight.dtype(),
+        });
+        if self.bias.is_some() {
+            weights.push(WeightInfo {
+                name: format!("{}.bias", prefix),
+                shape: vec![self.out_features],
+                dtype: DType::F32,
+            });
+        }
+    }
+
+    /// Record `forward` over the rows of `x` with `{prefix}.weight`/`.bias` as parameters
+    ///
+    /// With an adapter the weights are constants and the adapter's
+    /// `{prefix}.lora_A.weight`/`.lora_B.weight`, if it has them, are the
+    /// parameters of an added `scaling * (x A^T) B^T`.
+    fn record<'w>(
+        &'w self,
+        tape: &mut Tape<'w>,
+        prefix: &str,
+        x: Var,
+        adapter: Option<&'w LoraAdapter>,
+    ) -> Result<Var> {
+        let name = format!("{}.weight", prefix);
+        let weight = record_weight(tape, adapter, &name, self.weight.to_f32(), self.out_features, self.in_features);
+        let bias = self
+            .bias
+            .as_ref()
+            .map(|b| record_weight(tape, adapter, &format!("{}.bias", prefix), b.into(), 1, self.out_features));
+        let y = tape.linear(x, weight, bias)?;
+
+        let Some((adapter, lora)) = adapter.and_then(|a| a.layer(&name).map(|l| (a, l))) else {
+            return Ok(y);
+        };
+        let a = tape.param(format!("{}.lora_A.weight", prefix), &lora.a[..], lora.rank, self.in_features);
+        let b = tape.param(format!("{}.lora_B.weight", prefix), &lora.b[..], self.out_features, lora.rank);
+        let down = tape.linear(x, a, None)?;
+        let up = tape.linear(down, b, None)?;
+        let delta = tape.scale(up, adapter.scaling());
+        tape.add(y, delta)
+    }
+
+    fn parameters_mut<'a>(&'a mut self, prefix: &str, params: &mut Vec<ParamMut<'a>>) {
+        params.push(ParamMut {
+            name: format!("{}.weight", prefix),
+            rows: self.out_features,
+            cols: self.in_features,
+            values: self.weight.values_mut(),
+        });
+        if let Some(bias) = &mut self.bias {
+            params.push(ParamMut {
+                name: format!("{}.bias", prefix),
+                rows: 1,
+                cols: self.out_features,
+                values: bias,
+            });
+        }
+    }
+
+    /// Add `adapter`'s update for the weight `name`, if it has one
+    ///
+    /// The merged weight is stored in its original dtype again.
+    fn merge_adapter(&mut self, adapter: &LoraAdapter, name: String) {
+        if adapter.layer(&name).is_none() {
+            return;
+        }
+        let dtype = self.weight.dtype();
+        adapter.merge_into(vec![ParamMut {
+            name,
+            rows: self.out_features,
+            cols: self.in_features,
+            values: self.weight.values_mut(),
+        }]);
+        self.weight.convert(dtype);
+    }
+
+    /// y = W x + b
+    pub fn forward(&self, x: &[f32]) -> Vec<f32> {
+        debug_assert_eq!(x.len(), self.in_features);
+
+        let mut out = self.weight.matvec(x);
+
+        if let Some(bias) = &self.bias {
+            for (o, b) in out.iter_mut().zip(bias) {
+                *o += b;
+            }
+        }
+
+        out
+    }
+
+    pub fn num_params(&self) -> usize {
+        self.in_features * self.out_features + self.bias.as_ref().map_or(0, |b| b.len())
+    }
+}
+
+/// RMSNorm or LayerNorm with learned parameters
+#[derive(Debug, Clone)]
+pub struct Norm {
+    pub kind: NormType,
+    pub weight: Vec<f32>,
+    pub bias: Option<Vec<f32>>,
+    pub eps: f32,
+}
+
+impl Norm {
+    fn identity(kind: NormType, dim: usize, eps: f32) -> Self {
+        let bias = match kind {
+            NormType::RmsNorm => None,
+            NormType::LayerNorm => Some(vec![0.0; dim]),
+        };
+
+        Self {
+            kind,
+            weight: vec![1.0; dim],
+            bias,
+            eps,
+        }
+    }
+
+    fn from_loader(loader: &dyn TensorSource, prefix: &str, kind: NormType, dim: usize, eps: f32) -> Result<Self> {
+        Ok(Self {
+            kind,
+            weight: take_tensor(loader, &format!("{}.weight", prefix), dim)?,
+            bias: optional_tensor(loader, &format!("{}.bias", prefix), dim)?,
+            eps,
+        })
+    }
+
+    fn export(&self, loader: &mut WeightLoader, prefix: &str) -> Result<()> {
+        let dim = self.weight.len();
+        loader.insert_tensor_with_shape(format!("{}.weight", prefix), vec![dim], self.weight.clone())?;
+        if let Some(bias) = &self.bias {
+            loader.insert_tensor_with_shape(format!("{}.bias", prefix), vec![dim], bias.clone())?;
+        }
+        Ok(())
+    }
+
+    fn describe(&self, prefix: &str, weights: &mut Vec<WeightInfo>) {
+        let dim = self.weight.len();
+        weights.push(WeightInfo {
+            name: format!("{}.weight", prefix),
+            shape: vec![dim],
+            dtype: DType::F32,
+        });
+        if self.bias.is_some() {
+            weights.push(WeightInfo {
+                name: format!("{}.bias", prefix),
+                shape: vec![dim],
+                dtype: DType::F32,
+            });
+        }
+    }
+
+    fn record<'w>(
+        &'w self,
+        tape: &mut Tape<'w>,
+        prefix: &str,
+        x: Var,
+        adapter: Option<&'w LoraAdapter>,
+    ) -> Result<Var> {
+        let dim = self.weight.len();
+        let weight = record_weight(tape, adapter, &format!("{}.weight", prefix), (&self.weight).into(), 1, dim);
+        let bias = self
+            .bias
+            .as_ref()
+            .map(|b| record_weight(tape, adapter, &format!("{}.bias", prefix), b.into(), 1, dim));
+        tape.norm(x, weight, bias, self.kind, self.eps)
+    }
+
+    fn parameters_mut<'a>(&'a mut self, prefix: &str, params: &mut Vec<ParamMut<'a>>) {
+        let dim = self.weight.len();
+        params.push(ParamMut {
+            name: format!("{}.weight", prefix),
+            rows: 1,
+            cols: dim,
+            values: &mut self.weight,
+        });
+        if let Some(bias) = &mut self.bias {
+            params.push(ParamMut {
+                name: format!("{}.bias", prefix),
+                rows: 1,
+                cols: dim,
+                values: bias,
+            });
+        }
+    }
+
+    pub fn forward(&self, x: &[f32]) -> Vec<f32> {
+        let n = x.len() as f32;
+
+        let (mean, inv_std) = match self.kind {
+            NormType::RmsNorm => {
+                let mean_sq = x.iter().map(|v| v * v).sum::<f32>() / n;
+                (0.0, 1.0 / (mean_sq + self.eps).sqrt())
+            }
+            NormType::LayerNorm => {
+                let mean = x.iter().sum::<f32>() / n;
+                let var = x.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / n;
+                (mean, 1.0 / (var + self.eps).sqrt())
+            }
+        };
+
+        let mut out: Vec<f32> = x
+            .iter()
+            .zip(&self.weight)
+            .map(|(v, w)| (v - mean) * inv_std * w)
+            .collect();
+
+        if let Some(bias) = &self.bias {
+            for (o, b) in out.iter_mut().zip(bias) {
+                *o += b;
+            }
+        }
+
+        out
+    }
+
+    pub fn num_params(&self) -> usize {
+        self.weight.len() + self.bias.as_ref().map_or(0, |b| b.len())
+    }
+}
+
+/// Rotary position embedding (rotate-half convention, as in HF Llama)
+#[derive(Debug, Clone)]
+pub struct RotaryEmbedding {
+    inv_freq: Vec<f32>,
+}
+
+impl RotaryEmbedding {
+    pub fn new(head_dim: usize, theta: f32) -> Self {
+        let half = head_dim / 2;
+        let inv_freq = (0..half)
+            .map(|i| 1.0 / theta.powf((2 * i) as f32 / head_dim as f32))
+            .collect();
+
+        Self { inv_freq }
+    }
+
+    /// Rotate one head vector in place for `position`
+    pub fn apply(&self, head: &mut [f32], position: usize) {
+        let half = self.inv_freq.len();
+        for (i, freq) in self.inv_freq.iter().enumerate() {
+            let (sin, cos) = (position as f32 * freq).sin_cos();
+            let x1 = head[i];
+            let x2 = head[i + half];
+            head[i] = x1 * cos - x2 * sin;
+            head[i + half] = x2 * cos + x1 * sin;
+        }
+    }
+
+    /// Undo `apply` (rotate by the negative angle); also its transpose
+    pub fn apply_inverse(&self, head: &mut [f32], position: usize) {
+        let half = self.inv_freq.len();
+        for (i, freq) in self.inv_freq.iter().enumerate() {
+            let (sin, cos) = (position as f32 * freq).sin_cos();
+            let y1 = head[i];
+            let y2 = head[i + half];
+            head[i] = y1 * cos + y2 * sin;
+            head[i + half] = y2 * cos - y1 * sin;
+        }
+    }
+}
+
+/// Per-sequence key/value cache for incremental decoding
+///
+/// Keys are stored after rotary embedding, so cached positions never need to
+/// be recomputed. The cache remembers which tokens it holds; `Transformer::prefill`
+/// reuses the longest common prefix with a new prompt (e.g. the query shared by
+/// consecutive Markovian chunks) and recomputes only the rest.
+#[derive(Debug, Clone)]
+pub struct KvCache {
+    /// Per layer, flat [len * kv_dim]
+    keys: Vec<Vec<f32>>,
+    values: Vec<Vec<f32>>,
+    tokens: Vec<usize>,
+    kv_dim: usize,
+    max_tokens: usize,
+}
+
+impl KvCache {
+    /// Cache holding up to `max_tokens` positions
+    pub fn new(config: &ModelConfig, max_tokens: usize) -> Self {
+        Self {
+            keys: vec![Vec::new(); config.num_layers],
+            values: vec![Vec::new(); config.num_layers],
+            tokens: Vec::new(),
+            kv_dim: config.num_kv_heads * config.head_dim,
+            max_tokens,
+        }
+    }
+
+    /// Cache capped at `max_bytes` of key/value storage (and at `max_seq_len`)
+    pub fn with_memory_limit(config: &ModelConfig, max_bytes: usize) -> Self {
+        let max_tokens = (max_bytes / Self::bytes_per_token(config)).min(config.max_seq_len);
+        Self::new(config, max_tokens)
+    }
+
+    /// Key + value bytes for one position across all layers
+    pub fn bytes_per_token(config: &ModelConfig) -> usize {
+        (2 * config.num_layers * config.num_kv_heads * config.head_dim * std::mem::size_of::<f32>()).max(1)
+    }
+
+    /// Number of cached positions
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    pub fn max_tokens(&self) -> usize {
+        self.max_tokens
+    }
+
+    /// Positions that can still be appended
+    pub fn remaining(&self) -> usize {
+        self.max_tokens.saturating_sub(self.len())
+    }
+
+    /// Tokens whose keys/values are cached
+    pub fn tokens(&self) -> &[usize] {
+        &self.tokens
+    }
+
+    /// Bytes currently held
+    pub fn memory_bytes(&self) -> usize {
+        (self.keys.iter().chain(&self.values).map(Vec::len).sum::<usize>()) * std::mem::size_of::<f32>()
+    }
+
+    /// Drop everything (e.g. when a new problem starts)
+    pub fn reset(&mut self) {
+        self.truncate(0);
+    }
+
+    /// Keep only the first `len` positions
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.len() {
+            return;
+        }
+        for layer in self.keys.iter_mut().chain(self.values.iter_mut()) {
+            layer.truncate(len * self.kv_dim);
+        }
+        self.tokens.truncate(len);
+    }
+
+    /// Length of the shared prefix between the cache and `tokens`
+    pub fn common_prefix_len(&self, tokens: &[usize]) -> usize {
+        self.tokens
+            .iter()
+            .zip(tokens)
+            .take_while(|(a, b)| a == b)
+            .count()
+    }
+}
+
+/// One pre-norm decoder block
+#[derive(Debug, Clone)]
+pub struct TransformerLayer {
+    pub attn_norm: Norm,
+    pub q_proj: Linear,
+    pub k_proj: Linear,
+    pub v_proj: Linear,
+    pub o_proj: Linear,
+    pub mlp_norm: Norm,
+    /// Present for SwiGLU, absent for GELU
+    pub gate_proj: Option<Linear>,
+    pub up_proj: Linear,
+    pub down_proj: Linear,
+}
+
+impl TransformerLayer {
+    fn random(rng: &mut StdRng, config: &ModelConfig) -> Self {
+        let d = config.embed_dim;
+        let q_dim = config.num_heads * config.head_dim;
+        let kv_dim = config.num_kv_heads * config.head_dim;
+        let ff = config.intermediate_size;
+        let dt = config.compute_dtype;
+
+        Self {
+            attn_norm: Norm::identity(config.norm, d, config.norm_eps),
+            q_proj: Linear::random(rng, d, q_dim, dt),
+            k_proj: Linear::random(rng, d, kv_dim, dt),
+            v_proj: Linear::random(rng, d, kv_dim, dt),
+            o_proj: Linear::random(rng, q_dim, d, dt),
+            mlp_norm: Norm::identity(config.norm, d, config.norm_eps),
+            gate_proj: match config.activation {
+                Activation::SwiGlu => Some(Linear::random(rng, d, ff, dt)),
+                Activation::Gelu => None,
+            },
+            up_proj: Linear::random(rng, d, ff, dt),
+            down_proj: Linear::random(rng, ff, d, dt),
+        }
+    }
+
+    /// Load `model.layers.{index}.*` (HF Llama naming)
+    fn from_loader(loader: &dyn TensorSource, config: &ModelConfig, index: usize) -> Result<Self> {
+        let p = format!("model.layers.{}", index);
+        let d = config.embed_dim;
+        let q_dim = config.num_heads * config.head_dim;
+        let kv_dim = config.num_kv_heads * config.head_dim;
+        let ff = config.intermediate_size;
+        let dt = config.compute_dtype;
+
+        Ok(Self {
+            attn_norm: Norm::from_loader(loader, &format!("{}.input_layernorm", p), config.norm, d, config.norm_eps)?,
+            q_proj: Linear::from_loader(loader, &format!("{}.self_attn.q_proj", p), d, q_dim, dt)?,
+            k_proj: Linear::from_loader(loader, &format!("{}.self_attn.k_proj", p), d, kv_dim, dt)?,
+            v_proj: Linear::from_loader(loader, &format!("{}.self_attn.v_proj", p), d, kv_dim, dt)?,
+            o_proj: Linear::from_loader(loader, &format!("{}.self_attn.o_proj", p), q_dim, d, dt)?,
+            mlp_norm: Norm::from_loader(loader, &format!("{}.post_attention_layernorm", p), config.norm, d, config.norm_eps)?,
+            gate_proj: match config.activation {
+                Activation::SwiGlu => Some(Linear::from_loader(loader, &format!("{}.mlp.gate_proj", p), d, ff, dt)?),
+                Activation::Gelu => None,
+            },
+            up_proj: Linear::from_loader(loader, &format!("{}.mlp.up_proj", p), d, ff, dt)?,
+            down_proj: Linear::from_loader(loader, &format!("{}.mlp.down_proj", p), ff, d, dt)?,
+        })
+    }
+
+    /// Linear layers by name within the block (`gate_proj` is absent for GELU)
+    fn linears_mut(&mut self) -> [(&'static str, Option<&mut Linear>); 7] {
+        [
+            ("self_attn.q_proj", Some(&mut self.q_proj)),
+            ("self_attn.k_proj", Some(&mut self.k_proj)),
+            ("self_attn.v_proj", Some(&mut self.v_proj)),
+            ("self_attn.o_proj", Some(&mut self.o_proj)),
+            ("mlp.gate_proj", self.gate_proj.as_mut()),
+            ("mlp.up_proj", Some(&mut self.up_proj)),
+            ("mlp.down_proj", Some(&mut self.down_proj)),
+        ]
+    }
+
+    fn export(&self, loader: &mut WeightLoader, index: usize) -> Result<()> {
+        let p = format!("model.layers.{}", index);
+        self.attn_norm.export(loader, &format!("{}.input_layernorm", p))?;
+        self.q_proj.export(loader, &format!("{}.self_attn.q_proj", p))?;
+        self.k_proj.export(loader, &format!("{}.self_attn.k_proj", p))?;
+        self.v_proj.export(loader, &format!("{}.self_attn.v_proj", p))?;
+        self.o_proj.export(loader, &format!("{}.self_attn.o_proj", p))?;
+        self.mlp_norm.export(loader, &format!("{}.post_attention_layernorm", p))?;
+        if let Some(gate_proj) = &self.gate_proj {
+            gate_proj.export(loader, &format!("{}.mlp.gate_proj", p))?;
+        }
+        self.up_proj.export(loader, &format!("{}.mlp.up_proj", p))?;
+        self.down_proj.export(loader, &format!("{}.mlp.down_proj", p))
+    }
+
+    fn describe(&self, index: usize, weights: &mut Vec<WeightInfo>) {
+        let p = format!("model.layers.{}", index);
+        self.attn_norm.describe(&format!("{}.input_layernorm", p), weights);
+        self.q_proj.describe(&format!("{}.self_attn.q_proj", p), weights);
+        self.k_proj.describe(&format!("{}.self_attn.k_proj", p), weights);
+        self.v_proj.describe(&format!("{}.self_attn.v_proj", p), weights);
+        self.o_proj.describe(&format!("{}.self_attn.o_proj", p), weights);
+        self.mlp_norm.describe(&format!("{}.post_attention_layernorm", p), weights);
+        if let Some(gate_proj) = &self.gate_proj {
+            gate_proj.describe(&format!("{}.mlp.gate_proj", p), weights);
+        }
+        self.up_proj.describe(&format!("{}.mlp.up_proj", p), weights);
+        self.down_proj.describe(&format!("{}.mlp.down_proj", p), weights);
+    }
+
+    fn parameters_mut<'a>(&'a mut self, index: usize, params: &mut Vec<ParamMut<'a>>) {
+        let p = format!("model.layers.{}", index);
+        self.attn_norm.parameters_mut(&format!("{}.input_layernorm", p), params);
+        self.q_proj.parameters_mut(&format!("{}.self_attn.q_proj", p), params);
+        self.k_proj.parameters_mut(&format!("{}.self_attn.k_proj", p), params);
+        self.v_proj.parameters_mut(&format!("{}.self_attn.v_proj", p), params);
+        self.o_proj.parameters_mut(&format!("{}.self_attn.o_proj", p), params);
+        self.mlp_norm.parameters_mut(&format!("{}.post_attention_layernorm", p), params);
+        if let Some(gate_proj) = &mut self.gate_proj {
+            gate_proj.parameters_mut(&format!("{}.mlp.gate_proj", p), params);
+        }
+        self.up_proj.parameters_mut(&format!("{}.mlp.up_proj", p), params);
+        self.down_proj.parameters_mut(&format!("{}.mlp.down_proj", p), params);
+    }
+
+    /// Record the block over all rows of `x` (positions 0..rows)
+    fn record<'w>(
+        &'w self,
+        tape: &mut Tape<'w>,
+        config: &ModelConfig,
+        index: usize,
+        x: Var,
+        adapter: Option<&'w LoraAdapter>,
+    ) -> Result<Var> {
+        let p = format!("model.layers.{}", index);
+        let hd = config.head_dim;
+
+        let h = self.attn_norm.record(tape, &format!("{}.input_layernorm", p), x, adapter)?;
+        let q = self.q_proj.record(tape, &format!("{}.self_attn.q_proj", p), h, adapter)?;
+        let q = tape.rope(q, hd, config.rope_theta, 0)?;
+        let k = self.k_proj.record(tape, &format!("{}.self_attn.k_proj", p), h, adapter)?;
+        let k = tape.rope(k, hd, config.rope_theta, 0)?;
+        let v = self.v_proj.record(tape, &format!("{}.self_attn.v_proj", p), h, adapter)?;
+        let context = tape.attention(q, k, v, config.num_heads, config.num_kv_heads)?;
+        let attn = self.o_proj.record(tape, &format!("{}.self_attn.o_proj", p), context, adapter)?;
+        let x = tape.add(x, attn)?;
+
+        let h = self.mlp_norm.record(tape, &format!("{}.post_attention_layernorm", p), x, adapter)?;
+        let up = self.up_proj.record(tape, &format!("{}.mlp.up_proj", p), h, adapter)?;
+        let hidden = match &self.gate_proj {
+            Some(gate_proj) => {
+                let gate = gate_proj.record(tape, &format!("{}.mlp.gate_proj", p), h, adapter)?;
+                let gate = tape.silu(gate);
+                tape.mul(gate, up)?
+            }
+            None => tape.gelu(up),
+        };
+        let mlp = self.down_proj.record(tape, &format!("{}.mlp.down_proj", p), hidden, adapter)?;
+        tape.add(x, mlp)
+    }
+
+    fn mlp(&self, x: &[f32]) -> Vec<f32> {
+        let up = self.up_proj.forward(x);
+        let hidden: Vec<f32> = match &self.gate_proj {
+            Some(gate_proj) => gate_proj
+                .forward(x)
+                .iter()
+                .zip(&up)
+                .map(|(g, u)| silu(*g) * u)
+                .collect(),
+            None => up.iter().map(|u| gelu(*u)).collect(),
+        };
+        self.down_proj.forward(&hidden)
+    }
+
+    fn num_params(&self) -> usize {
+        self.attn_norm.num_params()
+            + self.q_proj.num_params()
+            + self.k_proj.num_params()
+            + self.v_proj.num_params()
+            + self.o_proj.num_params()
+            + self.mlp_norm.num_params()
+            + self.gate_proj.as_ref().map_or(0, |g| g.num_params())
+            + self.up_proj.num_params()
+            + self.down_proj.num_params()
+    }
+}
+
+/// Decoder stack: layers, final norm and (optionally untied) LM head
+#[derive(Debug, Clone)]
+pub struct Transformer {
+    config: ModelConfig,
+    layers: Vec<TransformerLayer>,
+    final_norm: Norm,
+    /// None = tied to the embedding table
+    lm_head: Option<Linear>,
+    rope: RotaryEmbedding,
+}
+
+impl Transformer {
+    /// Randomly initialised transformer (deterministic for a given seed)
+    pub fn random(config: &ModelConfig, seed: u64) -> Result<Self> {
+        config.validate()?;
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        let layers = (0..config.num_layers)
+            .map(|_| TransformerLayer::random(&mut rng, config))
+            .collect();
+        let lm_head = if config.tie_word_embeddings {
+            None
+        } else {
+            Some(Linear::random(&mut rng, config.embed_dim, config.vocab_size, config.compute_dtype))
+        };
+
+        Ok(Self {
+            config: config.clone(),
+            layers,
+            final_norm: Norm::identity(config.norm, config.embed_dim, config.norm_eps),
+            lm_head,
+            rope: RotaryEmbedding::new(config.head_dim, config.rope_theta),
+        })
+    }
+
+    /// Build from HF Llama-style tensor names in a `WeightLoader`
+    ///
+    /// Expects `model.layers.{i}.{input_layernorm, self_attn.{q,k,v,o}_proj,
+    /// post_attention_layernorm, mlp.{gate,up,down}_proj}`, `model.norm` and,
+    /// when embeddings are untied, `lm_head`.
+    pub fn from_loader(config: &ModelConfig, loader: &WeightLoader) -> Result<Self> {
+        Self::from_source(config, loader)
+    }
+
+    /// `from_loader` for any tensor source, such as a `MappedSafeTensors`
+    ///
+    /// Linear weights are stored in `config.compute_dtype`.
+    pub fn from_source(config: &ModelConfig, loader: &dyn TensorSource) -> Result<Self> {
+        config.validate()?;
+
+        let layers = (0..config.num_layers)
+            .map(|i| TransformerLayer::from_loader(loader, config, i))
+            .collect::<Result<Vec<_>>>()?;
+        let final_norm = Norm::from_loader(loader, "model.norm", config.norm, config.embed_dim, config.norm_eps)?;
+        let lm_head = if config.tie_word_embeddings {
+            None
+        } else {
+            Some(Linear::from_loader(loader, "lm_head", config.embed_dim, config.vocab_size, config.compute_dtype)?)
+        };
+
+        Ok(Self {
+            config: config.clone(),
+            layers,
+            final_norm,
+            lm_head,
+            rope: RotaryEmbedding::new(config.head_dim, config.rope_theta),
+        })
+    }
+
+    /// Store all weights in `loader` under the names `from_loader` reads
+    pub fn export(&self, loader: &mut WeightLoader) -> Result<()> {
+        for (i, layer) in self.layers.iter().enumerate() {
+            layer.export(loader, i)?;
+        }
+        self.final_norm.export(loader, "model.norm")?;
+        if let Some(lm_head) = &self.lm_head {
+            lm_head.export(loader, "lm_head")?;
+        }
+        Ok(())
+    }
+
+    /// What `export` writes, without copying any values
+    pub fn describe(&self) -> Vec<WeightInfo> {
+        let mut weights = Vec::new();
+        for (i, layer) in self.layers.iter().enumerate() {
+            layer.describe(i, &mut weights);
+        }
+        self.final_norm.describe("model.norm", &mut weights);
+        if let Some(lm_head) = &self.lm_head {
+            lm_head.describe("lm_head", &mut weights);
+        }
+        weights
+    }
+
+    /// Record a full forward pass on `tape` for training
+    ///
+    /// Every weight, including the embedding table, becomes a tape parameter
+    /// under the name `export` uses. Returns logits for every position,
+    /// shape [tokens.len(), vocab_size].
+    pub fn record<'w>(&'w self, tape: &mut Tape<'w>, embedding: &'w EmbeddingLayer, tokens: &[usize]) -> Result<Var> {
+        self.record_with(tape, embedding, tokens, None)
+    }
+
+    /// `record`, or with an adapter: base weights (embedding table
+    /// included) become constants and only the adapter's `A`/`B` matrices
+    /// are parameters, under the names `LoraAdapter::parameters_mut` uses
+    pub fn record_with<'w>(
+        &'w self,
+        tape: &mut Tape<'w>,
+        embedding: &'w EmbeddingLayer,
+        tokens: &[usize],
+        adapter: Option<&'w LoraAdapter>,
+    ) -> Result<Var> {
+        if tokens.is_empty() {
+            anyhow::bail!("Cannot run forward pass on an empty sequence");
+        }
+        if tokens.len() > self.config.max_seq_len {
+            anyhow::bail!(
+                "Sequence of {} tokens exceeds max_seq_len {}",
+                tokens.len(),
+                self.config.max_seq_len
+            );
+        }
+
+        let table = record_weight(
+            tape,
+            adapter,
+            "model.embed_tokens.weight",
+            embedding.weights(),
+            embedding.vocab_size(),
+            embedding.embed_dim(),
+        );
+        let mut x = tape.embedding(table, tokens)?;
+        for (i, layer) in self.layers.iter().enumerate() {
+            x = layer.record(tape, &self.config, i, x, adapter)?;
+        }
+        let x = self.final_norm.record(tape, "model.norm", x, adapter)?;
+
+        match &self.lm_head {
+            Some(head) => head.record(tape, "lm_head", x, adapter),
+            None => tape.linear(x, table, None),
+        }
+    }
+
+    /// Name, out_features and in_features of every linear weight (the
+    /// weights a `LoraAdapter` can target)
+    pub fn linear_weights(&self) -> Vec<(String, usize, usize)> {
+        let mut weights = Vec::new();
+        for (i, layer) in self.layers.iter().enumerate() {
+            let linears = [
+                ("self_attn.q_proj", Some(&layer.q_proj)),
+                ("self_attn.k_proj", Some(&layer.k_proj)),
+                ("self_attn.v_proj", Some(&layer.v_proj)),
+                ("self_attn.o_proj", Some(&layer.o_proj)),
+                ("mlp.gate_proj", layer.gate_proj.as_ref()),
+                ("mlp.up_proj", Some(&layer.up_proj)),
+                ("mlp.down_proj", Some(&layer.down_proj)),
+            ];
+            for (name, linear) in linears {
+                if let Some(linear) = linear {
+                    let weight = format!("model.layers.{}.{}.weight", i, name);
+                    weights.push((weight, linear.out_features, linear.in_features));
+                }
+            }
+        }
+        if let Some(lm_head) = &self.lm_head {
+            weights.push(("lm_head.weight".to_string(), lm_head.out_features, lm_head.in_features));
+        }
+        weights
+    }
+
+    /// Add `adapter`'s low-rank updates to the weights it targets
+    ///
+    /// Only the targeted weights change; they keep their storage dtype.
+    pub fn merge_adapter(&mut self, adapter: &LoraAdapter) {
+        for (i, layer) in self.layers.iter_mut().enumerate() {
+            for (name, linear) in layer.linears_mut() {
+                if let Some(linear) = linear {
+                    linear.merge_adapter(adapter, format!("model.layers.{}.{}.weight", i, name));
+                }
+            }
+        }
+        if let Some(lm_head) = &mut self.lm_head {
+            lm_head.merge_adapter(adapter, "lm_head.weight".to_string());
+        }
+    }
+
+    /// Mutable weights under the names `record` and `export` use
+    /// (everything except the embedding table)
+    pub fn parameters_mut(&mut self) -> Vec<ParamMut<'_>> {
+        let mut params = Vec::new();
+        for (i, layer) in self.layers.iter_mut().enumerate() {
+            layer.parameters_mut(i, &mut params);
+        }
+        self.final_norm.parameters_mut("model.norm", &mut params);
+        if let Some(lm_head) = &mut self.lm_head {
+            lm_head.parameters_mut("lm_head", &mut params);
+        }
+        params
+    }
+
+    /// True if `loader` holds transformer blocks (not just embeddings)
+    pub fn present_in(loader: &dyn TensorSource) -> bool {
+        loader.tensor_len("model.layers.0.self_attn.q_proj.weight").is_some()
+    }
+
+    /// Logits over the vocabulary for the last position of `tokens`
+    pub fn forward(&self, embedding: &EmbeddingLayer, tokens: &[usize]) -> Result<Vec<f32>> {
+        let mut cache = KvCache::new(&self.config, tokens.len());
+        self.forward_cached(embedding, tokens, &mut cache)
+    }
+
+    /// Logits for the token after every position of `tokens`
+    pub fn forward_all(&self, embedding: &EmbeddingLayer, tokens: &[usize]) -> Result<Vec<Vec<f32>>> {
+        let mut cache = KvCache::new(&self.config, tokens.len());
+        let hidden = self.forward_hidden_cached(embedding, tokens, &mut cache)?;
+        Ok(hidden.iter().map(|h| self.logits(embedding, h)).collect())
+    }
+
+    /// Final-normed hidden states for every position, flat [seq_len * embed_dim]
+    pub fn forward_hidden(&self, embedding: &EmbeddingLayer, tokens: &[usize]) -> Result<Vec<f32>> {
+        let mut cache = KvCache::new(&self.config, tokens.len());
+        let hidden = self.forward_hidden_cached(embedding, tokens, &mut cache)?;
+        Ok(hidden.concat())
+    }
+
+    /// Append `tokens` to `cache` and return logits for the last one
+    pub fn forward_cached(
+        &self,
+        embedding: &EmbeddingLayer,
+        tokens: &[usize],
+        cache: &mut KvCache,
+    ) -> Result<Vec<f32>> {
+        let hidden = self.forward_hidden_cached(embedding, tokens, cache)?;
+        let last = hidden.last().expect("forward_hidden_cached rejects empty input");
+        Ok(self.logits(embedding, last))
+    }
+
+    /// Run a prompt through the cache, reusing whatever prefix it already holds
+    ///
+    /// Returns logits for the last prompt token. Cached positions past the
+    /// shared prefix are discarded first.
+    pub fn prefill(
+        &self,
+        embedding: &EmbeddingLayer,
+        tokens: &[usize],
+        cache: &mut KvCache,
+    ) -> Result<Vec<f32>> {
+        if tokens.is_empty() {
+            anyhow::bail!("Cannot run forward pass on an empty sequence");
+        }
+
+        // Always recompute the last token so its logits are available
+        let keep = cache.common_prefix_len(tokens).min(tokens.len() - 1);
+        cache.truncate(keep);
+        self.forward_cached(embedding, &tokens[keep..], cache)
+    }
+
+    /// Hidden states for `tokens` appended after the cached positions
+    fn forward_hidden_cached(
+        &self,
+        embedding: &EmbeddingLayer,
+        tokens: &[usize],
+        cache: &mut KvCache,
+    ) -> Result<Vec<Vec<f32>>> {
+        if tokens.is_empty() {
+            anyhow::bail!("Cannot run forward pass on an empty sequence");
+        }
+        if embedding.embed_dim() != self.config.embed_dim {
+            anyhow::bail!(
+                "Embedding dim {} does not match model dim {}",
+                embedding.embed_dim(),
+                self.config.embed_dim
+            );
+        }
+        if cache.keys.len() != self.layers.len()
+            || cache.kv_dim != self.config.num_kv_heads * self.config.head_dim
+        {
+            anyhow::bail!("KV cache was built for a different model configuration");
+        }
+        if tokens.len() > cache.remaining() {
+            anyhow::bail!(
+                "KV cache full: {} cached + {} new tokens exceeds capacity {}",
+                cache.len(),
+                tokens.len(),
+                cache.max_tokens()
+            );
+        }
+
+        let start = cache.len();
+        let d = self.config.embed_dim;
+        let mut x: Vec<Vec<f32>> = embedding
+            .embed_sequence(tokens)?
+            .chunks_exact(d)
+            .map(|row| row.to_vec())
+            .collect();
+
+        for (index, layer) in self.layers.iter().enumerate() {
+            for (offset, row) in x.iter_mut().enumerate() {
+                let delta = self.attention(layer, row, start + offset, cache, index);
+                add_in_place(row, &delta);
+
+                let delta = layer.mlp(&layer.mlp_norm.forward(row));
+                add_in_place(row, &delta);
+            }
+        }
+
+        cache.tokens.extend_from_slice(tokens);
+        Ok(x.iter().map(|row| self.final_norm.forward(row)).collect())
+    }
+
+    /// Causal self-attention for one position, appending its key/value to the cache
+    fn attention(
+        &self,
+        layer: &TransformerLayer,
+        row: &[f32],
+        position: usize,
+        cache: &mut KvCache,
+        layer_index: usize,
+    ) -> Vec<f32> {
+        let hd = self.config.head_dim;
+        let n_heads = self.config.num_heads;
+        let kv_dim = cache.kv_dim;
+        let group = n_heads / self.config.num_kv_heads;
+        let scale = 1.0 / (hd as f32).sqrt();
+
+        let normed = layer.attn_norm.forward(row);
+        let mut q = layer.q_proj.forward(&normed);
+        let mut k = layer.k_proj.forward(&normed);
+        for head in q.chunks_exact_mut(hd) {
+            self.rope.apply(head, position);
+        }
+        for head in k.chunks_exact_mut(hd) {
+            self.rope.apply(head, position);
+        }
+
+        let keys = &mut cache.keys[layer_index];
+        let values = &mut cache.values[layer_index];
+        keys.extend_from_slice(&k);
+        values.extend_from_slice(&layer.v_proj.forward(&normed));
+
+        let mut context = vec![0.0f32; n_heads * hd];
+        let mut scores = Vec::with_capacity(position + 1);
+
+        for h in 0..n_heads {
+            let kv = (h / group) * hd;
+            let q_head = &q[h * hd..(h + 1) * hd];
+
+            scores.clear();
+            scores.extend(
+                keys.chunks_exact(kv_dim)
+                    .map(|k| dot(q_head, &k[kv..kv + hd]) * scale),
+            );
+            softmax_in_place(&mut scores);
+
+            let out = &mut context[h * hd..(h + 1) * hd];
+            for (weight, v) in scores.iter().zip(values.chunks_exact(kv_dim)) {
+                for (o, v) in out.iter_mut().zip(&v[kv..kv + hd]) {
+                    *o += weight * v;
+                }
+            }
+        }
+
+        layer.o_proj.forward(&context)
+    }
+
+    /// Project one final hidden state to vocabulary logits
+    pub fn logits(&self, embedding: &EmbeddingLayer, hidden: &[f32]) -> Vec<f32> {
+        match &self.lm_head {
+            Some(head) => head.forward(hidden),
+            None => embedding.matrix().matvec(hidden),
+        }
+    }
+
+    pub fn config(&self) -> &ModelConfig {
+        &self.config
+    }
+
+    pub fn layers(&self) -> &[TransformerLayer] {
+        &self.layers
+    }
+
+    pub fn is_tied(&self) -> bool {
+        self.lm_head.is_none()
+    }
+
+    /// Parameter count excluding the embedding table
+    pub fn num_params(&self) -> usize {
+        self.layers.iter().map(|l| l.num_params()).sum::<usize>()
+            + self.final_norm.num_params()
+            + self.lm_head.as_ref().map_or(0, |h| h.num_params())
+    }
+}
+
+/// A weight on `tape`: a named parameter, or a constant while an adapter trains
+///
+/// The tape borrows `values` unless they had to be converted to f32.
+fn record_weight<'w>(
+    tape: &mut Tape<'w>,
+    adapter: Option<&LoraAdapter>,
+    name: &str,
+    values: Cow<'w, [f32]>,
+    rows: usize,
+    cols: usize,
+) -> Var {
+    match adapter {
+        Some(_) => tape.constant(values, rows, cols),
+        None => tape.param(name, values, rows, cols),
+    }
+}
+
+/// Fetch a required tensor and check its element count
+fn check_tensor(loader: &dyn TensorSource, name: &str, expected_len: usize) -> Result<()> {
+    let len = loader
+        .tensor_len(name)
+        .with_context(|| format!("Missing tensor {}", name))?;
+    if len != expected_len {
+        anyhow::bail!(
+            "Tensor {} has {} elements, expected {} for this ModelConfig",
+            name,
+            len,
+            expected_len
+        );
+    }
+    Ok(())
+}
+
+fn take_tensor(loader: &dyn TensorSource, name: &str, expected_len: usize) -> Result<Vec<f32>> {
+    check_tensor(loader, name, expected_len)?;
+    loader.vector(name)
+}
+
+fn take_matrix(loader: &dyn TensorSource, name: &str, rows: usize, cols: usize, dtype: DType) -> Result<Matrix> {
+    check_tensor(loader, name, rows * cols)?;
+    loader.matrix(name, rows, cols, dtype)
+}
+
+fn optional_tensor(loader: &dyn TensorSource, name: &str, expected_len: usize) -> Result<Option<Vec<f32>>> {
+    match loader.tensor_len(name) {
+        Some(_) => take_tensor(loader, name, expected_len).map(Some),
+        None => Ok(None),
+    }
+}
+
+pub(crate) fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+fn add_in_place(a: &mut [f32], b: &[f32]) {
+    for (x, y) in a.iter_mut().zip(b) {
+        *x += y;
+    }
+}
+
+pub(crate) fn softmax_in_place(x: &mut [f32]) {
+    let max = x.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+    let mut sum = 0.0;
+    for v in x.iter_mut() {
+        *v = (*v - max).exp();
+        sum += *v;
+    }
+    for v in x.iter_mut() {
+        *v /= sum;
+    }
+}
+
+pub(crate) fn silu(x: f32) -> f32 {
+    x / (1.0 + (-x).exp())
+}
+
+/// GELU, tanh approximation
+pub(crate) fn gelu(x: f32) -> f32 {
+    0.5 * x * (1.0 + ((2.0 / std::f32::consts::PI).sqrt() * (x + 0.044715 * x.powi(3))).tanh())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tiny_config() -> ModelConfig {
+        ModelConfig {
+            vocab_size: 32,
+            embed_dim: 16,
+            num_heads: 4,
+            num_kv_heads: 2,
+            head_dim: 4,
+            num_layers: 2,
+            intermediate_size: 24,
+            max_seq_len: 64,
+            ..ModelConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_rms_norm_unit_scale() {
+        let norm = Norm::identity(NormType::RmsNorm, 4, 0.0);
+        let out = norm.forward(&[2.0, -2.0, 2.0, -2.0]);
+        assert_eq!(out, vec![1.0, -1.0, 1.0, -1.0]);
+    }
+
+    #[test]
+    fn test_layer_norm_zero_mean() {
+        let norm = Norm::identity(NormType::LayerNorm, 4, 1e-6);
+        let out = norm.forward(&[1.0, 2.0, 3.0, 4.0]);
+        assert!(out.iter().sum::<f32>().abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_rope_preserves_norm_and_is_identity_at_zero() {
+        let rope = RotaryEmbedding::new(4, 10000.0);
+        let mut head = vec![1.0, 2.0, 3.0, 4.0];
+
+        rope.apply(&mut head, 0);
+        assert_eq!(head, vec![1.0, 2.0, 3.0, 4.0]);
+
+        rope.apply(&mut head, 7);
+        let norm: f32 = head.iter().map(|v| v * v).sum();
+        assert!((norm - 30.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_recorded_forward_matches_inference() {
+        for activation in [Activation::SwiGlu, Activation::Gelu] {
+            let config = ModelConfig {
+                activation,
+                norm: match activation {
+                    Activation::SwiGlu => NormType::RmsNorm,
+                    Activation::Gelu => NormType::LayerNorm,
+                },
+                ..tiny_config()
+            };
+            let model = Transformer::random(&config, 5).unwrap();
+            let embedding = EmbeddingLayer::new(config.vocab_size, config.embed_dim);
+            let tokens = [3, 1, 4, 1, 5];
+
+            let mut tape = Tape::new();
+            let logits = model.record(&mut tape, &embedding, &tokens).unwrap();
+            assert_eq!(tape.shape(logits), (tokens.len(), config.vocab_size));
+
+            let recorded = &tape.value(logits)[(tokens.len() - 1) * config.vocab_size..];
+            let expected = model.forward(&embedding, &tokens).unwrap();
+            for (a, b) in recorded.iter().zip(&expected) {
+                assert!((a - b).abs() < 1e-4, "{} vs {}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn test_parameters_mut_match_exported_names() {
+        let mut model = Transformer::random(&tiny_config(), 3).unwrap();
+        let mut loader = WeightLoader::new(crate::training::WeightFormat::SafeTensors);
+        model.export(&mut loader).unwrap();
+
+        let params = model.parameters_mut();
+        assert_eq!(params.len(), loader.tensor_names().len());
+        for param in params {
+            let shape = &loader.get_metadata(&param.name).unwrap().shape;
+            let expected = if param.rows == 1 { vec![param.cols] } else { vec![param.rows, param.cols] };
+            assert_eq!(shape, &expected, "{}", param.name);
+            assert_eq!(param.values.len(), param.rows * param.cols);
+        }
+    }
+
+    #[test]
+    fn test_forward_is_causal() {
+        let config = tiny_config();
+        let model = Transformer::random(&config, 7).unwrap();
+        let embedding = EmbeddingLayer::new(config.vocab_size, config.embed_dim);
+
+        // Hidden states of a prefix must not depend on later tokens
+        let a = model.forward_hidden(&embedding, &[1, 2, 3, 4]).unwrap();
+        let b = model.forward_hidden(&embedding, &[1, 2, 3, 9]).unwrap();
+        let prefix = 3 * config.embed_dim;
+        for (x, y) in a[..prefix].iter().zip(&b[..prefix]) {
+            assert!((x - y).abs() < 1e-6);
+        }
+        assert!(a[prefix..].iter().zip(&b[prefix..]).any(|(x, y)| (x - y).abs() > 1e-6));
+    }
+
+    #[test]
+    fn test_cached_decoding_matches_full_forward() {
+        let config = tiny_config();
+        let model = Transformer::random(&config, 11).unwrap();
+        let embedding = EmbeddingLayer::new(config.vocab_size, config.embed_dim);
+        let tokens = [4, 8, 15, 16, 23, 2];
+
+        let mut cache = KvCache::new(&config, 16);
+        model.prefill(&embedding, &tokens[..3], &mut cache).unwrap();
+        let mut cached = Vec::new();
+        for &token in &tokens[3..] {
+            cached = model.forward_cached(&embedding, &[token], &mut cache).unwrap();
+        }
+
+        let full = model.forward(&embedding, &tokens).unwrap();
+        for (a, b) in cached.iter().zip(&full) {
+            assert!((a - b).abs() < 1e-4, "{} vs {}", a, b);
+        }
+        assert_eq!(cache.tokens(), &tokens);
+    }
+
+    #[test]
+    fn test_prefill_reuses_common_prefix() {
+        let config = tiny_config();
+        let model = Transformer::random(&config, 5).unwrap();
+        let embedding = EmbeddingLayer::new(config.vocab_size, config.embed_dim);
+
+        let mut cache = KvCache::new(&config, 16);
+        model.prefill(&embedding, &[1, 2, 3, 4, 5], &mut cache).unwrap();
+        let bytes_per_token = cache.memory_bytes() / cache.len();
+        assert_eq!(bytes_per_token, KvCache::bytes_per_token(&config));
+
+        // Diverges after 3 tokens: keep those, drop the rest, append the new tail
+        let logits = model.prefill(&embedding, &[1, 2, 3, 9], &mut cache).unwrap();
+        assert_eq!(cache.tokens(), &[1, 2, 3, 9]);
+        let fresh = model.forward(&embedding, &[1, 2, 3, 9]).unwrap();
+        for (a, b) in logits.iter().zip(&fresh) {
+            assert!((a - b).abs() < 1e-4);
+        }
+
+        cache.reset();
+        assert!(cache.is_empty());
+        assert_eq!(cache.memory_bytes(), 0);
+    }
+
+    #[test]
+    fn test_cache_capacity_enforced() {
+        let config = tiny_config();
+        let model = Transformer::random(&config, 1).unwrap();
+        let embedding = EmbeddingLayer::new(config.vocab_size, config.embed_dim);
+
+        let mut cache = KvCache::with_memory_limit(&config, 3 * KvCache::bytes_per_token(&config));
+        assert_eq!(cache.max_tokens(), 3);
+        model.forward_cached(&embedding, &[1, 2, 3], &mut cache).unwrap();
+        assert!(model.forward_cached(&embedding, &[4], &mut cache).is_err());
+        assert_eq!(cache.len(), 3);
+    }
+
+    #[test]
+    fn test_forward_logits_shape_and_determinism() {
+        let mut config = tiny_config();
+        config.tie_word_embeddings = false;
+        config.activation = Activation::Gelu;
+        config.norm = NormType::LayerNorm;
+
+        let embedding = EmbeddingLayer::new(config.vocab_size, config.embed_dim);
+        let a = Transformer::random(&config, 42).unwrap();
+        let b = Transformer::random(&config, 42).unwrap();
+
+        let logits = a.forward(&embedding, &[3, 1, 4]).unwrap();
+        assert_eq!(logits.len(), config.vocab_size);
+        assert!(logits.iter().all(|l| l.is_finite()));
+        assert_eq!(logits, b.forward(&embedding, &[3, 1, 4]).unwrap());
+        assert!(!a.is_tied());
+    }
+
+    #[test]
+    fn test_from_loader_round_trip() {
+        let config = tiny_config();
+        let source = Transformer::random(&config, 3).unwrap();
+
+        let mut loader = WeightLoader::new(crate::training::WeightFormat::Custom);
+        for (i, layer) in source.layers().iter().enumerate() {
+            let p = format!("model.layers.{}", i);
+            loader.insert_tensor(format!("{}.input_layernorm.weight", p), layer.attn_norm.weight.clone());
+            loader.insert_tensor(format!("{}.self_attn.q_proj.weight", p), layer.q_proj.weight.to_f32().to_vec());
+            loader.insert_tensor(format!("{}.self_attn.k_proj.weight", p), layer.k_proj.weight.to_f32().to_vec());
+            loader.insert_tensor(format!("{}.self_attn.v_proj.weight", p), layer.v_proj.weight.to_f32().to_vec());
+            loader.insert_tensor(format!("{}.self_attn.o_proj.weight", p), layer.o_proj.weight.to_f32().to_vec());
+            loader.insert_tensor(format!("{}.post_attention_layernorm.weight", p), layer.mlp_norm.weight.clone());
+            loader.insert_tensor(format!("{}.mlp.gate_proj.weight", p), layer.gate_proj.as_ref().unwrap().weight.to_f32().to_vec());
+            loader.insert_tensor(format!("{}.mlp.up_proj.weight", p), layer.up_proj.weight.to_f32().to_vec());
+            loader.insert_tensor(format!("{}.mlp.down_proj.weight", p), layer.down_proj.weight.to_f32().to_vec());
+        }
+        loader.insert_tensor("model.norm.weight".to_string(), vec![1.0; config.embed_dim]);
+        assert!(Transformer::present_in(&loader));
+
+        let loaded = Transformer::from_loader(&config, &loader).unwrap();
+        let embedding = EmbeddingLayer::new(config.vocab_size, config.embed_dim);
+        assert_eq!(
+            loaded.forward(&embedding, &[5, 6]).unwrap(),
+            source.forward(&embedding, &[5, 6]).unwrap()
+        );
+        assert_eq!(loaded.num_params(), source.num_params());
+
+        // A wrongly-shaped tensor is reported by name
+        loader.insert_tensor("model.norm.weight".to_string(), vec![1.0; 3]);
+        let err = Transformer::from_loader(&config, &loader).unwrap_err().to_string();
+        assert!(err.contains("model.norm.weight"));
+    }
+}
@@ -21,9 +21,9 @@ pub use parallel::{ParallelExecutor, Task, TaskType, TaskResult};
 #[cfg(feature = "gpu")]
 pub use gpu::CudaContext;
 
-pub use inference::{Tokenizer, EmbeddingLayer, InferenceModel, ModelConfig};
+pub use inference::{Tokenizer, EmbeddingLayer, InferenceModel, ModelConfig, Transformer, KvCache, GenerationConfig, GenerationOutput};
 pub use training::{
     WeightLoader, WeightFormat,
     Optimizer, AdamOptimizer, SGDOptimizer, OptimizerConfig,
-    BackpropEngine, OnlineLearner, LearningConfig,
+    Tape, OnlineLearner, LearningConfig, TrainingExample,
 };
@@ -1,10 +1,81 @@
 // Markovian Thinker MCP Server
-// Stdio-based MCP server for chunk-based reasoning with Claude Code
+// Stdio-based MCP server for chunk-based reasoning with Claude Code,
+// plus an offline `train` subcommand
 
 use markovian_thinker::{MarkovianMCPServer, ModelConfig, InferenceModel, OnlineLearner, LearningConfig};
+use markovian_thinker::inference::{DType, Tokenizer};
+use markovian_thinker::training::{
+    example_tokens, Dataset, LoraAdapter, LoraConfig, OptimizerKind, Regularizer, ReplayPolicy, TrainingExample,
+    WeightFormat, WeightLoader,
+};
 use anyhow::Result;
+use clap::{Args, Parser, Subcommand};
+use std::path::PathBuf;
 use std::sync::{Arc, RwLock};
 
+#[derive(Parser)]
+#[command(name = "markovian-thinker", version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run the MCP server on stdio (the default)
+    Serve,
+    /// Train offline on a dataset and save the weights
+    Train(Box<TrainArgs>),
+}
+
+#[derive(Args)]
+struct TrainArgs {
+    /// Dataset files: JSONL (prompt/completion, text or chat messages) or TraceDataset JSON
+    #[arg(long, required = true, num_args = 1..)]
+    data: Vec<PathBuf>,
+    /// Passes over the training set
+    #[arg(long, default_value_t = 1)]
+    epochs: usize,
+    /// Output file (.safetensors or .gguf); with --lora-rank, the adapter
+    #[arg(long)]
+    out: PathBuf,
+    /// Initial weights (GGUF files also set the model configuration)
+    #[arg(long)]
+    weights: Option<PathBuf>,
+    /// Tokenizer: cl100k_base, o200k_base, a tokenizer.json or a .gguf file
+    /// (default: the vocabulary in GGUF weights, else cl100k_base)
+    #[arg(long)]
+    tokenizer: Option<String>,
+    /// Train a LoRA adapter of this rank instead of the full model
+    #[arg(long)]
+    lora_rank: Option<usize>,
+    /// Modules the LoRA adapter targets
+    #[arg(long, value_delimiter = ',', default_value = "q_proj,v_proj")]
+    lora_targets: Vec<String>,
+    #[arg(long, default_value_t = 1e-4)]
+    learning_rate: f32,
+    /// adam, adamw, lion, adafactor or sgd_nesterov
+    #[arg(long, default_value = "adamw")]
+    optimizer: String,
+    /// Fraction of the examples held out for validation
+    #[arg(long, default_value_t = 0.1)]
+    validation_split: f32,
+    /// Upper bound on tokens per batch (batches group examples of similar length)
+    #[arg(long, default_value_t = 2048)]
+    max_batch_tokens: usize,
+    /// Penalty against drifting from the initial weights: none, l2sp or ewc
+    #[arg(long, default_value = "none")]
+    regularizer: String,
+    #[arg(long, default_value_t = 0.01)]
+    regularizer_strength: f32,
+    /// Seed for shuffling and batching
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    /// Save resumable checkpoints here
+    #[arg(long)]
+    checkpoint_dir: Option<PathBuf>,
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     // Setup logging to STDERR (stdout is for MCP JSON messages!)
@@ -16,22 +87,69 @@ async fn main() -> Result<()> {
         .with_thread_names(false)
         .init();
 
+    match Cli::parse().command {
+        Some(Command::Train(args)) => train(*args),
+        Some(Command::Serve) | None => serve().await,
+    }
+}
+
+async fn serve() -> Result<()> {
     tracing::info!("Initializing Markovian Thinker MCP Server");
 
-    // Initialize model with default configuration
-    let model_config = ModelConfig::default();
+    // Initialize model with default configuration; MARKOVIAN_COMPUTE_DTYPE
+    // (f32, f16, bf16 or int8) sets the weight storage dtype
+    let compute_dtype = match std::env::var("MARKOVIAN_COMPUTE_DTYPE") {
+        Ok(name) => DType::parse(&name)?,
+        Err(_) => DType::F32,
+    };
+    let model_config = ModelConfig {
+        compute_dtype,
+        ..ModelConfig::default()
+    };
 
     #[cfg(feature = "gpu")]
-    let model = InferenceModel::new(model_config, None)?;
+    let mut model = InferenceModel::new(model_config, None)?;
 
     #[cfg(not(feature = "gpu"))]
-    let model = InferenceModel::new(model_config, ())?;
+    let mut model = InferenceModel::new(model_config, ())?;
+
+    // MARKOVIAN_TOKENIZER: a tiktoken encoding, tokenizer.json or .gguf file
+    if let Ok(spec) = std::env::var("MARKOVIAN_TOKENIZER") {
+        model.set_tokenizer(Tokenizer::load(&spec)?)?;
+        tracing::info!("Using tokenizer {}", model.tokenizer().name());
+    }
 
     let model = Arc::new(RwLock::new(model));
 
-    // Initialize online learner
-    let learning_config = LearningConfig::default();
-    let learner = OnlineLearner::new(learning_config, model.clone());
+    // Initialize online learner, configured from the environment:
+    // MARKOVIAN_CHECKPOINT_DIR, MARKOVIAN_REPLAY_POLICY, MARKOVIAN_REGULARIZER
+    // and MARKOVIAN_REGULARIZER_STRENGTH
+    let replay = match std::env::var("MARKOVIAN_REPLAY_POLICY") {
+        Ok(name) => ReplayPolicy::parse(&name)?,
+        Err(_) => ReplayPolicy::Fifo,
+    };
+    let regularizer_strength = match std::env::var("MARKOVIAN_REGULARIZER_STRENGTH") {
+        Ok(value) => value.parse()?,
+        Err(_) => 0.01,
+    };
+    let regularizer = match std::env::var("MARKOVIAN_REGULARIZER") {
+        Ok(name) => Regularizer::parse(&name, regularizer_strength)?,
+        Err(_) => Regularizer::None,
+    };
+    let learning_config = LearningConfig {
+        checkpoint_dir: std::env::var_os("MARKOVIAN_CHECKPOINT_DIR").map(Into::into),
+        replay,
+        regularizer,
+        ..LearningConfig::default()
+    };
+    let mut learner = OnlineLearner::new(learning_config, model.clone());
+
+    // Pick up training where a previous (possibly crashed) run left off
+    match learner.resume_latest() {
+        Ok(Some(info)) => tracing::info!("Resumed from checkpoint at step {}", info.step),
+        Ok(None) => {}
+        Err(e) => tracing::warn!("Could not resume from checkpoint: {}", e),
+    }
     let learner = Arc::new(RwLock::new(learner));
 
     // Create server with stdio wiring
@@ -44,3 +162,126 @@ async fn main() -> Result<()> {
 
     Ok(())
 }
+
+fn train(args: TrainArgs) -> Result<()> {
+    let out_format = WeightFormat::from_path(&args.out)?;
+    if args.lora_rank.is_some() && out_format != WeightFormat::SafeTensors {
+        anyhow::bail!("LoRA adapters are saved as .safetensors");
+    }
+
+    // Model: default configuration, or the one stored in GGUF weights
+    let initial = match &args.weights {
+        Some(path) => {
+            let mut loader = WeightLoader::new(WeightFormat::from_path(path)?);
+            loader.load_from_file(path)?;
+            Some(loader)
+        }
+        None => None,
+    };
+    let model_config = match initial.as_ref().filter(|l| l.gguf_metadata().is_some()) {
+        Some(loader) => ModelConfig::from_gguf(loader)?,
+        None => ModelConfig::default(),
+    };
+
+    #[cfg(feature = "gpu")]
+    let mut model = InferenceModel::new(model_config, None)?;
+    #[cfg(not(feature = "gpu"))]
+    let mut model = InferenceModel::new(model_config, ())?;
+
+    let embedded = initial
+        .as_ref()
+        .and_then(WeightLoader::gguf_metadata)
+        .filter(|meta| meta.tokenizer_model().is_some());
+    match (&args.tokenizer, embedded) {
+        (Some(spec), _) => model.set_tokenizer(Tokenizer::load(spec)?)?,
+        (None, Some(meta)) => model.set_tokenizer(Tokenizer::from_gguf(meta)?)?,
+        (None, None) => {}
+    }
+    tracing::info!("Using tokenizer {}", model.tokenizer().name());
+
+    if let Some(loader) = &initial {
+        let loaded = model.load_weights(loader)?;
+        tracing::info!("Loaded {} weights", loaded);
+    }
+    if let Some(rank) = args.lora_rank {
+        let config = LoraConfig {
+            rank,
+            alpha: 2.0 * rank as f32,
+            target_modules: args.lora_targets.clone(),
+            seed: args.seed,
+        };
+        let name = args.out.file_stem().map_or("adapter".into(), |s| s.to_string_lossy().into_owned());
+        let adapter = LoraAdapter::new(&name, &config, &model.transformer().linear_weights())?;
+        tracing::info!("Training LoRA adapter {} ({} parameters)", name, adapter.num_params());
+        model.insert_adapter(adapter)?;
+        model.set_active_adapter(Some(&name))?;
+    }
+
+    // Data: load, drop what does not fit the context, shuffle and split
+    let mut dataset = Dataset::default();
+    for path in &args.data {
+        let part = Dataset::load(path)?;
+        tracing::info!("Loaded {} examples from {}", part.len(), path.display());
+        dataset.extend(part);
+    }
+    let count_tokens = |example: &TrainingExample| example_tokens(&model, example);
+    let dropped = dataset.retain_max_tokens(model.config().max_seq_len, count_tokens);
+    if dropped > 0 {
+        tracing::warn!("Skipped {} examples longer than max_seq_len {}", dropped, model.config().max_seq_len);
+    }
+    dataset.shuffle(args.seed);
+    let (train_set, validation_set) = dataset.split(args.validation_split)?;
+    if train_set.is_empty() {
+        anyhow::bail!("No training examples");
+    }
+    println!("{} training / {} validation examples", train_set.len(), validation_set.len());
+
+    let epoch_batches: Vec<_> = (0..args.epochs)
+        .map(|epoch| train_set.batches(args.max_batch_tokens, args.seed.wrapping_add(epoch as u64), count_tokens))
+        .collect();
+
+    let model = Arc::new(RwLock::new(model));
+    let learning_config = LearningConfig {
+        learning_rate: args.learning_rate,
+        optimizer: OptimizerKind::parse(&args.optimizer)?,
+        regularizer: Regularizer::parse(&args.regularizer, args.regularizer_strength)?,
+        use_gpu: false,
+        enabled: true,
+        checkpoint_dir: args.checkpoint_dir.clone(),
+        seed: args.seed,
+        ..LearningConfig::default()
+    };
+    let mut learner = OnlineLearner::new(learning_config, model.clone());
+
+    for (epoch, batches) in epoch_batches.iter().enumerate() {
+        let mut train_loss = 0.0;
+        for batch in batches {
+            train_loss += learner.train_batch(batch)?;
+        }
+        train_loss /= batches.len() as f32;
+
+        if validation_set.is_empty() {
+            println!("epoch {}: train loss {:.4}", epoch + 1, train_loss);
+        } else {
+            let validation_loss = learner.mean_loss(validation_set.examples())?;
+            println!(
+                "epoch {}: train loss {:.4}, validation loss {:.4}, perplexity {:.2}",
+                epoch + 1,
+                train_loss,
+                validation_loss,
+                validation_loss.exp()
+            );
+        }
+    }
+
+    let model = model
+        .read()
+        .map_err(|_| anyhow::anyhow!("Failed to acquire read lock on model"))?;
+    match model.active_adapter() {
+        Some(adapter) => adapter.save(&args.out)?,
+        None => model.export_weights()?.save_to_file(&args.out, out_format)?,
+    }
+    println!("Saved to {}", args.out.display());
+
+    Ok(())
+}
@@ -10,12 +10,13 @@ use crate::parallel::{
 };
 
 use anyhow::Result;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::sync::Arc;
 
 /// MCP tool parameters for parallel code generation
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ParallelCodeGenParams {
     /// List of prompts for code generation
     pub prompts: Vec<String>,
@@ -33,12 +34,12 @@ fn default_max_tokens() -> usize { 2048 }
 fn default_temperature() -> f32 { 0.7 }
 
 /// MCP tool parameters for parallel analysis
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ParallelAnalysisParams {
     /// List of texts to analyze
     pub texts: Vec<String>,
-    /// Type of analysis to perform
-    pub analysis_type: String, // "summarize", "extract", "classify", "reason"
+    /// Type of analysis to perform: summarize, extract, classify or reason
+    pub analysis_type: String,
     /// Maximum output tokens per analysis
     #[serde(default = "default_analysis_tokens")]
     pub max_output_tokens: usize,
@@ -47,24 +48,24 @@ pub struct ParallelAnalysisParams {
 fn default_analysis_tokens() -> usize { 1024 }
 
 /// MCP tool parameters for parallel data processing
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ParallelDataProcessParams {
     /// List of data arrays to process
     pub data_arrays: Vec<Vec<f32>>,
-    /// Operation to perform
-    pub operation: String, // "transform", "filter", "aggregate"
-    /// Operation parameters
+    /// Operation to perform: transform, filter or aggregate
+    pub operation: String,
+    /// Operation-specific parameters (e.g. {"factor": 2.0} for transform)
     pub params: serde_json::Value,
 }
 
 /// MCP tool parameters for multi-agent simulation
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct SimulationParams {
     /// Number of agents in simulation
     pub num_agents: usize,
     /// Number of simulation steps
     pub steps: usize,
-    /// Environment parameters
+    /// Environment parameters as key-value pairs
     #[serde(default)]
     pub environment_params: std::collections::HashMap<String, f32>,
 }
@@ -0,0 +1,158 @@
+//! Prompts of the Markovian Thinker MCP server
+//!
+//! The templates `markovian_think` sends through sampling, for clients that
+//! cannot sample and drive the chunk loop themselves:
+//!
+//! - `markovian_first_chunk`: the first chunk of a problem
+//! - `markovian_continue_chunk`: a later chunk, from the carryover of the
+//!   previous one
+//!
+//! Both embed `DELETHINK_SYSTEM_PROMPT`, since prompt messages have no system
+//! role.
+
+use anyhow::Result;
+use mcp_framework::{PromptHandler, PromptMessage, PromptRouter};
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+use super::sampling::{build_chunk_prompt, extract_carryover, DELETHINK_SYSTEM_PROMPT};
+use super::MarkovianMCPServer;
+
+/// All prompts, in the order `prompts/list` reports them
+pub(super) fn router() -> PromptRouter<MarkovianMCPServer> {
+    PromptRouter::new()
+        .with(FirstChunk)
+        .with(ContinueChunk)
+}
+
+fn chunk_message(problem: &str, carryover: &str) -> PromptMessage {
+    PromptMessage::user(format!(
+        "{}\n\n{}",
+        DELETHINK_SYSTEM_PROMPT,
+        build_chunk_prompt(problem, carryover)
+    ))
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct FirstChunkArgs {
+    /// The problem to reason about
+    pub problem: String,
+}
+
+struct FirstChunk;
+
+impl PromptHandler<MarkovianMCPServer> for FirstChunk {
+    type Args = FirstChunkArgs;
+    const NAME: &'static str = "markovian_first_chunk";
+    const DESCRIPTION: &'static str = "First chunk of Delethink reasoning: the problem and the chunking instructions. Generate at most chunk_size tokens, then continue with markovian_continue_chunk unless the output contains [SOLUTION].";
+
+    fn get(&self, _server: &MarkovianMCPServer, args: FirstChunkArgs) -> Result<Vec<PromptMessage>> {
+        Ok(vec![chunk_message(&args.problem, "")])
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ContinueChunkArgs {
+    /// The problem being reasoned about
+    pub problem: String,
+    /// The previous chunk, or its tail
+    pub carryover: String,
+    /// Keep only the last N tokens of `carryover`, counted with the server's tokenizer (default: use it as given)
+    pub carryover_tokens: Option<String>,
+}
+
+struct ContinueChunk;
+
+impl PromptHandler<MarkovianMCPServer> for ContinueChunk {
+    type Args = ContinueChunkArgs;
+    const NAME: &'static str = "markovian_continue_chunk";
+    const DESCRIPTION: &'static str = "Later chunk of Delethink reasoning: the problem plus the bounded carryover of the previous chunk. Pass carryover_tokens to have the server cut the previous chunk down to its last N tokens.";
+
+    fn get(&self, server: &MarkovianMCPServer, args: ContinueChunkArgs) -> Result<Vec<PromptMessage>> {
+        let carryover = match args.carryover_tokens {
+            Some(tokens) => {
+                let tokens: usize = tokens.trim().parse()
+                    .map_err(|_| anyhow::anyhow!("carryover_tokens must be a number, got '{}'", tokens))?;
+                let tokenizer = server.model.read()
+                    .map_err(|_| anyhow::anyhow!("Failed to acquire read lock on model"))?
+                    .tokenizer();
+                extract_carryover(&tokenizer, &args.carryover, tokens)
+            }
+            None => args.carryover.trim().to_string(),
+        };
+
+        if carryover.is_empty() {
+            anyhow::bail!("carryover is empty; use markovian_first_chunk for the first chunk");
+        }
+
+        Ok(vec![chunk_message(&args.problem, &carryover)])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inference::{InferenceModel, ModelConfig};
+    use crate::mcp::{GetPromptResult, JsonRpcRequest, McpServer, RequestContext, RequestId};
+    use crate::training::OnlineLearner;
+    use serde_json::{json, Value};
+    use std::sync::{Arc, RwLock};
+
+    fn tiny_server() -> MarkovianMCPServer {
+        let model = Arc::new(RwLock::new(InferenceModel::for_tests(ModelConfig::tiny())));
+        let learner = OnlineLearner::new(Default::default(), model.clone());
+        MarkovianMCPServer::new(model, Arc::new(RwLock::new(learner)))
+    }
+
+    async fn get(server: &MarkovianMCPServer, params: Value) -> Result<GetPromptResult, i32> {
+        let request = JsonRpcRequest::new(Some(RequestId::Number(1)), "prompts/get".to_string(), Some(params));
+        let response = server.handle_request(request, RequestContext::default()).await;
+        match response.error {
+            Some(error) => Err(error.code),
+            None => Ok(serde_json::from_value(response.result.unwrap()).unwrap()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_chunk_prompts_match_sampling() {
+        let server = tiny_server();
+
+        let request = JsonRpcRequest::new(Some(RequestId::Number(1)), "prompts/list".to_string(), None);
+        let list = server.handle_request(request, RequestContext::default()).await.result.unwrap();
+        let names: Vec<&str> = list["prompts"].as_array().unwrap()
+            .iter().map(|p| p["name"].as_str().unwrap()).collect();
+        assert_eq!(names, ["markovian_first_chunk", "markovian_continue_chunk"]);
+
+        let first = get(&server, json!({"name": "markovian_first_chunk", "arguments": {"problem": "What is 6 * 7?"}})).await.unwrap();
+        let text = first.messages[0].content.as_text().unwrap();
+        assert!(text.starts_with(DELETHINK_SYSTEM_PROMPT));
+        assert!(text.ends_with(&build_chunk_prompt("What is 6 * 7?", "")));
+
+        let next = get(&server, json!({
+            "name": "markovian_continue_chunk",
+            "arguments": {"problem": "What is 6 * 7?", "carryover": "6 * 7 = 6 * 5 + 12"}
+        })).await.unwrap();
+        assert!(next.messages[0].content.as_text().unwrap()
+            .ends_with(&build_chunk_prompt("What is 6 * 7?", "6 * 7 = 6 * 5 + 12")));
+    }
+
+    #[tokio::test]
+    async fn test_continue_chunk_trims_carryover() {
+        let server = tiny_server();
+        let tokenizer = server.model.read().unwrap().tokenizer();
+        let carryover = "first we note that the answer is forty two";
+        let expected = extract_carryover(&tokenizer, carryover, 3);
+
+        let result = get(&server, json!({
+            "name": "markovian_continue_chunk",
+            "arguments": {"problem": "p", "carryover": carryover, "carryover_tokens": "3"}
+        })).await.unwrap();
+        assert!(result.messages[0].content.as_text().unwrap().ends_with(&expected));
+
+        let bad = json!({
+            "name": "markovian_continue_chunk",
+            "arguments": {"problem": "p", "carryover": carryover, "carryover_tokens": "three"}
+        });
+        assert_eq!(get(&server, bad).await.unwrap_err(), -32602);
+    }
+}
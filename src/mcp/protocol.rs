@@ -1,421 +0,0 @@
-// MCP Protocol Types
-// Implementation of Model Context Protocol (MCP) message structures
-// See: https://spec.modelcontextprotocol.io/
-
-use serde::{Deserialize, Serialize};
-use serde_json::Value;
-
-/// JSON-RPC 2.0 Request
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct JsonRpcRequest {
-    pub jsonrpc: String, // Always "2.0"
-    pub id: Option<RequestId>,
-    pub method: String,
-    pub params: Option<Value>,
-}
-
-/// JSON-RPC 2.0 Response
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct JsonRpcResponse {
-    pub jsonrpc: String, // Always "2.0"
-    pub id: Option<RequestId>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub result: Option<Value>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub error: Option<JsonRpcError>,
-}
-
-/// JSON-RPC Error
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct JsonRpcError {
-    pub code: i32,
-    pub message: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub data: Option<Value>,
-}
-
-/// Request ID (can be string or number)
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
-#[serde(untagged)]
-pub enum RequestId {
-    String(String),
-    Number(i64),
-}
-
-// ============================================================================
-// MCP Initialization
-// ============================================================================
-
-/// Initialize request
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct InitializeParams {
-    pub protocol_version: String,
-    pub capabilities: ClientCapabilities,
-    pub client_info: Implementation,
-}
-
-/// Client capabilities
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct ClientCapabilities {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub sampling: Option<SamplingCapability>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub roots: Option<RootsCapability>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct SamplingCapability {}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct RootsCapability {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub list_changed: Option<bool>,
-}
-
-/// Initialize response
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct InitializeResult {
-    pub protocol_version: String,
-    pub capabilities: ServerCapabilities,
-    pub server_info: Implementation,
-}
-
-/// Server capabilities
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct ServerCapabilities {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub tools: Option<ToolsCapability>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub resources: Option<ResourcesCapability>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub prompts: Option<PromptsCapability>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct ToolsCapability {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub list_changed: Option<bool>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct ResourcesCapability {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub subscribe: Option<bool>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub list_changed: Option<bool>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct PromptsCapability {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub list_changed: Option<bool>,
-}
-
-/// Implementation info (client or server)
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Implementation {
-    pub name: String,
-    pub version: String,
-}
-
-// ============================================================================
-// MCP Tools
-// ============================================================================
-
-/// List tools request (no params)
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ListToolsParams {}
-
-/// List tools response
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ListToolsResult {
-    pub tools: Vec<Tool>,
-}
-
-/// Tool definition
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Tool {
-    pub name: String,
-    pub description: String,
-    pub input_schema: Value, // JSON Schema
-}
-
-/// Call tool request
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct CallToolParams {
-    pub name: String,
-    pub arguments: Value,
-}
-
-/// Call tool response
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct CallToolResult {
-    pub content: Vec<Content>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub is_error: Option<bool>,
-}
-
-// ============================================================================
-// MCP Resources
-// ============================================================================
-
-/// List resources request (no params)
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ListResourcesParams {}
-
-/// List resources response
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ListResourcesResult {
-    pub resources: Vec<Resource>,
-}
-
-/// Resource definition
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Resource {
-    pub uri: String,
-    pub name: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub description: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub mime_type: Option<String>,
-}
-
-/// Read resource request
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ReadResourceParams {
-    pub uri: String,
-}
-
-/// Read resource response
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ReadResourceResult {
-    pub contents: Vec<ResourceContents>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct ResourceContents {
-    pub uri: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub mime_type: Option<String>,
-    #[serde(flatten)]
-    pub content: Content,
-}
-
-// ============================================================================
-// MCP Sampling (key for Markovian reasoning!)
-// ============================================================================
-
-/// Sampling create message request
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct CreateMessageParams {
-    pub messages: Vec<SamplingMessage>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub model_preferences: Option<ModelPreferences>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub system_prompt: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub include_context: Option<String>, // "none", "thisServer", "allServers"
-    pub max_tokens: u32,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub temperature: Option<f32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub stop_sequences: Option<Vec<String>>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub metadata: Option<Value>,
-}
-
-/// Sampling message (user or assistant)
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct SamplingMessage {
-    pub role: MessageRole,
-    pub content: Content,
-}
-
-/// Message role
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "lowercase")]
-pub enum MessageRole {
-    User,
-    Assistant,
-}
-
-/// Model preferences (hints for client)
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct ModelPreferences {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub hints: Option<Vec<ModelHint>>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub cost_priority: Option<f32>, // 0-1
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub speed_priority: Option<f32>, // 0-1
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub intelligence_priority: Option<f32>, // 0-1
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ModelHint {
-    pub name: String,
-}
-
-/// Sampling create message response
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct CreateMessageResult {
-    pub role: MessageRole,
-    pub content: Content,
-    pub model: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub stop_reason: Option<String>,
-}
-
-// ============================================================================
-// Content Types
-// ============================================================================
-
-/// Content (can be text, image, or resource)
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(tag = "type", rename_all = "lowercase")]
-pub enum Content {
-    Text { text: String },
-    Image { data: String, mime_type: String },
-    Resource { uri: String, text: Option<String> },
-}
-
-// ============================================================================
-// Helper Functions
-// ============================================================================
-
-impl JsonRpcRequest {
-    pub fn new(id: Option<RequestId>, method: String, params: Option<Value>) -> Self {
-        Self {
-            jsonrpc: "2.0".to_string(),
-            id,
-            method,
-            params,
-        }
-    }
-
-    pub fn notification(method: String, params: Option<Value>) -> Self {
-        Self::new(None, method, params)
-    }
-}
-
-impl JsonRpcResponse {
-    pub fn success(id: Option<RequestId>, result: Value) -> Self {
-        Self {
-            jsonrpc: "2.0".to_string(),
-            id,
-            result: Some(result),
-            error: None,
-        }
-    }
-
-    pub fn error(id: Option<RequestId>, error: JsonRpcError) -> Self {
-        Self {
-            jsonrpc: "2.0".to_string(),
-            id,
-            result: None,
-            error: Some(error),
-        }
-    }
-}
-
-impl JsonRpcError {
-    pub fn parse_error() -> Self {
-        Self {
-            code: -32700,
-            message: "Parse error".to_string(),
-            data: None,
-        }
-    }
-
-    pub fn invalid_request() -> Self {
-        Self {
-            code: -32600,
-            message: "Invalid Request".to_string(),
-            data: None,
-        }
-    }
-
-    pub fn method_not_found(method: &str) -> Self {
-        Self {
-            code: -32601,
-            message: format!("Method not found: {}", method),
-            data: None,
-        }
-    }
-
-    pub fn invalid_params(details: &str) -> Self {
-        Self {
-            code: -32602,
-            message: format!("Invalid params: {}", details),
-            data: None,
-        }
-    }
-
-    pub fn internal_error(details: &str) -> Self {
-        Self {
-            code: -32603,
-            message: format!("Internal error: {}", details),
-            data: None,
-        }
-    }
-}
-
-impl Content {
-    pub fn text(text: String) -> Self {
-        Content::Text { text }
-    }
-
-    pub fn as_text(&self) -> Option<&str> {
-        match self {
-            Content::Text { text } => Some(text),
-            _ => None,
-        }
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_json_rpc_request_serialization() {
-        let req = JsonRpcRequest::new(
-            Some(RequestId::Number(1)),
-            "test/method".to_string(),
-            Some(serde_json::json!({"foo": "bar"})),
-        );
-
-        let json = serde_json::to_string(&req).unwrap();
-        assert!(json.contains("\"jsonrpc\":\"2.0\""));
-        assert!(json.contains("\"method\":\"test/method\""));
-    }
-
-    #[test]
-    fn test_content_text() {
-        let content = Content::text("Hello, world!".to_string());
-        assert_eq!(content.as_text(), Some("Hello, world!"));
-    }
-
-    #[test]
-    fn test_error_codes() {
-        assert_eq!(JsonRpcError::parse_error().code, -32700);
-        assert_eq!(JsonRpcError::invalid_request().code, -32600);
-        assert_eq!(JsonRpcError::method_not_found("foo").code, -32601);
-    }
-}
@@ -0,0 +1,408 @@
+//! Resources of the Markovian Thinker MCP server
+//!
+//! - `markovian://learning/stats`: online learning statistics
+//! - `markovian://weights/manifest`: names, shapes and dtypes of the model's tensors
+//! - `markovian://sessions/{id}/trace`: the chunk trace of a `markovian_think` call
+//!
+//! Subscribers get `notifications/resources/updated` when a tool call changes
+//! the learning stats, and after every chunk of a session.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use mcp_framework::{Resource, ResourceHandler, ResourceRouter, UriVars};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::sync::RwLock;
+
+use super::sampling::{ThinkChunk, ThinkConfig, ThinkOutcome, ThinkTermination};
+use super::training_tools::LearningStatsJson;
+use super::MarkovianMCPServer;
+
+pub const LEARNING_STATS_URI: &str = "markovian://learning/stats";
+pub const WEIGHTS_MANIFEST_URI: &str = "markovian://weights/manifest";
+
+/// Sessions kept for reading; the oldest finished ones are dropped first
+const MAX_SESSIONS: usize = 32;
+
+/// All resources, in the order `resources/list` reports them
+pub(super) fn router() -> ResourceRouter<MarkovianMCPServer> {
+    ResourceRouter::new()
+        .with(LearningStats)
+        .with(WeightsManifest)
+        .with(SessionTrace)
+}
+
+/// URI of a session's trace
+pub fn session_uri(id: &str) -> String {
+    format!("markovian://sessions/{}/trace", id)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionStatus {
+    Running,
+    Completed,
+    Failed,
+}
+
+/// One `markovian_think` call and the chunks sampled so far
+#[derive(Debug, Clone, Serialize)]
+pub struct ThinkSession {
+    pub id: String,
+    pub problem: String,
+    pub status: SessionStatus,
+    pub config: ThinkConfig,
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    pub total_tokens: usize,
+    pub chunks: Vec<ThinkChunk>,
+    pub solution: Option<String>,
+    pub termination: Option<ThinkTermination>,
+    pub error: Option<String>,
+}
+
+/// Think sessions, oldest first
+#[derive(Default)]
+pub struct SessionStore {
+    sessions: RwLock<VecDeque<ThinkSession>>,
+}
+
+impl SessionStore {
+    /// Record a new running session and return its id
+    pub fn start(&self, problem: &str, config: ThinkConfig) -> String {
+        let id = uuid::Uuid::new_v4().to_string();
+        let session = ThinkSession {
+            id: id.clone(),
+            problem: problem.to_string(),
+            status: SessionStatus::Running,
+            config,
+            started_at: chrono::Utc::now(),
+            total_tokens: 0,
+            chunks: Vec::new(),
+            solution: None,
+            termination: None,
+            error: None,
+        };
+
+        if let Ok(mut sessions) = self.sessions.write() {
+            sessions.push_back(session);
+            if sessions.len() > MAX_SESSIONS {
+                if let Some(finished) = sessions.iter().position(|s| s.status != SessionStatus::Running) {
+                    sessions.remove(finished);
+                }
+            }
+        }
+        id
+    }
+
+    /// Append a completed chunk
+    pub fn record_chunk(&self, id: &str, chunk: &ThinkChunk) {
+        self.update(id, |session| {
+            session.total_tokens += chunk.tokens;
+            session.chunks.push(chunk.clone());
+        });
+    }
+
+    /// Mark a session completed or failed
+    pub fn finish(&self, id: &str, outcome: &Result<ThinkOutcome>) {
+        self.update(id, |session| match outcome {
+            Ok(outcome) => {
+                session.status = SessionStatus::Completed;
+                session.total_tokens = outcome.total_tokens;
+                session.solution = outcome.solution.clone();
+                session.termination = Some(outcome.termination);
+            }
+            Err(e) => {
+                session.status = SessionStatus::Failed;
+                session.error = Some(e.to_string());
+            }
+        });
+    }
+
+    pub fn get(&self, id: &str) -> Option<ThinkSession> {
+        self.sessions.read().ok()?.iter().find(|s| s.id == id).cloned()
+    }
+
+    /// (id, problem) of every session, oldest first
+    pub fn list(&self) -> Vec<(String, String)> {
+        self.sessions
+            .read()
+            .map(|sessions| sessions.iter().map(|s| (s.id.clone(), s.problem.clone())).collect())
+            .unwrap_or_default()
+    }
+
+    fn update(&self, id: &str, f: impl FnOnce(&mut ThinkSession)) {
+        if let Ok(mut sessions) = self.sessions.write() {
+            if let Some(session) = sessions.iter_mut().find(|s| s.id == id) {
+                f(session);
+            }
+        }
+    }
+}
+
+/// Current learning stats as served by `markovian://learning/stats`
+pub(super) fn learning_stats(server: &MarkovianMCPServer) -> Result<Value> {
+    let learner = server.learner.read()
+        .map_err(|_| anyhow::anyhow!("Failed to acquire read lock on learner"))?;
+    Ok(serde_json::to_value(LearningStatsJson::from(learner.get_stats()))?)
+}
+
+struct LearningStats;
+
+#[async_trait]
+impl ResourceHandler<MarkovianMCPServer> for LearningStats {
+    const URI: &'static str = LEARNING_STATS_URI;
+    const NAME: &'static str = "Learning statistics";
+    const DESCRIPTION: &'static str = "Online learning statistics: examples, updates, loss, optimizer, schedule and current learning rate.";
+
+    async fn read(&self, server: &MarkovianMCPServer, _vars: &UriVars) -> Result<Value> {
+        learning_stats(server)
+    }
+}
+
+struct WeightsManifest;
+
+#[async_trait]
+impl ResourceHandler<MarkovianMCPServer> for WeightsManifest {
+    const URI: &'static str = WEIGHTS_MANIFEST_URI;
+    const NAME: &'static str = "Weights manifest";
+    const DESCRIPTION: &'static str = "Names, shapes and storage dtypes of the model's tensors under their HuggingFace names, with the configured compute dtype.";
+
+    async fn read(&self, server: &MarkovianMCPServer, _vars: &UriVars) -> Result<Value> {
+        let model = server.model.read()
+            .map_err(|_| anyhow::anyhow!("Failed to acquire read lock on model"))?;
+        let mut weights = model.describe_weights();
+        weights.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let total_params: usize = weights.iter().map(|w| w.shape.iter().product::<usize>()).sum();
+        let tensors: Vec<Value> = weights
+            .iter()
+            .map(|w| json!({ "name": w.name, "shape": w.shape, "dtype": w.dtype.name() }))
+            .collect();
+
+        Ok(json!({
+            "compute_dtype": model.config().compute_dtype.name(),
+            "total_params": total_params,
+            "active_adapter": model.active_adapter().map(|adapter| adapter.name().to_string()),
+            "tensors": tensors,
+        }))
+    }
+}
+
+struct SessionTrace;
+
+#[async_trait]
+impl ResourceHandler<MarkovianMCPServer> for SessionTrace {
+    const URI: &'static str = "markovian://sessions/{id}/trace";
+    const NAME: &'static str = "Think session trace";
+    const DESCRIPTION: &'static str = "Config, status, chunks and solution of a markovian_think call, updated after every chunk.";
+
+    fn instances(&self, server: &MarkovianMCPServer) -> Vec<Resource> {
+        server
+            .sessions
+            .list()
+            .into_iter()
+            .map(|(id, problem)| Resource {
+                uri: session_uri(&id),
+                name: format!("Think session {}", id),
+                description: Some(problem.chars().take(80).collect()),
+                mime_type: Some(Self::MIME_TYPE.to_string()),
+            })
+            .collect()
+    }
+
+    async fn read(&self, server: &MarkovianMCPServer, vars: &UriVars) -> Result<Value> {
+        let session = server
+            .sessions
+            .get(&vars["id"])
+            .ok_or_else(|| anyhow::anyhow!("Unknown session {}", vars["id"]))?;
+        Ok(serde_json::to_value(session)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inference::{DType, InferenceModel, ModelConfig};
+    use crate::mcp::{JsonRpcRequest, JsonRpcResponse, McpServer, RequestContext, RequestId, StdioHandler};
+    use crate::training::OnlineLearner;
+    use std::sync::Arc;
+
+    fn tiny_server() -> MarkovianMCPServer {
+        server_for(ModelConfig::tiny())
+    }
+
+    fn server_for(config: ModelConfig) -> MarkovianMCPServer {
+        let model = Arc::new(RwLock::new(InferenceModel::for_tests(config)));
+        let learner = OnlineLearner::new(Default::default(), model.clone());
+        MarkovianMCPServer::new(model, Arc::new(RwLock::new(learner)))
+    }
+
+    fn request(method: &str, params: Value) -> JsonRpcRequest {
+        JsonRpcRequest::new(Some(RequestId::Number(1)), method.to_string(), Some(params))
+    }
+
+    fn chunk(iteration: usize, tokens: usize) -> ThinkChunk {
+        ThinkChunk {
+            iteration,
+            prompt: "p".to_string(),
+            output: "o".to_string(),
+            tokens,
+            model: None,
+            stop_reason: None,
+        }
+    }
+
+    #[test]
+    fn test_session_lifecycle() {
+        let store = SessionStore::default();
+        let id = store.start("What is 6*7?", ThinkConfig::default());
+        store.record_chunk(&id, &chunk(1, 5));
+        store.record_chunk(&id, &chunk(2, 3));
+
+        let session = store.get(&id).unwrap();
+        assert_eq!(session.status, SessionStatus::Running);
+        assert_eq!(session.total_tokens, 8);
+
+        store.finish(&id, &Err(anyhow::anyhow!("client went away")));
+        let session = store.get(&id).unwrap();
+        assert_eq!(session.status, SessionStatus::Failed);
+        assert_eq!(session.error.as_deref(), Some("client went away"));
+        assert_eq!(session.chunks.len(), 2);
+    }
+
+    #[test]
+    fn test_finished_sessions_are_evicted_first() {
+        let store = SessionStore::default();
+        let running = store.start("still running", ThinkConfig::default());
+        let finished: Vec<String> = (0..MAX_SESSIONS)
+            .map(|i| {
+                let id = store.start(&format!("problem {}", i), ThinkConfig::default());
+                store.finish(&id, &Err(anyhow::anyhow!("done")));
+                id
+            })
+            .collect();
+
+        assert_eq!(store.list().len(), MAX_SESSIONS);
+        assert!(store.get(&running).is_some());
+        assert!(store.get(&finished[0]).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_manifest_lists_tensors() {
+        let server = tiny_server();
+        let response = server.handle_request(request("resources/list", json!({})), RequestContext::default()).await;
+        let uris: Vec<Value> = response.result.unwrap()["resources"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| r["uri"].clone())
+            .collect();
+        assert_eq!(uris, [LEARNING_STATS_URI, WEIGHTS_MANIFEST_URI]);
+
+        let manifest = read_manifest(&server).await;
+        let vocab_size = server.model.read().unwrap().config().vocab_size;
+        let embedding = manifest_tensor(&manifest, "model.embed_tokens.weight");
+        assert_eq!(embedding["shape"], json!([vocab_size, 16]));
+        assert_eq!(embedding["dtype"], "f32");
+        assert_eq!(manifest["compute_dtype"], "f32");
+
+        // Same tensors as a save would write
+        let exported = server.model.read().unwrap().export_weights().unwrap();
+        let mut names = exported.tensor_names();
+        names.sort();
+        let listed: Vec<&str> = manifest["tensors"].as_array().unwrap()
+            .iter().map(|t| t["name"].as_str().unwrap()).collect();
+        assert_eq!(listed, names);
+        assert_eq!(manifest["total_params"], exported.total_params());
+
+        // Storage dtypes are reported as held, not as exported
+        let server = server_for(ModelConfig { compute_dtype: DType::BF16, ..ModelConfig::tiny() });
+        let manifest = read_manifest(&server).await;
+        assert_eq!(manifest["compute_dtype"], "bf16");
+        assert_eq!(manifest_tensor(&manifest, "model.embed_tokens.weight")["dtype"], "bf16");
+        assert_eq!(manifest_tensor(&manifest, "model.layers.0.self_attn.q_proj.weight")["dtype"], "bf16");
+        assert_eq!(manifest_tensor(&manifest, "model.norm.weight")["dtype"], "f32");
+    }
+
+    async fn read_manifest(server: &MarkovianMCPServer) -> Value {
+        let response = server.handle_request(request("resources/read", json!({"uri": WEIGHTS_MANIFEST_URI})), RequestContext::default()).await;
+        let text = response.result.unwrap()["contents"][0]["text"].as_str().unwrap().to_string();
+        serde_json::from_str(&text).unwrap()
+    }
+
+    fn manifest_tensor<'a>(manifest: &'a Value, name: &str) -> &'a Value {
+        manifest["tensors"].as_array().unwrap().iter().find(|t| t["name"] == name).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_stats_subscribers_are_notified_of_changes() {
+        let mut server = tiny_server();
+        let ((session, _), (client, _)) = StdioHandler::in_memory_pair();
+        server.peer = Some(Arc::new(session));
+
+        let response = server.handle_request(request("resources/subscribe", json!({"uri": LEARNING_STATS_URI})), RequestContext::default()).await;
+        assert!(response.error.is_none());
+
+        // Reading the stats changes nothing; setting the learning rate does
+        let call = |name: &str, arguments: Value| request("tools/call", json!({"name": name, "arguments": arguments}));
+        server.handle_request(call("get_learning_stats", json!({})), RequestContext::default()).await;
+        server.handle_request(call("set_learning_rate", json!({"learning_rate": 0.5})), RequestContext::default()).await;
+
+        let notification = client.recv_notification().await.unwrap();
+        assert_eq!(notification.method, "notifications/resources/updated");
+        assert_eq!(notification.params.unwrap()["uri"], LEARNING_STATS_URI);
+        assert!(tokio::time::timeout(std::time::Duration::from_millis(50), client.recv_notification())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_trace_is_readable_while_the_loop_runs() {
+        let mut server = tiny_server();
+        let ((session, _), (client, _)) = StdioHandler::in_memory_pair();
+        let (session, client) = (Arc::new(session), Arc::new(client));
+        server.peer = Some(session.clone());
+        let running = tokio::spawn(mcp_framework::serve(Arc::new(server), session));
+
+        let initialize = json!({
+            "protocolVersion": "2024-11-05",
+            "capabilities": {"sampling": {}},
+            "clientInfo": {"name": "test", "version": "0"},
+        });
+        client.send_request("initialize", Some(initialize)).await.unwrap();
+        let think = {
+            let client = client.clone();
+            let arguments = json!({"problem": "What is 6*7?", "max_iterations": 3, "chunk_size": 64});
+            tokio::spawn(async move {
+                client.send_request("tools/call", Some(json!({"name": "markovian_think", "arguments": arguments}))).await
+            })
+        };
+        let reply = |request: JsonRpcRequest, text: &str| {
+            let result = json!({"role": "assistant", "content": {"type": "text", "text": text}, "model": "test"});
+            client.send_response(JsonRpcResponse::success(request.id, result)).unwrap();
+        };
+
+        // First chunk answered; the second is outstanding
+        reply(client.recv_request().await.unwrap(), "The partial result is 6.");
+        let second = client.recv_request().await.unwrap();
+
+        let list = client.send_request("resources/list", None).await.unwrap();
+        let trace_uri = list["resources"].as_array().unwrap().iter()
+            .filter_map(|r| r["uri"].as_str())
+            .find(|uri| uri.starts_with("markovian://sessions/"))
+            .unwrap()
+            .to_string();
+        let read = client.send_request("resources/read", Some(json!({"uri": trace_uri}))).await.unwrap();
+        let trace: Value = serde_json::from_str(read["contents"][0]["text"].as_str().unwrap()).unwrap();
+        assert_eq!(trace["status"], "running");
+        assert_eq!(trace["chunks"].as_array().unwrap().len(), 1);
+
+        reply(second, "Multiplying by 7 gives [SOLUTION] 42");
+        let result = think.await.unwrap().unwrap();
+        let outcome: Value = serde_json::from_str(result["content"][0]["text"].as_str().unwrap()).unwrap();
+        assert_eq!(outcome["solution"], "42");
+
+        running.abort();
+    }
+}
@@ -0,0 +1,411 @@
+//! Delethink chunk loop driven by MCP sampling
+//!
+//! The server does not call an LLM itself. Each reasoning chunk is requested
+//! from the client via `sampling/createMessage`, and only the query plus a
+//! bounded carryover (the last `carryover_size` tokens of the previous chunk)
+//! is sent with the next request, keeping per-chunk context constant.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+
+use crate::inference::Tokenizer;
+
+/// System prompt sent with every sampling request
+pub const DELETHINK_SYSTEM_PROMPT: &str = "You are reasoning in fixed-size chunks. \
+After each chunk your context is reset: you will only see the original problem and \
+the last part of your previous reasoning. Keep the important intermediate results near \
+the end of each chunk. When you have the final answer, write it on its own line as \
+`[SOLUTION] <answer>`.";
+
+/// Chunk loop configuration (C, m and iteration limits from the Delethink paper)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThinkConfig {
+    /// Maximum tokens generated per chunk (C)
+    pub chunk_size: usize,
+    /// Tokens carried over into the next chunk (m)
+    pub carryover_size: usize,
+    /// Maximum number of chunks
+    pub max_iterations: usize,
+    /// Total generated-token budget across all chunks
+    pub token_budget: usize,
+}
+
+impl ThinkConfig {
+    /// Create a validated config with the default budget C + (I-1)*(C-m)
+    pub fn new(chunk_size: usize, carryover_size: usize, max_iterations: usize) -> Result<Self> {
+        if carryover_size >= chunk_size {
+            anyhow::bail!(
+                "Carryover size ({}) must be < chunk size ({})",
+                carryover_size,
+                chunk_size
+            );
+        }
+        if max_iterations == 0 {
+            anyhow::bail!("Max iterations must be > 0");
+        }
+
+        let token_budget = chunk_size + (max_iterations - 1) * (chunk_size - carryover_size);
+
+        Ok(Self {
+            chunk_size,
+            carryover_size,
+            max_iterations,
+            token_budget,
+        })
+    }
+
+    /// Override the total token budget
+    pub fn with_token_budget(mut self, token_budget: usize) -> Self {
+        self.token_budget = token_budget;
+        self
+    }
+}
+
+impl Default for ThinkConfig {
+    fn default() -> Self {
+        Self::new(2048, 1024, 5).expect("default think config is valid")
+    }
+}
+
+/// Why the chunk loop stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThinkTermination {
+    SolutionFound,
+    MaxIterations,
+    TokenBudgetExceeded,
+}
+
+/// One sampled chunk in the reasoning trace
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThinkChunk {
+    /// 1-indexed chunk number
+    pub iteration: usize,
+    /// Prompt sent for this chunk (query ⊕ carryover)
+    pub prompt: String,
+    /// Text returned by the client
+    pub output: String,
+    /// Tokens in `output`
+    pub tokens: usize,
+    /// Model reported by the client
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Client stop reason (e.g. "endTurn", "maxTokens")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_reason: Option<String>,
+}
+
+/// Result of a complete chunk loop
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThinkOutcome {
+    pub solution: Option<String>,
+    pub termination: ThinkTermination,
+    pub total_tokens: usize,
+    pub chunks: Vec<ThinkChunk>,
+}
+
+/// A single chunk request handed to the sampler
+#[derive(Debug, Clone)]
+pub struct ChunkRequest {
+    pub iteration: usize,
+    pub prompt: String,
+    pub max_tokens: usize,
+}
+
+/// A sampler's answer to a chunk request
+#[derive(Debug, Clone)]
+pub struct ChunkResponse {
+    pub text: String,
+    pub model: Option<String>,
+    pub stop_reason: Option<String>,
+}
+
+impl ChunkResponse {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            model: None,
+            stop_reason: None,
+        }
+    }
+}
+
+/// Run Delethink chunked reasoning on `problem`.
+///
+/// `sample` is called once per chunk; token counts are measured with `tokenizer`
+/// so budgets hold regardless of what the client reports.
+pub async fn run_chunk_loop<F, Fut>(
+    problem: &str,
+    config: &ThinkConfig,
+    tokenizer: &Tokenizer,
+    sample: F,
+) -> Result<ThinkOutcome>
+where
+    F: FnMut(ChunkRequest) -> Fut,
+    Fut: Future<Output = Result<ChunkResponse>>,
+{
+    run_chunk_loop_observed(problem, config, tokenizer, sample, |_| {}).await
+}
+
+/// `run_chunk_loop`, calling `on_chunk` with each chunk as it completes
+pub async fn run_chunk_loop_observed<F, Fut>(
+    problem: &str,
+    config: &ThinkConfig,
+    tokenizer: &Tokenizer,
+    mut sample: F,
+    mut on_chunk: impl FnMut(&ThinkChunk),
+) -> Result<ThinkOutcome>
+where
+    F: FnMut(ChunkRequest) -> Fut,
+    Fut: Future<Output = Result<ChunkResponse>>,
+{
+    let mut chunks = Vec::new();
+    let mut carryover = String::new();
+    let mut total_tokens = 0;
+
+    for iteration in 1..=config.max_iterations {
+        let remaining = config.token_budget.saturating_sub(total_tokens);
+        if remaining == 0 {
+            return Ok(ThinkOutcome {
+                solution: last_line(&chunks),
+                termination: ThinkTermination::TokenBudgetExceeded,
+                total_tokens,
+                chunks,
+            });
+        }
+
+        let prompt = build_chunk_prompt(problem, &carryover);
+        let request = ChunkRequest {
+            iteration,
+            prompt: prompt.clone(),
+            max_tokens: config.chunk_size.min(remaining),
+        };
+
+        tracing::debug!(
+            "Chunk {}/{} | max_tokens: {} | carryover: {} chars",
+            iteration,
+            config.max_iterations,
+            request.max_tokens,
+            carryover.len()
+        );
+
+        let response = sample(request).await?;
+        let tokens = tokenizer.count_tokens(&response.text);
+        total_tokens += tokens;
+
+        let solution = extract_solution(&response.text);
+        carryover = extract_carryover(tokenizer, &response.text, config.carryover_size);
+
+        chunks.push(ThinkChunk {
+            iteration,
+            prompt,
+            output: response.text,
+            tokens,
+            model: response.model,
+            stop_reason: response.stop_reason,
+        });
+        on_chunk(chunks.last().expect("chunk was just pushed"));
+
+        if solution.is_some() {
+            return Ok(ThinkOutcome {
+                solution,
+                termination: ThinkTermination::SolutionFound,
+                total_tokens,
+                chunks,
+            });
+        }
+
+        if total_tokens >= config.token_budget {
+            return Ok(ThinkOutcome {
+                solution: last_line(&chunks),
+                termination: ThinkTermination::TokenBudgetExceeded,
+                total_tokens,
+                chunks,
+            });
+        }
+    }
+
+    Ok(ThinkOutcome {
+        solution: last_line(&chunks),
+        termination: ThinkTermination::MaxIterations,
+        total_tokens,
+        chunks,
+    })
+}
+
+/// Build the prompt for a chunk: query ⊕ carryover
+pub fn build_chunk_prompt(problem: &str, carryover: &str) -> String {
+    if carryover.is_empty() {
+        problem.to_string()
+    } else {
+        format!(
+            "{}\n\nContinue your reasoning from where it left off:\n\n{}",
+            problem, carryover
+        )
+    }
+}
+
+/// Keep the last `carryover_tokens` tokens of a chunk
+pub fn extract_carryover(tokenizer: &Tokenizer, text: &str, carryover_tokens: usize) -> String {
+    let tokens = tokenizer.encode(text);
+    if tokens.len() <= carryover_tokens {
+        return text.trim().to_string();
+    }
+
+    // A cut can land inside a multi-byte character; step forward until it decodes
+    let mut start = tokens.len() - carryover_tokens;
+    while start < tokens.len() {
+        if let Ok(tail) = tokenizer.decode(&tokens[start..]) {
+            return tail.trim().to_string();
+        }
+        start += 1;
+    }
+
+    String::new()
+}
+
+/// Find an explicit final answer in a chunk
+pub fn extract_solution(text: &str) -> Option<String> {
+    for marker in ["[SOLUTION]", "[DONE]", "[EOS]"] {
+        if let Some(pos) = text.rfind(marker) {
+            let answer = text[pos + marker.len()..].trim();
+            if let Some(line) = answer.lines().next().filter(|l| !l.trim().is_empty()) {
+                return Some(line.trim().to_string());
+            }
+            return text[..pos].lines().rev()
+                .find(|l| !l.trim().is_empty())
+                .map(|l| l.trim().to_string());
+        }
+    }
+
+    if let Some(start) = text.rfind("\\boxed{") {
+        let after = &text[start + 7..];
+        let mut depth = 1;
+        for (i, c) in after.char_indices() {
+            match c {
+                '{' => depth += 1,
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(after[..i].trim().to_string());
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    if let Some(start) = text.rfind("#### ") {
+        return text[start + 5..].lines().next().map(|l| l.trim().to_string());
+    }
+
+    None
+}
+
+/// Best-effort answer when no marker was produced: last non-empty line
+fn last_line(chunks: &[ThinkChunk]) -> Option<String> {
+    chunks.last().and_then(|c| {
+        c.output.lines().rev()
+            .find(|l| !l.trim().is_empty())
+            .map(|l| l.trim().to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    #[test]
+    fn test_think_config_budget() {
+        let config = ThinkConfig::new(100, 50, 3).unwrap();
+        assert_eq!(config.token_budget, 200);
+        assert!(ThinkConfig::new(100, 100, 3).is_err());
+        assert!(ThinkConfig::new(100, 50, 0).is_err());
+    }
+
+    #[test]
+    fn test_extract_solution() {
+        assert_eq!(extract_solution("so [SOLUTION] 42"), Some("42".to_string()));
+        assert_eq!(extract_solution("x = \\boxed{a_{1}}"), Some("a_{1}".to_string()));
+        assert_eq!(extract_solution("#### 7\n"), Some("7".to_string()));
+        assert_eq!(extract_solution("still thinking"), None);
+    }
+
+    #[test]
+    fn test_carryover_is_bounded() {
+        let tokenizer = Tokenizer::new().unwrap();
+        let text = "one two three four five six seven eight nine ten";
+        let carryover = extract_carryover(&tokenizer, text, 3);
+        assert!(tokenizer.count_tokens(&carryover) <= 3);
+        assert!(text.ends_with(&carryover));
+    }
+
+    #[tokio::test]
+    async fn test_loop_carries_state_and_stops_on_solution() {
+        let tokenizer = Tokenizer::new().unwrap();
+        let config = ThinkConfig::new(64, 8, 5).unwrap();
+        let mut replies = VecDeque::from(vec![
+            "First we note that the partial result is 6.",
+            "Multiplying by 7 gives [SOLUTION] 42",
+        ]);
+        let mut prompts = Vec::new();
+
+        let outcome = run_chunk_loop("What is 6*7?", &config, &tokenizer, |req| {
+            prompts.push(req.prompt.clone());
+            let reply = replies.pop_front().unwrap();
+            async move { Ok(ChunkResponse::text(reply)) }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(outcome.termination, ThinkTermination::SolutionFound);
+        assert_eq!(outcome.solution, Some("42".to_string()));
+        assert_eq!(outcome.chunks.len(), 2);
+        assert_eq!(prompts[0], "What is 6*7?");
+        assert!(prompts[1].starts_with("What is 6*7?"));
+        assert!(prompts[1].contains("result is 6."));
+    }
+
+    #[tokio::test]
+    async fn test_loop_stops_at_max_iterations() {
+        let tokenizer = Tokenizer::new().unwrap();
+        let config = ThinkConfig::new(64, 8, 3).unwrap();
+        let mut observed = Vec::new();
+
+        let outcome = run_chunk_loop_observed(
+            "Problem",
+            &config,
+            &tokenizer,
+            |_| async { Ok(ChunkResponse::text("more work")) },
+            |chunk| observed.push(chunk.iteration),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(outcome.termination, ThinkTermination::MaxIterations);
+        assert_eq!(outcome.chunks.len(), 3);
+        assert_eq!(observed, vec![1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_loop_stops_on_token_budget() {
+        let tokenizer = Tokenizer::new().unwrap();
+        let config = ThinkConfig::new(64, 8, 10).unwrap().with_token_budget(6);
+        let mut max_tokens = Vec::new();
+
+        let outcome = run_chunk_loop("Problem", &config, &tokenizer, |req| {
+            max_tokens.push(req.max_tokens);
+            async { Ok(ChunkResponse::text("one two three four")) }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(outcome.termination, ThinkTermination::TokenBudgetExceeded);
+        assert_eq!(outcome.chunks.len(), 2);
+        assert_eq!(outcome.total_tokens, 8);
+        // The second chunk is capped by what is left of the budget
+        assert_eq!(max_tokens, vec![6, 2]);
+    }
+}
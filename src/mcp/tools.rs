@@ -0,0 +1,535 @@
+//! Tools of the Markovian Thinker MCP server
+//!
+//! Each tool is a `ToolHandler` over the server; its params struct doubles as
+//! the input schema. Adding a tool means writing the handler and registering
+//! it in `router`.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use mcp_framework::{NoParams, RequestContext, ToolHandler, ToolRouter};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+#[cfg(feature = "gpu")]
+use super::parallel_tools::{self, ParallelAnalysisParams, ParallelCodeGenParams, ParallelDataProcessParams, SimulationParams};
+use super::training_tools::{self, *};
+use super::*;
+
+/// All tools, in the order `tools/list` reports them
+pub(super) fn router() -> ToolRouter<MarkovianMCPServer> {
+    let mut router = ToolRouter::new();
+    router.register(MarkovianThink);
+
+    #[cfg(feature = "gpu")]
+    router
+        .register(ParallelCodegen)
+        .register(ParallelAnalysis)
+        .register(ParallelDataProcess)
+        .register(MultiAgentSimulation)
+        .register(ExecutorStats);
+
+    router
+        .register(LoadWeights)
+        .register(SaveWeights)
+        .register(EnableLearning)
+        .register(DisableLearning)
+        .register(AddTrainingExample)
+        .register(GetLearningStats)
+        .register(SetLearningRate)
+        .register(ForceUpdate)
+        .register(ListCheckpoints)
+        .register(SaveCheckpoint)
+        .register(RestoreCheckpoint)
+        .register(CreateAdapter)
+        .register(LoadAdapter)
+        .register(SaveAdapter)
+        .register(ActivateAdapter)
+        .register(UnloadAdapter)
+        .register(MergeAdapter)
+        .register(EvaluateModel);
+    router
+}
+
+// Core reasoning tool
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ThinkParams {
+    /// The problem or question to analyze using Markovian reasoning
+    pub problem: String,
+    /// Maximum number of reasoning chunks
+    #[serde(default = "default_max_iterations")]
+    pub max_iterations: usize,
+    /// Maximum tokens generated per chunk
+    #[serde(default = "default_chunk_size")]
+    pub chunk_size: usize,
+    /// Tokens carried over between chunks (default: chunk_size / 2)
+    #[serde(default)]
+    pub carryover_size: Option<usize>,
+    /// Total token budget across chunks (default: chunk_size + (max_iterations - 1) * (chunk_size - carryover_size))
+    #[serde(default)]
+    pub token_budget: Option<usize>,
+    /// Sampling temperature requested from the client
+    #[serde(default)]
+    pub temperature: Option<f32>,
+}
+
+fn default_max_iterations() -> usize { 5 }
+fn default_chunk_size() -> usize { 2048 }
+
+struct MarkovianThink;
+
+#[async_trait]
+impl ToolHandler<MarkovianMCPServer> for MarkovianThink {
+    type Params = ThinkParams;
+    const NAME: &'static str = "markovian_think";
+    const DESCRIPTION: &'static str = "Perform chunk-based Markovian reasoning on a complex problem. Uses fixed-size reasoning chunks with bounded carryover for linear complexity scaling. Each chunk is generated by the client via MCP sampling.";
+
+    async fn call(&self, server: &MarkovianMCPServer, params: ThinkParams, ctx: &RequestContext) -> Result<Value> {
+        use super::resources::session_uri;
+        use super::sampling::{run_chunk_loop_observed, ChunkRequest, ChunkResponse, ThinkConfig, DELETHINK_SYSTEM_PROMPT};
+
+        let sampling_supported = server.client_capabilities.read()
+            .map(|caps| caps.as_ref().is_some_and(|c| c.sampling.is_some()))
+            .unwrap_or(false);
+        if !sampling_supported {
+            anyhow::bail!(
+                "markovian_think requires a client that supports MCP sampling (sampling/createMessage)"
+            );
+        }
+        let peer = server.peer.clone()
+            .ok_or_else(|| anyhow::anyhow!("No client connection available for sampling"))?;
+
+        let mut config = ThinkConfig::new(
+            params.chunk_size,
+            params.carryover_size.unwrap_or(params.chunk_size / 2),
+            params.max_iterations,
+        )?;
+        if let Some(budget) = params.token_budget {
+            config = config.with_token_budget(budget);
+        }
+
+        let tokenizer = server.model.read()
+            .map_err(|_| anyhow::anyhow!("Failed to acquire read lock on model"))?
+            .tokenizer();
+
+        // The session's trace is readable (and subscribable) while the loop runs
+        let session_id = server.sessions.start(&params.problem, config.clone());
+        let trace_uri = session_uri(&session_id);
+        server.resource_list_changed();
+
+        let temperature = params.temperature;
+        let sample = |chunk: ChunkRequest| {
+            let peer = peer.clone();
+            let cancel = ctx.cancel.clone();
+            async move {
+                let request = CreateMessageParams {
+                    messages: vec![SamplingMessage {
+                        role: MessageRole::User,
+                        content: Content::text(chunk.prompt),
+                    }],
+                    model_preferences: None,
+                    system_prompt: Some(DELETHINK_SYSTEM_PROMPT.to_string()),
+                    include_context: Some("none".to_string()),
+                    max_tokens: chunk.max_tokens as u32,
+                    temperature,
+                    stop_sequences: None,
+                    metadata: Some(json!({ "markovian_iteration": chunk.iteration })),
+                };
+
+                // Dropping the sampling request cancels it on the client too
+                let result = tokio::select! {
+                    result = peer.send_request("sampling/createMessage", Some(serde_json::to_value(request)?)) => result?,
+                    _ = cancel.cancelled() => anyhow::bail!("Cancelled before chunk {}", chunk.iteration),
+                };
+                let message: CreateMessageResult = serde_json::from_value(result)?;
+                let text = message.content.as_text()
+                    .ok_or_else(|| anyhow::anyhow!("Sampling returned non-text content"))?
+                    .to_string();
+
+                Ok(ChunkResponse {
+                    text,
+                    model: Some(message.model),
+                    stop_reason: message.stop_reason,
+                })
+            }
+        };
+        let outcome = run_chunk_loop_observed(&params.problem, &config, &tokenizer, sample, |chunk| {
+            server.sessions.record_chunk(&session_id, chunk);
+            server.resource_updated(&trace_uri);
+            ctx.progress.report(
+                chunk.iteration,
+                Some(config.max_iterations),
+                format!("Chunk {}/{} ({} tokens)", chunk.iteration, config.max_iterations, chunk.tokens),
+            );
+        })
+        .await;
+
+        server.sessions.finish(&session_id, &outcome);
+        server.resource_updated(&trace_uri);
+        let outcome = outcome?;
+
+        Ok(json!({
+            "status": "success",
+            "session_id": session_id,
+            "trace_uri": trace_uri,
+            "solution": outcome.solution,
+            "termination_reason": outcome.termination,
+            "iterations": outcome.chunks.len(),
+            "total_tokens": outcome.total_tokens,
+            "config": config,
+            "chunks": outcome.chunks,
+        }))
+    }
+}
+
+// GPU parallel execution tools
+
+#[cfg(feature = "gpu")]
+fn executor(server: &MarkovianMCPServer) -> Result<&Arc<ParallelExecutor>> {
+    server.executor.as_ref().ok_or_else(|| anyhow::anyhow!("GPU executor not initialized"))
+}
+
+#[cfg(feature = "gpu")]
+struct ParallelCodegen;
+
+#[cfg(feature = "gpu")]
+#[async_trait]
+impl ToolHandler<MarkovianMCPServer> for ParallelCodegen {
+    type Params = ParallelCodeGenParams;
+    const NAME: &'static str = "parallel_codegen";
+    const DESCRIPTION: &'static str = "Execute multiple code generation tasks in parallel on GPU. Processes multiple prompts simultaneously for high throughput.";
+
+    async fn call(&self, server: &MarkovianMCPServer, params: ParallelCodeGenParams, _ctx: &RequestContext) -> Result<Value> {
+        parallel_tools::handle_parallel_codegen(executor(server)?, params).await
+    }
+}
+
+#[cfg(feature = "gpu")]
+struct ParallelAnalysis;
+
+#[cfg(feature = "gpu")]
+#[async_trait]
+impl ToolHandler<MarkovianMCPServer> for ParallelAnalysis {
+    type Params = ParallelAnalysisParams;
+    const NAME: &'static str = "parallel_analysis";
+    const DESCRIPTION: &'static str = "Execute multiple analysis tasks in parallel on GPU. Analyze multiple documents, code snippets, or problems simultaneously.";
+
+    async fn call(&self, server: &MarkovianMCPServer, params: ParallelAnalysisParams, _ctx: &RequestContext) -> Result<Value> {
+        parallel_tools::handle_parallel_analysis(executor(server)?, params).await
+    }
+}
+
+#[cfg(feature = "gpu")]
+struct ParallelDataProcess;
+
+#[cfg(feature = "gpu")]
+#[async_trait]
+impl ToolHandler<MarkovianMCPServer> for ParallelDataProcess {
+    type Params = ParallelDataProcessParams;
+    const NAME: &'static str = "parallel_data_process";
+    const DESCRIPTION: &'static str = "Execute parallel data processing operations on GPU. Transform, filter, or aggregate data arrays with GPU acceleration.";
+
+    async fn call(&self, server: &MarkovianMCPServer, params: ParallelDataProcessParams, _ctx: &RequestContext) -> Result<Value> {
+        parallel_tools::handle_parallel_data_process(executor(server)?, params).await
+    }
+}
+
+#[cfg(feature = "gpu")]
+struct MultiAgentSimulation;
+
+#[cfg(feature = "gpu")]
+#[async_trait]
+impl ToolHandler<MarkovianMCPServer> for MultiAgentSimulation {
+    type Params = SimulationParams;
+    const NAME: &'static str = "multi_agent_simulation";
+    const DESCRIPTION: &'static str = "Run multi-agent simulation with GPU acceleration. Simulate multiple AI agents working on different parts of a larger problem in parallel.";
+
+    async fn call(&self, server: &MarkovianMCPServer, params: SimulationParams, _ctx: &RequestContext) -> Result<Value> {
+        parallel_tools::handle_simulation(executor(server)?, params).await
+    }
+}
+
+#[cfg(feature = "gpu")]
+struct ExecutorStats;
+
+#[cfg(feature = "gpu")]
+#[async_trait]
+impl ToolHandler<MarkovianMCPServer> for ExecutorStats {
+    type Params = NoParams;
+    const NAME: &'static str = "executor_stats";
+    const DESCRIPTION: &'static str = "Get statistics about the parallel executor including GPU status, queue sizes, and worker information.";
+
+    async fn call(&self, server: &MarkovianMCPServer, _params: NoParams, _ctx: &RequestContext) -> Result<Value> {
+        parallel_tools::handle_executor_stats(executor(server)?).await
+    }
+}
+
+// Weight management tools
+
+struct LoadWeights;
+
+#[async_trait]
+impl ToolHandler<MarkovianMCPServer> for LoadWeights {
+    type Params = LoadWeightsParams;
+    const NAME: &'static str = "load_weights";
+    const DESCRIPTION: &'static str = "Load model weights from a file (supports SafeTensors, GGUF, binary, custom formats).";
+
+    async fn call(&self, server: &MarkovianMCPServer, params: LoadWeightsParams, ctx: &RequestContext) -> Result<Value> {
+        // Blocking, so cancellation notifications are still read meanwhile
+        let (model, ctx) = (server.model.clone(), ctx.clone());
+        tokio::task::spawn_blocking(move || training_tools::handle_load_weights(params, model, &ctx)).await?
+    }
+}
+
+struct SaveWeights;
+
+#[async_trait]
+impl ToolHandler<MarkovianMCPServer> for SaveWeights {
+    type Params = SaveWeightsParams;
+    const NAME: &'static str = "save_weights";
+    const DESCRIPTION: &'static str = "Save current model weights to a file (SafeTensors and GGUF files can be loaded by HuggingFace and llama.cpp).";
+
+    async fn call(&self, server: &MarkovianMCPServer, params: SaveWeightsParams, _ctx: &RequestContext) -> Result<Value> {
+        training_tools::handle_save_weights(params, server.model.clone(), server.learner.clone())
+    }
+}
+
+// Training tools
+
+struct EnableLearning;
+
+#[async_trait]
+impl ToolHandler<MarkovianMCPServer> for EnableLearning {
+    type Params = NoParams;
+    const NAME: &'static str = "enable_learning";
+    const DESCRIPTION: &'static str = "Enable online learning - the model will continuously learn from examples during inference.";
+
+    async fn call(&self, server: &MarkovianMCPServer, _params: NoParams, _ctx: &RequestContext) -> Result<Value> {
+        training_tools::handle_enable_learning(server.learner.clone())
+    }
+}
+
+struct DisableLearning;
+
+#[async_trait]
+impl ToolHandler<MarkovianMCPServer> for DisableLearning {
+    type Params = NoParams;
+    const NAME: &'static str = "disable_learning";
+    const DESCRIPTION: &'static str = "Disable online learning.";
+
+    async fn call(&self, server: &MarkovianMCPServer, _params: NoParams, _ctx: &RequestContext) -> Result<Value> {
+        training_tools::handle_disable_learning(server.learner.clone())
+    }
+}
+
+struct AddTrainingExample;
+
+#[async_trait]
+impl ToolHandler<MarkovianMCPServer> for AddTrainingExample {
+    type Params = AddTrainingExampleParams;
+    const NAME: &'static str = "add_training_example";
+    const DESCRIPTION: &'static str = "Add a training example for online learning. The model will learn from this example.";
+
+    async fn call(&self, server: &MarkovianMCPServer, params: AddTrainingExampleParams, _ctx: &RequestContext) -> Result<Value> {
+        training_tools::handle_add_training_example(params, server.learner.clone())
+    }
+}
+
+struct GetLearningStats;
+
+#[async_trait]
+impl ToolHandler<MarkovianMCPServer> for GetLearningStats {
+    type Params = NoParams;
+    const NAME: &'static str = "get_learning_stats";
+    const DESCRIPTION: &'static str = "Get statistics about online learning (examples, updates, loss, optimizer, learning-rate schedule and current rate).";
+
+    async fn call(&self, server: &MarkovianMCPServer, _params: NoParams, _ctx: &RequestContext) -> Result<Value> {
+        training_tools::handle_get_learning_stats(server.learner.clone())
+    }
+}
+
+struct SetLearningRate;
+
+#[async_trait]
+impl ToolHandler<MarkovianMCPServer> for SetLearningRate {
+    type Params = SetLearningRateParams;
+    const NAME: &'static str = "set_learning_rate";
+    const DESCRIPTION: &'static str = "Set the base learning rate for online learning (the configured schedule still applies).";
+
+    async fn call(&self, server: &MarkovianMCPServer, params: SetLearningRateParams, _ctx: &RequestContext) -> Result<Value> {
+        training_tools::handle_set_learning_rate(params, server.learner.clone())
+    }
+}
+
+struct ForceUpdate;
+
+#[async_trait]
+impl ToolHandler<MarkovianMCPServer> for ForceUpdate {
+    type Params = NoParams;
+    const NAME: &'static str = "force_update";
+    const DESCRIPTION: &'static str = "Force an immediate weight update using buffered training examples.";
+
+    async fn call(&self, server: &MarkovianMCPServer, _params: NoParams, ctx: &RequestContext) -> Result<Value> {
+        // Blocking, so cancellation notifications are still read meanwhile
+        let (learner, ctx) = (server.learner.clone(), ctx.clone());
+        tokio::task::spawn_blocking(move || training_tools::handle_force_update(learner, &ctx)).await?
+    }
+}
+
+struct ListCheckpoints;
+
+#[async_trait]
+impl ToolHandler<MarkovianMCPServer> for ListCheckpoints {
+    type Params = ListCheckpointsParams;
+    const NAME: &'static str = "list_checkpoints";
+    const DESCRIPTION: &'static str = "List saved training checkpoints (step, time, optimizer, buffer size, loss).";
+
+    async fn call(&self, server: &MarkovianMCPServer, params: ListCheckpointsParams, _ctx: &RequestContext) -> Result<Value> {
+        training_tools::handle_list_checkpoints(params, server.learner.clone())
+    }
+}
+
+struct SaveCheckpoint;
+
+#[async_trait]
+impl ToolHandler<MarkovianMCPServer> for SaveCheckpoint {
+    type Params = NoParams;
+    const NAME: &'static str = "save_checkpoint";
+    const DESCRIPTION: &'static str = "Save a resumable checkpoint: weights, optimizer state, schedule, buffer and stats.";
+
+    async fn call(&self, server: &MarkovianMCPServer, _params: NoParams, _ctx: &RequestContext) -> Result<Value> {
+        training_tools::handle_save_checkpoint(server.learner.clone())
+    }
+}
+
+struct RestoreCheckpoint;
+
+#[async_trait]
+impl ToolHandler<MarkovianMCPServer> for RestoreCheckpoint {
+    type Params = RestoreCheckpointParams;
+    const NAME: &'static str = "restore_checkpoint";
+    const DESCRIPTION: &'static str = "Restore a training checkpoint and continue from it. Defaults to the latest checkpoint.";
+
+    async fn call(&self, server: &MarkovianMCPServer, params: RestoreCheckpointParams, _ctx: &RequestContext) -> Result<Value> {
+        training_tools::handle_restore_checkpoint(params, server.learner.clone())
+    }
+}
+
+// LoRA adapter tools
+
+struct CreateAdapter;
+
+#[async_trait]
+impl ToolHandler<MarkovianMCPServer> for CreateAdapter {
+    type Params = CreateAdapterParams;
+    const NAME: &'static str = "create_adapter";
+    const DESCRIPTION: &'static str = "Create a LoRA adapter. While active, online learning trains only the adapter and the base weights stay frozen.";
+
+    async fn call(&self, server: &MarkovianMCPServer, params: CreateAdapterParams, _ctx: &RequestContext) -> Result<Value> {
+        training_tools::handle_create_adapter(params, server.model.clone())
+    }
+}
+
+struct LoadAdapter;
+
+#[async_trait]
+impl ToolHandler<MarkovianMCPServer> for LoadAdapter {
+    type Params = LoadAdapterParams;
+    const NAME: &'static str = "load_adapter";
+    const DESCRIPTION: &'static str = "Load a LoRA adapter from a safetensors file.";
+
+    async fn call(&self, server: &MarkovianMCPServer, params: LoadAdapterParams, _ctx: &RequestContext) -> Result<Value> {
+        training_tools::handle_load_adapter(params, server.model.clone())
+    }
+}
+
+struct SaveAdapter;
+
+#[async_trait]
+impl ToolHandler<MarkovianMCPServer> for SaveAdapter {
+    type Params = SaveAdapterParams;
+    const NAME: &'static str = "save_adapter";
+    const DESCRIPTION: &'static str = "Save a LoRA adapter (without the base weights) to a safetensors file.";
+
+    async fn call(&self, server: &MarkovianMCPServer, params: SaveAdapterParams, _ctx: &RequestContext) -> Result<Value> {
+        training_tools::handle_save_adapter(params, server.model.clone())
+    }
+}
+
+struct ActivateAdapter;
+
+#[async_trait]
+impl ToolHandler<MarkovianMCPServer> for ActivateAdapter {
+    type Params = ActivateAdapterParams;
+    const NAME: &'static str = "activate_adapter";
+    const DESCRIPTION: &'static str = "Switch generation and training to a loaded LoRA adapter, or back to the base model.";
+
+    async fn call(&self, server: &MarkovianMCPServer, params: ActivateAdapterParams, _ctx: &RequestContext) -> Result<Value> {
+        training_tools::handle_activate_adapter(params, server.model.clone())
+    }
+}
+
+struct UnloadAdapter;
+
+#[async_trait]
+impl ToolHandler<MarkovianMCPServer> for UnloadAdapter {
+    type Params = AdapterNameParams;
+    const NAME: &'static str = "unload_adapter";
+    const DESCRIPTION: &'static str = "Unload a LoRA adapter, discarding its changes to the model.";
+
+    async fn call(&self, server: &MarkovianMCPServer, params: AdapterNameParams, _ctx: &RequestContext) -> Result<Value> {
+        training_tools::handle_unload_adapter(params, server.model.clone())
+    }
+}
+
+struct MergeAdapter;
+
+#[async_trait]
+impl ToolHandler<MarkovianMCPServer> for MergeAdapter {
+    type Params = AdapterNameParams;
+    const NAME: &'static str = "merge_adapter";
+    const DESCRIPTION: &'static str = "Merge a LoRA adapter into the base weights permanently and unload it.";
+
+    async fn call(&self, server: &MarkovianMCPServer, params: AdapterNameParams, _ctx: &RequestContext) -> Result<Value> {
+        training_tools::handle_merge_adapter(params, server.model.clone())
+    }
+}
+
+// Evaluation
+
+struct EvaluateModel;
+
+#[async_trait]
+impl ToolHandler<MarkovianMCPServer> for EvaluateModel {
+    type Params = EvaluateModelParams;
+    const NAME: &'static str = "evaluate_model";
+    const DESCRIPTION: &'static str = "Measure perplexity and token accuracy on a held-out set (and optionally reasoning-task accuracy with the chunk loop), compare two weight files, or set a regression gate that blocks save_weights and automatic checkpoints when metrics get worse.";
+
+    async fn call(&self, server: &MarkovianMCPServer, params: EvaluateModelParams, _ctx: &RequestContext) -> Result<Value> {
+        // Forward passes and generation block, so keep them off the serve loop
+        let (model, learner) = (server.model.clone(), server.learner.clone());
+        tokio::task::spawn_blocking(move || training_tools::handle_evaluate_model(params, model, learner)).await?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tool_schemas() {
+        let tools = router().list();
+        assert_eq!(tools[0].name, "markovian_think");
+        assert!(tools.iter().any(|t| t.name == "evaluate_model"));
+
+        let think = &tools[0].input_schema;
+        assert_eq!(think["required"], json!(["problem"]));
+        assert_eq!(think["properties"]["chunk_size"]["default"], 2048);
+        assert_eq!(think["properties"]["carryover_size"]["type"], "integer");
+
+        let save = tools.iter().find(|t| t.name == "save_weights").unwrap();
+        assert_eq!(save.input_schema["properties"]["format"]["default"], "custom");
+        assert_eq!(save.input_schema["required"], json!(["file_path"]));
+    }
+}
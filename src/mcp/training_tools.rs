@@ -4,39 +4,57 @@
 //! loading/saving weights, and managing online learning.
 
 use anyhow::Result;
+use mcp_framework::RequestContext;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::sync::{Arc, RwLock};
 
-use crate::training::{WeightLoader, WeightFormat, OnlineLearner, TrainingExample};
-use crate::inference::InferenceModel;
+use crate::training::{
+    WeightLoader, WeightFormat, OnlineLearner, LearningStats, TrainingExample, SaveDtype, SaveOptions,
+    CheckpointInfo, CheckpointManager, LoraAdapter, LoraConfig, Dataset, Evaluator, EvalMetrics, Comparison,
+    ReasoningTask, RegressionGate, RegressionThresholds,
+};
+use crate::inference::{InferenceModel, MappedSafeTensors, ObservedSource, Tokenizer};
+use crate::mcp::sampling::ThinkConfig;
 
 /// MCP tool parameters for loading model weights
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct LoadWeightsParams {
     /// Path to the weights file
     pub file_path: String,
     /// Weight format (safetensors, gguf, binary, custom)
     #[serde(default = "default_format")]
     pub format: String,
+    /// Tokenizer to switch to: a tiktoken encoding name, a `tokenizer.json`
+    /// or a `.gguf` file (GGUF weights bring their own vocabulary by default)
+    #[serde(default)]
+    pub tokenizer: Option<String>,
 }
 
 fn default_format() -> String { "safetensors".to_string() }
 
 /// MCP tool parameters for saving model weights
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct SaveWeightsParams {
     /// Path to save the weights
     pub file_path: String,
-    /// Weight format to save as (custom, binary)
+    /// Weight format to save as (safetensors, gguf, custom, binary)
     #[serde(default = "default_save_format")]
     pub format: String,
+    /// Element type for safetensors/gguf (f32, f16, bf16; int8 for safetensors)
+    #[serde(default = "default_save_dtype")]
+    pub dtype: String,
+    /// Save even if the regression gate reports worse metrics
+    #[serde(default)]
+    pub force: bool,
 }
 
 fn default_save_format() -> String { "custom".to_string() }
+fn default_save_dtype() -> String { "f32".to_string() }
 
 /// MCP tool parameters for adding training examples
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct AddTrainingExampleParams {
     /// Input text
     pub input: String,
@@ -50,12 +68,213 @@ pub struct AddTrainingExampleParams {
 fn default_weight() -> f32 { 1.0 }
 
 /// MCP tool parameters for setting learning rate
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct SetLearningRateParams {
-    /// New learning rate
+    /// New learning rate (e.g. 0.001)
     pub learning_rate: f32,
 }
 
+/// MCP tool parameters for listing checkpoints
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ListCheckpointsParams {
+    /// Directory to list (default: the learner's checkpoint directory)
+    #[serde(default)]
+    pub directory: Option<String>,
+}
+
+/// MCP tool parameters for restoring a checkpoint
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RestoreCheckpointParams {
+    /// Checkpoint directory to restore
+    #[serde(default)]
+    pub path: Option<String>,
+    /// Step of a checkpoint in the learner's checkpoint directory
+    #[serde(default)]
+    pub step: Option<usize>,
+}
+
+/// Checkpoint tool responses
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckpointResponse {
+    pub success: bool,
+    pub message: String,
+    pub checkpoints: Vec<CheckpointInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stats: Option<LearningStatsJson>,
+}
+
+/// MCP tool parameters for evaluating the model
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct EvaluateModelParams {
+    /// Held-out set: JSONL (prompt/completion, text or chat) or TraceDataset JSON
+    /// (default: the regression gate's set)
+    #[serde(default)]
+    pub data: Option<String>,
+    /// Also solve reasoning tasks with the chunk loop
+    #[serde(default)]
+    pub reasoning: bool,
+    /// JSONL of {"problem", "answer"} records (default: the built-in fixtures)
+    #[serde(default)]
+    pub reasoning_tasks: Option<String>,
+    /// Tokens per reasoning chunk
+    #[serde(default = "default_eval_chunk_size")]
+    pub chunk_size: usize,
+    /// Chunks per reasoning task
+    #[serde(default = "default_eval_max_iterations")]
+    pub max_iterations: usize,
+    /// Weight file to compare with candidate_weights, instead of evaluating
+    /// the loaded model
+    #[serde(default)]
+    pub baseline_weights: Option<String>,
+    /// Weight file to compare with baseline_weights
+    #[serde(default)]
+    pub candidate_weights: Option<String>,
+    /// Gate save_weights and automatic checkpoints on the current metrics
+    #[serde(default)]
+    pub set_gate: bool,
+    /// Remove the regression gate
+    #[serde(default)]
+    pub clear_gate: bool,
+    /// Allowed relative perplexity increase
+    #[serde(default = "default_max_perplexity_increase")]
+    pub max_perplexity_increase: f32,
+    /// Allowed absolute token accuracy drop
+    #[serde(default = "default_max_token_accuracy_drop")]
+    pub max_token_accuracy_drop: f32,
+    /// Allowed absolute reasoning accuracy drop
+    #[serde(default = "default_max_reasoning_accuracy_drop")]
+    pub max_reasoning_accuracy_drop: f32,
+}
+
+fn default_eval_chunk_size() -> usize { 256 }
+fn default_eval_max_iterations() -> usize { 3 }
+fn default_max_perplexity_increase() -> f32 { RegressionThresholds::default().max_perplexity_increase }
+fn default_max_token_accuracy_drop() -> f32 { RegressionThresholds::default().max_token_accuracy_drop }
+fn default_max_reasoning_accuracy_drop() -> f32 { RegressionThresholds::default().max_reasoning_accuracy_drop }
+
+/// Evaluation tool responses
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvaluateResponse {
+    pub success: bool,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metrics: Option<EvalMetrics>,
+    /// Metrics the regression gate compares against
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub baseline: Option<EvalMetrics>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comparison: Option<Comparison>,
+    pub regressions: Vec<String>,
+    pub gate_active: bool,
+}
+
+/// MCP tool parameters for creating a LoRA adapter
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CreateAdapterParams {
+    /// Adapter name
+    pub name: String,
+    /// Rank of the low-rank update
+    #[serde(default = "default_rank")]
+    pub rank: usize,
+    /// Scaling numerator (the update is scaled by alpha / rank)
+    #[serde(default = "default_alpha")]
+    pub alpha: f32,
+    /// Modules to adapt (q_proj, k_proj, v_proj, o_proj, gate_proj, up_proj, down_proj, lm_head)
+    #[serde(default = "default_target_modules")]
+    pub target_modules: Vec<String>,
+    /// Seed for the random init
+    #[serde(default)]
+    pub seed: u64,
+    /// Make it the active adapter
+    #[serde(default = "default_activate")]
+    pub activate: bool,
+}
+
+fn default_rank() -> usize { LoraConfig::default().rank }
+fn default_alpha() -> f32 { LoraConfig::default().alpha }
+fn default_target_modules() -> Vec<String> { LoraConfig::default().target_modules }
+fn default_activate() -> bool { true }
+
+/// MCP tool parameters for loading a LoRA adapter
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct LoadAdapterParams {
+    /// Path to the adapter safetensors file
+    pub file_path: String,
+    /// Name to load it under (default: the name saved in the file)
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Make it the active adapter
+    #[serde(default = "default_activate")]
+    pub activate: bool,
+}
+
+/// MCP tool parameters for saving a LoRA adapter
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SaveAdapterParams {
+    /// Path to save the adapter (safetensors)
+    pub file_path: String,
+    /// Adapter to save (default: the active one)
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+/// MCP tool parameters for activating a LoRA adapter
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ActivateAdapterParams {
+    /// Adapter to activate; omit to use the base model
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+/// MCP tool parameters for unloading or merging a LoRA adapter
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct AdapterNameParams {
+    /// Adapter name
+    pub name: String,
+}
+
+/// Summary of a loaded adapter
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdapterInfo {
+    pub name: String,
+    pub rank: usize,
+    pub alpha: f32,
+    pub num_layers: usize,
+    pub num_params: usize,
+}
+
+impl From<&LoraAdapter> for AdapterInfo {
+    fn from(adapter: &LoraAdapter) -> Self {
+        Self {
+            name: adapter.name().to_string(),
+            rank: adapter.rank(),
+            alpha: adapter.alpha(),
+            num_layers: adapter.layers().count(),
+            num_params: adapter.num_params(),
+        }
+    }
+}
+
+/// Adapter tool responses: the loaded adapters after the change
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdapterResponse {
+    pub success: bool,
+    pub message: String,
+    pub active_adapter: Option<String>,
+    pub adapters: Vec<AdapterInfo>,
+}
+
+impl AdapterResponse {
+    fn new(model: &InferenceModel, message: String) -> Self {
+        Self {
+            success: true,
+            message,
+            active_adapter: model.active_adapter().map(|a| a.name().to_string()),
+            adapters: model.adapters().map(AdapterInfo::from).collect(),
+        }
+    }
+}
+
 /// Training tool responses
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TrainingResponse {
@@ -72,13 +291,45 @@ pub struct LearningStatsJson {
     pub buffer_size: usize,
     pub average_loss: f32,
     pub learning_rate: f32,
+    pub base_learning_rate: f32,
+    pub optimizer: String,
+    pub lr_schedule: String,
+    pub replay_policy: String,
+    pub regularizer: String,
     pub enabled: bool,
+    pub skipped_updates: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+impl From<LearningStats> for LearningStatsJson {
+    fn from(stats: LearningStats) -> Self {
+        Self {
+            total_examples: stats.total_examples,
+            total_updates: stats.total_updates,
+            buffer_size: stats.buffer_size,
+            average_loss: stats.average_loss,
+            learning_rate: stats.learning_rate,
+            base_learning_rate: stats.base_learning_rate,
+            optimizer: stats.optimizer,
+            lr_schedule: stats.lr_schedule,
+            replay_policy: stats.replay_policy,
+            regularizer: stats.regularizer,
+            enabled: stats.enabled,
+            skipped_updates: stats.skipped_updates,
+            last_error: stats.last_error,
+        }
+    }
 }
 
 /// Handle load_weights MCP tool
+///
+/// Reports tensors loaded through `ctx.progress`; a cancelled load leaves the
+/// model's weights untouched.
 pub fn handle_load_weights(
     params: LoadWeightsParams,
     model: Arc<RwLock<InferenceModel>>,
+    ctx: &RequestContext,
 ) -> Result<Value> {
     // Parse format
     let format = match params.format.to_lowercase().as_str() {
@@ -89,28 +340,55 @@ pub fn handle_load_weights(
         _ => anyhow::bail!("Unknown weight format: {}", params.format),
     };
 
-    // Load weights
-    let mut loader = WeightLoader::new(format);
-    loader.load_from_file(&params.file_path)?;
-
-    // Get embedding weights
-    let embedding_weights = loader.get_embedding_weights()
-        .ok_or_else(|| anyhow::anyhow!("No embedding weights found in file"))?;
+    let mut tokenizer = match &params.tokenizer {
+        Some(spec) => Some(Tokenizer::load(spec)?),
+        None => None,
+    };
 
-    // Update model weights
+    // Update model weights (embeddings, plus transformer blocks if present).
+    // SafeTensors files are memory-mapped, so weights already in the
+    // model's compute dtype are not copied.
     let mut model = model.write()
         .map_err(|_| anyhow::anyhow!("Failed to acquire write lock on model"))?;
-    let embedding = Arc::get_mut(model.embedding_mut())
-        .ok_or_else(|| anyhow::anyhow!("Failed to get mutable access to embedding layer"))?;
-
-    embedding.load_weights(embedding_weights.clone())?;
+    let loaded = if format == WeightFormat::SafeTensors {
+        let mapped = MappedSafeTensors::open(&params.file_path)?;
+        if let Some(tokenizer) = &tokenizer {
+            model.config().check_tokenizer(tokenizer)?;
+        }
+        let observed = ObservedSource::new(&mapped, &ctx.cancel, |read| {
+            ctx.progress.report(read, None, format!("{} tensors loaded", read));
+        });
+        model.load_from(&observed)?
+    } else {
+        let mut loader = WeightLoader::new(format);
+        loader.load_from_file_with_progress(&params.file_path, &ctx.cancel, |done, total| {
+            ctx.progress.report(done, Some(total), format!("{}/{} tensors loaded", done, total));
+        })?;
+        let embedded = loader.gguf_metadata().filter(|meta| meta.tokenizer_model().is_some());
+        if let (None, Some(meta)) = (&tokenizer, embedded) {
+            tokenizer = Some(Tokenizer::from_gguf(meta)?);
+        }
+        if let Some(tokenizer) = &tokenizer {
+            model.config().check_tokenizer(tokenizer)?;
+        }
+        model.load_weights(&loader)?
+    };
+    let tokenizer_name = match tokenizer {
+        Some(tokenizer) => {
+            let name = tokenizer.name().to_string();
+            model.set_tokenizer(tokenizer)?;
+            name
+        }
+        None => model.tokenizer().name().to_string(),
+    };
 
     let response = TrainingResponse {
         success: true,
         message: format!(
-            "Loaded {} parameters from {}",
-            embedding_weights.len(),
-            params.file_path
+            "Loaded {} parameters from {} (tokenizer: {})",
+            loaded,
+            params.file_path,
+            tokenizer_name
         ),
         stats: None,
     };
@@ -119,29 +397,52 @@ pub fn handle_load_weights(
 }
 
 /// Handle save_weights MCP tool
+///
+/// Refused when the learner's regression gate reports worse metrics, unless `force` is set.
 pub fn handle_save_weights(
     params: SaveWeightsParams,
     model: Arc<RwLock<InferenceModel>>,
+    learner: Arc<RwLock<OnlineLearner>>,
 ) -> Result<Value> {
     // Parse format
     let format = match params.format.to_lowercase().as_str() {
+        "safetensors" | "st" => WeightFormat::SafeTensors,
+        "gguf" => WeightFormat::GGUF,
         "custom" => WeightFormat::Custom,
         "binary" | "bin" => WeightFormat::Binary,
-        _ => anyhow::bail!("Unsupported save format: {} (use 'custom' or 'binary')", params.format),
+        _ => anyhow::bail!(
+            "Unsupported save format: {} (use 'safetensors', 'gguf', 'custom' or 'binary')",
+            params.format
+        ),
     };
+    let options = SaveOptions {
+        dtype: SaveDtype::parse(&params.dtype)?,
+        ..Default::default()
+    };
+
+    if !params.force {
+        learner.read()
+            .map_err(|_| anyhow::anyhow!("Failed to acquire read lock on learner"))?
+            .ensure_no_regression()
+            .map_err(|e| anyhow::anyhow!("{} (pass force to save anyway)", e))?;
+    }
 
     // Get model weights
     let model = model.read()
         .map_err(|_| anyhow::anyhow!("Failed to acquire read lock on model"))?;
-    let embedding = model.embedding();
-    let weights = embedding.weights().to_vec();
 
-    // Create loader with current weights
-    let mut loader = WeightLoader::new(format);
-    loader.load_embedding_weights(embedding.vocab_size(), embedding.embed_dim(), weights)?;
+    let loader = if format == WeightFormat::Binary {
+        // Raw binary holds a single flat tensor: the embedding table
+        let embedding = model.embedding();
+        let mut loader = WeightLoader::new(format);
+        loader.insert_tensor("weights".to_string(), embedding.weights().to_vec());
+        loader
+    } else {
+        model.export_weights()?
+    };
 
     // Save to file
-    loader.save_to_file(&params.file_path, format)?;
+    loader.save_with_options(&params.file_path, format, &options)?;
 
     let response = TrainingResponse {
         success: true,
@@ -159,14 +460,7 @@ pub fn handle_enable_learning(learner: Arc<RwLock<OnlineLearner>>) -> Result<Val
     learner.enable();
 
     let stats = learner.get_stats();
-    let stats_json = LearningStatsJson {
-        total_examples: stats.total_examples,
-        total_updates: stats.total_updates,
-        buffer_size: stats.buffer_size,
-        average_loss: stats.average_loss,
-        learning_rate: stats.learning_rate,
-        enabled: stats.enabled,
-    };
+    let stats_json = LearningStatsJson::from(stats);
 
     let response = TrainingResponse {
         success: true,
@@ -184,14 +478,7 @@ pub fn handle_disable_learning(learner: Arc<RwLock<OnlineLearner>>) -> Result<Va
     learner.disable();
 
     let stats = learner.get_stats();
-    let stats_json = LearningStatsJson {
-        total_examples: stats.total_examples,
-        total_updates: stats.total_updates,
-        buffer_size: stats.buffer_size,
-        average_loss: stats.average_loss,
-        learning_rate: stats.learning_rate,
-        enabled: stats.enabled,
-    };
+    let stats_json = LearningStatsJson::from(stats);
 
     let response = TrainingResponse {
         success: true,
@@ -215,14 +502,7 @@ pub fn handle_add_training_example(
     learner.add_example(example)?;
 
     let stats = learner.get_stats();
-    let stats_json = LearningStatsJson {
-        total_examples: stats.total_examples,
-        total_updates: stats.total_updates,
-        buffer_size: stats.buffer_size,
-        average_loss: stats.average_loss,
-        learning_rate: stats.learning_rate,
-        enabled: stats.enabled,
-    };
+    let stats_json = LearningStatsJson::from(stats);
 
     let response = TrainingResponse {
         success: true,
@@ -239,14 +519,7 @@ pub fn handle_get_learning_stats(learner: Arc<RwLock<OnlineLearner>>) -> Result<
         .map_err(|_| anyhow::anyhow!("Failed to acquire read lock on learner"))?;
     let stats = learner.get_stats();
 
-    let stats_json = LearningStatsJson {
-        total_examples: stats.total_examples,
-        total_updates: stats.total_updates,
-        buffer_size: stats.buffer_size,
-        average_loss: stats.average_loss,
-        learning_rate: stats.learning_rate,
-        enabled: stats.enabled,
-    };
+    let stats_json = LearningStatsJson::from(stats);
 
     let response = TrainingResponse {
         success: true,
@@ -267,14 +540,7 @@ pub fn handle_set_learning_rate(
     learner.set_learning_rate(params.learning_rate);
 
     let stats = learner.get_stats();
-    let stats_json = LearningStatsJson {
-        total_examples: stats.total_examples,
-        total_updates: stats.total_updates,
-        buffer_size: stats.buffer_size,
-        average_loss: stats.average_loss,
-        learning_rate: stats.learning_rate,
-        enabled: stats.enabled,
-    };
+    let stats_json = LearningStatsJson::from(stats);
 
     let response = TrainingResponse {
         success: true,
@@ -286,20 +552,18 @@ pub fn handle_set_learning_rate(
 }
 
 /// Handle force_update MCP tool
-pub fn handle_force_update(learner: Arc<RwLock<OnlineLearner>>) -> Result<Value> {
+///
+/// Reports each example's forward/backward pass through `ctx.progress`; a
+/// cancelled update leaves the weights untouched.
+pub fn handle_force_update(learner: Arc<RwLock<OnlineLearner>>, ctx: &RequestContext) -> Result<Value> {
     let mut learner = learner.write()
         .map_err(|_| anyhow::anyhow!("Failed to acquire write lock on learner"))?;
-    learner.force_update()?;
+    learner.force_update_with_progress(&ctx.cancel, |done, total| {
+        ctx.progress.report(done, Some(total), format!("Example {}/{}", done, total));
+    })?;
 
     let stats = learner.get_stats();
-    let stats_json = LearningStatsJson {
-        total_examples: stats.total_examples,
-        total_updates: stats.total_updates,
-        buffer_size: stats.buffer_size,
-        average_loss: stats.average_loss,
-        learning_rate: stats.learning_rate,
-        enabled: stats.enabled,
-    };
+    let stats_json = LearningStatsJson::from(stats);
 
     let response = TrainingResponse {
         success: true,
@@ -309,3 +573,286 @@ pub fn handle_force_update(learner: Arc<RwLock<OnlineLearner>>) -> Result<Value>
 
     Ok(serde_json::to_value(response)?)
 }
+
+/// Handle list_checkpoints MCP tool
+pub fn handle_list_checkpoints(
+    params: ListCheckpointsParams,
+    learner: Arc<RwLock<OnlineLearner>>,
+) -> Result<Value> {
+    let manager = match params.directory {
+        Some(dir) => CheckpointManager::new(dir, 0),
+        None => {
+            let learner = learner.read()
+                .map_err(|_| anyhow::anyhow!("Failed to acquire read lock on learner"))?;
+            learner.checkpoint_manager()
+                .ok_or_else(|| anyhow::anyhow!("No checkpoint directory configured; pass `directory`"))?
+        }
+    };
+    let checkpoints = manager.list()?;
+
+    let response = CheckpointResponse {
+        success: true,
+        message: format!("{} checkpoint(s) in {}", checkpoints.len(), manager.dir().display()),
+        checkpoints,
+        stats: None,
+    };
+
+    Ok(serde_json::to_value(response)?)
+}
+
+/// Handle save_checkpoint MCP tool
+pub fn handle_save_checkpoint(learner: Arc<RwLock<OnlineLearner>>) -> Result<Value> {
+    let learner = learner.read()
+        .map_err(|_| anyhow::anyhow!("Failed to acquire read lock on learner"))?;
+    let info = learner.save_checkpoint()?;
+
+    let response = CheckpointResponse {
+        success: true,
+        message: format!("Saved checkpoint at step {} to {}", info.step, info.path.display()),
+        checkpoints: vec![info],
+        stats: Some(LearningStatsJson::from(learner.get_stats())),
+    };
+
+    Ok(serde_json::to_value(response)?)
+}
+
+/// Handle restore_checkpoint MCP tool
+///
+/// Restores `path`, the checkpoint for `step`, or else the latest checkpoint.
+pub fn handle_restore_checkpoint(
+    params: RestoreCheckpointParams,
+    learner: Arc<RwLock<OnlineLearner>>,
+) -> Result<Value> {
+    let mut learner = learner.write()
+        .map_err(|_| anyhow::anyhow!("Failed to acquire write lock on learner"))?;
+
+    let path = match (params.path, params.step) {
+        (Some(_), Some(_)) => anyhow::bail!("Pass either `path` or `step`, not both"),
+        (Some(path), None) => path.into(),
+        (None, step) => {
+            let manager = learner.checkpoint_manager()
+                .ok_or_else(|| anyhow::anyhow!("No checkpoint directory configured; pass `path`"))?;
+            let found = match step {
+                Some(step) => manager.find(step)?,
+                None => manager.latest()?,
+            };
+            found.ok_or_else(|| anyhow::anyhow!("No matching checkpoint in {}", manager.dir().display()))?.path
+        }
+    };
+    let info = learner.resume_from(&path)?;
+
+    let response = CheckpointResponse {
+        success: true,
+        message: format!("Restored checkpoint at step {} from {}", info.step, info.path.display()),
+        checkpoints: vec![info],
+        stats: Some(LearningStatsJson::from(learner.get_stats())),
+    };
+
+    Ok(serde_json::to_value(response)?)
+}
+
+/// Handle create_adapter MCP tool
+pub fn handle_create_adapter(
+    params: CreateAdapterParams,
+    model: Arc<RwLock<InferenceModel>>,
+) -> Result<Value> {
+    let mut model = model.write()
+        .map_err(|_| anyhow::anyhow!("Failed to acquire write lock on model"))?;
+    if model.adapter(&params.name).is_some() {
+        anyhow::bail!("Adapter {} already exists; unload it first", params.name);
+    }
+
+    let config = LoraConfig {
+        rank: params.rank,
+        alpha: params.alpha,
+        target_modules: params.target_modules,
+        seed: params.seed,
+    };
+    let adapter = LoraAdapter::new(&params.name, &config, &model.transformer().linear_weights())?;
+    let message = format!(
+        "Created adapter {} ({} layers, {} parameters)",
+        params.name,
+        adapter.layers().count(),
+        adapter.num_params()
+    );
+    model.insert_adapter(adapter)?;
+    if params.activate {
+        model.set_active_adapter(Some(&params.name))?;
+    }
+
+    Ok(serde_json::to_value(AdapterResponse::new(&model, message))?)
+}
+
+/// Handle load_adapter MCP tool
+pub fn handle_load_adapter(
+    params: LoadAdapterParams,
+    model: Arc<RwLock<InferenceModel>>,
+) -> Result<Value> {
+    let mut adapter = LoraAdapter::load(&params.file_path)?;
+    if let Some(name) = params.name {
+        adapter.set_name(name);
+    }
+    let name = adapter.name().to_string();
+
+    let mut model = model.write()
+        .map_err(|_| anyhow::anyhow!("Failed to acquire write lock on model"))?;
+    model.insert_adapter(adapter)?;
+    if params.activate {
+        model.set_active_adapter(Some(&name))?;
+    }
+
+    let message = format!("Loaded adapter {} from {}", name, params.file_path);
+    Ok(serde_json::to_value(AdapterResponse::new(&model, message))?)
+}
+
+/// Handle save_adapter MCP tool
+pub fn handle_save_adapter(
+    params: SaveAdapterParams,
+    model: Arc<RwLock<InferenceModel>>,
+) -> Result<Value> {
+    let model = model.read()
+        .map_err(|_| anyhow::anyhow!("Failed to acquire read lock on model"))?;
+    let adapter = match &params.name {
+        Some(name) => model.adapter(name).ok_or_else(|| anyhow::anyhow!("No adapter named {}", name))?,
+        None => model.active_adapter().ok_or_else(|| anyhow::anyhow!("No active adapter; pass `name`"))?,
+    };
+    adapter.save(&params.file_path)?;
+
+    let message = format!("Saved adapter {} to {}", adapter.name(), params.file_path);
+    Ok(serde_json::to_value(AdapterResponse::new(&model, message))?)
+}
+
+/// Handle activate_adapter MCP tool
+pub fn handle_activate_adapter(
+    params: ActivateAdapterParams,
+    model: Arc<RwLock<InferenceModel>>,
+) -> Result<Value> {
+    let mut model = model.write()
+        .map_err(|_| anyhow::anyhow!("Failed to acquire write lock on model"))?;
+    model.set_active_adapter(params.name.as_deref())?;
+
+    let message = match &params.name {
+        Some(name) => format!("Activated adapter {}", name),
+        None => "Using the base model".to_string(),
+    };
+    Ok(serde_json::to_value(AdapterResponse::new(&model, message))?)
+}
+
+/// Handle unload_adapter MCP tool
+pub fn handle_unload_adapter(
+    params: AdapterNameParams,
+    model: Arc<RwLock<InferenceModel>>,
+) -> Result<Value> {
+    let mut model = model.write()
+        .map_err(|_| anyhow::anyhow!("Failed to acquire write lock on model"))?;
+    model.remove_adapter(&params.name)?;
+
+    let message = format!("Unloaded adapter {}", params.name);
+    Ok(serde_json::to_value(AdapterResponse::new(&model, message))?)
+}
+
+/// Handle merge_adapter MCP tool
+pub fn handle_merge_adapter(
+    params: AdapterNameParams,
+    model: Arc<RwLock<InferenceModel>>,
+) -> Result<Value> {
+    let mut model = model.write()
+        .map_err(|_| anyhow::anyhow!("Failed to acquire write lock on model"))?;
+    model.merge_adapter(&params.name)?;
+
+    let message = format!("Merged adapter {} into the base weights", params.name);
+    Ok(serde_json::to_value(AdapterResponse::new(&model, message))?)
+}
+
+/// Handle evaluate_model MCP tool
+///
+/// Evaluates the loaded model (or compares two weight files) on a held-out
+/// set, optionally with reasoning tasks, and reports regressions against the
+/// regression gate; `set_gate` makes the current metrics the new gate.
+pub fn handle_evaluate_model(
+    params: EvaluateModelParams,
+    model: Arc<RwLock<InferenceModel>>,
+    learner: Arc<RwLock<OnlineLearner>>,
+) -> Result<Value> {
+    let thresholds = RegressionThresholds {
+        max_perplexity_increase: params.max_perplexity_increase,
+        max_token_accuracy_drop: params.max_token_accuracy_drop,
+        max_reasoning_accuracy_drop: params.max_reasoning_accuracy_drop,
+    };
+
+    let mut learner = learner.write()
+        .map_err(|_| anyhow::anyhow!("Failed to acquire write lock on learner"))?;
+    if params.clear_gate {
+        learner.set_regression_gate(None);
+    }
+
+    let evaluator = match &params.data {
+        Some(path) => {
+            let mut evaluator = Evaluator::new(Dataset::load(path)?);
+            if params.reasoning {
+                let tasks = match &params.reasoning_tasks {
+                    Some(path) => ReasoningTask::load(path)?,
+                    None => ReasoningTask::fixtures(),
+                };
+                let think = ThinkConfig::new(params.chunk_size, params.chunk_size / 4, params.max_iterations)?;
+                evaluator = evaluator.with_reasoning(tasks, think);
+            }
+            evaluator
+        }
+        None => learner.regression_gate()
+            .map(|gate| gate.evaluator().clone())
+            .ok_or_else(|| anyhow::anyhow!("No held-out set: pass `data` or set a regression gate first"))?,
+    };
+
+    let model = model.read()
+        .map_err(|_| anyhow::anyhow!("Failed to acquire read lock on model"))?;
+
+    let response = match (&params.baseline_weights, &params.candidate_weights) {
+        (Some(baseline), Some(candidate)) => {
+            let comparison = evaluator.compare_weights(
+                model.config(),
+                baseline.as_ref(),
+                candidate.as_ref(),
+                &thresholds,
+            )?;
+            EvaluateResponse {
+                success: true,
+                message: format!("Compared {} with {}", candidate, baseline),
+                metrics: None,
+                baseline: None,
+                regressions: comparison.regressions.clone(),
+                comparison: Some(comparison),
+                gate_active: learner.regression_gate().is_some(),
+            }
+        }
+        (None, None) => {
+            if params.set_gate {
+                learner.set_regression_gate(Some(RegressionGate::new(evaluator.clone(), thresholds, &model)?));
+            }
+            // Data passed without `set_gate` is evaluated on its own
+            let use_gate = params.data.is_none() || params.set_gate;
+            let (metrics, baseline, regressions) = match learner.regression_gate().filter(|_| use_gate) {
+                Some(gate) => {
+                    let report = gate.check(&model)?;
+                    (report.metrics, Some(report.baseline), report.regressions)
+                }
+                None => (evaluator.evaluate(&model)?, None, Vec::new()),
+            };
+            EvaluateResponse {
+                success: true,
+                message: format!(
+                    "Perplexity {:.3}, token accuracy {:.3} on {} examples",
+                    metrics.perplexity, metrics.token_accuracy, metrics.examples
+                ),
+                metrics: Some(metrics),
+                baseline,
+                comparison: None,
+                regressions,
+                gate_active: learner.regression_gate().is_some(),
+            }
+        }
+        _ => anyhow::bail!("Pass both `baseline_weights` and `candidate_weights` to compare"),
+    };
+
+    Ok(serde_json::to_value(response)?)
+}
@@ -246,7 +246,7 @@ mod tests {
         let envelope = TaskEnvelope::new(task, 1);
         let task_id = envelope.id;
 
-        let rx = queue.submit(envelope).await.unwrap();
+        let _rx = queue.submit(envelope).await.unwrap();
 
         let stats = queue.stats().await;
         if queue.config.group_by_type {
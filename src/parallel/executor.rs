@@ -301,7 +301,6 @@ impl std::fmt::Display for ExecutorStats {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::parallel::task::{CodeGenTask, DataProcessTask, DataOperation};
 
     #[tokio::test]
     async fn test_executor_creation() {
@@ -325,7 +324,7 @@ mod tests {
             ..Default::default()
         };
 
-        let executor = ParallelExecutor::new(config).unwrap();
+        let _executor = ParallelExecutor::new(config).unwrap();
 
         // This would fail currently because output deserialization is not implemented
         // But the framework is in place
@@ -2,6 +2,7 @@
 
 use anyhow::Result;
 use std::sync::Arc;
+#[cfg(feature = "gpu")]
 use tracing::debug;
 
 #[cfg(feature = "gpu")]
@@ -11,7 +12,9 @@ use cudarc::driver::CudaSlice;
 use crate::gpu::{CudaContext, kernels::*};
 
 use crate::inference::{InferenceModel, ModelConfig};
-use super::task::{TaskEnvelope, TaskResult, TaskType};
+use super::task::{TaskEnvelope, TaskResult};
+#[cfg(feature = "gpu")]
+use super::task::TaskType;
 
 /// GPU execution pipeline
 pub struct GpuExecutionPipeline {
@@ -361,8 +364,6 @@ impl GpuExecutionPipeline {
 
 #[cfg(test)]
 mod tests {
-    use super::*;
-
     #[tokio::test]
     #[ignore] // Only run with GPU
     async fn test_gpu_pipeline() {
@@ -0,0 +1,975 @@
+//! Reverse-mode automatic differentiation over 2-D f32 tensors
+//!
+//! A `Tape` records every operation of a forward pass. `Tape::backward`
+//! then walks the tape in reverse and returns gradients for the named
+//! parameters. All values are row-major `[rows, cols]` matrices; a scalar is
+//! `[1, 1]`. The ops cover what a decoder-only transformer needs: matmul,
+//! linear layers, elementwise add/mul, GELU/SiLU, softmax, RMSNorm/LayerNorm,
+//! embedding gather, rotary embedding, causal multi-head attention and
+//! masked cross-entropy.
+//!
+//! `check_gradients` compares tape gradients against central finite
+//! differences.
+
+use anyhow::Result;
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use crate::inference::transformer::{gelu, silu, RotaryEmbedding};
+use crate::inference::NormType;
+
+/// Handle to a value recorded on a `Tape`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Var(usize);
+
+#[derive(Debug, Clone)]
+enum Op {
+    Leaf,
+    MatMul(Var, Var),
+    Linear {
+        x: Var,
+        weight: Var,
+        bias: Option<Var>,
+    },
+    Add(Var, Var),
+    Mul(Var, Var),
+    Scale(Var, f32),
+    Gelu(Var),
+    Silu(Var),
+    Softmax(Var),
+    Norm {
+        x: Var,
+        weight: Var,
+        bias: Option<Var>,
+        kind: NormType,
+        /// Per row: (mean, 1 / std)
+        stats: Vec<(f32, f32)>,
+    },
+    Embedding {
+        table: Var,
+        ids: Vec<usize>,
+    },
+    Rope {
+        x: Var,
+        head_dim: usize,
+        rope: RotaryEmbedding,
+        start: usize,
+    },
+    Attention {
+        q: Var,
+        k: Var,
+        v: Var,
+        n_heads: usize,
+        n_kv_heads: usize,
+        /// Causal attention weights, [n_heads][t][t] (upper triangle zero)
+        probs: Vec<f32>,
+    },
+    CrossEntropy {
+        logits: Var,
+        targets: Vec<Option<usize>>,
+        probs: Vec<f32>,
+    },
+    Mean(Var),
+}
+
+#[derive(Debug, Clone)]
+struct Node<'w> {
+    /// Leaves borrow weights the caller owns; computed values are owned
+    value: Cow<'w, [f32]>,
+    rows: usize,
+    cols: usize,
+    op: Op,
+    requires_grad: bool,
+}
+
+/// Records a forward computation for backpropagation
+///
+/// `'w` is the lifetime of weights recorded by reference with `Tape::param`
+/// or `Tape::constant`.
+#[derive(Debug, Default)]
+pub struct Tape<'w> {
+    nodes: Vec<Node<'w>>,
+    params: Vec<(String, Var)>,
+}
+
+/// Gradients produced by `Tape::backward`
+#[derive(Debug)]
+pub struct Gradients {
+    grads: Vec<Option<Vec<f32>>>,
+    params: Vec<(String, Var)>,
+}
+
+impl Gradients {
+    /// Gradient of a named parameter
+    pub fn get(&self, name: &str) -> Option<&[f32]> {
+        let (_, var) = self.params.iter().find(|(n, _)| n == name)?;
+        self.of(*var)
+    }
+
+    /// Gradient of any recorded value that required one
+    pub fn of(&self, var: Var) -> Option<&[f32]> {
+        self.grads.get(var.0)?.as_deref()
+    }
+
+    /// Parameter gradients by name (parameters the loss doesn't reach get zeros)
+    pub fn into_named(mut self) -> HashMap<String, Vec<f32>> {
+        let params = std::mem::take(&mut self.params);
+        params
+            .into_iter()
+            .filter_map(|(name, var)| self.grads[var.0].take().map(|g| (name, g)))
+            .collect()
+    }
+}
+
+impl<'w> Tape<'w> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a trainable parameter
+    pub fn param(
+        &mut self,
+        name: impl Into<String>,
+        value: impl Into<Cow<'w, [f32]>>,
+        rows: usize,
+        cols: usize,
+    ) -> Var {
+        let var = self.leaf(value, rows, cols, true);
+        self.params.push((name.into(), var));
+        var
+    }
+
+    /// Record a value that gradients don't flow into
+    pub fn constant(&mut self, value: impl Into<Cow<'w, [f32]>>, rows: usize, cols: usize) -> Var {
+        self.leaf(value, rows, cols, false)
+    }
+
+    pub fn value(&self, var: Var) -> &[f32] {
+        &self.nodes[var.0].value
+    }
+
+    /// (rows, cols)
+    pub fn shape(&self, var: Var) -> (usize, usize) {
+        let node = &self.nodes[var.0];
+        (node.rows, node.cols)
+    }
+
+    /// Scalar value of a `[1, 1]` result such as a loss
+    pub fn scalar(&self, var: Var) -> f32 {
+        self.nodes[var.0].value[0]
+    }
+
+    /// Number of recorded operations
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    fn leaf(&mut self, value: impl Into<Cow<'w, [f32]>>, rows: usize, cols: usize, requires_grad: bool) -> Var {
+        let value = value.into();
+        assert_eq!(value.len(), rows * cols, "leaf value does not match its shape");
+        self.record(value, rows, cols, Op::Leaf, requires_grad)
+    }
+
+    fn push(&mut self, value: Vec<f32>, rows: usize, cols: usize, op: Op, requires_grad: bool) -> Var {
+        self.record(Cow::Owned(value), rows, cols, op, requires_grad)
+    }
+
+    fn record(&mut self, value: Cow<'w, [f32]>, rows: usize, cols: usize, op: Op, requires_grad: bool) -> Var {
+        self.nodes.push(Node {
+            value,
+            rows,
+            cols,
+            op,
+            requires_grad,
+        });
+        Var(self.nodes.len() - 1)
+    }
+
+    fn requires_grad(&self, vars: &[Var]) -> bool {
+        vars.iter().any(|v| self.nodes[v.0].requires_grad)
+    }
+
+    fn check_same_shape(&self, a: Var, b: Var, op: &str) -> Result<()> {
+        if self.shape(a) != self.shape(b) {
+            anyhow::bail!("{}: shape mismatch {:?} vs {:?}", op, self.shape(a), self.shape(b));
+        }
+        Ok(())
+    }
+
+    fn unary(&mut self, a: Var, op: Op, f: impl Fn(f32) -> f32) -> Var {
+        let (rows, cols) = self.shape(a);
+        let value = self.value(a).iter().map(|&x| f(x)).collect();
+        let requires_grad = self.requires_grad(&[a]);
+        self.push(value, rows, cols, op, requires_grad)
+    }
+
+    /// a [m, k] × b [k, n]
+    pub fn matmul(&mut self, a: Var, b: Var) -> Result<Var> {
+        let (m, k) = self.shape(a);
+        let (k2, n) = self.shape(b);
+        if k != k2 {
+            anyhow::bail!("matmul: inner dimensions differ ({} vs {})", k, k2);
+        }
+        let value = matmul(self.value(a), self.value(b), m, k, n);
+        let requires_grad = self.requires_grad(&[a, b]);
+        Ok(self.push(value, m, n, Op::MatMul(a, b), requires_grad))
+    }
+
+    /// x [m, in] × weightᵀ + bias, with weight stored [out, in] like `Linear`
+    pub fn linear(&mut self, x: Var, weight: Var, bias: Option<Var>) -> Result<Var> {
+        let (m, in_features) = self.shape(x);
+        let (out_features, w_in) = self.shape(weight);
+        if in_features != w_in {
+            anyhow::bail!("linear: input has {} features, weight expects {}", in_features, w_in);
+        }
+        if let Some(b) = bias {
+            if self.value(b).len() != out_features {
+                anyhow::bail!("linear: bias has {} values, expected {}", self.value(b).len(), out_features);
+            }
+        }
+
+        let xs = self.value(x);
+        let w = self.value(weight);
+        let mut value = Vec::with_capacity(m * out_features);
+        for row in xs.chunks_exact(in_features) {
+            value.extend(w.chunks_exact(in_features).map(|w_row| dot(row, w_row)));
+        }
+        if let Some(b) = bias {
+            let b = self.value(b).to_vec();
+            for row in value.chunks_exact_mut(out_features) {
+                add_into(row, &b);
+            }
+        }
+
+        let mut inputs = vec![x, weight];
+        inputs.extend(bias);
+        let requires_grad = self.requires_grad(&inputs);
+        Ok(self.push(value, m, out_features, Op::Linear { x, weight, bias }, requires_grad))
+    }
+
+    pub fn add(&mut self, a: Var, b: Var) -> Result<Var> {
+        self.check_same_shape(a, b, "add")?;
+        let (rows, cols) = self.shape(a);
+        let value = self.value(a).iter().zip(self.value(b)).map(|(x, y)| x + y).collect();
+        let requires_grad = self.requires_grad(&[a, b]);
+        Ok(self.push(value, rows, cols, Op::Add(a, b), requires_grad))
+    }
+
+    /// Elementwise product
+    pub fn mul(&mut self, a: Var, b: Var) -> Result<Var> {
+        self.check_same_shape(a, b, "mul")?;
+        let (rows, cols) = self.shape(a);
+        let value = self.value(a).iter().zip(self.value(b)).map(|(x, y)| x * y).collect();
+        let requires_grad = self.requires_grad(&[a, b]);
+        Ok(self.push(value, rows, cols, Op::Mul(a, b), requires_grad))
+    }
+
+    pub fn scale(&mut self, a: Var, factor: f32) -> Var {
+        self.unary(a, Op::Scale(a, factor), |x| x * factor)
+    }
+
+    /// GELU (tanh approximation, as in inference)
+    pub fn gelu(&mut self, a: Var) -> Var {
+        self.unary(a, Op::Gelu(a), gelu)
+    }
+
+    pub fn silu(&mut self, a: Var) -> Var {
+        self.unary(a, Op::Silu(a), silu)
+    }
+
+    /// Row-wise softmax
+    pub fn softmax(&mut self, a: Var) -> Var {
+        let (rows, cols) = self.shape(a);
+        let mut value = self.value(a).to_vec();
+        for row in value.chunks_exact_mut(cols) {
+            softmax_in_place(row);
+        }
+        let requires_grad = self.requires_grad(&[a]);
+        self.push(value, rows, cols, Op::Softmax(a), requires_grad)
+    }
+
+    /// Row-wise RMSNorm or LayerNorm with learned weight (and optional bias)
+    pub fn norm(&mut self, x: Var, weight: Var, bias: Option<Var>, kind: NormType, eps: f32) -> Result<Var> {
+        let (rows, cols) = self.shape(x);
+        if self.value(weight).len() != cols || bias.is_some_and(|b| self.value(b).len() != cols) {
+            anyhow::bail!("norm: parameters must have {} values", cols);
+        }
+
+        let w = self.value(weight);
+        let b = bias.map(|b| self.value(b));
+        let mut value = Vec::with_capacity(rows * cols);
+        let mut stats = Vec::with_capacity(rows);
+        for row in self.value(x).chunks_exact(cols) {
+            let n = cols as f32;
+            let mean = match kind {
+                NormType::RmsNorm => 0.0,
+                NormType::LayerNorm => row.iter().sum::<f32>() / n,
+            };
+            let var = row.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / n;
+            let inv_std = 1.0 / (var + eps).sqrt();
+            stats.push((mean, inv_std));
+
+            for (i, v) in row.iter().enumerate() {
+                value.push((v - mean) * inv_std * w[i] + b.map_or(0.0, |b| b[i]));
+            }
+        }
+
+        let mut inputs = vec![x, weight];
+        inputs.extend(bias);
+        let requires_grad = self.requires_grad(&inputs);
+        let op = Op::Norm {
+            x,
+            weight,
+            bias,
+            kind,
+            stats,
+        };
+        Ok(self.push(value, rows, cols, op, requires_grad))
+    }
+
+    /// Rows of `table` [vocab, dim] for each id
+    pub fn embedding(&mut self, table: Var, ids: &[usize]) -> Result<Var> {
+        let (vocab, dim) = self.shape(table);
+        if let Some(&bad) = ids.iter().find(|&&id| id >= vocab) {
+            anyhow::bail!("embedding: token {} out of range for vocab {}", bad, vocab);
+        }
+
+        let t = self.value(table);
+        let value = ids.iter().flat_map(|&id| t[id * dim..(id + 1) * dim].iter().copied()).collect();
+        let requires_grad = self.requires_grad(&[table]);
+        let op = Op::Embedding {
+            table,
+            ids: ids.to_vec(),
+        };
+        Ok(self.push(value, ids.len(), dim, op, requires_grad))
+    }
+
+    /// Rotary embedding of each `head_dim` slice; row r is position `start + r`
+    pub fn rope(&mut self, x: Var, head_dim: usize, theta: f32, start: usize) -> Result<Var> {
+        let (rows, cols) = self.shape(x);
+        if !cols.is_multiple_of(head_dim) || !head_dim.is_multiple_of(2) {
+            anyhow::bail!("rope: {} columns are not whole heads of (even) size {}", cols, head_dim);
+        }
+
+        let rope = RotaryEmbedding::new(head_dim, theta);
+        let mut value = self.value(x).to_vec();
+        for (r, row) in value.chunks_exact_mut(cols).enumerate() {
+            for head in row.chunks_exact_mut(head_dim) {
+                rope.apply(head, start + r);
+            }
+        }
+
+        let requires_grad = self.requires_grad(&[x]);
+        let op = Op::Rope {
+            x,
+            head_dim,
+            rope,
+            start,
+        };
+        Ok(self.push(value, rows, cols, op, requires_grad))
+    }
+
+    /// Causal scaled dot-product attention with grouped-query heads
+    ///
+    /// q is [t, n_heads * hd]; k and v are [t, n_kv_heads * hd].
+    pub fn attention(&mut self, q: Var, k: Var, v: Var, n_heads: usize, n_kv_heads: usize) -> Result<Var> {
+        let (t, q_dim) = self.shape(q);
+        let (tk, kv_dim) = self.shape(k);
+        if self.shape(v) != (tk, kv_dim) || tk != t {
+            anyhow::bail!("attention: q/k/v sequence lengths or k/v shapes differ");
+        }
+        if n_kv_heads == 0 || !n_heads.is_multiple_of(n_kv_heads) || !q_dim.is_multiple_of(n_heads) {
+            anyhow::bail!("attention: bad head configuration ({} / {})", n_heads, n_kv_heads);
+        }
+        let hd = q_dim / n_heads;
+        if kv_dim != n_kv_heads * hd {
+            anyhow::bail!("attention: k/v width {} != {} heads × {}", kv_dim, n_kv_heads, hd);
+        }
+
+        let group = n_heads / n_kv_heads;
+        let scale = 1.0 / (hd as f32).sqrt();
+        let (qs, ks, vs) = (self.value(q), self.value(k), self.value(v));
+
+        let mut probs = vec![0.0; n_heads * t * t];
+        let mut out = vec![0.0; t * q_dim];
+        for h in 0..n_heads {
+            let kv = (h / group) * hd;
+            for i in 0..t {
+                let q_row = &qs[i * q_dim + h * hd..i * q_dim + (h + 1) * hd];
+                let p = &mut probs[(h * t + i) * t..(h * t + i) * t + i + 1];
+                for (j, score) in p.iter_mut().enumerate() {
+                    *score = dot(q_row, &ks[j * kv_dim + kv..j * kv_dim + kv + hd]) * scale;
+                }
+                softmax_in_place(p);
+
+                let o = &mut out[i * q_dim + h * hd..i * q_dim + (h + 1) * hd];
+                for (j, &w) in p.iter().enumerate() {
+                    for (o, v) in o.iter_mut().zip(&vs[j * kv_dim + kv..j * kv_dim + kv + hd]) {
+                        *o += w * v;
+                    }
+                }
+            }
+        }
+
+        let requires_grad = self.requires_grad(&[q, k, v]);
+        let op = Op::Attention {
+            q,
+            k,
+            v,
+            n_heads,
+            n_kv_heads,
+            probs,
+        };
+        Ok(self.push(out, t, q_dim, op, requires_grad))
+    }
+
+    /// Mean next-token cross-entropy over rows with a target
+    ///
+    /// `targets[r]` is the expected class of row r; `None` masks the row out
+    /// (e.g. prompt positions). Returns a `[1, 1]` loss.
+    pub fn cross_entropy(&mut self, logits: Var, targets: &[Option<usize>]) -> Result<Var> {
+        let (rows, cols) = self.shape(logits);
+        if targets.len() != rows {
+            anyhow::bail!("cross_entropy: {} targets for {} rows", targets.len(), rows);
+        }
+        if let Some(bad) = targets.iter().flatten().find(|&&c| c >= cols) {
+            anyhow::bail!("cross_entropy: target {} out of range for {} classes", bad, cols);
+        }
+        let count = targets.iter().flatten().count();
+        if count == 0 {
+            anyhow::bail!("cross_entropy: every row is masked");
+        }
+
+        let mut probs = self.value(logits).to_vec();
+        let mut loss = 0.0;
+        for (row, target) in probs.chunks_exact_mut(cols).zip(targets) {
+            softmax_in_place(row);
+            if let Some(c) = target {
+                loss -= row[*c].max(f32::MIN_POSITIVE).ln();
+            }
+        }
+
+        let requires_grad = self.requires_grad(&[logits]);
+        let op = Op::CrossEntropy {
+            logits,
+            targets: targets.to_vec(),
+            probs,
+        };
+        Ok(self.push(vec![loss / count as f32], 1, 1, op, requires_grad))
+    }
+
+    /// Mean of all elements, as a `[1, 1]` value
+    pub fn mean(&mut self, a: Var) -> Var {
+        let values = self.value(a);
+        let mean = values.iter().sum::<f32>() / values.len() as f32;
+        let requires_grad = self.requires_grad(&[a]);
+        self.push(vec![mean], 1, 1, Op::Mean(a), requires_grad)
+    }
+
+    /// Backpropagate from a scalar `loss`
+    pub fn backward(&self, loss: Var) -> Result<Gradients> {
+        if self.shape(loss) != (1, 1) {
+            anyhow::bail!("backward: loss must be a scalar, got shape {:?}", self.shape(loss));
+        }
+
+        let mut grads: Vec<Option<Vec<f32>>> = vec![None; self.nodes.len()];
+        grads[loss.0] = Some(vec![1.0]);
+
+        for index in (0..=loss.0).rev() {
+            let node = &self.nodes[index];
+            if !node.requires_grad {
+                continue;
+            }
+            let Some(grad) = grads[index].take() else {
+                continue;
+            };
+            self.backward_node(node, &grad, &mut grads);
+            grads[index] = Some(grad);
+        }
+
+        // Parameters the loss doesn't depend on get zero gradients
+        for (_, var) in &self.params {
+            if grads[var.0].is_none() {
+                grads[var.0] = Some(vec![0.0; self.nodes[var.0].value.len()]);
+            }
+        }
+
+        Ok(Gradients {
+            grads,
+            params: self.params.clone(),
+        })
+    }
+
+    /// Accumulate `grad` into `target`'s slot (if it needs one)
+    fn accumulate(&self, grads: &mut [Option<Vec<f32>>], target: Var, grad: Vec<f32>) {
+        if !self.nodes[target.0].requires_grad {
+            return;
+        }
+        match &mut grads[target.0] {
+            Some(existing) => add_into(existing, &grad),
+            slot => *slot = Some(grad),
+        }
+    }
+
+    fn backward_node(&self, node: &Node, grad: &[f32], grads: &mut [Option<Vec<f32>>]) {
+        let (rows, cols) = (node.rows, node.cols);
+
+        match &node.op {
+            Op::Leaf => {}
+            Op::MatMul(a, b) => {
+                let (m, k) = self.shape(*a);
+                let n = cols;
+                let (av, bv) = (self.value(*a), self.value(*b));
+                // dA = dC Bᵀ, dB = Aᵀ dC
+                self.accumulate(grads, *a, matmul(grad, &transpose(bv, k, n), m, n, k));
+                self.accumulate(grads, *b, matmul(&transpose(av, m, k), grad, k, m, n));
+            }
+            Op::Linear { x, weight, bias } => {
+                let in_features = self.shape(*x).1;
+                let (xv, w) = (self.value(*x), self.value(*weight));
+                // dX = dY W, dW = dYᵀ X, db = Σ_rows dY
+                self.accumulate(grads, *x, matmul(grad, w, rows, cols, in_features));
+                self.accumulate(
+                    grads,
+                    *weight,
+                    matmul(&transpose(grad, rows, cols), xv, cols, rows, in_features),
+                );
+                if let Some(b) = bias {
+                    let mut db = vec![0.0; cols];
+                    for row in grad.chunks_exact(cols) {
+                        add_into(&mut db, row);
+                    }
+                    self.accumulate(grads, *b, db);
+                }
+            }
+            Op::Add(a, b) => {
+                self.accumulate(grads, *a, grad.to_vec());
+                self.accumulate(grads, *b, grad.to_vec());
+            }
+            Op::Mul(a, b) => {
+                let (av, bv) = (self.value(*a), self.value(*b));
+                self.accumulate(grads, *a, grad.iter().zip(bv).map(|(g, y)| g * y).collect());
+                self.accumulate(grads, *b, grad.iter().zip(av).map(|(g, x)| g * x).collect());
+            }
+            Op::Scale(a, factor) => {
+                self.accumulate(grads, *a, grad.iter().map(|g| g * factor).collect());
+            }
+            Op::Gelu(a) => {
+                let dx = grad.iter().zip(self.value(*a)).map(|(g, &x)| g * gelu_derivative(x)).collect();
+                self.accumulate(grads, *a, dx);
+            }
+            Op::Silu(a) => {
+                let dx = grad
+                    .iter()
+                    .zip(self.value(*a))
+                    .map(|(g, &x)| {
+                        let s = 1.0 / (1.0 + (-x).exp());
+                        g * s * (1.0 + x * (1.0 - s))
+                    })
+                    .collect();
+                self.accumulate(grads, *a, dx);
+            }
+            Op::Softmax(a) => {
+                let mut dx = Vec::with_capacity(grad.len());
+                for (y, dy) in node.value.chunks_exact(cols).zip(grad.chunks_exact(cols)) {
+                    let inner = dot(y, dy);
+                    dx.extend(y.iter().zip(dy).map(|(y, dy)| y * (dy - inner)));
+                }
+                self.accumulate(grads, *a, dx);
+            }
+            Op::Norm {
+                x,
+                weight,
+                bias,
+                kind,
+                stats,
+            } => {
+                let (xv, w) = (self.value(*x), self.value(*weight));
+                let n = cols as f32;
+                let mut dx = Vec::with_capacity(xv.len());
+                let mut dw = vec![0.0; cols];
+                let mut db = vec![0.0; cols];
+
+                for ((row, dy), &(mean, inv_std)) in xv.chunks_exact(cols).zip(grad.chunks_exact(cols)).zip(stats) {
+                    let xhat: Vec<f32> = row.iter().map(|v| (v - mean) * inv_std).collect();
+                    let dxhat: Vec<f32> = dy.iter().zip(w).map(|(g, w)| g * w).collect();
+                    for i in 0..cols {
+                        dw[i] += dy[i] * xhat[i];
+                        db[i] += dy[i];
+                    }
+
+                    let mean_dxhat_xhat = dot(&dxhat, &xhat) / n;
+                    let mean_dxhat = match kind {
+                        NormType::RmsNorm => 0.0,
+                        NormType::LayerNorm => dxhat.iter().sum::<f32>() / n,
+                    };
+                    dx.extend(
+                        dxhat
+                            .iter()
+                            .zip(&xhat)
+                            .map(|(d, xh)| inv_std * (d - mean_dxhat - xh * mean_dxhat_xhat)),
+                    );
+                }
+
+                self.accumulate(grads, *x, dx);
+                self.accumulate(grads, *weight, dw);
+                if let Some(b) = bias {
+                    self.accumulate(grads, *b, db);
+                }
+            }
+            Op::Embedding { table, ids } => {
+                let (vocab, dim) = self.shape(*table);
+                let mut dt = vec![0.0; vocab * dim];
+                for (&id, dy) in ids.iter().zip(grad.chunks_exact(dim)) {
+                    add_into(&mut dt[id * dim..(id + 1) * dim], dy);
+                }
+                self.accumulate(grads, *table, dt);
+            }
+            Op::Rope {
+                x,
+                head_dim,
+                rope,
+                start,
+            } => {
+                let mut dx = grad.to_vec();
+                for (r, row) in dx.chunks_exact_mut(cols).enumerate() {
+                    for head in row.chunks_exact_mut(*head_dim) {
+                        rope.apply_inverse(head, start + r);
+                    }
+                }
+                self.accumulate(grads, *x, dx);
+            }
+            Op::Attention {
+                q,
+                k,
+                v,
+                n_heads,
+                n_kv_heads,
+                probs,
+            } => {
+                let t = rows;
+                let q_dim = cols;
+                let hd = q_dim / n_heads;
+                let kv_dim = n_kv_heads * hd;
+                let group = n_heads / n_kv_heads;
+                let scale = 1.0 / (hd as f32).sqrt();
+                let (qs, ks, vs) = (self.value(*q), self.value(*k), self.value(*v));
+
+                let mut dq = vec![0.0; qs.len()];
+                let mut dk = vec![0.0; ks.len()];
+                let mut dv = vec![0.0; vs.len()];
+                let mut dp = vec![0.0; t];
+
+                for h in 0..*n_heads {
+                    let kv = (h / group) * hd;
+                    for i in 0..t {
+                        let p = &probs[(h * t + i) * t..(h * t + i) * t + i + 1];
+                        let d_out = &grad[i * q_dim + h * hd..i * q_dim + (h + 1) * hd];
+
+                        // dV_j += P_ij dO_i ; dP_ij = dO_i · V_j
+                        for (j, &w) in p.iter().enumerate() {
+                            let v_off = j * kv_dim + kv;
+                            for (dv, g) in dv[v_off..v_off + hd].iter_mut().zip(d_out) {
+                                *dv += w * g;
+                            }
+                            dp[j] = dot(d_out, &vs[v_off..v_off + hd]);
+                        }
+
+                        // Softmax backward: dS_ij = P_ij (dP_ij - Σ_j P_ij dP_ij)
+                        let inner = dot(p, &dp[..=i]);
+                        let q_off = i * q_dim + h * hd;
+                        for (j, &w) in p.iter().enumerate() {
+                            let ds = w * (dp[j] - inner) * scale;
+                            let k_off = j * kv_dim + kv;
+                            for d in 0..hd {
+                                dq[q_off + d] += ds * ks[k_off + d];
+                                dk[k_off + d] += ds * qs[q_off + d];
+                            }
+                        }
+                    }
+                }
+
+                self.accumulate(grads, *q, dq);
+                self.accumulate(grads, *k, dk);
+                self.accumulate(grads, *v, dv);
+            }
+            Op::CrossEntropy {
+                logits,
+                targets,
+                probs,
+            } => {
+                let classes = self.shape(*logits).1;
+                let count = targets.iter().flatten().count() as f32;
+                let g = grad[0] / count;
+                let mut dl = vec![0.0; probs.len()];
+                for ((d, p), target) in dl.chunks_exact_mut(classes).zip(probs.chunks_exact(classes)).zip(targets) {
+                    if let Some(c) = target {
+                        for (d, p) in d.iter_mut().zip(p) {
+                            *d = p * g;
+                        }
+                        d[*c] -= g;
+                    }
+                }
+                self.accumulate(grads, *logits, dl);
+            }
+            Op::Mean(a) => {
+                let n = self.value(*a).len();
+                self.accumulate(grads, *a, vec![grad[0] / n as f32; n]);
+            }
+        }
+    }
+}
+
+/// Result of `check_gradients`
+#[derive(Debug, Clone)]
+pub struct GradCheckReport {
+    /// Largest |analytic - numeric|
+    pub max_abs_error: f32,
+    /// Largest |analytic - numeric| / max(|analytic| + |numeric|, 1e-3)
+    pub max_rel_error: f32,
+    /// Parameter and element index of the largest relative error
+    pub worst: Option<(String, usize)>,
+    /// Number of elements compared
+    pub checked: usize,
+}
+
+impl GradCheckReport {
+    pub fn passed(&self, tolerance: f32) -> bool {
+        self.max_rel_error <= tolerance
+    }
+}
+
+/// Compare tape gradients with central finite differences
+///
+/// `params` holds (name, rows, cols, values). `build` must register them on
+/// the tape with `Tape::param` (in order, using the given names and values)
+/// and return a scalar loss.
+pub fn check_gradients<F>(params: &[(String, usize, usize, Vec<f32>)], epsilon: f32, build: F) -> Result<GradCheckReport>
+where
+    F: Fn(&mut Tape, &[Var]) -> Result<Var>,
+{
+    let evaluate = |values: &[Vec<f32>]| -> Result<(Tape, Var)> {
+        let mut tape = Tape::new();
+        let vars: Vec<Var> = params
+            .iter()
+            .zip(values)
+            .map(|((name, rows, cols, _), v)| tape.param(name.clone(), v.clone(), *rows, *cols))
+            .collect();
+        let loss = build(&mut tape, &vars)?;
+        Ok((tape, loss))
+    };
+
+    let mut values: Vec<Vec<f32>> = params.iter().map(|p| p.3.clone()).collect();
+    let (tape, loss) = evaluate(&values)?;
+    let analytic = tape.backward(loss)?;
+
+    let mut report = GradCheckReport {
+        max_abs_error: 0.0,
+        max_rel_error: 0.0,
+        worst: None,
+        checked: 0,
+    };
+
+    for (p, (name, ..)) in params.iter().enumerate() {
+        let grad = analytic
+            .get(name)
+            .ok_or_else(|| anyhow::anyhow!("No gradient for parameter {}", name))?
+            .to_vec();
+
+        for i in 0..values[p].len() {
+            let original = values[p][i];
+            values[p][i] = original + epsilon;
+            let (t, l) = evaluate(&values)?;
+            let plus = t.scalar(l);
+            values[p][i] = original - epsilon;
+            let (t, l) = evaluate(&values)?;
+            let minus = t.scalar(l);
+            values[p][i] = original;
+
+            let numeric = (plus - minus) / (2.0 * epsilon);
+            let abs_error = (grad[i] - numeric).abs();
+            let rel_error = abs_error / (grad[i].abs() + numeric.abs()).max(1e-3);
+
+            report.max_abs_error = report.max_abs_error.max(abs_error);
+            if rel_error > report.max_rel_error {
+                report.max_rel_error = rel_error;
+                report.worst = Some((name.clone(), i));
+            }
+            report.checked += 1;
+        }
+    }
+
+    Ok(report)
+}
+
+/// Derivative of the tanh-approximated GELU
+fn gelu_derivative(x: f32) -> f32 {
+    let c = (2.0 / std::f32::consts::PI).sqrt();
+    let u = c * (x + 0.044715 * x.powi(3));
+    let t = u.tanh();
+    0.5 * (1.0 + t) + 0.5 * x * (1.0 - t * t) * c * (1.0 + 3.0 * 0.044715 * x * x)
+}
+
+/// a [m, k] × b [k, n]
+fn matmul(a: &[f32], b: &[f32], m: usize, k: usize, n: usize) -> Vec<f32> {
+    let mut out = vec![0.0; m * n];
+    for i in 0..m {
+        let row = &mut out[i * n..(i + 1) * n];
+        for p in 0..k {
+            let a_ip = a[i * k + p];
+            if a_ip == 0.0 {
+                continue;
+            }
+            for (o, b) in row.iter_mut().zip(&b[p * n..(p + 1) * n]) {
+                *o += a_ip * b;
+            }
+        }
+    }
+    out
+}
+
+fn transpose(a: &[f32], rows: usize, cols: usize) -> Vec<f32> {
+    let mut out = vec![0.0; a.len()];
+    for r in 0..rows {
+        for c in 0..cols {
+            out[c * rows + r] = a[r * cols + c];
+        }
+    }
+    out
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+fn add_into(a: &mut [f32], b: &[f32]) {
+    for (x, y) in a.iter_mut().zip(b) {
+        *x += y;
+    }
+}
+
+fn softmax_in_place(x: &mut [f32]) {
+    let max = x.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+    let mut sum = 0.0;
+    for v in x.iter_mut() {
+        *v = (*v - max).exp();
+        sum += *v;
+    }
+    for v in x.iter_mut() {
+        *v /= sum;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic values in [-0.5, 0.5)
+    fn values(n: usize, seed: u32) -> Vec<f32> {
+        let mut state = seed;
+        (0..n)
+            .map(|_| {
+                state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+                (state >> 8) as f32 / (1u32 << 24) as f32 - 0.5
+            })
+            .collect()
+    }
+
+    fn param(name: &str, rows: usize, cols: usize, seed: u32) -> (String, usize, usize, Vec<f32>) {
+        (name.to_string(), rows, cols, values(rows * cols, seed))
+    }
+
+    #[test]
+    fn test_matmul_and_linear_gradients() {
+        let params = [param("a", 3, 4, 1), param("b", 4, 2, 2), param("w", 5, 2, 3), param("bias", 1, 5, 4)];
+        let report = check_gradients(&params, 1e-2, |tape, v| {
+            let h = tape.matmul(v[0], v[1])?;
+            let y = tape.linear(h, v[2], Some(v[3]))?;
+            let y = tape.mul(y, y)?;
+            Ok(tape.mean(y))
+        })
+        .unwrap();
+        assert_eq!(report.checked, 12 + 8 + 10 + 5);
+        assert!(report.passed(1e-2), "{:?}", report);
+    }
+
+    #[test]
+    fn test_norm_activation_softmax_gradients() {
+        for kind in [NormType::RmsNorm, NormType::LayerNorm] {
+            let params = [param("x", 3, 6, 5), param("g", 1, 6, 6), param("b", 1, 6, 7), param("t", 3, 6, 8)];
+            let report = check_gradients(&params, 1e-2, |tape, v| {
+                let h = tape.norm(v[0], v[1], Some(v[2]), kind, 1e-5)?;
+                let a = tape.gelu(h);
+                let s = tape.silu(h);
+                let h = tape.add(a, s)?;
+                let h = tape.scale(h, 1.5);
+                let p = tape.softmax(h);
+                let weighted = tape.mul(p, v[3])?;
+                Ok(tape.mean(weighted))
+            })
+            .unwrap();
+            assert!(report.passed(2e-2), "{:?}: {:?}", kind, report);
+        }
+    }
+
+    #[test]
+    fn test_attention_block_gradients() {
+        // Embedding → RoPE'd grouped-query causal attention → cross-entropy
+        let (vocab, dim, hd) = (7, 8, 2);
+        let params = [
+            param("embed", vocab, dim, 9),
+            param("q", 4 * hd, dim, 10),
+            param("k", 2 * hd, dim, 11),
+            param("v", 2 * hd, dim, 12),
+            param("o", vocab, 4 * hd, 13),
+        ];
+        let ids = [3, 1, 4, 1];
+        let targets = [None, Some(4), Some(1), Some(5)];
+
+        let report = check_gradients(&params, 1e-2, |tape, v| {
+            let x = tape.embedding(v[0], &ids)?;
+            let q = tape.linear(x, v[1], None)?;
+            let q = tape.rope(q, hd, 10000.0, 0)?;
+            let k = tape.linear(x, v[2], None)?;
+            let k = tape.rope(k, hd, 10000.0, 0)?;
+            let val = tape.linear(x, v[3], None)?;
+            let ctx = tape.attention(q, k, val, 4, 2)?;
+            let logits = tape.linear(ctx, v[4], None)?;
+            tape.cross_entropy(logits, &targets)
+        })
+        .unwrap();
+        assert!(report.passed(2e-2), "{:?}", report);
+    }
+
+    #[test]
+    fn test_cross_entropy_masking_and_unused_params() {
+        let mut tape = Tape::new();
+        let logits = tape.param("logits", vec![0.0, 0.0, 5.0, -5.0], 2, 2);
+        tape.param("unused", vec![1.0, 2.0], 1, 2);
+        let loss = tape.cross_entropy(logits, &[None, Some(1)]).unwrap();
+        assert!((tape.scalar(loss) - 10.0).abs() < 1e-3);
+
+        let grads = tape.backward(loss).unwrap();
+        let g = grads.get("logits").unwrap();
+        // Masked row gets no gradient
+        assert_eq!(&g[..2], &[0.0, 0.0]);
+        assert!(g[2] > 0.0 && g[3] < 0.0);
+        assert_eq!(grads.into_named()["unused"], vec![0.0, 0.0]);
+
+        assert!(tape.cross_entropy(logits, &[None, None]).is_err());
+    }
+
+    #[test]
+    fn test_shape_errors() {
+        let mut tape = Tape::new();
+        let a = tape.constant(vec![1.0; 6], 2, 3);
+        let b = tape.constant(vec![1.0; 6], 2, 3);
+        assert!(tape.matmul(a, b).is_err());
+        assert!(tape.embedding(a, &[2]).is_err());
+        assert!(tape.backward(a).is_err());
+    }
+}
@@ -0,0 +1,440 @@
+//! Resumable training checkpoints
+//!
+//! A checkpoint is a directory `checkpoint-{step:08}` holding:
+//! - `model.safetensors`: every model weight (HF names, f32)
+//! - `optimizer.safetensors`: optimizer moments, keyed "{slot}/{parameter}"
+//! - `state.json`: `TrainerState` (counters, scheduler, seed, buffer, stats)
+//! - `adapter.safetensors`: the active LoRA adapter, if one was training
+//! - `anchor.safetensors`: the regularizer's reference weights, keyed
+//!   "weights/{parameter}" (and EWC's "fisher/{parameter}"), if captured
+//!
+//! Checkpoints are written to a hidden temporary directory and renamed into
+//! place, so a crash mid-write never leaves a partial checkpoint behind.
+//! `CheckpointManager` keeps only the newest `keep_last` checkpoints.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::lora::LoraAdapter;
+use super::online_learning::TrainingExample;
+use super::optimizer::{OptimizerState, SchedulerState};
+use super::regularizer::Anchor;
+use super::weight_loader::{SaveOptions, WeightFormat, WeightLoader};
+
+/// Current `state.json` layout version
+pub const CHECKPOINT_VERSION: u32 = 1;
+
+const MODEL_FILE: &str = "model.safetensors";
+const OPTIMIZER_FILE: &str = "optimizer.safetensors";
+const STATE_FILE: &str = "state.json";
+const ADAPTER_FILE: &str = "adapter.safetensors";
+const ANCHOR_FILE: &str = "anchor.safetensors";
+const DIR_PREFIX: &str = "checkpoint-";
+
+/// Everything except tensors: `state.json`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrainerState {
+    pub version: u32,
+    /// Completed weight updates
+    pub step: usize,
+    /// Unix seconds at save time
+    pub created_at: u64,
+    /// `OptimizerKind::name` of the optimizer that owns the moments
+    pub optimizer: String,
+    pub optimizer_step_count: usize,
+    pub optimizer_param_steps: HashMap<String, usize>,
+    pub lr_schedule: String,
+    pub scheduler: SchedulerState,
+    /// Seed the learner derives its per-update randomness from
+    pub seed: u64,
+    pub buffer: Vec<TrainingExample>,
+    /// Last training loss of each buffered example (None = not trained yet)
+    #[serde(default)]
+    pub buffer_losses: Vec<Option<f32>>,
+    pub total_examples: usize,
+    pub skipped_updates: usize,
+    pub recent_losses: Vec<f32>,
+    pub last_error: Option<String>,
+    /// Name of the adapter being trained (its tensors are in `adapter.safetensors`)
+    #[serde(default)]
+    pub adapter: Option<String>,
+}
+
+/// A checkpoint in memory
+pub struct Checkpoint {
+    pub state: TrainerState,
+    pub model: WeightLoader,
+    pub optimizer: OptimizerState,
+    /// Active adapter; `model` then holds the frozen base weights
+    pub adapter: Option<LoraAdapter>,
+    /// Regularizer reference weights, once captured
+    pub anchor: Option<Anchor>,
+}
+
+impl std::fmt::Debug for Checkpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Checkpoint")
+            .field("step", &self.state.step)
+            .field("model_tensors", &self.model.tensor_names().len())
+            .field("optimizer_tensors", &self.optimizer.tensors.len())
+            .field("adapter", &self.state.adapter)
+            .finish()
+    }
+}
+
+/// Summary of a checkpoint on disk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckpointInfo {
+    pub path: PathBuf,
+    pub step: usize,
+    pub created_at: u64,
+    pub optimizer: String,
+    pub buffer_size: usize,
+    /// Mean of the recent losses recorded in the checkpoint
+    pub average_loss: Option<f32>,
+    /// Adapter that was training, if any
+    pub adapter: Option<String>,
+}
+
+impl CheckpointInfo {
+    pub(crate) fn from_state(path: PathBuf, state: &TrainerState) -> Self {
+        let average_loss = if state.recent_losses.is_empty() {
+            None
+        } else {
+            Some(state.recent_losses.iter().sum::<f32>() / state.recent_losses.len() as f32)
+        };
+
+        Self {
+            path,
+            step: state.step,
+            created_at: state.created_at,
+            optimizer: state.optimizer.clone(),
+            buffer_size: state.buffer.len(),
+            average_loss,
+            adapter: state.adapter.clone(),
+        }
+    }
+}
+
+impl Checkpoint {
+    /// Write into directory `path` atomically (replacing an existing checkpoint there)
+    pub fn write(&self, path: &Path) -> Result<CheckpointInfo> {
+        let name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .ok_or_else(|| anyhow::anyhow!("Invalid checkpoint path {}", path.display()))?;
+        let parent = path.parent().unwrap_or_else(|| Path::new("."));
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create checkpoint directory {}", parent.display()))?;
+
+        let tmp = parent.join(format!(".{}.tmp", name));
+        if tmp.exists() {
+            fs::remove_dir_all(&tmp)?;
+        }
+        fs::create_dir(&tmp)?;
+
+        self.model
+            .save_with_options(tmp.join(MODEL_FILE), WeightFormat::SafeTensors, &SaveOptions::default())?;
+
+        let mut moments = WeightLoader::new(WeightFormat::SafeTensors);
+        for (key, values) in &self.optimizer.tensors {
+            moments.insert_tensor_with_shape(key.clone(), vec![values.len()], values.clone())?;
+        }
+        moments.save_with_options(tmp.join(OPTIMIZER_FILE), WeightFormat::SafeTensors, &SaveOptions::default())?;
+
+        fs::write(tmp.join(STATE_FILE), serde_json::to_vec_pretty(&self.state)?)?;
+
+        let mut files = vec![MODEL_FILE, OPTIMIZER_FILE, STATE_FILE];
+        if let Some(adapter) = &self.adapter {
+            adapter.save(tmp.join(ADAPTER_FILE))?;
+            files.push(ADAPTER_FILE);
+        }
+        if let Some(anchor) = &self.anchor {
+            let mut tensors = WeightLoader::new(WeightFormat::SafeTensors);
+            for (prefix, map) in [("weights", &anchor.weights), ("fisher", &anchor.fisher)] {
+                for (name, values) in map {
+                    tensors.insert_tensor_with_shape(format!("{}/{}", prefix, name), vec![values.len()], values.clone())?;
+                }
+            }
+            tensors.save_with_options(tmp.join(ANCHOR_FILE), WeightFormat::SafeTensors, &SaveOptions::default())?;
+            files.push(ANCHOR_FILE);
+        }
+        for file in files {
+            fs::File::open(tmp.join(file))?.sync_all()?;
+        }
+
+        if path.exists() {
+            fs::remove_dir_all(path)?;
+        }
+        fs::rename(&tmp, path)
+            .with_context(|| format!("Failed to move checkpoint into {}", path.display()))?;
+
+        Ok(CheckpointInfo::from_state(path.to_path_buf(), &self.state))
+    }
+
+    /// Read a checkpoint directory
+    pub fn read(path: &Path) -> Result<Self> {
+        let state = read_state(path)?;
+
+        let mut model = WeightLoader::new(WeightFormat::SafeTensors);
+        model.load_from_file(path.join(MODEL_FILE))?;
+
+        let mut moments = WeightLoader::new(WeightFormat::SafeTensors);
+        moments.load_from_file(path.join(OPTIMIZER_FILE))?;
+        let tensors = moments
+            .tensor_names()
+            .into_iter()
+            .map(|name| {
+                let values = moments.get_tensor(&name).cloned().unwrap_or_default();
+                (name, values)
+            })
+            .collect();
+
+        let optimizer = OptimizerState {
+            step_count: state.optimizer_step_count,
+            param_steps: state.optimizer_param_steps.clone(),
+            tensors,
+        };
+
+        let adapter = match &state.adapter {
+            Some(_) => Some(LoraAdapter::load(path.join(ADAPTER_FILE))?),
+            None => None,
+        };
+
+        let anchor = if path.join(ANCHOR_FILE).exists() {
+            let mut tensors = WeightLoader::new(WeightFormat::SafeTensors);
+            tensors.load_from_file(path.join(ANCHOR_FILE))?;
+            let mut anchor = Anchor::default();
+            for key in tensors.tensor_names() {
+                let values = tensors.get_tensor(&key).cloned().unwrap_or_default();
+                match key.split_once('/') {
+                    Some(("weights", name)) => anchor.weights.insert(name.to_string(), values),
+                    Some(("fisher", name)) => anchor.fisher.insert(name.to_string(), values),
+                    _ => anyhow::bail!("Unexpected tensor {} in {}", key, ANCHOR_FILE),
+                };
+            }
+            Some(anchor)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            state,
+            model,
+            optimizer,
+            adapter,
+            anchor,
+        })
+    }
+}
+
+fn read_state(path: &Path) -> Result<TrainerState> {
+    let file = path.join(STATE_FILE);
+    let bytes = fs::read(&file).with_context(|| format!("Failed to read {}", file.display()))?;
+    let state: TrainerState =
+        serde_json::from_slice(&bytes).with_context(|| format!("Invalid checkpoint state {}", file.display()))?;
+    if state.version != CHECKPOINT_VERSION {
+        anyhow::bail!(
+            "Checkpoint {} has version {}, expected {}",
+            path.display(),
+            state.version,
+            CHECKPOINT_VERSION
+        );
+    }
+    Ok(state)
+}
+
+/// Numbered checkpoints in one directory, with rotation
+#[derive(Debug, Clone)]
+pub struct CheckpointManager {
+    dir: PathBuf,
+    /// Checkpoints to keep (0 = keep all)
+    keep_last: usize,
+}
+
+impl CheckpointManager {
+    pub fn new(dir: impl Into<PathBuf>, keep_last: usize) -> Self {
+        Self {
+            dir: dir.into(),
+            keep_last,
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Directory a checkpoint for `step` is written to
+    pub fn path_for(&self, step: usize) -> PathBuf {
+        self.dir.join(format!("{}{:08}", DIR_PREFIX, step))
+    }
+
+    /// Write `checkpoint` under its step, then drop the oldest beyond `keep_last`
+    pub fn save(&self, checkpoint: &Checkpoint) -> Result<CheckpointInfo> {
+        let info = checkpoint.write(&self.path_for(checkpoint.state.step))?;
+        self.rotate()?;
+        Ok(info)
+    }
+
+    /// Complete checkpoints, oldest first (temporary directories are ignored)
+    pub fn list(&self) -> Result<Vec<CheckpointInfo>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut checkpoints = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            let is_checkpoint = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.strip_prefix(DIR_PREFIX).is_some_and(|s| s.parse::<usize>().is_ok()));
+            if !is_checkpoint || !path.join(STATE_FILE).exists() {
+                continue;
+            }
+
+            match read_state(&path) {
+                Ok(state) => checkpoints.push(CheckpointInfo::from_state(path, &state)),
+                Err(e) => tracing::warn!("Skipping unreadable checkpoint {}: {}", path.display(), e),
+            }
+        }
+
+        checkpoints.sort_by_key(|c| c.step);
+        Ok(checkpoints)
+    }
+
+    pub fn latest(&self) -> Result<Option<CheckpointInfo>> {
+        Ok(self.list()?.pop())
+    }
+
+    /// Checkpoint saved for `step`, if any
+    pub fn find(&self, step: usize) -> Result<Option<CheckpointInfo>> {
+        Ok(self.list()?.into_iter().find(|c| c.step == step))
+    }
+
+    fn rotate(&self) -> Result<()> {
+        if self.keep_last == 0 {
+            return Ok(());
+        }
+
+        let checkpoints = self.list()?;
+        let excess = checkpoints.len().saturating_sub(self.keep_last);
+        for old in &checkpoints[..excess] {
+            fs::remove_dir_all(&old.path)
+                .with_context(|| format!("Failed to remove old checkpoint {}", old.path.display()))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checkpoint(step: usize) -> Checkpoint {
+        let mut model = WeightLoader::new(WeightFormat::SafeTensors);
+        model
+            .insert_tensor_with_shape("model.embed_tokens.weight".to_string(), vec![2, 2], vec![1.0, 2.0, 3.0, 4.0])
+            .unwrap();
+
+        let mut optimizer = OptimizerState {
+            step_count: step,
+            ..OptimizerState::default()
+        };
+        optimizer.param_steps.insert("model.embed_tokens.weight".to_string(), step);
+        optimizer
+            .tensors
+            .insert("momentum/model.embed_tokens.weight".to_string(), vec![0.1, 0.2, 0.3, 0.4]);
+
+        Checkpoint {
+            state: TrainerState {
+                version: CHECKPOINT_VERSION,
+                step,
+                created_at: 1_700_000_000,
+                optimizer: "adamw".to_string(),
+                optimizer_step_count: step,
+                optimizer_param_steps: optimizer.param_steps.clone(),
+                lr_schedule: "constant".to_string(),
+                scheduler: SchedulerState {
+                    base_lr: 1e-3,
+                    scale: 0.5,
+                    best_loss: Some(1.25),
+                    bad_updates: 1,
+                },
+                seed: 7,
+                buffer: vec![TrainingExample::new("in".to_string(), Some("out".to_string()))],
+                buffer_losses: vec![Some(0.5)],
+                total_examples: 3,
+                skipped_updates: 0,
+                recent_losses: vec![2.0, 1.0],
+                last_error: None,
+                adapter: None,
+            },
+            model,
+            optimizer,
+            adapter: None,
+            anchor: None,
+        }
+    }
+
+    #[test]
+    fn test_write_read_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("checkpoint-00000004");
+
+        let mut original = checkpoint(4);
+        let mut anchor = Anchor::default();
+        anchor.capture("model.embed_tokens.weight", &[1.0, 2.0, 3.0, 4.0]);
+        anchor.accumulate_fisher("model.embed_tokens.weight", &[0.5, 0.0, 1.0, 2.0], 1.0);
+        original.anchor = Some(anchor);
+        let info = original.write(&path).unwrap();
+        assert_eq!(info.step, 4);
+        assert_eq!(info.average_loss, Some(1.5));
+
+        let loaded = Checkpoint::read(&path).unwrap();
+        assert_eq!(loaded.state, original.state);
+        assert_eq!(loaded.optimizer, original.optimizer);
+        assert_eq!(loaded.anchor, original.anchor);
+        assert_eq!(
+            loaded.model.get_tensor("model.embed_tokens.weight"),
+            original.model.get_tensor("model.embed_tokens.weight")
+        );
+
+        // No temporary directory is left behind
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_manager_rotates_and_ignores_partial_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = CheckpointManager::new(dir.path(), 2);
+        assert!(manager.latest().unwrap().is_none());
+
+        for step in [10, 20, 30] {
+            manager.save(&checkpoint(step)).unwrap();
+        }
+        // A crash mid-write leaves only a hidden temporary directory
+        fs::create_dir(dir.path().join(".checkpoint-00000040.tmp")).unwrap();
+
+        let steps: Vec<usize> = manager.list().unwrap().iter().map(|c| c.step).collect();
+        assert_eq!(steps, vec![20, 30]);
+        assert_eq!(manager.latest().unwrap().unwrap().step, 30);
+        assert!(manager.find(10).unwrap().is_none());
+        assert!(manager.find(20).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_rejects_unknown_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("checkpoint-00000001");
+        let mut old = checkpoint(1);
+        old.state.version = CHECKPOINT_VERSION + 1;
+        old.write(&path).unwrap();
+
+        let err = Checkpoint::read(&path).unwrap_err().to_string();
+        assert!(err.contains("version"));
+    }
+}
@@ -0,0 +1,315 @@
+//! Training data ingestion for offline training
+//!
+//! Reads three formats into `TrainingExample`s:
+//! - JSONL with `{"prompt", "completion"}` pairs (or `{"text"}` for
+//!   self-supervised lines), optionally with a `"weight"`
+//! - JSONL chat records `{"messages": [{"role", "content"}, ...]}`: every
+//!   assistant turn becomes an example whose prompt is the conversation so far
+//! - icarus-core `TraceDataset` JSON: every chunk of every trace becomes a
+//!   prompt/output example
+//!
+//! A `Dataset` can be shuffled, split into train/validation sets and grouped
+//! into batches of similar token length.
+
+use anyhow::{Context, Result};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use serde::Deserialize;
+use std::path::Path;
+
+use super::online_learning::TrainingExample;
+
+/// One line of a prompt/completion or chat JSONL file
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum JsonlRecord {
+    Chat {
+        messages: Vec<ChatMessage>,
+        #[serde(default)]
+        weight: Option<f32>,
+    },
+    PromptCompletion {
+        prompt: String,
+        completion: String,
+        #[serde(default)]
+        weight: Option<f32>,
+    },
+    Text {
+        text: String,
+        #[serde(default)]
+        weight: Option<f32>,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatMessage {
+    role: String,
+    content: String,
+}
+
+/// The parts of an icarus-core `TraceDataset` that training needs
+#[derive(Debug, Deserialize)]
+struct TraceDatasetJson {
+    traces: Vec<TraceJson>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TraceJson {
+    chunks: Vec<TraceChunkJson>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TraceChunkJson {
+    prompt: String,
+    output: String,
+}
+
+/// Training examples loaded from one or more files
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Dataset {
+    examples: Vec<TrainingExample>,
+}
+
+impl Dataset {
+    pub fn new(examples: Vec<TrainingExample>) -> Self {
+        Self { examples }
+    }
+
+    /// Load a file: `.json` is read as a `TraceDataset`, anything else as JSONL
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+
+        let parsed = match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => Self::from_trace_json(&text),
+            _ => Self::from_jsonl(&text),
+        };
+        parsed.with_context(|| format!("Invalid dataset {}", path.display()))
+    }
+
+    /// Parse prompt/completion, text and chat records, one JSON object per line
+    pub fn from_jsonl(text: &str) -> Result<Self> {
+        let mut examples = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record: JsonlRecord = serde_json::from_str(line).with_context(|| {
+                format!("Line {}: expected prompt/completion, text or messages", i + 1)
+            })?;
+
+            match record {
+                JsonlRecord::Chat { messages, weight } => {
+                    for example in chat_examples(&messages) {
+                        examples.push(example.with_weight(weight.unwrap_or(1.0)));
+                    }
+                }
+                JsonlRecord::PromptCompletion {
+                    prompt,
+                    completion,
+                    weight,
+                } => examples.push(TrainingExample::new(prompt, Some(completion)).with_weight(weight.unwrap_or(1.0))),
+                JsonlRecord::Text { text, weight } => {
+                    examples.push(TrainingExample::new(text, None).with_weight(weight.unwrap_or(1.0)))
+                }
+            }
+        }
+        Ok(Self { examples })
+    }
+
+    /// Parse an icarus-core `TraceDataset` saved with `save_json`
+    pub fn from_trace_json(text: &str) -> Result<Self> {
+        let dataset: TraceDatasetJson = serde_json::from_str(text).context("Expected a TraceDataset")?;
+        let examples = dataset
+            .traces
+            .into_iter()
+            .flat_map(|trace| trace.chunks)
+            .filter(|chunk| !chunk.output.is_empty())
+            .map(|chunk| TrainingExample::new(chunk.prompt, Some(chunk.output)))
+            .collect();
+        Ok(Self { examples })
+    }
+
+    pub fn len(&self) -> usize {
+        self.examples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.examples.is_empty()
+    }
+
+    pub fn examples(&self) -> &[TrainingExample] {
+        &self.examples
+    }
+
+    /// Append the examples of `other`
+    pub fn extend(&mut self, other: Dataset) {
+        self.examples.extend(other.examples);
+    }
+
+    /// Shuffle in place (deterministic for a given seed)
+    pub fn shuffle(&mut self, seed: u64) {
+        self.examples.shuffle(&mut StdRng::seed_from_u64(seed));
+    }
+
+    /// Split off the last `validation_fraction` of the examples as a
+    /// validation set; shuffle first for a random split
+    pub fn split(mut self, validation_fraction: f32) -> Result<(Dataset, Dataset)> {
+        if !(0.0..1.0).contains(&validation_fraction) {
+            anyhow::bail!("Validation fraction must be in [0, 1), got {}", validation_fraction);
+        }
+        let validation_len = (self.examples.len() as f32 * validation_fraction).round() as usize;
+        let validation = self.examples.split_off(self.examples.len() - validation_len);
+        Ok((self, Dataset::new(validation)))
+    }
+
+    /// Drop examples for which `count_tokens` exceeds `max_tokens`; returns how many were dropped
+    pub fn retain_max_tokens(&mut self, max_tokens: usize, count_tokens: impl Fn(&TrainingExample) -> usize) -> usize {
+        let before = self.examples.len();
+        self.examples.retain(|example| count_tokens(example) <= max_tokens);
+        before - self.examples.len()
+    }
+
+    /// Batches of examples of similar token length, each holding at most
+    /// `max_batch_tokens` tokens (or a single longer example)
+    ///
+    /// Examples are sorted by length so a batch does not mix very short and
+    /// very long sequences; the batch order is then shuffled with `seed`.
+    pub fn batches(
+        &self,
+        max_batch_tokens: usize,
+        seed: u64,
+        count_tokens: impl Fn(&TrainingExample) -> usize,
+    ) -> Vec<Vec<TrainingExample>> {
+        let mut by_length: Vec<(usize, &TrainingExample)> =
+            self.examples.iter().map(|example| (count_tokens(example), example)).collect();
+        by_length.sort_by_key(|(len, _)| *len);
+
+        let mut batches = Vec::new();
+        let mut batch = Vec::new();
+        let mut batch_tokens = 0;
+        for (len, example) in by_length {
+            if !batch.is_empty() && batch_tokens + len > max_batch_tokens {
+                batches.push(std::mem::take(&mut batch));
+                batch_tokens = 0;
+            }
+            batch.push(example.clone());
+            batch_tokens += len;
+        }
+        if !batch.is_empty() {
+            batches.push(batch);
+        }
+
+        batches.shuffle(&mut StdRng::seed_from_u64(seed));
+        batches
+    }
+}
+
+/// One example per assistant turn, prompted with the conversation before it
+///
+/// Turns are rendered as "Role: content" lines and the prompt ends with
+/// "Assistant:", so the completion is the assistant's reply.
+fn chat_examples(messages: &[ChatMessage]) -> Vec<TrainingExample> {
+    let mut examples = Vec::new();
+    let mut transcript = String::new();
+    for message in messages {
+        let role = match message.role.as_str() {
+            "system" => "System",
+            "user" => "User",
+            "assistant" => "Assistant",
+            other => other,
+        };
+        if message.role == "assistant" {
+            examples.push(TrainingExample::new(
+                format!("{}Assistant:", transcript),
+                Some(format!(" {}", message.content)),
+            ));
+        }
+        transcript.push_str(&format!("{}: {}\n", role, message.content));
+    }
+    examples
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jsonl_formats() {
+        let text = r#"{"prompt": "2+2=", "completion": " 4"}
+
+{"text": "plain text", "weight": 0.5}
+{"messages": [{"role": "system", "content": "Be brief."}, {"role": "user", "content": "Hi"}, {"role": "assistant", "content": "Hello"}, {"role": "user", "content": "Bye"}, {"role": "assistant", "content": "Goodbye"}]}
+"#;
+        let dataset = Dataset::from_jsonl(text).unwrap();
+        let examples = dataset.examples();
+        assert_eq!(examples.len(), 4);
+        assert_eq!(examples[0], TrainingExample::new("2+2=".into(), Some(" 4".into())));
+        assert_eq!(examples[1], TrainingExample::new("plain text".into(), None).with_weight(0.5));
+        assert_eq!(examples[2].input, "System: Be brief.\nUser: Hi\nAssistant:");
+        assert_eq!(examples[2].target.as_deref(), Some(" Hello"));
+        assert_eq!(
+            examples[3].input,
+            "System: Be brief.\nUser: Hi\nAssistant: Hello\nUser: Bye\nAssistant:"
+        );
+
+        let err = Dataset::from_jsonl("{\"question\": \"?\"}").unwrap_err();
+        assert!(err.to_string().contains("Line 1"));
+    }
+
+    #[test]
+    fn test_trace_dataset_json() {
+        let text = r#"{
+            "traces": [{
+                "id": "00000000-0000-0000-0000-000000000000",
+                "problem": "What is 2+2?",
+                "chunks": [
+                    {"index": 1, "prompt": "What is 2+2?", "output": "Adding...", "tokens": 3, "timestamp": "2024-01-01T00:00:00Z", "latency_ms": 5},
+                    {"index": 2, "prompt": "Adding...", "output": "4 [EOS]", "tokens": 2, "timestamp": "2024-01-01T00:00:01Z", "latency_ms": 5}
+                ],
+                "solution": "4",
+                "completed": true,
+                "termination_reason": "SolutionFound",
+                "total_tokens": 5
+            }],
+            "metadata": {"name": "t", "created": "2024-01-01T00:00:00Z", "total_traces": 1, "total_tokens": 5}
+        }"#;
+        let dataset = Dataset::from_trace_json(text).unwrap();
+        assert_eq!(dataset.len(), 2);
+        assert_eq!(dataset.examples()[1], TrainingExample::new("Adding...".into(), Some("4 [EOS]".into())));
+    }
+
+    #[test]
+    fn test_shuffle_split_and_length_batches() {
+        let examples = (0..10)
+            .map(|i| TrainingExample::new("x".repeat(i + 1), None))
+            .collect();
+        let mut dataset = Dataset::new(examples);
+        let original = dataset.clone();
+
+        dataset.shuffle(3);
+        assert_ne!(dataset, original);
+        let mut again = original.clone();
+        again.shuffle(3);
+        assert_eq!(dataset, again);
+
+        let (train, validation) = dataset.split(0.2).unwrap();
+        assert_eq!((train.len(), validation.len()), (8, 2));
+        assert!(original.clone().split(1.0).is_err());
+
+        let count = |e: &TrainingExample| e.input.len();
+        let batches = original.batches(6, 0, count);
+        assert_eq!(batches.iter().map(Vec::len).sum::<usize>(), 10);
+        for batch in &batches {
+            let tokens: usize = batch.iter().map(count).sum();
+            assert!(tokens <= 6 || batch.len() == 1, "{:?}", batch);
+        }
+        // Sorting by length puts the short examples together
+        assert!(batches.iter().any(|b| b.len() == 3 && b.iter().map(count).sum::<usize>() == 6));
+
+        let mut short = original;
+        assert_eq!(short.retain_max_tokens(4, count), 6);
+        assert_eq!(short.len(), 4);
+    }
+}
@@ -0,0 +1,468 @@
+//! Evaluation of learned weights
+//!
+//! An `Evaluator` measures a model on:
+//! - a held-out `Dataset`: mean next-token loss over the scored (target)
+//!   tokens, perplexity and greedy token accuracy, with the same prompt
+//!   masking as training
+//! - optionally, reasoning tasks: each problem is solved greedily with the
+//!   Markovian chunk loop and the extracted solution is compared with the
+//!   expected answer
+//!
+//! `RegressionThresholds` turn two sets of metrics into a list of
+//! regressions, and a `RegressionGate` keeps baseline metrics so that
+//! `OnlineLearner` can refuse to checkpoint (and `save_weights` to save)
+//! weights that got worse.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use super::dataset::Dataset;
+use super::online_learning::OnlineLearner;
+use super::weight_loader::{WeightFormat, WeightLoader};
+use crate::inference::{GenerationConfig, InferenceModel, ModelConfig};
+use crate::mcp::sampling::{run_chunk_loop, ChunkResponse, ThinkConfig};
+
+/// Built-in reasoning fixtures: short arithmetic and word problems
+const REASONING_FIXTURES: &str = include_str!("fixtures/reasoning_tasks.jsonl");
+
+/// A problem with a known answer
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReasoningTask {
+    pub problem: String,
+    pub answer: String,
+}
+
+impl ReasoningTask {
+    /// Parse `{"problem": ..., "answer": ...}` records, one per line
+    pub fn from_jsonl(text: &str) -> Result<Vec<Self>> {
+        text.lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                serde_json::from_str(line).with_context(|| format!("Line {}: expected problem and answer", i + 1))
+            })
+            .collect()
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Vec<Self>> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::from_jsonl(&text).with_context(|| format!("Invalid reasoning tasks {}", path.display()))
+    }
+
+    /// The built-in fixture set
+    pub fn fixtures() -> Vec<Self> {
+        Self::from_jsonl(REASONING_FIXTURES).expect("reasoning fixtures are valid")
+    }
+
+    /// Whether `solution` states this task's answer
+    ///
+    /// Case, surrounding whitespace, `$`, thousands separators and a trailing
+    /// period are ignored.
+    pub fn is_correct(&self, solution: &str) -> bool {
+        fn normalize(text: &str) -> String {
+            text.trim()
+                .trim_end_matches('.')
+                .chars()
+                .filter(|c| !matches!(c, '$' | ','))
+                .collect::<String>()
+                .trim()
+                .to_lowercase()
+        }
+        normalize(solution) == normalize(&self.answer)
+    }
+}
+
+/// Outcome of one reasoning task
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskResult {
+    pub problem: String,
+    pub expected: String,
+    pub solution: Option<String>,
+    pub correct: bool,
+    pub chunks: usize,
+}
+
+/// Metrics of one model on one evaluation set
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EvalMetrics {
+    pub examples: usize,
+    /// Scored (target) tokens
+    pub tokens: usize,
+    /// Mean cross-entropy per scored token
+    pub loss: f32,
+    pub perplexity: f32,
+    /// Fraction of scored tokens predicted exactly by the argmax
+    pub token_accuracy: f32,
+    /// Fraction of reasoning tasks solved, if any were run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reasoning_accuracy: Option<f32>,
+}
+
+/// Largest changes that do not count as a regression
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RegressionThresholds {
+    /// Relative perplexity increase (0.05 = 5% higher)
+    pub max_perplexity_increase: f32,
+    /// Absolute drop in token accuracy
+    pub max_token_accuracy_drop: f32,
+    /// Absolute drop in reasoning accuracy
+    pub max_reasoning_accuracy_drop: f32,
+}
+
+impl Default for RegressionThresholds {
+    fn default() -> Self {
+        Self {
+            max_perplexity_increase: 0.05,
+            max_token_accuracy_drop: 0.02,
+            max_reasoning_accuracy_drop: 0.1,
+        }
+    }
+}
+
+impl RegressionThresholds {
+    /// Every metric of `candidate` that is worse than `baseline` by more than allowed
+    pub fn regressions(&self, baseline: &EvalMetrics, candidate: &EvalMetrics) -> Vec<String> {
+        let mut regressions = Vec::new();
+
+        let limit = baseline.perplexity * (1.0 + self.max_perplexity_increase);
+        if candidate.perplexity.is_nan() || candidate.perplexity > limit {
+            regressions.push(format!(
+                "perplexity {:.3} -> {:.3} (limit {:.3})",
+                baseline.perplexity, candidate.perplexity, limit
+            ));
+        }
+        if baseline.token_accuracy - candidate.token_accuracy > self.max_token_accuracy_drop {
+            regressions.push(format!(
+                "token accuracy {:.3} -> {:.3} (max drop {:.3})",
+                baseline.token_accuracy, candidate.token_accuracy, self.max_token_accuracy_drop
+            ));
+        }
+        if let (Some(before), Some(after)) = (baseline.reasoning_accuracy, candidate.reasoning_accuracy) {
+            if before - after > self.max_reasoning_accuracy_drop {
+                regressions.push(format!(
+                    "reasoning accuracy {:.3} -> {:.3} (max drop {:.3})",
+                    before, after, self.max_reasoning_accuracy_drop
+                ));
+            }
+        }
+        regressions
+    }
+}
+
+/// Baseline and candidate metrics on the same evaluation set
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Comparison {
+    pub baseline: EvalMetrics,
+    pub candidate: EvalMetrics,
+    pub regressions: Vec<String>,
+}
+
+/// A held-out set and, optionally, reasoning tasks
+#[derive(Debug, Clone)]
+pub struct Evaluator {
+    dataset: Dataset,
+    reasoning_tasks: Vec<ReasoningTask>,
+    think: ThinkConfig,
+}
+
+impl Evaluator {
+    pub fn new(dataset: Dataset) -> Self {
+        Self {
+            dataset,
+            reasoning_tasks: Vec::new(),
+            think: ThinkConfig::default(),
+        }
+    }
+
+    /// Also solve `tasks` with the chunk loop configured by `think`
+    pub fn with_reasoning(mut self, tasks: Vec<ReasoningTask>, think: ThinkConfig) -> Self {
+        self.reasoning_tasks = tasks;
+        self.think = think;
+        self
+    }
+
+    pub fn dataset(&self) -> &Dataset {
+        &self.dataset
+    }
+
+    pub fn reasoning_tasks(&self) -> &[ReasoningTask] {
+        &self.reasoning_tasks
+    }
+
+    /// Held-out metrics of `model`, plus reasoning accuracy if tasks are set
+    pub fn evaluate(&self, model: &InferenceModel) -> Result<EvalMetrics> {
+        let mut metrics = self.held_out(model)?;
+        if !self.reasoning_tasks.is_empty() {
+            let results = self.reasoning(model)?;
+            let correct = results.iter().filter(|r| r.correct).count();
+            metrics.reasoning_accuracy = Some(correct as f32 / results.len() as f32);
+        }
+        Ok(metrics)
+    }
+
+    /// Loss, perplexity and token accuracy on the held-out set
+    pub fn held_out(&self, model: &InferenceModel) -> Result<EvalMetrics> {
+        if self.dataset.is_empty() {
+            anyhow::bail!("Evaluation set is empty");
+        }
+
+        let mut total_loss = 0.0f64;
+        let mut tokens = 0;
+        let mut correct = 0;
+        for (i, example) in self.dataset.examples().iter().enumerate() {
+            let (input, targets) =
+                OnlineLearner::encode_example(model, example).with_context(|| format!("Example {}", i + 1))?;
+            let logits = model.forward_all(&input)?;
+
+            for (row, target) in logits.iter().zip(&targets) {
+                let Some(target) = *target else { continue };
+                let max = row.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+                let log_sum = row.iter().map(|l| (l - max).exp()).sum::<f32>().ln() + max;
+                total_loss += f64::from(log_sum - row[target]);
+                tokens += 1;
+
+                let argmax = row
+                    .iter()
+                    .enumerate()
+                    .max_by(|a, b| a.1.total_cmp(b.1))
+                    .map(|(i, _)| i);
+                if argmax == Some(target) {
+                    correct += 1;
+                }
+            }
+        }
+
+        let loss = (total_loss / tokens as f64) as f32;
+        Ok(EvalMetrics {
+            examples: self.dataset.len(),
+            tokens,
+            loss,
+            perplexity: loss.exp(),
+            token_accuracy: correct as f32 / tokens as f32,
+            reasoning_accuracy: None,
+        })
+    }
+
+    /// Solve every reasoning task with greedy chunked generation
+    pub fn reasoning(&self, model: &InferenceModel) -> Result<Vec<TaskResult>> {
+        let tokenizer = model.tokenizer();
+        let mut results = Vec::with_capacity(self.reasoning_tasks.len());
+
+        for task in &self.reasoning_tasks {
+            // CPU generation never waits, so the loop is driven to completion in place
+            let chunk_loop = run_chunk_loop(&task.problem, &self.think, &tokenizer, |chunk| async move {
+                let config = GenerationConfig::greedy(chunk.max_tokens);
+                let output = model.generate_with_config(&chunk.prompt, &config).await?;
+                Ok(ChunkResponse::text(output.text))
+            });
+            let outcome = futures::executor::block_on(chunk_loop)?;
+
+            let correct = outcome.solution.as_deref().is_some_and(|s| task.is_correct(s));
+            results.push(TaskResult {
+                problem: task.problem.clone(),
+                expected: task.answer.clone(),
+                solution: outcome.solution,
+                correct,
+                chunks: outcome.chunks.len(),
+            });
+        }
+        Ok(results)
+    }
+
+    /// Evaluate two weight files with a model of `config` each
+    ///
+    /// GGUF files carry their own configuration, which takes precedence.
+    pub fn compare_weights(
+        &self,
+        config: &ModelConfig,
+        baseline: &Path,
+        candidate: &Path,
+        thresholds: &RegressionThresholds,
+    ) -> Result<Comparison> {
+        let baseline = self.evaluate(&load_model(config, baseline)?)?;
+        let candidate = self.evaluate(&load_model(config, candidate)?)?;
+        let regressions = thresholds.regressions(&baseline, &candidate);
+        Ok(Comparison {
+            baseline,
+            candidate,
+            regressions,
+        })
+    }
+}
+
+fn load_model(config: &ModelConfig, path: &Path) -> Result<InferenceModel> {
+    let mut loader = WeightLoader::new(WeightFormat::from_path(path)?);
+    loader.load_from_file(path)?;
+    let config = match loader.gguf_metadata() {
+        Some(_) => ModelConfig::from_gguf(&loader)?,
+        None => config.clone(),
+    };
+
+    #[cfg(feature = "gpu")]
+    let mut model = InferenceModel::new(config, None)?;
+    #[cfg(not(feature = "gpu"))]
+    let mut model = InferenceModel::new(config, ())?;
+
+    model.load_weights(&loader)?;
+    Ok(model)
+}
+
+/// Baseline metrics that later weights must not regress from
+#[derive(Debug, Clone)]
+pub struct RegressionGate {
+    evaluator: Evaluator,
+    thresholds: RegressionThresholds,
+    baseline: EvalMetrics,
+}
+
+/// Result of checking a model against a `RegressionGate`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GateReport {
+    pub baseline: EvalMetrics,
+    pub metrics: EvalMetrics,
+    pub regressions: Vec<String>,
+}
+
+impl GateReport {
+    pub fn passed(&self) -> bool {
+        self.regressions.is_empty()
+    }
+}
+
+impl RegressionGate {
+    /// Gate on `model`'s current metrics
+    pub fn new(evaluator: Evaluator, thresholds: RegressionThresholds, model: &InferenceModel) -> Result<Self> {
+        let baseline = evaluator.evaluate(model)?;
+        Ok(Self {
+            evaluator,
+            thresholds,
+            baseline,
+        })
+    }
+
+    pub fn evaluator(&self) -> &Evaluator {
+        &self.evaluator
+    }
+
+    pub fn thresholds(&self) -> &RegressionThresholds {
+        &self.thresholds
+    }
+
+    pub fn baseline(&self) -> &EvalMetrics {
+        &self.baseline
+    }
+
+    /// Evaluate `model` and list its regressions against the baseline
+    pub fn check(&self, model: &InferenceModel) -> Result<GateReport> {
+        let metrics = self.evaluator.evaluate(model)?;
+        let regressions = self.thresholds.regressions(&self.baseline, &metrics);
+        Ok(GateReport {
+            baseline: self.baseline.clone(),
+            metrics,
+            regressions,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::training::TrainingExample;
+
+    fn tiny_model() -> InferenceModel {
+        InferenceModel::for_tests(ModelConfig {
+            max_seq_len: 64,
+            ..ModelConfig::tiny()
+        })
+    }
+
+    fn held_out() -> Dataset {
+        Dataset::new(vec![
+            TrainingExample::new("The capital of France is".to_string(), Some(" Paris".to_string())),
+            TrainingExample::new("one two three four".to_string(), None),
+        ])
+    }
+
+    #[test]
+    fn test_held_out_metrics_match_training_loss() {
+        let model = tiny_model();
+        let evaluator = Evaluator::new(held_out());
+        let metrics = evaluator.evaluate(&model).unwrap();
+
+        assert_eq!(metrics.examples, 2);
+        assert!(metrics.tokens > 2);
+        assert!((metrics.perplexity - metrics.loss.exp()).abs() < 1e-3 * metrics.perplexity);
+        assert!((0.0..=1.0).contains(&metrics.token_accuracy));
+        assert!(metrics.reasoning_accuracy.is_none());
+
+        // A single example scores the same loss as training does
+        let example = held_out().examples()[0].clone();
+        let single = Evaluator::new(Dataset::new(vec![example.clone()])).held_out(&model).unwrap();
+        let learner = OnlineLearner::new(Default::default(), std::sync::Arc::new(std::sync::RwLock::new(model)));
+        let training_loss = learner.example_loss(&example).unwrap();
+        assert!((single.loss - training_loss).abs() < 1e-4, "{} vs {}", single.loss, training_loss);
+    }
+
+    #[test]
+    fn test_thresholds_flag_regressions() {
+        let baseline = EvalMetrics {
+            perplexity: 10.0,
+            token_accuracy: 0.5,
+            reasoning_accuracy: Some(0.5),
+            ..EvalMetrics::default()
+        };
+        let thresholds = RegressionThresholds::default();
+
+        let slightly_worse = EvalMetrics {
+            perplexity: 10.4,
+            token_accuracy: 0.49,
+            ..baseline.clone()
+        };
+        assert!(thresholds.regressions(&baseline, &slightly_worse).is_empty());
+
+        let worse = EvalMetrics {
+            perplexity: f32::NAN,
+            token_accuracy: 0.4,
+            reasoning_accuracy: Some(0.25),
+            ..baseline.clone()
+        };
+        let regressions = thresholds.regressions(&baseline, &worse);
+        assert_eq!(regressions.len(), 3, "{:?}", regressions);
+    }
+
+    #[test]
+    fn test_reasoning_tasks_run_through_chunk_loop() {
+        let tasks = ReasoningTask::fixtures();
+        assert_eq!(tasks.len(), 8);
+        assert!(tasks[0].is_correct(" 42."));
+        assert!(!tasks[0].is_correct("41"));
+
+        let model = tiny_model();
+        let think = ThinkConfig::new(4, 1, 2).unwrap();
+        let evaluator = Evaluator::new(held_out()).with_reasoning(tasks[..1].to_vec(), think);
+        let results = evaluator.reasoning(&model).unwrap();
+        assert_eq!(results.len(), 1);
+        assert!((1..=2).contains(&results[0].chunks));
+
+        let metrics = evaluator.evaluate(&model).unwrap();
+        assert_eq!(metrics.reasoning_accuracy, Some(if results[0].correct { 1.0 } else { 0.0 }));
+    }
+
+    #[test]
+    fn test_compare_weight_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let model = tiny_model();
+        let path = dir.path().join("a.safetensors");
+        model.export_weights().unwrap().save_to_file(&path, WeightFormat::SafeTensors).unwrap();
+
+        let evaluator = Evaluator::new(held_out());
+        let comparison = evaluator
+            .compare_weights(model.config(), &path, &path, &RegressionThresholds::default())
+            .unwrap();
+        assert_eq!(comparison.baseline, comparison.candidate);
+        assert!(comparison.regressions.is_empty());
+        assert_eq!(comparison.baseline, evaluator.evaluate(&model).unwrap());
+    }
+}
@@ -0,0 +1,952 @@
+//! GGUF (llama.cpp) file parsing and dequantization
+//!
+//! Supports GGUF v2 and v3: header, key/value metadata, tensor infos and
+//! aligned tensor data. The file is memory-mapped and tensors are
+//! dequantized to f32 on demand. F32, F16, BF16, Q8_0, Q4_0 and Q4_K tensors
+//! are supported. `GgufWriter` writes v3 files with F32, F16 or BF16 tensors.
+
+use anyhow::{Context, Result};
+use half::{bf16, f16};
+use memmap2::Mmap;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+pub const GGUF_MAGIC: &[u8; 4] = b"GGUF";
+
+/// Alignment of the tensor data section unless `general.alignment` says otherwise
+pub const GGUF_DEFAULT_ALIGNMENT: usize = 32;
+
+/// Elements per Q8_0 / Q4_0 block
+const QK: usize = 32;
+/// Elements per k-quant super-block
+const QK_K: usize = 256;
+
+/// Tensor element types (ggml_type)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GgmlType {
+    F32,
+    F16,
+    Q4_0,
+    Q4_1,
+    Q5_0,
+    Q5_1,
+    Q8_0,
+    Q8_1,
+    Q2K,
+    Q3K,
+    Q4K,
+    Q5K,
+    Q6K,
+    Q8K,
+    BF16,
+    Other(u32),
+}
+
+impl GgmlType {
+    pub fn from_u32(value: u32) -> Self {
+        match value {
+            0 => Self::F32,
+            1 => Self::F16,
+            2 => Self::Q4_0,
+            3 => Self::Q4_1,
+            6 => Self::Q5_0,
+            7 => Self::Q5_1,
+            8 => Self::Q8_0,
+            9 => Self::Q8_1,
+            10 => Self::Q2K,
+            11 => Self::Q3K,
+            12 => Self::Q4K,
+            13 => Self::Q5K,
+            14 => Self::Q6K,
+            15 => Self::Q8K,
+            30 => Self::BF16,
+            other => Self::Other(other),
+        }
+    }
+
+    pub fn to_u32(self) -> u32 {
+        match self {
+            Self::F32 => 0,
+            Self::F16 => 1,
+            Self::Q4_0 => 2,
+            Self::Q4_1 => 3,
+            Self::Q5_0 => 6,
+            Self::Q5_1 => 7,
+            Self::Q8_0 => 8,
+            Self::Q8_1 => 9,
+            Self::Q2K => 10,
+            Self::Q3K => 11,
+            Self::Q4K => 12,
+            Self::Q5K => 13,
+            Self::Q6K => 14,
+            Self::Q8K => 15,
+            Self::BF16 => 30,
+            Self::Other(other) => other,
+        }
+    }
+
+    /// (elements per block, bytes per block) for types we can dequantize
+    fn block_layout(self) -> Option<(usize, usize)> {
+        match self {
+            Self::F32 => Some((1, 4)),
+            Self::F16 | Self::BF16 => Some((1, 2)),
+            Self::Q8_0 => Some((QK, 2 + QK)),
+            Self::Q4_0 => Some((QK, 2 + QK / 2)),
+            Self::Q4K => Some((QK_K, 2 + 2 + 12 + QK_K / 2)),
+            _ => None,
+        }
+    }
+
+    /// Bytes needed for `elements` values, if the type is supported
+    pub fn byte_size(self, elements: usize) -> Option<usize> {
+        let (block, bytes) = self.block_layout()?;
+        if !elements.is_multiple_of(block) {
+            return None;
+        }
+        Some(elements / block * bytes)
+    }
+}
+
+/// A metadata value
+#[derive(Debug, Clone, PartialEq)]
+pub enum GgufValue {
+    U8(u8),
+    I8(i8),
+    U16(u16),
+    I16(i16),
+    U32(u32),
+    I32(i32),
+    F32(f32),
+    Bool(bool),
+    String(String),
+    Array(Vec<GgufValue>),
+    U64(u64),
+    I64(i64),
+    F64(f64),
+}
+
+impl GgufValue {
+    /// Type id used in the file format
+    fn type_id(&self) -> u32 {
+        match self {
+            Self::U8(_) => 0,
+            Self::I8(_) => 1,
+            Self::U16(_) => 2,
+            Self::I16(_) => 3,
+            Self::U32(_) => 4,
+            Self::I32(_) => 5,
+            Self::F32(_) => 6,
+            Self::Bool(_) => 7,
+            Self::String(_) => 8,
+            Self::Array(_) => 9,
+            Self::U64(_) => 10,
+            Self::I64(_) => 11,
+            Self::F64(_) => 12,
+        }
+    }
+
+    fn write(&self, out: &mut Vec<u8>) {
+        match self {
+            Self::U8(v) => out.push(*v),
+            Self::I8(v) => out.push(*v as u8),
+            Self::U16(v) => out.extend(v.to_le_bytes()),
+            Self::I16(v) => out.extend(v.to_le_bytes()),
+            Self::U32(v) => out.extend(v.to_le_bytes()),
+            Self::I32(v) => out.extend(v.to_le_bytes()),
+            Self::F32(v) => out.extend(v.to_le_bytes()),
+            Self::Bool(v) => out.push(*v as u8),
+            Self::String(v) => write_string(out, v),
+            Self::Array(values) => {
+                // Element type of an empty array is arbitrary
+                let element_type = values.first().map_or(4, GgufValue::type_id);
+                out.extend(element_type.to_le_bytes());
+                out.extend((values.len() as u64).to_le_bytes());
+                for value in values {
+                    value.write(out);
+                }
+            }
+            Self::U64(v) => out.extend(v.to_le_bytes()),
+            Self::I64(v) => out.extend(v.to_le_bytes()),
+            Self::F64(v) => out.extend(v.to_le_bytes()),
+        }
+    }
+
+    /// Integer value of any integer type (negative values excluded)
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            Self::U8(v) => Some(v as u64),
+            Self::U16(v) => Some(v as u64),
+            Self::U32(v) => Some(v as u64),
+            Self::U64(v) => Some(v),
+            Self::I8(v) => u64::try_from(v).ok(),
+            Self::I16(v) => u64::try_from(v).ok(),
+            Self::I32(v) => u64::try_from(v).ok(),
+            Self::I64(v) => u64::try_from(v).ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_f32(&self) -> Option<f32> {
+        match *self {
+            Self::F32(v) => Some(v),
+            Self::F64(v) => Some(v as f32),
+            _ => self.as_u64().map(|v| v as f32),
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[GgufValue]> {
+        match self {
+            Self::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+/// Key/value metadata with typed accessors for the well-known keys
+#[derive(Debug, Clone, Default)]
+pub struct GgufMetadata {
+    values: HashMap<String, GgufValue>,
+}
+
+impl GgufMetadata {
+    pub fn new(values: HashMap<String, GgufValue>) -> Self {
+        Self { values }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&GgufValue> {
+        self.values.get(key)
+    }
+
+    pub fn insert(&mut self, key: impl Into<String>, value: GgufValue) {
+        self.values.insert(key.into(), value);
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.values.keys()
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// `general.architecture`, e.g. "llama"
+    pub fn architecture(&self) -> Option<&str> {
+        self.get("general.architecture")?.as_str()
+    }
+
+    /// Integer value of `{architecture}.{key}`
+    pub fn arch_u64(&self, key: &str) -> Option<u64> {
+        let arch = self.architecture()?;
+        self.get(&format!("{}.{}", arch, key))?.as_u64()
+    }
+
+    /// Float value of `{architecture}.{key}`
+    pub fn arch_f32(&self, key: &str) -> Option<f32> {
+        let arch = self.architecture()?;
+        self.get(&format!("{}.{}", arch, key))?.as_f32()
+    }
+
+    pub fn context_length(&self) -> Option<usize> {
+        self.arch_u64("context_length").map(|v| v as usize)
+    }
+
+    pub fn alignment(&self) -> usize {
+        self.get("general.alignment")
+            .and_then(GgufValue::as_u64)
+            .map_or(GGUF_DEFAULT_ALIGNMENT, |v| v as usize)
+    }
+
+    /// `tokenizer.ggml.model`, e.g. "llama" (SentencePiece) or "gpt2" (BPE)
+    pub fn tokenizer_model(&self) -> Option<&str> {
+        self.get("tokenizer.ggml.model")?.as_str()
+    }
+
+    /// Vocabulary in token-id order
+    pub fn tokens(&self) -> Option<Vec<&str>> {
+        self.get("tokenizer.ggml.tokens")?
+            .as_array()?
+            .iter()
+            .map(GgufValue::as_str)
+            .collect()
+    }
+
+    pub fn bos_token_id(&self) -> Option<usize> {
+        self.get("tokenizer.ggml.bos_token_id")?.as_u64().map(|v| v as usize)
+    }
+
+    pub fn eos_token_id(&self) -> Option<usize> {
+        self.get("tokenizer.ggml.eos_token_id")?.as_u64().map(|v| v as usize)
+    }
+}
+
+/// Where a tensor lives in the data section
+#[derive(Debug, Clone)]
+pub struct GgufTensorInfo {
+    pub name: String,
+    /// Dimensions, fastest-varying first (ggml order)
+    pub dims: Vec<u64>,
+    pub ggml_type: GgmlType,
+    /// Offset relative to the start of the data section
+    pub offset: u64,
+}
+
+impl GgufTensorInfo {
+    pub fn num_elements(&self) -> usize {
+        self.dims.iter().product::<u64>() as usize
+    }
+
+    /// Row-major shape (slowest-varying first), as SafeTensors / PyTorch use
+    pub fn shape(&self) -> Vec<usize> {
+        self.dims.iter().rev().map(|&d| d as usize).collect()
+    }
+}
+
+/// A parsed, memory-mapped GGUF file
+#[derive(Debug)]
+pub struct GgufFile {
+    pub version: u32,
+    pub metadata: GgufMetadata,
+    pub tensors: Vec<GgufTensorInfo>,
+    data_offset: usize,
+    mmap: Mmap,
+}
+
+impl GgufFile {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .with_context(|| format!("Failed to open GGUF file {}", path.display()))?;
+        // SAFETY: the map is read-only and the file is not modified while loaded
+        let mmap = unsafe { Mmap::map(&file)? };
+
+        let mut reader = Reader::new(&mmap);
+        if reader.bytes(4)? != GGUF_MAGIC {
+            anyhow::bail!("Invalid GGUF file: wrong magic bytes");
+        }
+
+        let version = reader.u32()?;
+        if !(2..=3).contains(&version) {
+            anyhow::bail!("Unsupported GGUF version {} (expected 2 or 3)", version);
+        }
+
+        let tensor_count = reader.u64()? as usize;
+        let kv_count = reader.u64()? as usize;
+
+        let mut values = HashMap::with_capacity(kv_count);
+        for _ in 0..kv_count {
+            let key = reader.string()?;
+            let value_type = reader.u32()?;
+            let value = reader
+                .value(value_type)
+                .with_context(|| format!("Invalid value for metadata key {}", key))?;
+            values.insert(key, value);
+        }
+        let metadata = GgufMetadata::new(values);
+
+        let mut tensors = Vec::with_capacity(tensor_count);
+        for _ in 0..tensor_count {
+            let name = reader.string()?;
+            let n_dims = reader.u32()? as usize;
+            let dims = (0..n_dims).map(|_| reader.u64()).collect::<Result<Vec<_>>>()?;
+            let ggml_type = GgmlType::from_u32(reader.u32()?);
+            let offset = reader.u64()?;
+            tensors.push(GgufTensorInfo {
+                name,
+                dims,
+                ggml_type,
+                offset,
+            });
+        }
+
+        let data_offset = align_to(reader.position(), metadata.alignment());
+
+        Ok(Self {
+            version,
+            metadata,
+            tensors,
+            data_offset,
+            mmap,
+        })
+    }
+
+    /// Raw bytes of a tensor
+    pub fn tensor_data(&self, info: &GgufTensorInfo) -> Result<&[u8]> {
+        let size = info.ggml_type.byte_size(info.num_elements()).with_context(|| {
+            format!(
+                "Tensor {} has unsupported type {:?} (supported: F32, F16, BF16, Q8_0, Q4_0, Q4_K)",
+                info.name, info.ggml_type
+            )
+        })?;
+
+        let start = self.data_offset + info.offset as usize;
+        self.mmap
+            .get(start..start + size)
+            .with_context(|| format!("Tensor {} extends past the end of the file", info.name))
+    }
+
+    /// Dequantize a tensor to f32
+    pub fn dequantize(&self, info: &GgufTensorInfo) -> Result<Vec<f32>> {
+        dequantize(self.tensor_data(info)?, info.ggml_type, info.num_elements())
+    }
+}
+
+/// Builds a GGUF v3 file
+#[derive(Debug, Default)]
+pub struct GgufWriter {
+    metadata: Vec<(String, GgufValue)>,
+    tensors: Vec<(String, Vec<u64>, GgmlType, Vec<u8>)>,
+}
+
+impl GgufWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a metadata entry (a repeated key replaces the earlier value)
+    pub fn add_metadata(&mut self, key: impl Into<String>, value: GgufValue) {
+        let key = key.into();
+        self.metadata.retain(|(k, _)| *k != key);
+        self.metadata.push((key, value));
+    }
+
+    /// Add a tensor with row-major `shape`, encoded as F32, F16 or BF16
+    pub fn add_tensor(
+        &mut self,
+        name: impl Into<String>,
+        shape: &[usize],
+        values: &[f32],
+        ggml_type: GgmlType,
+    ) -> Result<()> {
+        let name = name.into();
+        if shape.iter().product::<usize>() != values.len() {
+            anyhow::bail!("Tensor {} has {} values but shape {:?}", name, values.len(), shape);
+        }
+
+        let data = match ggml_type {
+            GgmlType::F32 => values.iter().flat_map(|v| v.to_le_bytes()).collect(),
+            GgmlType::F16 => values.iter().flat_map(|&v| f16::from_f32(v).to_le_bytes()).collect(),
+            GgmlType::BF16 => values.iter().flat_map(|&v| bf16::from_f32(v).to_le_bytes()).collect(),
+            other => anyhow::bail!("Writing {:?} tensors is not supported (use F32, F16 or BF16)", other),
+        };
+
+        let dims = shape.iter().rev().map(|&d| d as u64).collect();
+        self.add_raw_tensor(name, dims, ggml_type, data);
+        Ok(())
+    }
+
+    /// Add already-encoded tensor data with ggml-order `dims`
+    pub fn add_raw_tensor(&mut self, name: impl Into<String>, dims: Vec<u64>, ggml_type: GgmlType, data: Vec<u8>) {
+        self.tensors.push((name.into(), dims, ggml_type, data));
+    }
+
+    /// Serialize the file
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let alignment = self
+            .metadata
+            .iter()
+            .find(|(k, _)| k == "general.alignment")
+            .and_then(|(_, v)| v.as_u64())
+            .map_or(GGUF_DEFAULT_ALIGNMENT, |v| v as usize);
+
+        let mut out = Vec::new();
+        out.extend(GGUF_MAGIC);
+        out.extend(3u32.to_le_bytes());
+        out.extend((self.tensors.len() as u64).to_le_bytes());
+        out.extend((self.metadata.len() as u64).to_le_bytes());
+
+        for (key, value) in &self.metadata {
+            write_string(&mut out, key);
+            out.extend(value.type_id().to_le_bytes());
+            value.write(&mut out);
+        }
+
+        let mut offset = 0usize;
+        for (name, dims, ggml_type, data) in &self.tensors {
+            write_string(&mut out, name);
+            out.extend((dims.len() as u32).to_le_bytes());
+            for dim in dims {
+                out.extend(dim.to_le_bytes());
+            }
+            out.extend(ggml_type.to_u32().to_le_bytes());
+            out.extend((offset as u64).to_le_bytes());
+            offset = align_to(offset + data.len(), alignment);
+        }
+
+        for (_, _, _, data) in &self.tensors {
+            out.resize(align_to(out.len(), alignment), 0);
+            out.extend(data);
+        }
+
+        out
+    }
+
+    pub fn write_to_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let mut file = File::create(path)
+            .with_context(|| format!("Failed to create GGUF file {}", path.display()))?;
+        file.write_all(&self.to_bytes())?;
+        Ok(())
+    }
+}
+
+fn write_string(out: &mut Vec<u8>, s: &str) {
+    out.extend((s.len() as u64).to_le_bytes());
+    out.extend(s.as_bytes());
+}
+
+/// Dequantize `elements` values of `ggml_type` from `data`
+pub fn dequantize(data: &[u8], ggml_type: GgmlType, elements: usize) -> Result<Vec<f32>> {
+    let expected = ggml_type
+        .byte_size(elements)
+        .with_context(|| format!("Cannot dequantize {:?} tensor of {} elements", ggml_type, elements))?;
+    if data.len() != expected {
+        anyhow::bail!("{:?} data is {} bytes, expected {}", ggml_type, data.len(), expected);
+    }
+
+    let mut out = Vec::with_capacity(elements);
+    match ggml_type {
+        GgmlType::F32 => {
+            out.extend(data.chunks_exact(4).map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]])));
+        }
+        GgmlType::F16 => {
+            out.extend(data.chunks_exact(2).map(|c| f16_from(c).to_f32()));
+        }
+        GgmlType::BF16 => {
+            out.extend(
+                data.chunks_exact(2)
+                    .map(|c| bf16::from_bits(u16::from_le_bytes([c[0], c[1]])).to_f32()),
+            );
+        }
+        GgmlType::Q8_0 => {
+            for block in data.chunks_exact(2 + QK) {
+                let d = f16_from(&block[..2]).to_f32();
+                out.extend(block[2..].iter().map(|&q| q as i8 as f32 * d));
+            }
+        }
+        GgmlType::Q4_0 => {
+            for block in data.chunks_exact(2 + QK / 2) {
+                let d = f16_from(&block[..2]).to_f32();
+                let qs = &block[2..];
+                out.extend(qs.iter().map(|&q| ((q & 0x0F) as i32 - 8) as f32 * d));
+                out.extend(qs.iter().map(|&q| ((q >> 4) as i32 - 8) as f32 * d));
+            }
+        }
+        GgmlType::Q4K => {
+            for block in data.chunks_exact(4 + 12 + QK_K / 2) {
+                dequantize_q4_k_block(block, &mut out);
+            }
+        }
+        _ => unreachable!("byte_size returned None for unsupported types"),
+    }
+
+    Ok(out)
+}
+
+/// One Q4_K super-block: f16 d, f16 dmin, 12 bytes of packed 6-bit
+/// scales/mins for 8 sub-blocks, then 128 bytes of 4-bit quants
+fn dequantize_q4_k_block(block: &[u8], out: &mut Vec<f32>) {
+    let d = f16_from(&block[0..2]).to_f32();
+    let dmin = f16_from(&block[2..4]).to_f32();
+    let scales = &block[4..16];
+    let qs = &block[16..];
+
+    for (chunk, q) in qs.chunks_exact(32).enumerate() {
+        let (sc1, m1) = q4_k_scale_min(2 * chunk, scales);
+        let (sc2, m2) = q4_k_scale_min(2 * chunk + 1, scales);
+        let (d1, m1) = (d * sc1 as f32, dmin * m1 as f32);
+        let (d2, m2) = (d * sc2 as f32, dmin * m2 as f32);
+
+        out.extend(q.iter().map(|&b| d1 * (b & 0x0F) as f32 - m1));
+        out.extend(q.iter().map(|&b| d2 * (b >> 4) as f32 - m2));
+    }
+}
+
+/// Scale and min of sub-block `j` (get_scale_min_k4 in ggml)
+fn q4_k_scale_min(j: usize, q: &[u8]) -> (u8, u8) {
+    if j < 4 {
+        (q[j] & 63, q[j + 4] & 63)
+    } else {
+        (
+            (q[j + 4] & 0x0F) | ((q[j - 4] >> 6) << 4),
+            (q[j + 4] >> 4) | ((q[j] >> 6) << 4),
+        )
+    }
+}
+
+fn f16_from(bytes: &[u8]) -> f16 {
+    f16::from_bits(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+pub(crate) fn align_to(offset: usize, alignment: usize) -> usize {
+    offset.div_ceil(alignment) * alignment
+}
+
+/// HuggingFace name for a llama.cpp tensor name (unknown names pass through)
+///
+/// `blk.3.attn_q.weight` becomes `model.layers.3.self_attn.q_proj.weight`,
+/// which is what `Transformer::from_loader` looks up.
+pub fn hf_tensor_name(gguf_name: &str) -> String {
+    match gguf_name {
+        "token_embd.weight" => return "model.embed_tokens.weight".to_string(),
+        "output_norm.weight" => return "model.norm.weight".to_string(),
+        "output_norm.bias" => return "model.norm.bias".to_string(),
+        "output.weight" => return "lm_head.weight".to_string(),
+        _ => {}
+    }
+
+    let Some(rest) = gguf_name.strip_prefix("blk.") else {
+        return gguf_name.to_string();
+    };
+    let Some((layer, rest)) = rest.split_once('.') else {
+        return gguf_name.to_string();
+    };
+    let Some((module, suffix)) = rest.rsplit_once('.') else {
+        return gguf_name.to_string();
+    };
+
+    let hf_module = match module {
+        "attn_norm" => "input_layernorm",
+        "attn_q" => "self_attn.q_proj",
+        "attn_k" => "self_attn.k_proj",
+        "attn_v" => "self_attn.v_proj",
+        "attn_output" => "self_attn.o_proj",
+        "ffn_norm" => "post_attention_layernorm",
+        "ffn_gate" => "mlp.gate_proj",
+        "ffn_up" => "mlp.up_proj",
+        "ffn_down" => "mlp.down_proj",
+        _ => return gguf_name.to_string(),
+    };
+
+    format!("model.layers.{}.{}.{}", layer, hf_module, suffix)
+}
+
+/// llama.cpp name for a HuggingFace tensor name (inverse of `hf_tensor_name`)
+pub fn gguf_tensor_name(hf_name: &str) -> String {
+    match hf_name {
+        "model.embed_tokens.weight" => return "token_embd.weight".to_string(),
+        "model.norm.weight" => return "output_norm.weight".to_string(),
+        "model.norm.bias" => return "output_norm.bias".to_string(),
+        "lm_head.weight" => return "output.weight".to_string(),
+        _ => {}
+    }
+
+    let Some(rest) = hf_name.strip_prefix("model.layers.") else {
+        return hf_name.to_string();
+    };
+    let Some((layer, rest)) = rest.split_once('.') else {
+        return hf_name.to_string();
+    };
+    let Some((module, suffix)) = rest.rsplit_once('.') else {
+        return hf_name.to_string();
+    };
+
+    let gguf_module = match module {
+        "input_layernorm" => "attn_norm",
+        "self_attn.q_proj" => "attn_q",
+        "self_attn.k_proj" => "attn_k",
+        "self_attn.v_proj" => "attn_v",
+        "self_attn.o_proj" => "attn_output",
+        "post_attention_layernorm" => "ffn_norm",
+        "mlp.gate_proj" => "ffn_gate",
+        "mlp.up_proj" => "ffn_up",
+        "mlp.down_proj" => "ffn_down",
+        _ => return hf_name.to_string(),
+    };
+
+    format!("blk.{}.{}.{}", layer, gguf_module, suffix)
+}
+
+/// Apply llama.cpp's Q/K row permutation (inverse of `unpermute_rope_rows`)
+pub fn permute_rope_rows(weights: &[f32], n_heads: usize, row_len: usize) -> Vec<f32> {
+    let rows = weights.len() / row_len;
+    let head_dim = rows / n_heads;
+    let half = head_dim / 2;
+
+    let mut out = vec![0.0; weights.len()];
+    for head in 0..n_heads {
+        for i in 0..half {
+            for pair in 0..2 {
+                let dst = head * head_dim + 2 * i + pair;
+                let src = head * head_dim + pair * half + i;
+                out[dst * row_len..(dst + 1) * row_len]
+                    .copy_from_slice(&weights[src * row_len..(src + 1) * row_len]);
+            }
+        }
+    }
+    out
+}
+
+/// Undo the Q/K row permutation llama.cpp's converter applies for llama
+/// models, which store rotary pairs interleaved rather than rotate-half
+pub fn unpermute_rope_rows(weights: &[f32], n_heads: usize, row_len: usize) -> Vec<f32> {
+    let rows = weights.len() / row_len;
+    let head_dim = rows / n_heads;
+    let half = head_dim / 2;
+
+    let mut out = vec![0.0; weights.len()];
+    for head in 0..n_heads {
+        for i in 0..half {
+            for pair in 0..2 {
+                // GGUF row (i, pair) holds HF row (pair, i)
+                let src = head * head_dim + 2 * i + pair;
+                let dst = head * head_dim + pair * half + i;
+                out[dst * row_len..(dst + 1) * row_len]
+                    .copy_from_slice(&weights[src * row_len..(src + 1) * row_len]);
+            }
+        }
+    }
+    out
+}
+
+/// Little-endian cursor over the mapped file
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn position(&self) -> usize {
+        self.pos
+    }
+
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(n)
+            .filter(|&end| end <= self.data.len())
+            .with_context(|| format!("Truncated GGUF file at byte {}", self.pos))?;
+        let slice = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.bytes(N)?.try_into().unwrap())
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let len = self.u64()? as usize;
+        Ok(String::from_utf8_lossy(self.bytes(len)?).into_owned())
+    }
+
+    fn value(&mut self, value_type: u32) -> Result<GgufValue> {
+        Ok(match value_type {
+            0 => GgufValue::U8(self.array::<1>()?[0]),
+            1 => GgufValue::I8(self.array::<1>()?[0] as i8),
+            2 => GgufValue::U16(u16::from_le_bytes(self.array()?)),
+            3 => GgufValue::I16(i16::from_le_bytes(self.array()?)),
+            4 => GgufValue::U32(self.u32()?),
+            5 => GgufValue::I32(i32::from_le_bytes(self.array()?)),
+            6 => GgufValue::F32(f32::from_le_bytes(self.array()?)),
+            7 => GgufValue::Bool(self.array::<1>()?[0] != 0),
+            8 => GgufValue::String(self.string()?),
+            9 => {
+                let element_type = self.u32()?;
+                let len = self.u64()? as usize;
+                let mut values = Vec::with_capacity(len.min(1 << 20));
+                for _ in 0..len {
+                    values.push(self.value(element_type)?);
+                }
+                GgufValue::Array(values)
+            }
+            10 => GgufValue::U64(self.u64()?),
+            11 => GgufValue::I64(i64::from_le_bytes(self.array()?)),
+            12 => GgufValue::F64(f64::from_le_bytes(self.array()?)),
+            other => anyhow::bail!("Unknown GGUF value type {}", other),
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// GGUF bytes from raw tensors: (name, ggml-order dims, type, data)
+    pub(crate) fn build_gguf(
+        metadata: &[(&str, GgufValue)],
+        tensors: &[(&str, Vec<u64>, GgmlType, Vec<u8>)],
+    ) -> Vec<u8> {
+        let mut writer = GgufWriter::new();
+        for (key, value) in metadata {
+            writer.add_metadata(*key, value.clone());
+        }
+        for (name, dims, ggml_type, data) in tensors {
+            writer.add_raw_tensor(*name, dims.clone(), *ggml_type, data.clone());
+        }
+        writer.to_bytes()
+    }
+
+    fn f16_bytes(v: f32) -> [u8; 2] {
+        f16::from_f32(v).to_le_bytes()
+    }
+
+    #[test]
+    fn test_dequantize_q8_0_and_q4_0() {
+        let mut q8 = f16_bytes(0.5).to_vec();
+        q8.extend((0..32).map(|i| (i as i8 - 16) as u8));
+        let values = dequantize(&q8, GgmlType::Q8_0, 32).unwrap();
+        assert_eq!(values[0], -8.0);
+        assert_eq!(values[31], 7.5);
+
+        // Low nibbles hold elements 0..16, high nibbles 16..32
+        let mut q4 = f16_bytes(2.0).to_vec();
+        q4.extend((0..16).map(|i| (i as u8) | (15 - i as u8) << 4));
+        let values = dequantize(&q4, GgmlType::Q4_0, 32).unwrap();
+        assert_eq!(values[0], -16.0);
+        assert_eq!(values[15], 14.0);
+        assert_eq!(values[16], 14.0);
+        assert_eq!(values[31], -16.0);
+
+        assert!(dequantize(&q4[..10], GgmlType::Q4_0, 32).is_err());
+    }
+
+    #[test]
+    fn test_dequantize_q4_k() {
+        // d = 1, dmin = 0.5; sub-block j has scale j+1 and min j
+        let mut block = f16_bytes(1.0).to_vec();
+        block.extend(f16_bytes(0.5));
+        let mut scales = [0u8; 12];
+        for j in 0..8u8 {
+            let (sc, m) = (j + 1, j);
+            if j < 4 {
+                scales[j as usize] = sc;
+                scales[j as usize + 4] = m;
+            } else {
+                let j = j as usize;
+                scales[j + 4] = (sc & 0x0F) | ((m & 0x0F) << 4);
+                scales[j - 4] |= (sc >> 4) << 6;
+                scales[j] |= (m >> 4) << 6;
+            }
+        }
+        block.extend(scales);
+        block.extend(std::iter::repeat_n(0x31u8, 128)); // low nibble 1, high 3
+
+        let values = dequantize(&block, GgmlType::Q4K, 256).unwrap();
+        assert_eq!(values.len(), 256);
+        for sub in 0..8 {
+            let q = if sub % 2 == 0 { 1.0 } else { 3.0 };
+            let expected = (sub as f32 + 1.0) * q - 0.5 * sub as f32;
+            assert_eq!(values[sub * 32], expected, "sub-block {}", sub);
+            assert_eq!(values[sub * 32 + 31], expected);
+        }
+    }
+
+    #[test]
+    fn test_parse_header_metadata_and_tensors() {
+        let f32_data: Vec<u8> = [1.0f32, 2.0, 3.0, 4.0, 5.0, 6.0]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        let bf16_data: Vec<u8> = [1.5f32, -2.0]
+            .iter()
+            .flat_map(|&v| bf16::from_f32(v).to_le_bytes())
+            .collect();
+        let bytes = build_gguf(
+            &[
+                ("general.architecture", GgufValue::String("llama".into())),
+                ("llama.context_length", GgufValue::U32(4096)),
+                (
+                    "tokenizer.ggml.tokens",
+                    GgufValue::Array(vec![GgufValue::String("<s>".into()), GgufValue::String("a".into())]),
+                ),
+            ],
+            &[
+                ("token_embd.weight", vec![3, 2], GgmlType::F32, f32_data),
+                ("blk.0.attn_norm.weight", vec![2], GgmlType::BF16, bf16_data),
+            ],
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tiny.gguf");
+        std::fs::write(&path, bytes).unwrap();
+
+        let gguf = GgufFile::open(&path).unwrap();
+        assert_eq!(gguf.version, 3);
+        assert_eq!(gguf.metadata.architecture(), Some("llama"));
+        assert_eq!(gguf.metadata.context_length(), Some(4096));
+        assert_eq!(gguf.metadata.tokens().unwrap(), vec!["<s>", "a"]);
+
+        let embd = &gguf.tensors[0];
+        assert_eq!(embd.shape(), vec![2, 3]);
+        assert_eq!(gguf.dequantize(embd).unwrap(), vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+        assert_eq!(gguf.dequantize(&gguf.tensors[1]).unwrap(), vec![1.5, -2.0]);
+    }
+
+    #[test]
+    fn test_rejects_bad_magic_and_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bad.gguf");
+
+        std::fs::write(&path, b"GGML\x03\x00\x00\x00").unwrap();
+        assert!(GgufFile::open(&path).unwrap_err().to_string().contains("magic"));
+
+        std::fs::write(&path, b"GGUF\x01\x00\x00\x00").unwrap();
+        assert!(GgufFile::open(&path).unwrap_err().to_string().contains("version 1"));
+    }
+
+    #[test]
+    fn test_hf_names_and_rope_unpermute() {
+        assert_eq!(hf_tensor_name("blk.3.attn_q.weight"), "model.layers.3.self_attn.q_proj.weight");
+        assert_eq!(hf_tensor_name("blk.0.ffn_norm.weight"), "model.layers.0.post_attention_layernorm.weight");
+        assert_eq!(hf_tensor_name("output.weight"), "lm_head.weight");
+        assert_eq!(hf_tensor_name("rope_freqs.weight"), "rope_freqs.weight");
+
+        // One head, head_dim 4, rows of length 1: GGUF order [0, 2, 1, 3]
+        let gguf_rows = vec![0.0, 2.0, 1.0, 3.0];
+        assert_eq!(unpermute_rope_rows(&gguf_rows, 1, 1), vec![0.0, 1.0, 2.0, 3.0]);
+
+        let hf: Vec<f32> = (0..24).map(|v| v as f32).collect();
+        assert_eq!(unpermute_rope_rows(&permute_rope_rows(&hf, 2, 3), 2, 3), hf);
+        for name in ["model.layers.7.mlp.down_proj.weight", "lm_head.weight", "custom.weight"] {
+            assert_eq!(hf_tensor_name(&gguf_tensor_name(name)), name);
+        }
+    }
+
+    #[test]
+    fn test_writer_roundtrip() {
+        let mut writer = GgufWriter::new();
+        writer.add_metadata("general.architecture", GgufValue::String("llama".into()));
+        writer.add_metadata("general.flag", GgufValue::Bool(true));
+        writer.add_metadata("llama.rope.freq_base", GgufValue::F64(500000.0));
+        writer.add_tensor("a", &[2, 3], &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0], GgmlType::F16).unwrap();
+        writer.add_tensor("b", &[2], &[0.5, -1.0], GgmlType::F32).unwrap();
+        assert!(writer.add_tensor("c", &[4], &[0.0; 3], GgmlType::F32).is_err());
+        assert!(writer.add_tensor("c", &[32], &[0.0; 32], GgmlType::Q8_0).is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("written.gguf");
+        writer.write_to_file(&path).unwrap();
+
+        let gguf = GgufFile::open(&path).unwrap();
+        assert_eq!(gguf.metadata.get("general.flag"), Some(&GgufValue::Bool(true)));
+        assert_eq!(gguf.metadata.arch_f32("rope.freq_base"), Some(500000.0));
+        assert_eq!(gguf.tensors[0].shape(), vec![2, 3]);
+        assert_eq!(gguf.dequantize(&gguf.tensors[0]).unwrap(), vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+        assert_eq!(gguf.dequantize(&gguf.tensors[1]).unwrap(), vec![0.5, -1.0]);
+    }
+}
@@ -0,0 +1,395 @@
+//! Low-rank adapters (LoRA) for parameter-efficient online learning
+//!
+//! An adapter adds `scaling * B A` to selected linear weights `W [out, in]`,
+//! with `A [rank, in]`, `B [out, rank]` and `scaling = alpha / rank`. `B`
+//! starts at zero, so a fresh adapter leaves the model's outputs unchanged.
+//! While an adapter is active on `InferenceModel`, `OnlineLearner` trains
+//! only `A` and `B`; the base weights stay frozen, so unloading the adapter
+//! undoes all of its training.
+//!
+//! Adapters are saved on their own as SafeTensors with PEFT-style names
+//! (`{module}.lora_A.weight`, `{module}.lora_B.weight`) and `rank`/`alpha`
+//! in the header metadata.
+
+use anyhow::{Context, Result};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use super::weight_loader::{SaveOptions, WeightFormat, WeightLoader};
+use crate::inference::ParamMut;
+
+/// Which weights get an adapter, and how large it is
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoraConfig {
+    /// Inner dimension of `B A`
+    pub rank: usize,
+    /// Numerator of the `alpha / rank` scaling
+    pub alpha: f32,
+    /// Module names to adapt, e.g. "q_proj" matches every
+    /// `model.layers.{i}.self_attn.q_proj.weight`
+    pub target_modules: Vec<String>,
+    /// Seed for the random init of `A`
+    pub seed: u64,
+}
+
+impl Default for LoraConfig {
+    fn default() -> Self {
+        Self {
+            rank: 8,
+            alpha: 16.0,
+            target_modules: vec!["q_proj".to_string(), "v_proj".to_string()],
+            seed: 0,
+        }
+    }
+}
+
+/// Low-rank update of one `[out_features, in_features]` weight
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoraLayer {
+    /// Row-major [rank, in_features]
+    pub a: Vec<f32>,
+    /// Row-major [out_features, rank]
+    pub b: Vec<f32>,
+    pub rank: usize,
+    pub in_features: usize,
+    pub out_features: usize,
+}
+
+impl LoraLayer {
+    /// Uniform `A` scaled by fan-in, zero `B`
+    fn new(rng: &mut StdRng, rank: usize, in_features: usize, out_features: usize) -> Self {
+        let bound = 1.0 / (in_features as f32).sqrt();
+        Self {
+            a: (0..rank * in_features).map(|_| rng.gen_range(-bound..bound)).collect(),
+            b: vec![0.0; out_features * rank],
+            rank,
+            in_features,
+            out_features,
+        }
+    }
+
+    /// `scaling * B A`, row-major [out_features, in_features]
+    pub fn delta(&self, scaling: f32) -> Vec<f32> {
+        let mut delta = vec![0.0; self.out_features * self.in_features];
+        for (row, b_row) in delta.chunks_exact_mut(self.in_features).zip(self.b.chunks_exact(self.rank)) {
+            for (&b, a_row) in b_row.iter().zip(self.a.chunks_exact(self.in_features)) {
+                if b == 0.0 {
+                    continue;
+                }
+                for (d, a) in row.iter_mut().zip(a_row) {
+                    *d += scaling * b * a;
+                }
+            }
+        }
+        delta
+    }
+
+    pub fn num_params(&self) -> usize {
+        self.a.len() + self.b.len()
+    }
+}
+
+/// A named set of `LoraLayer`s keyed by the weight they adapt
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoraAdapter {
+    name: String,
+    rank: usize,
+    alpha: f32,
+    layers: BTreeMap<String, LoraLayer>,
+}
+
+impl LoraAdapter {
+    /// Adapter for every weight in `weights` (name, out_features,
+    /// in_features) whose module is listed in `config.target_modules`
+    pub fn new(name: impl Into<String>, config: &LoraConfig, weights: &[(String, usize, usize)]) -> Result<Self> {
+        if config.rank == 0 {
+            anyhow::bail!("LoRA rank must be positive");
+        }
+        let mut rng = StdRng::seed_from_u64(config.seed);
+
+        let layers: BTreeMap<_, _> = weights
+            .iter()
+            .filter(|(weight, _, _)| {
+                module_name(weight).is_some_and(|module| config.target_modules.iter().any(|t| t == module))
+            })
+            .map(|(weight, out_features, in_features)| {
+                (weight.clone(), LoraLayer::new(&mut rng, config.rank, *in_features, *out_features))
+            })
+            .collect();
+        if layers.is_empty() {
+            anyhow::bail!("No weights match target modules {:?}", config.target_modules);
+        }
+
+        Ok(Self {
+            name: name.into(),
+            rank: config.rank,
+            alpha: config.alpha,
+            layers,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn set_name(&mut self, name: impl Into<String>) {
+        self.name = name.into();
+    }
+
+    pub fn rank(&self) -> usize {
+        self.rank
+    }
+
+    pub fn alpha(&self) -> f32 {
+        self.alpha
+    }
+
+    /// Factor applied to `B A`
+    pub fn scaling(&self) -> f32 {
+        self.alpha / self.rank as f32
+    }
+
+    /// Adapter for the weight named `weight`, if it has one
+    pub fn layer(&self, weight: &str) -> Option<&LoraLayer> {
+        self.layers.get(weight)
+    }
+
+    /// Adapted weights and their adapters, ordered by weight name
+    pub fn layers(&self) -> impl Iterator<Item = (&String, &LoraLayer)> {
+        self.layers.iter()
+    }
+
+    pub fn num_params(&self) -> usize {
+        self.layers.values().map(LoraLayer::num_params).sum()
+    }
+
+    /// `A` and `B` of every layer, under the names `Transformer::record_with` registers
+    pub fn parameters_mut(&mut self) -> Vec<ParamMut<'_>> {
+        let mut params = Vec::new();
+        for (weight, layer) in &mut self.layers {
+            params.push(ParamMut {
+                name: lora_a_name(weight),
+                rows: layer.rank,
+                cols: layer.in_features,
+                values: &mut layer.a,
+            });
+            params.push(ParamMut {
+                name: lora_b_name(weight),
+                rows: layer.out_features,
+                cols: layer.rank,
+                values: &mut layer.b,
+            });
+        }
+        params
+    }
+
+    /// Add `scaling * B A` to the matching weights in `params`
+    pub fn merge_into(&self, params: Vec<ParamMut<'_>>) {
+        let scaling = self.scaling();
+        for param in params {
+            if let Some(layer) = self.layers.get(&param.name) {
+                for (w, d) in param.values.iter_mut().zip(layer.delta(scaling)) {
+                    *w += d;
+                }
+            }
+        }
+    }
+
+    /// Tensors under the names `save` writes
+    pub fn to_loader(&self) -> Result<WeightLoader> {
+        let mut loader = WeightLoader::new(WeightFormat::SafeTensors);
+        for (weight, layer) in &self.layers {
+            loader.insert_tensor_with_shape(lora_a_name(weight), vec![layer.rank, layer.in_features], layer.a.clone())?;
+            loader.insert_tensor_with_shape(lora_b_name(weight), vec![layer.out_features, layer.rank], layer.b.clone())?;
+        }
+        Ok(loader)
+    }
+
+    /// Rebuild an adapter from `to_loader` tensors; `alpha` defaults to the rank
+    pub fn from_loader(name: impl Into<String>, alpha: Option<f32>, loader: &WeightLoader) -> Result<Self> {
+        let mut layers = BTreeMap::new();
+        for tensor in loader.tensor_names() {
+            let Some(prefix) = tensor.strip_suffix(".lora_A.weight") else {
+                continue;
+            };
+            let weight = format!("{}.weight", prefix);
+            let a_shape = tensor_shape(loader, &tensor)?;
+            let b_name = lora_b_name(&weight);
+            let b_shape = tensor_shape(loader, &b_name)?;
+            let (rank, in_features, out_features) = match (a_shape.as_slice(), b_shape.as_slice()) {
+                ([rank, in_features], [out_features, b_rank]) if rank == b_rank => (*rank, *in_features, *out_features),
+                _ => anyhow::bail!("Mismatched LoRA shapes {:?} and {:?} for {}", a_shape, b_shape, weight),
+            };
+
+            layers.insert(
+                weight,
+                LoraLayer {
+                    a: loader.get_tensor(&tensor).cloned().unwrap_or_default(),
+                    b: loader.get_tensor(&b_name).cloned().unwrap_or_default(),
+                    rank,
+                    in_features,
+                    out_features,
+                },
+            );
+        }
+
+        let rank = match layers.values().next() {
+            Some(layer) => layer.rank,
+            None => anyhow::bail!("No LoRA tensors found"),
+        };
+        if rank == 0 {
+            anyhow::bail!("LoRA rank must be positive");
+        }
+        if layers.values().any(|layer| layer.rank != rank) {
+            anyhow::bail!("LoRA layers have different ranks");
+        }
+
+        Ok(Self {
+            name: name.into(),
+            rank,
+            alpha: alpha.unwrap_or(rank as f32),
+            layers,
+        })
+    }
+
+    /// Save as a SafeTensors file with `name`, `rank` and `alpha` metadata
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let options = SaveOptions {
+            metadata: HashMap::from([
+                ("format".to_string(), "pt".to_string()),
+                ("lora_name".to_string(), self.name.clone()),
+                ("lora_rank".to_string(), self.rank.to_string()),
+                ("lora_alpha".to_string(), self.alpha.to_string()),
+            ]),
+            ..SaveOptions::default()
+        };
+        self.to_loader()?.save_with_options(path, WeightFormat::SafeTensors, &options)
+    }
+
+    /// Load a file written by `save`
+    ///
+    /// The name comes from the file's metadata, else its stem. Files without
+    /// `lora_alpha` (plain PEFT exports) get `alpha = rank`, i.e. scaling 1.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let mut loader = WeightLoader::new(WeightFormat::SafeTensors);
+        loader
+            .load_from_file(path)
+            .with_context(|| format!("Failed to load adapter {}", path.display()))?;
+        let metadata = loader.safetensors_metadata();
+
+        let name = metadata
+            .get("lora_name")
+            .cloned()
+            .or_else(|| path.file_stem().map(|s| s.to_string_lossy().into_owned()))
+            .unwrap_or_else(|| "adapter".to_string());
+        let alpha = metadata.get("lora_alpha").map(|a| a.parse::<f32>()).transpose()?;
+
+        Self::from_loader(name, alpha, &loader)
+    }
+}
+
+/// "q_proj" for "model.layers.0.self_attn.q_proj.weight"
+fn module_name(weight: &str) -> Option<&str> {
+    weight.strip_suffix(".weight")?.rsplit('.').next()
+}
+
+fn lora_a_name(weight: &str) -> String {
+    format!("{}.lora_A.weight", weight.strip_suffix(".weight").unwrap_or(weight))
+}
+
+fn lora_b_name(weight: &str) -> String {
+    format!("{}.lora_B.weight", weight.strip_suffix(".weight").unwrap_or(weight))
+}
+
+fn tensor_shape(loader: &WeightLoader, name: &str) -> Result<Vec<usize>> {
+    loader
+        .get_metadata(name)
+        .map(|info| info.shape.clone())
+        .ok_or_else(|| anyhow::anyhow!("Missing LoRA tensor {}", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn weights() -> Vec<(String, usize, usize)> {
+        vec![
+            ("model.layers.0.self_attn.q_proj.weight".to_string(), 4, 3),
+            ("model.layers.0.self_attn.k_proj.weight".to_string(), 2, 3),
+            ("model.layers.0.self_attn.v_proj.weight".to_string(), 2, 3),
+        ]
+    }
+
+    #[test]
+    fn test_new_adapter_targets_modules_and_starts_at_zero() {
+        let adapter = LoraAdapter::new("a", &LoraConfig { rank: 2, ..LoraConfig::default() }, &weights()).unwrap();
+        let names: Vec<_> = adapter.layers().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["model.layers.0.self_attn.q_proj.weight", "model.layers.0.self_attn.v_proj.weight"]);
+        assert_eq!(adapter.num_params(), 2 * 3 + 4 * 2 + 2 * 3 + 2 * 2);
+
+        let q = adapter.layer("model.layers.0.self_attn.q_proj.weight").unwrap();
+        assert!(q.delta(adapter.scaling()).iter().all(|&d| d == 0.0));
+
+        let none = LoraConfig {
+            target_modules: vec!["gate_proj".to_string()],
+            ..LoraConfig::default()
+        };
+        assert!(LoraAdapter::new("b", &none, &weights()).is_err());
+    }
+
+    #[test]
+    fn test_merge_adds_scaled_low_rank_product() {
+        let config = LoraConfig {
+            rank: 1,
+            alpha: 2.0,
+            target_modules: vec!["k_proj".to_string()],
+            seed: 0,
+        };
+        let mut adapter = LoraAdapter::new("a", &config, &weights()).unwrap();
+        for param in adapter.parameters_mut() {
+            let fill: &[f32] = if param.name.ends_with("lora_A.weight") { &[1.0, 2.0, 3.0] } else { &[1.0, -1.0] };
+            param.values.copy_from_slice(fill);
+        }
+
+        let mut weight = vec![0.5; 6];
+        adapter.merge_into(vec![ParamMut {
+            name: "model.layers.0.self_attn.k_proj.weight".to_string(),
+            rows: 2,
+            cols: 3,
+            values: &mut weight,
+        }]);
+        assert_eq!(weight, vec![2.5, 4.5, 6.5, -1.5, -3.5, -5.5]);
+    }
+
+    #[test]
+    fn test_save_and_load_round_trip() {
+        let config = LoraConfig {
+            rank: 2,
+            alpha: 4.0,
+            ..LoraConfig::default()
+        };
+        let mut adapter = LoraAdapter::new("style", &config, &weights()).unwrap();
+        for param in adapter.parameters_mut() {
+            for (i, v) in param.values.iter_mut().enumerate() {
+                *v = i as f32 * 0.25;
+            }
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("renamed.safetensors");
+        adapter.save(&path).unwrap();
+
+        let loaded = LoraAdapter::load(&path).unwrap();
+        assert_eq!(loaded, adapter);
+        assert_eq!(loaded.scaling(), 2.0);
+
+        let mut empty = WeightLoader::new(WeightFormat::SafeTensors);
+        let weight = "model.layers.0.self_attn.q_proj.weight";
+        empty.insert_tensor_with_shape(lora_a_name(weight), vec![0, 3], Vec::new()).unwrap();
+        empty.insert_tensor_with_shape(lora_b_name(weight), vec![4, 0], Vec::new()).unwrap();
+        assert!(LoraAdapter::from_loader("empty", None, &empty).is_err());
+    }
+}
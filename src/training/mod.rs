@@ -1,11 +1,31 @@
 //! Training infrastructure for model learning and weight management
 
 pub mod weight_loader;
+pub mod gguf;
+pub mod checkpoint;
+pub mod lora;
+pub mod dataset;
+pub mod replay;
+pub mod regularizer;
+pub mod eval;
+pub mod autograd;
 pub mod optimizer;
-pub mod backprop;
 pub mod online_learning;
 
-pub use weight_loader::{WeightLoader, WeightFormat, TensorInfo};
-pub use optimizer::{Optimizer, AdamOptimizer, AdamConfig, SGDOptimizer, OptimizerConfig};
-pub use backprop::BackpropEngine;
-pub use online_learning::{OnlineLearner, LearningConfig, TrainingExample};
+pub use weight_loader::{WeightLoader, WeightFormat, TensorInfo, SaveDtype, SaveOptions};
+pub use gguf::{GgufFile, GgufMetadata, GgufValue, GgmlType};
+pub use optimizer::{
+    Optimizer, AdamOptimizer, AdamConfig, AdamWOptimizer, LionOptimizer, LionConfig,
+    AdafactorOptimizer, AdafactorConfig, SGDOptimizer, OptimizerConfig, OptimizerKind,
+    LrScheduler, LrSchedule,
+};
+pub use autograd::{Tape, Var, Gradients, GradCheckReport, check_gradients};
+pub use checkpoint::{Checkpoint, CheckpointInfo, CheckpointManager, TrainerState};
+pub use lora::{LoraAdapter, LoraConfig, LoraLayer};
+pub use dataset::Dataset;
+pub use replay::{ReplayBuffer, ReplayPolicy};
+pub use regularizer::{Anchor, Regularizer};
+pub use eval::{
+    Comparison, EvalMetrics, Evaluator, GateReport, ReasoningTask, RegressionGate, RegressionThresholds, TaskResult,
+};
+pub use online_learning::{OnlineLearner, LearningConfig, LearningStats, TrainingExample, example_tokens};
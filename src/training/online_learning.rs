@@ -1,11 +1,33 @@
 //! Online learning system for continuous training during inference
+//!
+//! Examples are trained with a causal language-modelling objective: the
+//! model reads `input` followed by `target` and is scored with teacher-forced
+//! cross-entropy on the target tokens only (the prompt is loss-masked).
+//! Gradients come from the autograd tape and every named weight of the model
+//! is updated through the `Optimizer` - or, while a LoRA adapter is active
+//! on the model, only that adapter's matrices.
+//!
+//! Buffered examples are kept and sampled by a `ReplayPolicy`; a
+//! `Regularizer` can pull the trained weights back towards the ones the
+//! learner started from, so continual updates do not erase the base model.
 
 use anyhow::Result;
-use std::collections::VecDeque;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, RwLock};
-
-use super::backprop::{BackpropEngine, LossFunction};
-use super::optimizer::{Optimizer, AdamOptimizer, AdamConfig};
+use tokio_util::sync::CancellationToken;
+
+use super::autograd::Tape;
+use super::checkpoint::{Checkpoint, CheckpointInfo, CheckpointManager, TrainerState, CHECKPOINT_VERSION};
+use super::eval::{GateReport, RegressionGate};
+use super::optimizer::{LrSchedule, LrScheduler, Optimizer, OptimizerConfig, OptimizerKind};
+use super::regularizer::{Anchor, Regularizer};
+use super::replay::{ReplayBuffer, ReplayPolicy};
+#[cfg(feature = "gpu")]
+use super::optimizer::{AdamOptimizer, AdamConfig};
 use crate::inference::InferenceModel;
 
 #[cfg(feature = "gpu")]
@@ -17,51 +39,80 @@ pub struct LearningConfig {
     /// Maximum number of examples to keep in buffer
     pub buffer_size: usize,
 
+    /// Which examples the buffer keeps and each update trains on
+    pub replay: ReplayPolicy,
+
     /// Number of examples to accumulate before updating
     pub update_frequency: usize,
 
     /// Whether to use GPU for training
     pub use_gpu: bool,
 
-    /// Learning rate
+    /// Base learning rate (the schedule scales it per update)
     pub learning_rate: f32,
 
-    /// Loss function
-    pub loss_fn: LossFunction,
+    /// Optimizer applied to every named weight
+    pub optimizer: OptimizerKind,
+
+    /// Learning-rate schedule over updates
+    pub lr_schedule: LrSchedule,
+
+    /// Weight decay (L2 for Adam/SGD, decoupled for AdamW/Lion/Adafactor)
+    pub weight_decay: f32,
+
+    /// Per-element gradient clipping threshold
+    pub grad_clip: Option<f32>,
+
+    /// Penalty for drifting from the weights before the first update
+    pub regularizer: Regularizer,
 
     /// Enable/disable learning
     pub enabled: bool,
 
     /// Save checkpoint every N updates
     pub checkpoint_frequency: Option<usize>,
+
+    /// Where checkpoints are written (None = only log at checkpoint_frequency)
+    pub checkpoint_dir: Option<PathBuf>,
+
+    /// Checkpoints kept in checkpoint_dir (0 = keep all)
+    pub keep_checkpoints: usize,
+
+    /// Seed for randomness during training; saved in checkpoints
+    pub seed: u64,
 }
 
 impl Default for LearningConfig {
     fn default() -> Self {
         Self {
             buffer_size: 1000,
+            replay: ReplayPolicy::Fifo,
             update_frequency: 10,
             use_gpu: true,
             learning_rate: 1e-4,
-            loss_fn: LossFunction::MSE,
+            optimizer: OptimizerKind::Adam,
+            lr_schedule: LrSchedule::Constant,
+            weight_decay: 0.01,
+            grad_clip: Some(1.0),
+            regularizer: Regularizer::None,
             enabled: false,
             checkpoint_frequency: Some(100),
+            checkpoint_dir: None,
+            keep_checkpoints: 3,
+            seed: 0,
         }
     }
 }
 
 /// Training example
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TrainingExample {
-    /// Input text
+    /// Input text (the prompt; not scored when a target is given)
     pub input: String,
 
-    /// Target output text (for supervised learning)
+    /// Target completion of `input`; None trains on `input` itself
     pub target: Option<String>,
 
-    /// Target embedding (for embedding-level training)
-    pub target_embedding: Option<Vec<f32>>,
-
     /// Weight for this example
     pub weight: f32,
 }
@@ -71,16 +122,6 @@ impl TrainingExample {
         Self {
             input,
             target,
-            target_embedding: None,
-            weight: 1.0,
-        }
-    }
-
-    pub fn with_embedding(input: String, target_embedding: Vec<f32>) -> Self {
-        Self {
-            input,
-            target: None,
-            target_embedding: Some(target_embedding),
             weight: 1.0,
         }
     }
@@ -94,17 +135,28 @@ impl TrainingExample {
 /// Online learner that trains during inference
 pub struct OnlineLearner {
     config: LearningConfig,
-    buffer: VecDeque<TrainingExample>,
+    buffer: ReplayBuffer,
 
     optimizer: Box<dyn Optimizer>,
-    backprop: BackpropEngine,
+    scheduler: Box<dyn LrScheduler>,
 
     model: Arc<RwLock<InferenceModel>>,
 
+    /// Reference weights for the regularizer, captured at the first update
+    anchor: Anchor,
+
+    /// Metrics that automatic checkpoints must not regress from
+    regression_gate: Option<RegressionGate>,
+
     // Statistics
     total_examples: usize,
     total_updates: usize,
     recent_losses: VecDeque<f32>,
+    skipped_updates: usize,
+    last_error: Option<String>,
+
+    /// Adapter the optimizer state belongs to (None = the full model)
+    trained_adapter: Option<String>,
 
     #[cfg(feature = "gpu")]
     gpu_context: Option<Arc<CudaContext>>,
@@ -116,29 +168,23 @@ impl OnlineLearner {
         config: LearningConfig,
         model: Arc<RwLock<InferenceModel>>,
     ) -> Self {
-        let adam_config = AdamConfig {
-            base: super::optimizer::OptimizerConfig {
-                learning_rate: config.learning_rate,
-                weight_decay: 0.01,
-                grad_clip: Some(1.0),
-            },
-            beta1: 0.9,
-            beta2: 0.999,
-            epsilon: 1e-8,
-        };
-
-        let optimizer = Box::new(AdamOptimizer::new(adam_config));
-        let backprop = BackpropEngine::new(config.loss_fn);
+        let optimizer = config.optimizer.build(Self::optimizer_config(&config));
+        let scheduler = config.lr_schedule.build(config.learning_rate);
 
         Self {
+            buffer: ReplayBuffer::new(config.replay, config.buffer_size),
             config,
-            buffer: VecDeque::new(),
             optimizer,
-            backprop,
+            scheduler,
             model,
+            anchor: Anchor::default(),
+            regression_gate: None,
             total_examples: 0,
             total_updates: 0,
             recent_losses: VecDeque::new(),
+            skipped_updates: 0,
+            last_error: None,
+            trained_adapter: None,
             #[cfg(feature = "gpu")]
             gpu_context: None,
         }
@@ -151,155 +197,507 @@ impl OnlineLearner {
         model: Arc<RwLock<InferenceModel>>,
         gpu_context: Arc<CudaContext>,
     ) -> Self {
-        let adam_config = AdamConfig {
-            base: super::optimizer::OptimizerConfig {
-                learning_rate: config.learning_rate,
-                weight_decay: 0.01,
-                grad_clip: Some(1.0),
-            },
-            beta1: 0.9,
-            beta2: 0.999,
-            epsilon: 1e-8,
+        // Only Adam has a GPU kernel; other optimizers update on the CPU
+        let optimizer: Box<dyn Optimizer> = match config.optimizer {
+            OptimizerKind::Adam => Box::new(AdamOptimizer::new_with_gpu(
+                AdamConfig {
+                    base: Self::optimizer_config(&config),
+                    ..AdamConfig::default()
+                },
+                gpu_context.clone(),
+            )),
+            kind => kind.build(Self::optimizer_config(&config)),
         };
-
-        let optimizer = Box::new(AdamOptimizer::new_with_gpu(adam_config, gpu_context.clone()));
-        let backprop = BackpropEngine::new_with_gpu(config.loss_fn, gpu_context.clone());
+        let scheduler = config.lr_schedule.build(config.learning_rate);
 
         Self {
+            buffer: ReplayBuffer::new(config.replay, config.buffer_size),
             config,
-            buffer: VecDeque::new(),
             optimizer,
-            backprop,
+            scheduler,
             model,
+            anchor: Anchor::default(),
+            regression_gate: None,
             total_examples: 0,
             total_updates: 0,
             recent_losses: VecDeque::new(),
+            skipped_updates: 0,
+            last_error: None,
+            trained_adapter: None,
             gpu_context: Some(gpu_context),
         }
     }
 
+    fn optimizer_config(config: &LearningConfig) -> OptimizerConfig {
+        OptimizerConfig {
+            learning_rate: config.learning_rate,
+            weight_decay: config.weight_decay,
+            grad_clip: config.grad_clip,
+        }
+    }
+
+    /// A new optimizer of the configured kind, on the GPU where `new_with_gpu` would use it
+    fn fresh_optimizer(&self) -> Box<dyn Optimizer> {
+        #[cfg(feature = "gpu")]
+        if let (OptimizerKind::Adam, Some(gpu_context)) = (self.config.optimizer, &self.gpu_context) {
+            return Box::new(AdamOptimizer::new_with_gpu(
+                AdamConfig {
+                    base: Self::optimizer_config(&self.config),
+                    ..AdamConfig::default()
+                },
+                gpu_context.clone(),
+            ));
+        }
+        self.config.optimizer.build(Self::optimizer_config(&self.config))
+    }
+
+    /// Start from fresh optimizer state when the model's active adapter
+    /// changed since the last update (moments belong to the weights they
+    /// were accumulated for)
+    fn sync_trained_adapter(&mut self) -> Result<()> {
+        let active = self
+            .model
+            .read()
+            .map_err(|_| anyhow::anyhow!("Failed to acquire read lock on model"))?
+            .active_adapter()
+            .map(|adapter| adapter.name().to_string());
+
+        if active != self.trained_adapter {
+            tracing::info!(
+                "Training {} with fresh optimizer state",
+                active.as_deref().map_or("the full model".to_string(), |name| format!("adapter {}", name))
+            );
+            self.optimizer = self.fresh_optimizer();
+            self.trained_adapter = active;
+        }
+        Ok(())
+    }
+
+    /// Randomness for `stream` at `counter`, reproducible from the seed
+    fn rng(&self, stream: u64, counter: usize) -> StdRng {
+        let mixed = (self.config.seed ^ stream.rotate_left(32)).wrapping_add(counter as u64);
+        StdRng::seed_from_u64(mixed.wrapping_mul(0x9E37_79B9_7F4A_7C15))
+    }
+
     /// Add a training example
     pub fn add_example(&mut self, example: TrainingExample) -> Result<()> {
         if !self.config.enabled {
             return Ok(());
         }
 
-        // Add to buffer
-        self.buffer.push_back(example);
+        // Add to buffer; the policy decides what it evicts
         self.total_examples += 1;
-
-        // Remove oldest if buffer is full
-        if self.buffer.len() > self.config.buffer_size {
-            self.buffer.pop_front();
-        }
+        let mut rng = self.rng(0, self.total_examples);
+        self.buffer.push(example, self.total_examples, &mut rng);
 
         // Check if we should update
-        if self.total_examples % self.config.update_frequency == 0 {
-            self.update_weights()?;
+        if self.total_examples.is_multiple_of(self.config.update_frequency) {
+            self.update_weights(&CancellationToken::new(), &mut |_, _| {})?;
         }
 
         Ok(())
     }
 
     /// Perform a weight update using buffered examples
-    fn update_weights(&mut self) -> Result<()> {
+    ///
+    /// A failed update leaves the weights untouched, is counted in
+    /// `LearningStats::skipped_updates` and is returned as an error.
+    fn update_weights(&mut self, cancel: &CancellationToken, on_example: &mut dyn FnMut(usize, usize)) -> Result<()> {
         if self.buffer.is_empty() {
             return Ok(());
         }
 
-        // Sample examples for this update
-        let batch_size = self.config.update_frequency.min(self.buffer.len());
-        let examples: Vec<_> = self.buffer.iter().take(batch_size).cloned().collect();
-
-        // Compute loss and gradients
-        let mut total_loss = 0.0;
-        let mut accumulated_grads: Option<Vec<f32>> = None;
-
-        for example in &examples {
-            // Forward pass
-            let (predictions, target_values) = self.compute_prediction_and_target(example)?;
+        let mut rng = self.rng(1, self.total_updates);
+        let indices = self.buffer.sample(self.config.update_frequency, &mut rng);
+        let examples = self.buffer.batch(&indices);
 
-            // Compute loss
-            let loss = self.backprop.compute_loss(&predictions, &target_values)?;
-            total_loss += loss * example.weight;
+        let (_, losses) = self.train(&examples, cancel, on_example)?;
+        self.buffer.record(&indices, &losses);
+        Ok(())
+    }
 
-            // Compute gradients
-            let grads = self.backprop.compute_gradients(&predictions, &target_values)?;
+    /// One weight update on `examples`, independent of the buffer; returns
+    /// their weighted mean loss before the update
+    ///
+    /// Counts towards the schedule, statistics and checkpoint frequency like
+    /// a buffered update. A failed update leaves the weights untouched.
+    pub fn train_batch(&mut self, examples: &[TrainingExample]) -> Result<f32> {
+        self.train(examples, &CancellationToken::new(), &mut |_, _| {})
+            .map(|(avg_loss, _)| avg_loss)
+    }
 
-            // Accumulate gradients
-            if let Some(ref mut acc) = accumulated_grads {
-                for (acc_g, g) in acc.iter_mut().zip(&grads) {
-                    *acc_g += g * example.weight;
-                }
-            } else {
-                accumulated_grads = Some(grads.iter().map(|g| g * example.weight).collect());
+    /// `train_batch`, also returning the loss of each example; stops before
+    /// touching the weights once `cancel` fires
+    fn train(
+        &mut self,
+        examples: &[TrainingExample],
+        cancel: &CancellationToken,
+        on_example: &mut dyn FnMut(usize, usize),
+    ) -> Result<(f32, Vec<f32>)> {
+        self.sync_trained_adapter()?;
+        self.optimizer.set_lr(self.scheduler.lr_at(self.total_updates));
+        let (avg_loss, losses) = match self.apply_batch(examples, cancel, on_example) {
+            Ok(losses) => losses,
+            // A cancelled update is not a failed one
+            Err(e) if cancel.is_cancelled() => return Err(e),
+            Err(e) => {
+                self.skipped_updates += 1;
+                self.last_error = Some(e.to_string());
+                tracing::warn!("Online learning update skipped: {}", e);
+                return Err(e);
             }
-        }
+        };
 
-        // Average loss
-        let avg_loss = total_loss / batch_size as f32;
         self.recent_losses.push_back(avg_loss);
         if self.recent_losses.len() > 100 {
             self.recent_losses.pop_front();
         }
-
-        // Update weights if we have gradients
-        if let Some(grads) = accumulated_grads {
-            // Average gradients
-            let avg_grads: Vec<f32> = grads.iter().map(|g| g / batch_size as f32).collect();
-
-            // Update embedding weights
-            let mut model = self.model.write().unwrap();
-            if let Some(embedding) = Arc::get_mut(model.embedding_mut()) {
-                let weights = embedding.weights_mut();
-                self.optimizer.step(weights, &avg_grads)?;
-            }
-        }
-
         self.total_updates += 1;
+        self.scheduler.observe_loss(avg_loss);
 
         // Checkpoint if needed
         if let Some(freq) = self.config.checkpoint_frequency {
-            if self.total_updates % freq == 0 {
+            if self.total_updates.is_multiple_of(freq) {
                 tracing::info!(
                     "Online learning checkpoint: {} examples, {} updates, avg loss: {:.4}",
                     self.total_examples,
                     self.total_updates,
                     avg_loss
                 );
+
+                // The update itself succeeded, so a failed (or refused) save is only reported
+                if self.config.checkpoint_dir.is_some() {
+                    if let Err(e) = self.ensure_no_regression().and_then(|_| self.save_checkpoint()) {
+                        tracing::warn!("Failed to save checkpoint: {}", e);
+                        self.last_error = Some(format!("Checkpoint failed: {}", e));
+                    }
+                }
             }
         }
 
-        Ok(())
+        Ok((avg_loss, losses))
     }
 
-    /// Compute prediction and target for an example
-    fn compute_prediction_and_target(&self, example: &TrainingExample) -> Result<(Vec<f32>, Vec<f32>)> {
-        let model = self.model.read().unwrap();
+    /// One optimizer step on the weighted mean loss of `examples` (plus the
+    /// regularizer); returns that mean and the loss of each example.
+    /// `on_example(done, total)` follows the forward/backward passes.
+    fn apply_batch(
+        &mut self,
+        examples: &[TrainingExample],
+        cancel: &CancellationToken,
+        on_example: &mut dyn FnMut(usize, usize),
+    ) -> Result<(f32, Vec<f32>)> {
+        let total_weight: f32 = examples.iter().map(|e| e.weight).sum();
+        if total_weight.is_nan() || total_weight <= 0.0 {
+            anyhow::bail!("Batch has no positive example weight");
+        }
+
+        let regularizer = self.config.regularizer;
+        let mut fisher = Anchor::default();
+        let mut total_loss = 0.0;
+        let mut losses = Vec::with_capacity(examples.len());
+        let mut grads: HashMap<String, Vec<f32>> = HashMap::new();
+        {
+            let model = self
+                .model
+                .read()
+                .map_err(|_| anyhow::anyhow!("Failed to acquire read lock on model"))?;
+
+            for (i, example) in examples.iter().enumerate() {
+                if cancel.is_cancelled() {
+                    anyhow::bail!("Update cancelled after {} of {} examples", i, examples.len());
+                }
+                let scale = example.weight / total_weight;
+                let (loss, example_grads) = Self::loss_and_gradients(&model, example)?;
+                total_loss += loss * scale;
+                losses.push(loss);
+
+                for (name, g) in example_grads {
+                    // EWC: the Fisher diagonal at the anchor is the mean squared
+                    // per-example gradient of the batch that captures it
+                    if regularizer.needs_fisher() && !self.anchor.weights.contains_key(&name) {
+                        fisher.accumulate_fisher(&name, &g, scale);
+                    }
+                    match grads.get_mut(&name) {
+                        Some(acc) => {
+                            for (a, g) in acc.iter_mut().zip(&g) {
+                                *a += g * scale;
+                            }
+                        }
+                        None => {
+                            grads.insert(name, g.iter().map(|g| g * scale).collect());
+                        }
+                    }
+                }
+                on_example(i + 1, examples.len());
+            }
+        }
+
+        if !total_loss.is_finite() || grads.values().flatten().any(|g| !g.is_finite()) {
+            anyhow::bail!("Non-finite loss or gradients (loss = {})", total_loss);
+        }
+
+        let mut model = self
+            .model
+            .write()
+            .map_err(|_| anyhow::anyhow!("Failed to acquire write lock on model"))?;
+        self.anchor.fisher.extend(fisher.fisher);
+        for param in model.parameters_mut() {
+            let grad = grads
+                .get_mut(&param.name)
+                .ok_or_else(|| anyhow::anyhow!("No gradient for parameter {}", param.name))?;
+            if regularizer != Regularizer::None {
+                self.anchor.capture(&param.name, param.values);
+                self.anchor.penalize(regularizer, &param.name, param.values, grad);
+            }
+            self.optimizer
+                .step_matrix(&param.name, param.rows, param.cols, param.values, grad)?;
+        }
+
+        Ok((total_loss, losses))
+    }
 
-        // Get tokenizer and embedding
-        let tokenizer = model.tokenizer();
-        let embedding = model.embedding();
+    /// Teacher-forced cross-entropy of one example and its parameter gradients
+    fn loss_and_gradients(model: &InferenceModel, example: &TrainingExample) -> Result<(f32, HashMap<String, Vec<f32>>)> {
+        let (tokens, targets) = Self::encode_example(model, example)?;
 
-        // Tokenize input
-        let tokens = tokenizer.encode(&example.input);
+        let mut tape = Tape::new();
+        let logits = model.record_forward(&mut tape, &tokens)?;
+        let loss = tape.cross_entropy(logits, &targets)?;
+        let grads = tape.backward(loss)?;
 
-        // Get embeddings
-        let predictions = embedding.embed_batch(&[tokens.clone()])?;
+        Ok((tape.scalar(loss), grads.into_named()))
+    }
 
-        // Get target
-        let target_values = if let Some(ref target_emb) = example.target_embedding {
-            target_emb.clone()
-        } else if let Some(ref target_text) = example.target {
-            // Tokenize and embed target
-            let target_tokens = tokenizer.encode(target_text);
-            embedding.embed_batch(&[target_tokens])?
-        } else {
-            // Self-supervised: use input as target
-            predictions.clone()
+    /// Tokens the model reads and the next-token target of each position
+    pub(crate) fn encode_example(model: &InferenceModel, example: &TrainingExample) -> Result<(Vec<usize>, Vec<Option<usize>>)> {
+        let prompt = model.encode(&example.input);
+        let completion = match &example.target {
+            Some(target) => {
+                let mut completion = model.encode(target);
+                completion.extend(model.config().eos_token_id);
+                completion
+            }
+            None => Vec::new(),
+        };
+
+        let (tokens, targets) = causal_lm_targets(&prompt, &completion);
+        if targets.iter().all(Option::is_none) {
+            anyhow::bail!("Example has no tokens to predict");
+        }
+        let vocab_size = model.config().vocab_size;
+        if let Some(bad) = tokens.iter().find(|&&t| t >= vocab_size) {
+            anyhow::bail!("Token {} is outside the model vocabulary ({})", bad, vocab_size);
+        }
+
+        Ok((tokens, targets))
+    }
+
+    /// Loss of `example` under the current weights, without updating them
+    pub fn example_loss(&self, example: &TrainingExample) -> Result<f32> {
+        let model = self
+            .model
+            .read()
+            .map_err(|_| anyhow::anyhow!("Failed to acquire read lock on model"))?;
+        let (tokens, targets) = Self::encode_example(&model, example)?;
+
+        let mut tape = Tape::new();
+        let logits = model.record_forward(&mut tape, &tokens)?;
+        let loss = tape.cross_entropy(logits, &targets)?;
+        Ok(tape.scalar(loss))
+    }
+
+    /// Weighted mean loss of `examples` under the current weights
+    pub fn mean_loss(&self, examples: &[TrainingExample]) -> Result<f32> {
+        let total_weight: f32 = examples.iter().map(|e| e.weight).sum();
+        if total_weight.is_nan() || total_weight <= 0.0 {
+            anyhow::bail!("No examples with positive weight");
+        }
+
+        let mut total = 0.0;
+        for example in examples {
+            total += self.example_loss(example)? * example.weight;
+        }
+        Ok(total / total_weight)
+    }
+
+    /// Forget the regularizer's reference weights; the next update captures new ones
+    pub fn reset_anchor(&mut self) {
+        self.anchor.clear();
+    }
+
+    /// Capture weights, active adapter, optimizer moments, schedule, buffer,
+    /// regularizer anchor and stats
+    pub fn checkpoint(&self) -> Result<Checkpoint> {
+        let (model, adapter) = {
+            let model = self
+                .model
+                .read()
+                .map_err(|_| anyhow::anyhow!("Failed to acquire read lock on model"))?;
+            (model.export_weights()?, model.active_adapter().cloned())
+        };
+
+        let mut optimizer = self.optimizer.export_state();
+        let created_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        let state = TrainerState {
+            version: CHECKPOINT_VERSION,
+            step: self.total_updates,
+            created_at,
+            optimizer: self.config.optimizer.name().to_string(),
+            optimizer_step_count: optimizer.step_count,
+            optimizer_param_steps: std::mem::take(&mut optimizer.param_steps),
+            lr_schedule: self.scheduler.name().to_string(),
+            scheduler: self.scheduler.export_state(),
+            seed: self.config.seed,
+            buffer: self.buffer.examples().cloned().collect(),
+            buffer_losses: self.buffer.losses().collect(),
+            total_examples: self.total_examples,
+            skipped_updates: self.skipped_updates,
+            recent_losses: self.recent_losses.iter().copied().collect(),
+            last_error: self.last_error.clone(),
+            adapter: adapter.as_ref().map(|a| a.name().to_string()),
+        };
+
+        Ok(Checkpoint {
+            state,
+            model,
+            optimizer,
+            adapter,
+            anchor: (!self.anchor.is_empty()).then(|| self.anchor.clone()),
+        })
+    }
+
+    /// Continue training from `checkpoint`
+    ///
+    /// The learner must use the optimizer and schedule the checkpoint was
+    /// written with; the model weights are replaced by the saved ones and the
+    /// saved adapter, if any, is loaded and made active.
+    pub fn restore(&mut self, checkpoint: Checkpoint) -> Result<()> {
+        let Checkpoint {
+            state,
+            model,
+            optimizer,
+            adapter,
+            anchor,
+        } = checkpoint;
+
+        if state.optimizer != self.config.optimizer.name() {
+            anyhow::bail!(
+                "Checkpoint was trained with {}, but this learner uses {}",
+                state.optimizer,
+                self.config.optimizer.name()
+            );
+        }
+        if state.lr_schedule != self.scheduler.name() {
+            anyhow::bail!(
+                "Checkpoint used the {} schedule, but this learner uses {}",
+                state.lr_schedule,
+                self.scheduler.name()
+            );
+        }
+
+        {
+            let mut target = self
+                .model
+                .write()
+                .map_err(|_| anyhow::anyhow!("Failed to acquire write lock on model"))?;
+            target.load_weights(&model)?;
+            let active = adapter.as_ref().map(|a| a.name().to_string());
+            if let Some(adapter) = adapter {
+                target.insert_adapter(adapter)?;
+            }
+            target.set_active_adapter(active.as_deref())?;
+            self.trained_adapter = active;
+        }
+        self.optimizer = self.fresh_optimizer();
+        self.optimizer.import_state(optimizer)?;
+        self.scheduler.import_state(&state.scheduler);
+        self.config.learning_rate = state.scheduler.base_lr;
+        self.config.seed = state.seed;
+
+        self.buffer = ReplayBuffer::restore(self.config.replay, self.config.buffer_size, state.buffer, state.buffer_losses);
+        self.anchor = anchor.unwrap_or_default();
+        self.total_examples = state.total_examples;
+        self.total_updates = state.step;
+        self.skipped_updates = state.skipped_updates;
+        self.recent_losses = state.recent_losses.into();
+        self.last_error = state.last_error;
+        Ok(())
+    }
+
+    /// Refuse automatic checkpoints whose metrics regress from `gate`'s baseline
+    pub fn set_regression_gate(&mut self, gate: Option<RegressionGate>) {
+        self.regression_gate = gate;
+    }
+
+    pub fn regression_gate(&self) -> Option<&RegressionGate> {
+        self.regression_gate.as_ref()
+    }
+
+    /// Check the current weights against the regression gate, if one is set
+    pub fn check_regression(&self) -> Result<Option<GateReport>> {
+        let Some(gate) = &self.regression_gate else {
+            return Ok(None);
         };
+        let model = self
+            .model
+            .read()
+            .map_err(|_| anyhow::anyhow!("Failed to acquire read lock on model"))?;
+        gate.check(&model).map(Some)
+    }
+
+    /// Fail if the current weights regress past the gate's thresholds
+    pub fn ensure_no_regression(&self) -> Result<()> {
+        match self.check_regression()? {
+            Some(report) if !report.passed() => {
+                anyhow::bail!("Metrics regressed: {}", report.regressions.join("; "))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Checkpoints in `checkpoint_dir`, if configured
+    pub fn checkpoint_manager(&self) -> Option<CheckpointManager> {
+        self.config
+            .checkpoint_dir
+            .as_ref()
+            .map(|dir| CheckpointManager::new(dir, self.config.keep_checkpoints))
+    }
 
-        Ok((predictions, target_values))
+    /// Save a checkpoint into `checkpoint_dir`, rotating old ones
+    pub fn save_checkpoint(&self) -> Result<CheckpointInfo> {
+        let manager = self
+            .checkpoint_manager()
+            .ok_or_else(|| anyhow::anyhow!("No checkpoint_dir configured"))?;
+        let info = manager.save(&self.checkpoint()?)?;
+        tracing::info!("Saved checkpoint {}", info.path.display());
+        Ok(info)
+    }
+
+    /// Restore the checkpoint directory at `path`
+    pub fn resume_from(&mut self, path: impl AsRef<Path>) -> Result<CheckpointInfo> {
+        let path = path.as_ref();
+        let checkpoint = Checkpoint::read(path)?;
+        let state = checkpoint.state.clone();
+        self.restore(checkpoint)?;
+
+        tracing::info!("Resumed training from {} (step {})", path.display(), state.step);
+        Ok(CheckpointInfo::from_state(path.to_path_buf(), &state))
+    }
+
+    /// Restore the newest checkpoint in `checkpoint_dir`, if there is one
+    pub fn resume_latest(&mut self) -> Result<Option<CheckpointInfo>> {
+        let Some(manager) = self.checkpoint_manager() else {
+            return Ok(None);
+        };
+        match manager.latest()? {
+            Some(latest) => self.resume_from(&latest.path).map(Some),
+            None => Ok(None),
+        }
     }
 
     /// Enable learning
@@ -332,8 +730,15 @@ impl OnlineLearner {
             total_updates: self.total_updates,
             buffer_size: self.buffer.len(),
             average_loss: avg_loss,
-            learning_rate: self.optimizer.get_lr(),
+            learning_rate: self.scheduler.lr_at(self.total_updates),
+            base_learning_rate: self.scheduler.base_lr(),
+            optimizer: self.config.optimizer.name().to_string(),
+            lr_schedule: self.scheduler.name().to_string(),
+            replay_policy: self.buffer.policy().name().to_string(),
+            regularizer: self.config.regularizer.name().to_string(),
             enabled: self.config.enabled,
+            skipped_updates: self.skipped_updates,
+            last_error: self.last_error.clone(),
         }
     }
 
@@ -344,12 +749,24 @@ impl OnlineLearner {
 
     /// Force an immediate update
     pub fn force_update(&mut self) -> Result<()> {
-        self.update_weights()
+        self.update_weights(&CancellationToken::new(), &mut |_, _| {})
+    }
+
+    /// `force_update`, calling `on_example(done, total)` after each example's
+    /// forward/backward pass; once `cancel` fires the update stops with an
+    /// error and leaves the weights untouched
+    pub fn force_update_with_progress(
+        &mut self,
+        cancel: &CancellationToken,
+        mut on_example: impl FnMut(usize, usize),
+    ) -> Result<()> {
+        self.update_weights(cancel, &mut on_example)
     }
 
-    /// Set learning rate
+    /// Set the base learning rate (the schedule still applies on top)
     pub fn set_learning_rate(&mut self, lr: f32) {
-        self.optimizer.set_lr(lr);
+        self.scheduler.set_base_lr(lr);
+        self.optimizer.set_lr(self.scheduler.lr_at(self.total_updates));
         self.config.learning_rate = lr;
     }
 }
@@ -361,8 +778,43 @@ pub struct LearningStats {
     pub total_updates: usize,
     pub buffer_size: usize,
     pub average_loss: f32,
+    /// Rate the next update will use
     pub learning_rate: f32,
+    /// Rate before scheduling
+    pub base_learning_rate: f32,
+    pub optimizer: String,
+    pub lr_schedule: String,
+    pub replay_policy: String,
+    pub regularizer: String,
     pub enabled: bool,
+    /// Updates abandoned because of an error (weights left unchanged)
+    pub skipped_updates: usize,
+    /// Error of the most recent skipped update
+    pub last_error: Option<String>,
+}
+
+/// Tokens `example` occupies in a training forward pass (prompt, completion and EOS)
+pub fn example_tokens(model: &InferenceModel, example: &TrainingExample) -> usize {
+    let mut tokens = model.encode(&example.input).len();
+    if let Some(target) = &example.target {
+        tokens += model.encode(target).len() + usize::from(model.config().eos_token_id.is_some());
+    }
+    tokens
+}
+
+/// Next-token targets for `prompt` followed by `completion`
+///
+/// Position i of the returned sequence is trained to predict token i + 1.
+/// With a completion only completion tokens are predicted (the prompt is
+/// masked); without one every prompt token after the first is.
+pub fn causal_lm_targets(prompt: &[usize], completion: &[usize]) -> (Vec<usize>, Vec<Option<usize>>) {
+    let tokens: Vec<usize> = prompt.iter().chain(completion).copied().collect();
+    let first_scored = if completion.is_empty() { 1 } else { prompt.len().max(1) };
+
+    let targets = (0..tokens.len())
+        .map(|i| tokens.get(i + 1).copied().filter(|_| i + 1 >= first_scored))
+        .collect();
+    (tokens, targets)
 }
 
 #[cfg(test)]
@@ -386,4 +838,296 @@ mod tests {
         assert_eq!(example.weight, 2.0);
         assert_eq!(example.input, "input");
     }
+
+    #[test]
+    fn test_causal_lm_targets_mask_prompt() {
+        let (tokens, targets) = causal_lm_targets(&[10, 11, 12], &[20, 21]);
+        assert_eq!(tokens, vec![10, 11, 12, 20, 21]);
+        assert_eq!(targets, vec![None, None, Some(20), Some(21), None]);
+
+        // Self-supervised: every next token is scored
+        let (_, targets) = causal_lm_targets(&[10, 11, 12], &[]);
+        assert_eq!(targets, vec![Some(11), Some(12), None]);
+    }
+
+    fn tiny_model(max_seq_len: usize) -> Arc<RwLock<InferenceModel>> {
+        use crate::inference::ModelConfig;
+
+        let config = ModelConfig {
+            max_seq_len,
+            ..ModelConfig::tiny()
+        };
+        Arc::new(RwLock::new(InferenceModel::for_tests(config)))
+    }
+
+    fn learner(model: Arc<RwLock<InferenceModel>>) -> OnlineLearner {
+        let config = LearningConfig {
+            update_frequency: 1,
+            use_gpu: false,
+            learning_rate: 1e-2,
+            enabled: true,
+            checkpoint_frequency: None,
+            ..LearningConfig::default()
+        };
+        OnlineLearner::new(config, model)
+    }
+
+    #[test]
+    fn test_schedule_and_optimizer_reported_in_stats() {
+        let config = LearningConfig {
+            learning_rate: 1e-3,
+            optimizer: OptimizerKind::AdamW,
+            lr_schedule: LrSchedule::LinearWarmup { warmup_steps: 4 },
+            ..LearningConfig::default()
+        };
+        let mut learner = OnlineLearner::new(config, tiny_model(16));
+
+        let stats = learner.get_stats();
+        assert_eq!(stats.optimizer, "adamw");
+        assert_eq!(stats.lr_schedule, "linear_warmup");
+        assert!((stats.learning_rate - 2.5e-4).abs() < 1e-9);
+        assert_eq!(stats.base_learning_rate, 1e-3);
+
+        learner.set_learning_rate(4e-3);
+        let stats = learner.get_stats();
+        assert!((stats.learning_rate - 1e-3).abs() < 1e-9);
+        assert_eq!(stats.base_learning_rate, 4e-3);
+    }
+
+    #[test]
+    fn test_fine_tuning_lowers_target_loss() {
+        let model = tiny_model(64);
+        let mut learner = learner(model.clone());
+        let example = TrainingExample::new("The capital of France is".to_string(), Some(" Paris".to_string()));
+
+        let before = learner.example_loss(&example).unwrap();
+        let embedding_before = model.read().unwrap().embedding();
+        for _ in 0..5 {
+            learner.add_example(example.clone()).unwrap();
+        }
+        let after = learner.example_loss(&example).unwrap();
+
+        assert!(after < before, "loss {} -> {}", before, after);
+        let stats = learner.get_stats();
+        assert_eq!(stats.total_updates, 5);
+        assert_eq!(stats.skipped_updates, 0);
+
+        // Transformer blocks and the (shared) embedding table were both updated
+        let model = model.read().unwrap();
+        assert_ne!(model.embedding().weights(), embedding_before.weights());
+    }
+
+    #[test]
+    fn test_resumed_training_matches_uninterrupted() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = LearningConfig {
+            update_frequency: 1,
+            learning_rate: 1e-2,
+            optimizer: OptimizerKind::AdamW,
+            enabled: true,
+            checkpoint_frequency: Some(1),
+            checkpoint_dir: Some(dir.path().to_path_buf()),
+            keep_checkpoints: 1,
+            seed: 42,
+            ..LearningConfig::default()
+        };
+        let first = TrainingExample::new("Hello".to_string(), Some(" world".to_string()));
+        let second = TrainingExample::new("Goodbye".to_string(), Some(" moon".to_string()));
+
+        let mut original = OnlineLearner::new(config.clone(), tiny_model(16));
+        original.add_example(first).unwrap();
+        let saved = original.checkpoint_manager().unwrap().latest().unwrap().unwrap();
+        assert_eq!(saved.step, 1);
+
+        // A fresh process with a differently initialised model picks up the run
+        let resumed_model = tiny_model(16);
+        let mut resumed = OnlineLearner::new(config, resumed_model.clone());
+        assert_eq!(resumed.resume_latest().unwrap().unwrap().step, 1);
+        assert_eq!(resumed.get_stats().total_updates, 1);
+        assert_eq!(resumed.get_stats().buffer_size, 1);
+
+        original.add_example(second.clone()).unwrap();
+        resumed.add_example(second).unwrap();
+
+        let a = original.model.read().unwrap().export_weights().unwrap();
+        let b = resumed_model.read().unwrap().export_weights().unwrap();
+        for name in a.tensor_names() {
+            assert_eq!(a.get_tensor(&name), b.get_tensor(&name), "{}", name);
+        }
+
+        // Rotation keeps only the newest checkpoint
+        let steps: Vec<usize> = resumed.checkpoint_manager().unwrap().list().unwrap().iter().map(|c| c.step).collect();
+        assert_eq!(steps, vec![2]);
+
+        let mismatched = LearningConfig {
+            optimizer: OptimizerKind::Lion,
+            ..resumed.config.clone()
+        };
+        let mut other = OnlineLearner::new(mismatched, tiny_model(16));
+        assert!(other.resume_latest().unwrap_err().to_string().contains("adamw"));
+    }
+
+    #[test]
+    fn test_adapter_training_leaves_base_weights_frozen() {
+        use crate::training::{LoraAdapter, LoraConfig};
+
+        let model = tiny_model(64);
+        let mut learner = learner(model.clone());
+        let example = TrainingExample::new("The capital of France is".to_string(), Some(" Paris".to_string()));
+        let prompt = model.read().unwrap().encode("The capital of France is");
+        let base_weights = model.read().unwrap().export_weights().unwrap();
+        let base_logits = model.read().unwrap().forward(&prompt).unwrap();
+
+        {
+            let mut model = model.write().unwrap();
+            let weights = model.transformer().linear_weights();
+            let config = LoraConfig {
+                rank: 4,
+                target_modules: vec!["q_proj".into(), "v_proj".into(), "down_proj".into()],
+                ..LoraConfig::default()
+            };
+            model.insert_adapter(LoraAdapter::new("paris", &config, &weights).unwrap()).unwrap();
+            model.set_active_adapter(Some("paris")).unwrap();
+        }
+        // B starts at zero, so a fresh adapter changes nothing
+        assert_eq!(model.read().unwrap().forward(&prompt).unwrap(), base_logits);
+
+        let before = learner.example_loss(&example).unwrap();
+        for _ in 0..5 {
+            learner.add_example(example.clone()).unwrap();
+        }
+        let after = learner.example_loss(&example).unwrap();
+        assert!(after < before, "loss {} -> {}", before, after);
+
+        let adapted_logits = model.read().unwrap().forward(&prompt).unwrap();
+        assert_ne!(adapted_logits, base_logits);
+        let trained = model.read().unwrap().export_weights().unwrap();
+        for name in base_weights.tensor_names() {
+            assert_eq!(trained.get_tensor(&name), base_weights.get_tensor(&name), "{}", name);
+        }
+
+        // Hot-swapping back to the base model restores its outputs exactly
+        model.write().unwrap().set_active_adapter(None).unwrap();
+        assert_eq!(model.read().unwrap().forward(&prompt).unwrap(), base_logits);
+
+        // Merging bakes the adapter into the base weights
+        model.write().unwrap().merge_adapter("paris").unwrap();
+        let merged_logits = model.read().unwrap().forward(&prompt).unwrap();
+        assert!(model.read().unwrap().adapter("paris").is_none());
+        for (m, a) in merged_logits.iter().zip(&adapted_logits) {
+            assert!((m - a).abs() < 1e-4, "{} vs {}", m, a);
+        }
+    }
+
+    #[test]
+    fn test_l2sp_limits_drift_from_initial_weights() {
+        let initial = tiny_model(64).read().unwrap().export_weights().unwrap();
+        let example = TrainingExample::new("The capital of France is".to_string(), Some(" Paris".to_string()));
+
+        let drift = |regularizer: Regularizer| {
+            let model = tiny_model(64);
+            model.write().unwrap().load_weights(&initial).unwrap();
+            let mut learner = learner(model.clone());
+            learner.config.regularizer = regularizer;
+            for _ in 0..8 {
+                learner.add_example(example.clone()).unwrap();
+            }
+            assert_eq!(learner.get_stats().regularizer, regularizer.name());
+            assert_eq!(learner.checkpoint().unwrap().anchor.is_some(), regularizer != Regularizer::None);
+
+            let trained = model.read().unwrap().export_weights().unwrap();
+            initial
+                .tensor_names()
+                .iter()
+                .map(|name| {
+                    let (a, b) = (initial.get_tensor(name).unwrap(), trained.get_tensor(name).unwrap());
+                    a.iter().zip(b).map(|(a, b)| (a - b).powi(2)).sum::<f32>()
+                })
+                .sum::<f32>()
+        };
+
+        let free = drift(Regularizer::None);
+        let anchored = drift(Regularizer::L2Sp { strength: 10.0 });
+        assert!(anchored < free, "drift {} (l2sp) vs {} (none)", anchored, free);
+    }
+
+    #[test]
+    fn test_regression_gate_refuses_checkpoints() {
+        use crate::training::{Dataset, Evaluator, RegressionGate, RegressionThresholds};
+
+        let dir = tempfile::tempdir().unwrap();
+        let model = tiny_model(64);
+        let initial = model.read().unwrap().export_weights().unwrap();
+        let example = TrainingExample::new("The capital of France is".to_string(), Some(" Paris".to_string()));
+        let mut learner = learner(model.clone());
+        for _ in 0..5 {
+            learner.add_example(example.clone()).unwrap();
+        }
+
+        // Gate on the fine-tuned weights, then fall back to the untrained ones
+        let evaluator = Evaluator::new(Dataset::new(vec![example.clone()]));
+        let gate = RegressionGate::new(evaluator, RegressionThresholds::default(), &model.read().unwrap()).unwrap();
+        learner.set_regression_gate(Some(gate));
+        assert!(learner.ensure_no_regression().is_ok());
+        model.write().unwrap().load_weights(&initial).unwrap();
+
+        learner.config.checkpoint_frequency = Some(1);
+        learner.config.checkpoint_dir = Some(dir.path().to_path_buf());
+        learner.add_example(example).unwrap();
+
+        let report = learner.check_regression().unwrap().unwrap();
+        assert!(!report.passed());
+        assert!(learner.checkpoint_manager().unwrap().list().unwrap().is_empty());
+        assert!(learner.get_stats().last_error.unwrap().contains("perplexity"));
+    }
+
+    #[test]
+    fn test_failed_update_is_reported() {
+        let model = tiny_model(4);
+        let mut learner = learner(model.clone());
+        let embedding_before = model.read().unwrap().embedding().weights().to_vec();
+
+        let long = TrainingExample::new("one two three four five six seven".to_string(), None);
+        assert!(learner.add_example(long).is_err());
+
+        let stats = learner.get_stats();
+        assert_eq!(stats.total_updates, 0);
+        assert_eq!(stats.skipped_updates, 1);
+        assert!(stats.last_error.unwrap().contains("max_seq_len"));
+        assert_eq!(model.read().unwrap().embedding().weights(), &embedding_before[..]);
+    }
+
+    #[test]
+    fn test_cancelled_update_leaves_weights_untouched() {
+        let model = tiny_model(64);
+        let config = LearningConfig {
+            update_frequency: 3,
+            enabled: true,
+            checkpoint_frequency: None,
+            ..LearningConfig::default()
+        };
+        let mut learner = OnlineLearner::new(config, model.clone());
+        learner.add_example(TrainingExample::new("two plus two is".to_string(), Some(" four".to_string()))).unwrap();
+        learner.add_example(TrainingExample::new("three plus three is".to_string(), Some(" six".to_string()))).unwrap();
+        let embedding_before = model.read().unwrap().embedding().weights().to_vec();
+
+        // Cancelled after the first example's backward pass
+        let cancel = CancellationToken::new();
+        let mut progress = Vec::new();
+        let result = learner.force_update_with_progress(&cancel, |done, total| {
+            progress.push((done, total));
+            cancel.cancel();
+        });
+        assert!(result.unwrap_err().to_string().contains("cancelled"));
+        assert_eq!(progress.len(), 1);
+        let stats = learner.get_stats();
+        assert_eq!((stats.total_updates, stats.skipped_updates), (0, 0));
+        assert_eq!(model.read().unwrap().embedding().weights(), &embedding_before[..]);
+
+        let mut progress = Vec::new();
+        learner.force_update_with_progress(&CancellationToken::new(), |done, total| progress.push((done, total))).unwrap();
+        let total = progress.last().unwrap().1;
+        assert_eq!(progress, (1..=total).map(|done| (done, total)).collect::<Vec<_>>());
+        assert_eq!(learner.get_stats().total_updates, 1);
+    }
 }
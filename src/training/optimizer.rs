@@ -1,7 +1,9 @@
 //! GPU-accelerated optimizers for training
 
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+#[cfg(feature = "gpu")]
 use std::sync::Arc;
 
 #[cfg(feature = "gpu")]
@@ -51,11 +53,65 @@ impl Default for AdamConfig {
     }
 }
 
+/// Optimizer state captured in a training checkpoint
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OptimizerState {
+    pub step_count: usize,
+    /// Updates applied to each parameter (Adam-family bias correction)
+    pub param_steps: HashMap<String, usize>,
+    /// State tensors keyed "{slot}/{parameter}", e.g. "momentum/lm_head.weight"
+    pub tensors: HashMap<String, Vec<f32>>,
+}
+
+impl OptimizerState {
+    fn insert_slot(&mut self, slot: &str, values: &HashMap<String, Vec<f32>>) {
+        for (name, v) in values {
+            self.tensors.insert(format!("{}/{}", slot, name), v.clone());
+        }
+    }
+
+    fn take_slot(&mut self, slot: &str) -> HashMap<String, Vec<f32>> {
+        let prefix = format!("{}/", slot);
+        let keys: Vec<String> = self.tensors.keys().filter(|k| k.starts_with(&prefix)).cloned().collect();
+        keys.into_iter()
+            .map(|key| {
+                let values = self.tensors.remove(&key).unwrap_or_default();
+                (key[prefix.len()..].to_string(), values)
+            })
+            .collect()
+    }
+
+    /// Fail if any tensors were not claimed by the optimizer
+    fn finish(self, optimizer: &str) -> Result<()> {
+        if let Some(key) = self.tensors.keys().next() {
+            anyhow::bail!("Checkpoint state tensor {} is not {} state", key, optimizer);
+        }
+        Ok(())
+    }
+}
+
 /// Optimizer trait for different optimization algorithms
 pub trait Optimizer: Send + Sync {
     /// Update parameters using gradients
     fn step(&mut self, params: &mut [f32], grads: &[f32]) -> Result<()>;
 
+    /// Update one named parameter of a model
+    ///
+    /// Optimizers with per-parameter state (moments) key it by `name`; the
+    /// default ignores the name.
+    fn step_named(&mut self, name: &str, params: &mut [f32], grads: &[f32]) -> Result<()> {
+        let _ = name;
+        self.step(params, grads)
+    }
+
+    /// Update a named parameter stored as a row-major [rows, cols] matrix
+    ///
+    /// Only optimizers that exploit the shape (Adafactor) override this.
+    fn step_matrix(&mut self, name: &str, rows: usize, cols: usize, params: &mut [f32], grads: &[f32]) -> Result<()> {
+        let _ = (rows, cols);
+        self.step_named(name, params, grads)
+    }
+
     /// Zero out gradients
     fn zero_grad(&mut self);
 
@@ -67,6 +123,19 @@ pub trait Optimizer: Send + Sync {
 
     /// Get optimization step count
     fn step_count(&self) -> usize;
+
+    /// Capture moments and counters for a checkpoint
+    fn export_state(&self) -> OptimizerState {
+        OptimizerState {
+            step_count: self.step_count(),
+            ..OptimizerState::default()
+        }
+    }
+
+    /// Restore state captured by `export_state`
+    fn import_state(&mut self, state: OptimizerState) -> Result<()> {
+        state.finish("this optimizer's")
+    }
 }
 
 /// Adam optimizer with GPU acceleration
@@ -80,6 +149,9 @@ pub struct AdamOptimizer {
     // Second moment (RMSProp)
     velocity: HashMap<String, Vec<f32>>,
 
+    // Updates applied to each parameter, for bias correction
+    param_steps: HashMap<String, usize>,
+
     #[cfg(feature = "gpu")]
     gpu_context: Option<Arc<CudaContext>>,
 }
@@ -92,6 +164,7 @@ impl AdamOptimizer {
             step_count: 0,
             momentum: HashMap::new(),
             velocity: HashMap::new(),
+            param_steps: HashMap::new(),
             #[cfg(feature = "gpu")]
             gpu_context: None,
         }
@@ -105,12 +178,15 @@ impl AdamOptimizer {
             step_count: 0,
             momentum: HashMap::new(),
             velocity: HashMap::new(),
+            param_steps: HashMap::new(),
             gpu_context: Some(gpu_context),
         }
     }
 
     /// Update a named parameter
     pub fn update_param(&mut self, name: &str, params: &mut [f32], grads: &[f32]) -> Result<()> {
+        check_lengths(name, params, grads)?;
+
         #[cfg(feature = "gpu")]
         if let Some(gpu_ctx) = self.gpu_context.clone() {
             return self.update_param_gpu(name, params, grads, &gpu_ctx);
@@ -123,6 +199,8 @@ impl AdamOptimizer {
     /// CPU implementation of Adam update
     fn update_param_cpu(&mut self, name: &str, params: &mut [f32], grads: &[f32]) -> Result<()> {
         let n = params.len();
+        self.step_count += 1;
+        let t = next_param_step(&mut self.param_steps, name);
 
         // Initialize momentum and velocity if needed
         let m = self.momentum.entry(name.to_string())
@@ -130,9 +208,6 @@ impl AdamOptimizer {
         let v = self.velocity.entry(name.to_string())
             .or_insert_with(|| vec![0.0; n]);
 
-        self.step_count += 1;
-        let t = self.step_count as f32;
-
         // Bias correction
         let lr = self.config.base.learning_rate *
                  (1.0 - self.config.beta2.powf(t)).sqrt() /
@@ -172,6 +247,8 @@ impl AdamOptimizer {
         use cudarc::driver::DeviceSlice;
 
         let n = params.len();
+        self.step_count += 1;
+        let t = next_param_step(&mut self.param_steps, name);
 
         // Initialize momentum and velocity if needed
         let m = self.momentum.entry(name.to_string())
@@ -179,9 +256,6 @@ impl AdamOptimizer {
         let v = self.velocity.entry(name.to_string())
             .or_insert_with(|| vec![0.0; n]);
 
-        self.step_count += 1;
-        let t = self.step_count as f32;
-
         // Bias correction
         let lr = self.config.base.learning_rate *
                  (1.0 - self.config.beta2.powf(t)).sqrt() /
@@ -251,6 +325,10 @@ impl Optimizer for AdamOptimizer {
         self.update_param("default", params, grads)
     }
 
+    fn step_named(&mut self, name: &str, params: &mut [f32], grads: &[f32]) -> Result<()> {
+        self.update_param(name, params, grads)
+    }
+
     fn zero_grad(&mut self) {
         // Gradients are passed in, so nothing to zero here
     }
@@ -266,13 +344,37 @@ impl Optimizer for AdamOptimizer {
     fn step_count(&self) -> usize {
         self.step_count
     }
+
+    fn export_state(&self) -> OptimizerState {
+        let mut state = OptimizerState {
+            step_count: self.step_count,
+            param_steps: self.param_steps.clone(),
+            ..OptimizerState::default()
+        };
+        state.insert_slot("momentum", &self.momentum);
+        state.insert_slot("velocity", &self.velocity);
+        state
+    }
+
+    fn import_state(&mut self, mut state: OptimizerState) -> Result<()> {
+        self.step_count = state.step_count;
+        self.param_steps = std::mem::take(&mut state.param_steps);
+        self.momentum = state.take_slot("momentum");
+        self.velocity = state.take_slot("velocity");
+        state.finish("Adam")
+    }
 }
 
-/// Simple SGD optimizer
+/// SGD, optionally with (Nesterov) momentum
 pub struct SGDOptimizer {
     config: OptimizerConfig,
     step_count: usize,
 
+    /// Momentum coefficient (0 = plain SGD)
+    momentum: f32,
+    nesterov: bool,
+    velocity: HashMap<String, Vec<f32>>,
+
     #[cfg(feature = "gpu")]
     gpu_context: Option<Arc<CudaContext>>,
 }
@@ -283,6 +385,9 @@ impl SGDOptimizer {
         Self {
             config,
             step_count: 0,
+            momentum: 0.0,
+            nesterov: false,
+            velocity: HashMap::new(),
             #[cfg(feature = "gpu")]
             gpu_context: None,
         }
@@ -294,9 +399,20 @@ impl SGDOptimizer {
         Self {
             config,
             step_count: 0,
+            momentum: 0.0,
+            nesterov: false,
+            velocity: HashMap::new(),
             gpu_context: Some(gpu_context),
         }
     }
+
+    /// Use heavy-ball momentum, or Nesterov momentum if `nesterov`
+    /// (momentum updates always run on the CPU)
+    pub fn with_momentum(mut self, momentum: f32, nesterov: bool) -> Self {
+        self.momentum = momentum;
+        self.nesterov = nesterov;
+        self
+    }
 }
 
 // SAFETY: SGDOptimizer contains Arc<CudaContext> which manages thread safety internally.
@@ -306,6 +422,15 @@ unsafe impl Sync for SGDOptimizer {}
 
 impl Optimizer for SGDOptimizer {
     fn step(&mut self, params: &mut [f32], grads: &[f32]) -> Result<()> {
+        self.step_named("default", params, grads)
+    }
+
+    fn step_named(&mut self, name: &str, params: &mut [f32], grads: &[f32]) -> Result<()> {
+        check_lengths(name, params, grads)?;
+        if self.momentum > 0.0 {
+            return self.step_momentum(name, params, grads);
+        }
+
         #[cfg(feature = "gpu")]
         if let Some(gpu_ctx) = self.gpu_context.clone() {
             return self.step_gpu(params, grads, &gpu_ctx);
@@ -330,6 +455,21 @@ impl Optimizer for SGDOptimizer {
     fn step_count(&self) -> usize {
         self.step_count
     }
+
+    fn export_state(&self) -> OptimizerState {
+        let mut state = OptimizerState {
+            step_count: self.step_count,
+            ..OptimizerState::default()
+        };
+        state.insert_slot("velocity", &self.velocity);
+        state
+    }
+
+    fn import_state(&mut self, mut state: OptimizerState) -> Result<()> {
+        self.step_count = state.step_count;
+        self.velocity = state.take_slot("velocity");
+        state.finish("SGD")
+    }
 }
 
 impl SGDOptimizer {
@@ -356,6 +496,35 @@ impl SGDOptimizer {
         Ok(())
     }
 
+    fn step_momentum(&mut self, name: &str, params: &mut [f32], grads: &[f32]) -> Result<()> {
+        self.step_count += 1;
+        let velocity = self
+            .velocity
+            .entry(name.to_string())
+            .or_insert_with(|| vec![0.0; params.len()]);
+
+        for i in 0..params.len() {
+            let mut g = grads[i];
+            if let Some(clip) = self.config.grad_clip {
+                g = g.clamp(-clip, clip);
+            }
+            if self.config.weight_decay > 0.0 {
+                g += self.config.weight_decay * params[i];
+            }
+
+            // v = μv + g; Nesterov steps along g + μv (the look-ahead)
+            velocity[i] = self.momentum * velocity[i] + g;
+            let update = if self.nesterov {
+                g + self.momentum * velocity[i]
+            } else {
+                velocity[i]
+            };
+            params[i] -= self.config.learning_rate * update;
+        }
+
+        Ok(())
+    }
+
     #[cfg(feature = "gpu")]
     fn step_gpu(&mut self, params: &mut [f32], grads: &[f32], gpu_ctx: &Arc<CudaContext>) -> Result<()> {
         use cudarc::driver::LaunchAsync;
@@ -409,6 +578,753 @@ impl SGDOptimizer {
     }
 }
 
+/// AdamW: Adam with weight decay decoupled from the gradient
+/// (Loshchilov & Hutter), the usual choice for transformer fine-tuning
+pub struct AdamWOptimizer {
+    config: AdamConfig,
+    step_count: usize,
+    momentum: HashMap<String, Vec<f32>>,
+    velocity: HashMap<String, Vec<f32>>,
+    param_steps: HashMap<String, usize>,
+}
+
+impl AdamWOptimizer {
+    pub fn new(config: AdamConfig) -> Self {
+        Self {
+            config,
+            step_count: 0,
+            momentum: HashMap::new(),
+            velocity: HashMap::new(),
+            param_steps: HashMap::new(),
+        }
+    }
+}
+
+impl Optimizer for AdamWOptimizer {
+    fn step(&mut self, params: &mut [f32], grads: &[f32]) -> Result<()> {
+        self.step_named("default", params, grads)
+    }
+
+    fn step_named(&mut self, name: &str, params: &mut [f32], grads: &[f32]) -> Result<()> {
+        check_lengths(name, params, grads)?;
+        let n = params.len();
+        self.step_count += 1;
+        let t = next_param_step(&mut self.param_steps, name);
+
+        let m = self.momentum.entry(name.to_string()).or_insert_with(|| vec![0.0; n]);
+        let v = self.velocity.entry(name.to_string()).or_insert_with(|| vec![0.0; n]);
+        let AdamConfig { base, beta1, beta2, epsilon } = &self.config;
+        let bias1 = 1.0 - beta1.powf(t);
+        let bias2 = 1.0 - beta2.powf(t);
+
+        for i in 0..n {
+            let mut g = grads[i];
+            if let Some(clip) = base.grad_clip {
+                g = g.clamp(-clip, clip);
+            }
+
+            m[i] = beta1 * m[i] + (1.0 - beta1) * g;
+            v[i] = beta2 * v[i] + (1.0 - beta2) * g * g;
+            let update = (m[i] / bias1) / ((v[i] / bias2).sqrt() + epsilon);
+
+            // Decay is applied to the weight directly, not mixed into the moments
+            params[i] -= base.learning_rate * (update + base.weight_decay * params[i]);
+        }
+
+        Ok(())
+    }
+
+    fn zero_grad(&mut self) {}
+
+    fn get_lr(&self) -> f32 {
+        self.config.base.learning_rate
+    }
+
+    fn set_lr(&mut self, lr: f32) {
+        self.config.base.learning_rate = lr;
+    }
+
+    fn step_count(&self) -> usize {
+        self.step_count
+    }
+
+    fn export_state(&self) -> OptimizerState {
+        let mut state = OptimizerState {
+            step_count: self.step_count,
+            param_steps: self.param_steps.clone(),
+            ..OptimizerState::default()
+        };
+        state.insert_slot("momentum", &self.momentum);
+        state.insert_slot("velocity", &self.velocity);
+        state
+    }
+
+    fn import_state(&mut self, mut state: OptimizerState) -> Result<()> {
+        self.step_count = state.step_count;
+        self.param_steps = std::mem::take(&mut state.param_steps);
+        self.momentum = state.take_slot("momentum");
+        self.velocity = state.take_slot("velocity");
+        state.finish("AdamW")
+    }
+}
+
+/// Lion optimizer configuration
+#[derive(Debug, Clone)]
+pub struct LionConfig {
+    pub base: OptimizerConfig,
+    /// Interpolation between momentum and gradient for the update sign
+    pub beta1: f32,
+    /// Momentum decay
+    pub beta2: f32,
+}
+
+impl Default for LionConfig {
+    fn default() -> Self {
+        Self {
+            // Lion's sign updates want a ~3-10x smaller rate than Adam
+            base: OptimizerConfig {
+                learning_rate: 1e-4,
+                ..OptimizerConfig::default()
+            },
+            beta1: 0.9,
+            beta2: 0.99,
+        }
+    }
+}
+
+/// Lion (Chen et al., "Symbolic Discovery of Optimization Algorithms"):
+/// sign-of-momentum updates with one state vector per parameter
+pub struct LionOptimizer {
+    config: LionConfig,
+    step_count: usize,
+    momentum: HashMap<String, Vec<f32>>,
+}
+
+impl LionOptimizer {
+    pub fn new(config: LionConfig) -> Self {
+        Self {
+            config,
+            step_count: 0,
+            momentum: HashMap::new(),
+        }
+    }
+}
+
+impl Optimizer for LionOptimizer {
+    fn step(&mut self, params: &mut [f32], grads: &[f32]) -> Result<()> {
+        self.step_named("default", params, grads)
+    }
+
+    fn step_named(&mut self, name: &str, params: &mut [f32], grads: &[f32]) -> Result<()> {
+        check_lengths(name, params, grads)?;
+        self.step_count += 1;
+
+        let m = self
+            .momentum
+            .entry(name.to_string())
+            .or_insert_with(|| vec![0.0; params.len()]);
+        let LionConfig { base, beta1, beta2 } = &self.config;
+
+        for i in 0..params.len() {
+            let mut g = grads[i];
+            if let Some(clip) = base.grad_clip {
+                g = g.clamp(-clip, clip);
+            }
+
+            let direction = beta1 * m[i] + (1.0 - beta1) * g;
+            let update = if direction == 0.0 { 0.0 } else { direction.signum() };
+            params[i] -= base.learning_rate * (update + base.weight_decay * params[i]);
+            m[i] = beta2 * m[i] + (1.0 - beta2) * g;
+        }
+
+        Ok(())
+    }
+
+    fn zero_grad(&mut self) {}
+
+    fn get_lr(&self) -> f32 {
+        self.config.base.learning_rate
+    }
+
+    fn set_lr(&mut self, lr: f32) {
+        self.config.base.learning_rate = lr;
+    }
+
+    fn step_count(&self) -> usize {
+        self.step_count
+    }
+
+    fn export_state(&self) -> OptimizerState {
+        let mut state = OptimizerState {
+            step_count: self.step_count,
+            ..OptimizerState::default()
+        };
+        state.insert_slot("momentum", &self.momentum);
+        state
+    }
+
+    fn import_state(&mut self, mut state: OptimizerState) -> Result<()> {
+        self.step_count = state.step_count;
+        self.momentum = state.take_slot("momentum");
+        state.finish("Lion")
+    }
+}
+
+/// Adafactor optimizer configuration
+#[derive(Debug, Clone)]
+pub struct AdafactorConfig {
+    pub base: OptimizerConfig,
+    /// Second-moment decay is 1 - t^decay_rate
+    pub decay_rate: f32,
+    /// Added to squared gradients
+    pub epsilon: f32,
+    /// Updates are scaled down to at most this RMS
+    pub clip_threshold: f32,
+}
+
+impl Default for AdafactorConfig {
+    fn default() -> Self {
+        Self {
+            base: OptimizerConfig::default(),
+            decay_rate: -0.8,
+            epsilon: 1e-30,
+            clip_threshold: 1.0,
+        }
+    }
+}
+
+/// Second-moment state of one Adafactor parameter
+enum FactoredMoment {
+    /// Row and column running means of g² for a [rows, cols] matrix
+    Factored { row: Vec<f32>, col: Vec<f32> },
+    /// Full running mean of g² for vectors
+    Full(Vec<f32>),
+}
+
+/// Adafactor (Shazeer & Stern) without momentum: matrices keep only
+/// row and column second-moment statistics, so a [vocab, dim] embedding
+/// table costs vocab + dim floats of state instead of 2 × vocab × dim
+pub struct AdafactorOptimizer {
+    config: AdafactorConfig,
+    step_count: usize,
+    moments: HashMap<String, FactoredMoment>,
+    param_steps: HashMap<String, usize>,
+}
+
+impl AdafactorOptimizer {
+    pub fn new(config: AdafactorConfig) -> Self {
+        Self {
+            config,
+            step_count: 0,
+            moments: HashMap::new(),
+            param_steps: HashMap::new(),
+        }
+    }
+
+    /// Floats of optimizer state held for `name`
+    pub fn state_size(&self, name: &str) -> usize {
+        match self.moments.get(name) {
+            Some(FactoredMoment::Factored { row, col }) => row.len() + col.len(),
+            Some(FactoredMoment::Full(v)) => v.len(),
+            None => 0,
+        }
+    }
+}
+
+impl Optimizer for AdafactorOptimizer {
+    fn step(&mut self, params: &mut [f32], grads: &[f32]) -> Result<()> {
+        self.step_named("default", params, grads)
+    }
+
+    fn step_named(&mut self, name: &str, params: &mut [f32], grads: &[f32]) -> Result<()> {
+        self.step_matrix(name, 1, params.len(), params, grads)
+    }
+
+    fn step_matrix(&mut self, name: &str, rows: usize, cols: usize, params: &mut [f32], grads: &[f32]) -> Result<()> {
+        check_lengths(name, params, grads)?;
+        if rows * cols != params.len() {
+            anyhow::bail!("Parameter {} has {} values, not {}x{}", name, params.len(), rows, cols);
+        }
+        self.step_count += 1;
+        let t = next_param_step(&mut self.param_steps, name);
+
+        let config = &self.config;
+        let beta2 = 1.0 - t.powf(config.decay_rate);
+        let g: Vec<f32> = match config.base.grad_clip {
+            Some(clip) => grads.iter().map(|g| g.clamp(-clip, clip)).collect(),
+            None => grads.to_vec(),
+        };
+        let sq = |g: f32| g * g + config.epsilon;
+
+        let moment = self.moments.entry(name.to_string()).or_insert_with(|| {
+            if rows > 1 && cols > 1 {
+                FactoredMoment::Factored {
+                    row: vec![0.0; rows],
+                    col: vec![0.0; cols],
+                }
+            } else {
+                FactoredMoment::Full(vec![0.0; params.len()])
+            }
+        });
+
+        let mut update: Vec<f32> = match moment {
+            FactoredMoment::Factored { row, col } => {
+                for (r, g_row) in row.iter_mut().zip(g.chunks_exact(cols)) {
+                    let mean = g_row.iter().map(|&g| sq(g)).sum::<f32>() / cols as f32;
+                    *r = beta2 * *r + (1.0 - beta2) * mean;
+                }
+                for (c, col_value) in col.iter_mut().enumerate() {
+                    let mean = (0..rows).map(|r| sq(g[r * cols + c])).sum::<f32>() / rows as f32;
+                    *col_value = beta2 * *col_value + (1.0 - beta2) * mean;
+                }
+
+                // V ≈ R Cᵀ / mean(R)
+                let row_mean = row.iter().sum::<f32>() / rows as f32;
+                g.iter()
+                    .enumerate()
+                    .map(|(i, g)| g / (row[i / cols] * col[i % cols] / row_mean).sqrt())
+                    .collect()
+            }
+            FactoredMoment::Full(v) => v
+                .iter_mut()
+                .zip(&g)
+                .map(|(v, &g)| {
+                    *v = beta2 * *v + (1.0 - beta2) * sq(g);
+                    g / v.sqrt()
+                })
+                .collect(),
+        };
+
+        let rms = (update.iter().map(|u| u * u).sum::<f32>() / update.len() as f32).sqrt();
+        let scale = 1.0 / (rms / config.clip_threshold).max(1.0);
+        for u in &mut update {
+            *u *= scale;
+        }
+
+        let OptimizerConfig { learning_rate, weight_decay, .. } = config.base;
+        for (p, u) in params.iter_mut().zip(&update) {
+            *p -= learning_rate * (u + weight_decay * *p);
+        }
+
+        Ok(())
+    }
+
+    fn zero_grad(&mut self) {}
+
+    fn get_lr(&self) -> f32 {
+        self.config.base.learning_rate
+    }
+
+    fn set_lr(&mut self, lr: f32) {
+        self.config.base.learning_rate = lr;
+    }
+
+    fn step_count(&self) -> usize {
+        self.step_count
+    }
+
+    fn export_state(&self) -> OptimizerState {
+        let mut state = OptimizerState {
+            step_count: self.step_count,
+            param_steps: self.param_steps.clone(),
+            ..OptimizerState::default()
+        };
+        for (name, moment) in &self.moments {
+            match moment {
+                FactoredMoment::Factored { row, col } => {
+                    state.tensors.insert(format!("row/{}", name), row.clone());
+                    state.tensors.insert(format!("col/{}", name), col.clone());
+                }
+                FactoredMoment::Full(v) => {
+                    state.tensors.insert(format!("full/{}", name), v.clone());
+                }
+            }
+        }
+        state
+    }
+
+    fn import_state(&mut self, mut state: OptimizerState) -> Result<()> {
+        self.step_count = state.step_count;
+        self.param_steps = std::mem::take(&mut state.param_steps);
+
+        let mut cols = state.take_slot("col");
+        let mut moments: HashMap<String, FactoredMoment> = state
+            .take_slot("full")
+            .into_iter()
+            .map(|(name, v)| (name, FactoredMoment::Full(v)))
+            .collect();
+        for (name, row) in state.take_slot("row") {
+            let col = cols
+                .remove(&name)
+                .ok_or_else(|| anyhow::anyhow!("Adafactor state for {} has rows but no columns", name))?;
+            moments.insert(name, FactoredMoment::Factored { row, col });
+        }
+        if let Some(name) = cols.keys().next() {
+            anyhow::bail!("Adafactor state for {} has columns but no rows", name);
+        }
+
+        self.moments = moments;
+        state.finish("Adafactor")
+    }
+}
+
+/// Optimizer choice for `LearningConfig`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OptimizerKind {
+    /// Adam with L2 weight decay folded into the gradient
+    Adam,
+    /// Adam with decoupled weight decay
+    AdamW,
+    Lion,
+    /// Factored second moments; low memory for large matrices
+    Adafactor,
+    /// SGD with Nesterov momentum
+    SgdNesterov { momentum: f32 },
+}
+
+impl OptimizerKind {
+    /// Parse "adam", "adamw", "lion", "adafactor" or "sgd"/"nesterov"
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.to_lowercase().as_str() {
+            "adam" => Ok(Self::Adam),
+            "adamw" => Ok(Self::AdamW),
+            "lion" => Ok(Self::Lion),
+            "adafactor" => Ok(Self::Adafactor),
+            "sgd" | "nesterov" | "sgd_nesterov" => Ok(Self::SgdNesterov { momentum: 0.9 }),
+            other => anyhow::bail!(
+                "Unknown optimizer: {} (expected adam, adamw, lion, adafactor or sgd)",
+                other
+            ),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Adam => "adam",
+            Self::AdamW => "adamw",
+            Self::Lion => "lion",
+            Self::Adafactor => "adafactor",
+            Self::SgdNesterov { .. } => "sgd_nesterov",
+        }
+    }
+
+    /// Build the optimizer with these shared settings and default betas
+    pub fn build(&self, base: OptimizerConfig) -> Box<dyn Optimizer> {
+        match *self {
+            Self::Adam => Box::new(AdamOptimizer::new(AdamConfig { base, ..AdamConfig::default() })),
+            Self::AdamW => Box::new(AdamWOptimizer::new(AdamConfig { base, ..AdamConfig::default() })),
+            Self::Lion => Box::new(LionOptimizer::new(LionConfig { base, ..LionConfig::default() })),
+            Self::Adafactor => Box::new(AdafactorOptimizer::new(AdafactorConfig {
+                base,
+                ..AdafactorConfig::default()
+            })),
+            Self::SgdNesterov { momentum } => Box::new(SGDOptimizer::new(base).with_momentum(momentum, true)),
+        }
+    }
+}
+
+/// Scheduler state beyond the update count, for checkpoints
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SchedulerState {
+    pub base_lr: f32,
+    /// Reductions applied so far (ReduceOnPlateau)
+    pub scale: f32,
+    pub best_loss: Option<f32>,
+    pub bad_updates: usize,
+}
+
+/// Learning-rate schedule driven by the update count
+///
+/// The scheduler owns the base learning rate; `OnlineLearner` asks it for
+/// the rate before every update and reports the resulting loss afterwards.
+pub trait LrScheduler: Send + Sync {
+    /// Learning rate for update number `step` (0-based)
+    fn lr_at(&self, step: usize) -> f32;
+
+    /// Record the loss of the update just applied
+    fn observe_loss(&mut self, _loss: f32) {}
+
+    fn base_lr(&self) -> f32;
+
+    fn set_base_lr(&mut self, lr: f32);
+
+    fn name(&self) -> &'static str;
+
+    fn export_state(&self) -> SchedulerState {
+        SchedulerState {
+            base_lr: self.base_lr(),
+            scale: 1.0,
+            best_loss: None,
+            bad_updates: 0,
+        }
+    }
+
+    fn import_state(&mut self, state: &SchedulerState) {
+        self.set_base_lr(state.base_lr);
+    }
+}
+
+/// Schedule choice for `LearningConfig`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LrSchedule {
+    /// Fixed rate
+    Constant,
+    /// Ramp linearly from 0 to the base rate, then hold
+    LinearWarmup { warmup_steps: usize },
+    /// Linear warmup, then half-cosine decay to `min_lr` at `total_steps`
+    Cosine {
+        warmup_steps: usize,
+        total_steps: usize,
+        min_lr: f32,
+    },
+    /// Linear warmup, then decay ∝ 1/√step (the original Transformer schedule)
+    InverseSqrt { warmup_steps: usize },
+    /// Multiply the rate by `factor` after `patience` updates without a
+    /// relative loss improvement of `threshold`
+    ReduceOnPlateau {
+        factor: f32,
+        patience: usize,
+        threshold: f32,
+        min_lr: f32,
+    },
+}
+
+impl LrSchedule {
+    pub fn build(&self, base_lr: f32) -> Box<dyn LrScheduler> {
+        match *self {
+            Self::Constant => Box::new(ConstantLr { base_lr }),
+            Self::LinearWarmup { warmup_steps } => Box::new(LinearWarmupLr { base_lr, warmup_steps }),
+            Self::Cosine {
+                warmup_steps,
+                total_steps,
+                min_lr,
+            } => Box::new(CosineLr {
+                base_lr,
+                warmup_steps,
+                total_steps,
+                min_lr,
+            }),
+            Self::InverseSqrt { warmup_steps } => Box::new(InverseSqrtLr { base_lr, warmup_steps }),
+            Self::ReduceOnPlateau {
+                factor,
+                patience,
+                threshold,
+                min_lr,
+            } => Box::new(ReduceOnPlateauLr::new(base_lr, factor, patience, threshold, min_lr)),
+        }
+    }
+}
+
+/// Fraction of the base rate reached after linear warmup
+fn warmup_factor(step: usize, warmup_steps: usize) -> f32 {
+    if step < warmup_steps {
+        (step + 1) as f32 / warmup_steps as f32
+    } else {
+        1.0
+    }
+}
+
+pub struct ConstantLr {
+    base_lr: f32,
+}
+
+impl LrScheduler for ConstantLr {
+    fn lr_at(&self, _step: usize) -> f32 {
+        self.base_lr
+    }
+
+    fn base_lr(&self) -> f32 {
+        self.base_lr
+    }
+
+    fn set_base_lr(&mut self, lr: f32) {
+        self.base_lr = lr;
+    }
+
+    fn name(&self) -> &'static str {
+        "constant"
+    }
+}
+
+pub struct LinearWarmupLr {
+    base_lr: f32,
+    warmup_steps: usize,
+}
+
+impl LrScheduler for LinearWarmupLr {
+    fn lr_at(&self, step: usize) -> f32 {
+        self.base_lr * warmup_factor(step, self.warmup_steps)
+    }
+
+    fn base_lr(&self) -> f32 {
+        self.base_lr
+    }
+
+    fn set_base_lr(&mut self, lr: f32) {
+        self.base_lr = lr;
+    }
+
+    fn name(&self) -> &'static str {
+        "linear_warmup"
+    }
+}
+
+pub struct CosineLr {
+    base_lr: f32,
+    warmup_steps: usize,
+    total_steps: usize,
+    min_lr: f32,
+}
+
+impl LrScheduler for CosineLr {
+    fn lr_at(&self, step: usize) -> f32 {
+        if step < self.warmup_steps {
+            return self.base_lr * warmup_factor(step, self.warmup_steps);
+        }
+
+        let decay_steps = self.total_steps.saturating_sub(self.warmup_steps).max(1);
+        let progress = ((step - self.warmup_steps) as f32 / decay_steps as f32).min(1.0);
+        let cosine = 0.5 * (1.0 + (std::f32::consts::PI * progress).cos());
+        self.min_lr + (self.base_lr - self.min_lr) * cosine
+    }
+
+    fn base_lr(&self) -> f32 {
+        self.base_lr
+    }
+
+    fn set_base_lr(&mut self, lr: f32) {
+        self.base_lr = lr;
+    }
+
+    fn name(&self) -> &'static str {
+        "cosine"
+    }
+}
+
+pub struct InverseSqrtLr {
+    base_lr: f32,
+    warmup_steps: usize,
+}
+
+impl LrScheduler for InverseSqrtLr {
+    fn lr_at(&self, step: usize) -> f32 {
+        let warmup = self.warmup_steps.max(1) as f32;
+        let step = (step + 1) as f32;
+        // Peaks at base_lr when warmup ends
+        self.base_lr * (step / warmup).min((warmup / step).sqrt())
+    }
+
+    fn base_lr(&self) -> f32 {
+        self.base_lr
+    }
+
+    fn set_base_lr(&mut self, lr: f32) {
+        self.base_lr = lr;
+    }
+
+    fn name(&self) -> &'static str {
+        "inverse_sqrt"
+    }
+}
+
+pub struct ReduceOnPlateauLr {
+    base_lr: f32,
+    factor: f32,
+    patience: usize,
+    threshold: f32,
+    min_lr: f32,
+    /// Product of all reductions so far
+    scale: f32,
+    best_loss: f32,
+    bad_updates: usize,
+}
+
+impl ReduceOnPlateauLr {
+    pub fn new(base_lr: f32, factor: f32, patience: usize, threshold: f32, min_lr: f32) -> Self {
+        Self {
+            base_lr,
+            factor,
+            patience,
+            threshold,
+            min_lr,
+            scale: 1.0,
+            best_loss: f32::INFINITY,
+            bad_updates: 0,
+        }
+    }
+}
+
+impl LrScheduler for ReduceOnPlateauLr {
+    fn lr_at(&self, _step: usize) -> f32 {
+        (self.base_lr * self.scale).max(self.min_lr)
+    }
+
+    fn observe_loss(&mut self, loss: f32) {
+        if loss < self.best_loss * (1.0 - self.threshold) {
+            self.best_loss = loss;
+            self.bad_updates = 0;
+            return;
+        }
+
+        self.bad_updates += 1;
+        if self.bad_updates > self.patience {
+            self.scale *= self.factor;
+            self.bad_updates = 0;
+            tracing::info!("Loss plateaued; learning rate reduced to {:.2e}", self.lr_at(0));
+        }
+    }
+
+    fn base_lr(&self) -> f32 {
+        self.base_lr
+    }
+
+    /// Setting the rate explicitly also clears earlier reductions
+    fn set_base_lr(&mut self, lr: f32) {
+        self.base_lr = lr;
+        self.scale = 1.0;
+        self.bad_updates = 0;
+    }
+
+    fn name(&self) -> &'static str {
+        "reduce_on_plateau"
+    }
+
+    fn export_state(&self) -> SchedulerState {
+        SchedulerState {
+            base_lr: self.base_lr,
+            scale: self.scale,
+            best_loss: Some(self.best_loss).filter(|l| l.is_finite()),
+            bad_updates: self.bad_updates,
+        }
+    }
+
+    fn import_state(&mut self, state: &SchedulerState) {
+        self.base_lr = state.base_lr;
+        self.scale = state.scale;
+        self.best_loss = state.best_loss.unwrap_or(f32::INFINITY);
+        self.bad_updates = state.bad_updates;
+    }
+}
+
+fn check_lengths(name: &str, params: &[f32], grads: &[f32]) -> Result<()> {
+    if params.len() != grads.len() {
+        anyhow::bail!(
+            "Parameter {} has {} values but {} gradients",
+            name,
+            params.len(),
+            grads.len()
+        );
+    }
+    Ok(())
+}
+
+/// Count one more update of `name`; returns its 1-based step number
+fn next_param_step(steps: &mut HashMap<String, usize>, name: &str) -> f32 {
+    let steps = steps.entry(name.to_string()).or_insert(0);
+    *steps += 1;
+    *steps as f32
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -445,4 +1361,210 @@ mod tests {
         assert!(params[1] < 2.0);
         assert!(params[2] < 3.0);
     }
+
+    #[test]
+    fn test_adam_named_params_keep_separate_state() {
+        let config = AdamConfig {
+            base: OptimizerConfig {
+                learning_rate: 0.1,
+                weight_decay: 0.0,
+                grad_clip: None,
+            },
+            ..AdamConfig::default()
+        };
+        let mut optimizer = AdamOptimizer::new(config);
+
+        let mut a = vec![1.0];
+        let mut b = vec![1.0];
+        optimizer.step_named("a", &mut a, &[0.5]).unwrap();
+        optimizer.step_named("b", &mut b, &[0.5]).unwrap();
+
+        // Each parameter's first update is bias-corrected as step 1
+        assert!((a[0] - 0.9).abs() < 1e-5);
+        assert_eq!(a, b);
+        assert_eq!(optimizer.step_count(), 2);
+        assert!(optimizer.step_named("a", &mut a, &[0.1, 0.2]).is_err());
+    }
+
+    fn base(learning_rate: f32, weight_decay: f32) -> OptimizerConfig {
+        OptimizerConfig {
+            learning_rate,
+            weight_decay,
+            grad_clip: None,
+        }
+    }
+
+    #[test]
+    fn test_every_optimizer_minimizes_a_quadratic() {
+        let kinds = [
+            OptimizerKind::Adam,
+            OptimizerKind::AdamW,
+            OptimizerKind::Lion,
+            OptimizerKind::Adafactor,
+            OptimizerKind::SgdNesterov { momentum: 0.9 },
+        ];
+        for kind in kinds {
+            let mut optimizer = kind.build(base(0.02, 0.0));
+            // f(p) = Σ p², ∇f = 2p, as a 2x3 matrix
+            let mut params = vec![1.0, -2.0, 0.5, 1.5, -1.0, 2.0];
+            let start: f32 = params.iter().map(|p| p * p).sum();
+            for _ in 0..100 {
+                let grads: Vec<f32> = params.iter().map(|p| 2.0 * p).collect();
+                optimizer.step_matrix("w", 2, 3, &mut params, &grads).unwrap();
+            }
+            let end: f32 = params.iter().map(|p| p * p).sum();
+            assert!(end < start * 0.5, "{}: {} -> {}", kind.name(), start, end);
+            assert_eq!(OptimizerKind::parse(kind.name()).unwrap(), kind);
+        }
+        assert!(OptimizerKind::parse("rmsprop").is_err());
+    }
+
+    #[test]
+    fn test_adamw_decay_is_decoupled() {
+        let mut adamw = AdamWOptimizer::new(AdamConfig {
+            base: base(0.1, 0.5),
+            ..AdamConfig::default()
+        });
+        let mut params = vec![2.0];
+        adamw.step(&mut params, &[0.0]).unwrap();
+        // Zero gradient: only the decay term lr * wd * p applies
+        assert!((params[0] - 1.9).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_lion_steps_by_the_learning_rate() {
+        let mut lion = LionOptimizer::new(LionConfig {
+            base: base(0.01, 0.0),
+            ..LionConfig::default()
+        });
+        let mut params = vec![1.0, 1.0, 1.0];
+        lion.step(&mut params, &[5.0, -0.001, 0.0]).unwrap();
+        assert_eq!(params, vec![0.99, 1.01, 1.0]);
+    }
+
+    #[test]
+    fn test_nesterov_looks_ahead() {
+        let mut plain = SGDOptimizer::new(base(0.1, 0.0)).with_momentum(0.9, false);
+        let mut nesterov = SGDOptimizer::new(base(0.1, 0.0)).with_momentum(0.9, true);
+        let (mut a, mut b) = (vec![0.0], vec![0.0]);
+        for _ in 0..2 {
+            plain.step(&mut a, &[1.0]).unwrap();
+            nesterov.step(&mut b, &[1.0]).unwrap();
+        }
+        // Heavy ball: v = 1, 1.9; Nesterov steps g + μv: 1.9, 2.71
+        assert!((a[0] + 0.29).abs() < 1e-6);
+        assert!((b[0] + 0.461).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_adafactor_factors_matrix_state() {
+        let mut adafactor = AdafactorOptimizer::new(AdafactorConfig {
+            base: base(0.01, 0.0),
+            ..AdafactorConfig::default()
+        });
+        let mut table = vec![0.5; 100 * 8];
+        let grads = vec![0.1; 100 * 8];
+        adafactor.step_matrix("embed", 100, 8, &mut table, &grads).unwrap();
+        assert_eq!(adafactor.state_size("embed"), 108);
+
+        let mut bias = vec![0.5; 8];
+        adafactor.step_named("bias", &mut bias, &[0.1; 8]).unwrap();
+        assert_eq!(adafactor.state_size("bias"), 8);
+        assert!(adafactor.step_matrix("bad", 3, 3, &mut bias, &[0.1; 8]).is_err());
+    }
+
+    #[test]
+    fn test_schedules() {
+        let warmup = LrSchedule::LinearWarmup { warmup_steps: 4 }.build(1.0);
+        assert_eq!(warmup.lr_at(0), 0.25);
+        assert_eq!(warmup.lr_at(3), 1.0);
+        assert_eq!(warmup.lr_at(100), 1.0);
+
+        let cosine = LrSchedule::Cosine {
+            warmup_steps: 2,
+            total_steps: 12,
+            min_lr: 0.1,
+        }
+        .build(1.0);
+        assert_eq!(cosine.lr_at(0), 0.5);
+        assert_eq!(cosine.lr_at(2), 1.0);
+        assert!((cosine.lr_at(7) - 0.55).abs() < 1e-6);
+        assert!((cosine.lr_at(12) - 0.1).abs() < 1e-6);
+        assert!((cosine.lr_at(50) - 0.1).abs() < 1e-6);
+
+        let inv_sqrt = LrSchedule::InverseSqrt { warmup_steps: 4 }.build(1.0);
+        assert_eq!(inv_sqrt.lr_at(1), 0.5);
+        assert_eq!(inv_sqrt.lr_at(3), 1.0);
+        assert_eq!(inv_sqrt.lr_at(15), 0.5);
+    }
+
+    #[test]
+    fn test_reduce_on_plateau() {
+        let mut plateau = LrSchedule::ReduceOnPlateau {
+            factor: 0.5,
+            patience: 1,
+            threshold: 0.01,
+            min_lr: 0.2,
+        }
+        .build(1.0);
+
+        plateau.observe_loss(2.0);
+        plateau.observe_loss(1.5);
+        plateau.observe_loss(1.5);
+        assert_eq!(plateau.lr_at(0), 1.0);
+        plateau.observe_loss(1.499);
+        assert_eq!(plateau.lr_at(0), 0.5);
+
+        for _ in 0..10 {
+            plateau.observe_loss(1.5);
+        }
+        assert_eq!(plateau.lr_at(0), 0.2);
+
+        let saved = plateau.export_state();
+        let mut restored = LrSchedule::ReduceOnPlateau {
+            factor: 0.5,
+            patience: 1,
+            threshold: 0.01,
+            min_lr: 0.2,
+        }
+        .build(1.0);
+        restored.import_state(&saved);
+        assert_eq!(restored.export_state(), saved);
+
+        plateau.set_base_lr(0.8);
+        assert_eq!(plateau.lr_at(0), 0.8);
+    }
+
+    #[test]
+    fn test_optimizer_state_round_trip() {
+        let kinds = [
+            OptimizerKind::Adam,
+            OptimizerKind::AdamW,
+            OptimizerKind::Lion,
+            OptimizerKind::Adafactor,
+            OptimizerKind::SgdNesterov { momentum: 0.9 },
+        ];
+        for kind in kinds {
+            let mut original = kind.build(base(0.01, 0.01));
+            let mut params = vec![1.0, -1.0, 0.5, 2.0];
+            original.step_matrix("w", 2, 2, &mut params, &[0.1, 0.2, -0.3, 0.4]).unwrap();
+            original.step_named("b", &mut params[..2], &[0.5, -0.5]).unwrap();
+
+            let mut restored = kind.build(base(0.01, 0.01));
+            restored.import_state(original.export_state()).unwrap();
+            assert_eq!(restored.export_state(), original.export_state(), "{}", kind.name());
+
+            // Both continue identically
+            let mut a = params.clone();
+            original.step_matrix("w", 2, 2, &mut params, &[0.3, 0.1, 0.2, -0.1]).unwrap();
+            restored.step_matrix("w", 2, 2, &mut a, &[0.3, 0.1, 0.2, -0.1]).unwrap();
+            assert_eq!(a, params, "{}", kind.name());
+        }
+
+        // Moments of another optimizer are rejected
+        let mut lion = OptimizerKind::Lion.build(base(0.01, 0.0));
+        let mut params = vec![1.0];
+        lion.step(&mut params, &[1.0]).unwrap();
+        assert!(OptimizerKind::Adafactor.build(base(0.01, 0.0)).import_state(lion.export_state()).is_err());
+    }
 }
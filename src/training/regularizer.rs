@@ -0,0 +1,143 @@
+//! Regularization towards reference weights, against forgetting
+//!
+//! Continuous learning drifts away from what the base model knew. Both
+//! regularizers pull the trained weights back towards an `Anchor` captured
+//! before they first change:
+//! - L2-SP: `strength / 2 * sum (w - w0)^2`
+//! - EWC: `strength / 2 * sum F (w - w0)^2`, with `F` the diagonal Fisher
+//!   information (mean squared per-example gradient) at `w0`, so weights that
+//!   mattered to the original model are held tighter
+//!
+//! The anchor keeps a copy of every trained weight; with a LoRA adapter
+//! active that is only the adapter.
+
+use anyhow::Result;
+use std::collections::HashMap;
+
+/// Regularizer choice for `LearningConfig`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Regularizer {
+    None,
+    L2Sp { strength: f32 },
+    Ewc { strength: f32 },
+}
+
+impl Regularizer {
+    /// Parse "none", "l2sp" or "ewc" with the given strength
+    pub fn parse(name: &str, strength: f32) -> Result<Self> {
+        match name.to_lowercase().as_str() {
+            "none" => Ok(Self::None),
+            "l2sp" | "l2_sp" | "l2-sp" => Ok(Self::L2Sp { strength }),
+            "ewc" => Ok(Self::Ewc { strength }),
+            other => anyhow::bail!("Unknown regularizer: {} (expected none, l2sp or ewc)", other),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::L2Sp { .. } => "l2sp",
+            Self::Ewc { .. } => "ewc",
+        }
+    }
+
+    /// EWC needs Fisher estimates collected from gradients at the anchor
+    pub fn needs_fisher(&self) -> bool {
+        matches!(self, Self::Ewc { .. })
+    }
+}
+
+/// Reference weights (and, for EWC, their Fisher diagonal) by parameter name
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Anchor {
+    pub weights: HashMap<String, Vec<f32>>,
+    pub fisher: HashMap<String, Vec<f32>>,
+}
+
+impl Anchor {
+    pub fn is_empty(&self) -> bool {
+        self.weights.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.weights.clear();
+        self.fisher.clear();
+    }
+
+    /// Remember `values` as the reference for `name` unless it already has one
+    pub fn capture(&mut self, name: &str, values: &[f32]) {
+        if !self.weights.contains_key(name) {
+            self.weights.insert(name.to_string(), values.to_vec());
+        }
+    }
+
+    /// Add `scale * grad^2` to the Fisher estimate of `name`
+    pub fn accumulate_fisher(&mut self, name: &str, grad: &[f32], scale: f32) {
+        let fisher = self
+            .fisher
+            .entry(name.to_string())
+            .or_insert_with(|| vec![0.0; grad.len()]);
+        for (f, g) in fisher.iter_mut().zip(grad) {
+            *f += scale * g * g;
+        }
+    }
+
+    /// Add the penalty gradient for `name` to `grad`; returns the penalty
+    ///
+    /// Weights without a reference are not penalized.
+    pub fn penalize(&self, regularizer: Regularizer, name: &str, values: &[f32], grad: &mut [f32]) -> f32 {
+        let Some(reference) = self.weights.get(name) else {
+            return 0.0;
+        };
+        let (strength, fisher) = match regularizer {
+            Regularizer::None => return 0.0,
+            Regularizer::L2Sp { strength } => (strength, None),
+            Regularizer::Ewc { strength } => (strength, self.fisher.get(name)),
+        };
+
+        let mut penalty = 0.0;
+        for (i, ((g, w), w0)) in grad.iter_mut().zip(values).zip(reference).enumerate() {
+            let importance = fisher.map_or(1.0, |f| f[i]);
+            let diff = w - w0;
+            *g += strength * importance * diff;
+            penalty += 0.5 * strength * importance * diff * diff;
+        }
+        penalty
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_l2sp_pulls_towards_reference() {
+        let mut anchor = Anchor::default();
+        anchor.capture("w", &[1.0, 2.0]);
+        anchor.capture("w", &[9.0, 9.0]);
+
+        let mut grad = [0.0, 0.0];
+        let penalty = anchor.penalize(Regularizer::L2Sp { strength: 0.5 }, "w", &[3.0, 2.0], &mut grad);
+        assert_eq!(grad, [1.0, 0.0]);
+        assert_eq!(penalty, 1.0);
+
+        let mut other = [0.0];
+        assert_eq!(anchor.penalize(Regularizer::L2Sp { strength: 0.5 }, "v", &[3.0], &mut other), 0.0);
+        assert_eq!(other, [0.0]);
+    }
+
+    #[test]
+    fn test_ewc_weights_penalty_by_fisher() {
+        let mut anchor = Anchor::default();
+        anchor.capture("w", &[0.0, 0.0]);
+        anchor.accumulate_fisher("w", &[2.0, 0.0], 0.5);
+        anchor.accumulate_fisher("w", &[0.0, 0.0], 0.5);
+        assert_eq!(anchor.fisher["w"], vec![2.0, 0.0]);
+
+        let mut grad = [0.0, 0.0];
+        anchor.penalize(Regularizer::Ewc { strength: 1.0 }, "w", &[1.0, 1.0], &mut grad);
+        // Only the weight the gradients depended on is held back
+        assert_eq!(grad, [2.0, 0.0]);
+        assert!(Regularizer::parse("ewc", 1.0).unwrap().needs_fisher());
+    }
+}
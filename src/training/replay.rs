@@ -0,0 +1,306 @@
+//! Replay buffer policies for online learning
+//!
+//! The policy decides which examples stay in the buffer and which ones the
+//! next update trains on:
+//! - `Fifo`: keep the newest `capacity` examples, train on the most recent
+//! - `Reservoir`: keep a uniform sample of every example ever added
+//!   (Algorithm R) and train on a uniform draw from it
+//! - `Prioritized`: train on examples never trained before, then on a draw
+//!   weighted by `(last loss)^alpha`, so badly fitted examples come back
+//! - `WeightDecay`: train on the most recent; every time an example is
+//!   trained its weight is multiplied by `decay`, and it leaves the buffer
+//!   once the weight falls below `min_weight`
+//!
+//! Randomness comes from the caller, so `OnlineLearner` can derive it from
+//! its seed and counters and resumed runs draw the same batches.
+
+use anyhow::Result;
+use rand::seq::index;
+use rand::Rng;
+use std::collections::VecDeque;
+
+use super::online_learning::TrainingExample;
+
+/// Buffer policy for `LearningConfig`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplayPolicy {
+    Fifo,
+    Reservoir,
+    Prioritized { alpha: f32 },
+    WeightDecay { decay: f32, min_weight: f32 },
+}
+
+impl ReplayPolicy {
+    /// Parse "fifo", "reservoir", "prioritized" or "weight_decay" (with default parameters)
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.to_lowercase().as_str() {
+            "fifo" => Ok(Self::Fifo),
+            "reservoir" => Ok(Self::Reservoir),
+            "prioritized" => Ok(Self::Prioritized { alpha: 0.6 }),
+            "weight_decay" => Ok(Self::WeightDecay {
+                decay: 0.5,
+                min_weight: 0.05,
+            }),
+            other => anyhow::bail!(
+                "Unknown replay policy: {} (expected fifo, reservoir, prioritized or weight_decay)",
+                other
+            ),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Fifo => "fifo",
+            Self::Reservoir => "reservoir",
+            Self::Prioritized { .. } => "prioritized",
+            Self::WeightDecay { .. } => "weight_decay",
+        }
+    }
+}
+
+/// Bounded example buffer governed by a `ReplayPolicy`
+#[derive(Debug, Clone)]
+pub struct ReplayBuffer {
+    policy: ReplayPolicy,
+    capacity: usize,
+    examples: VecDeque<TrainingExample>,
+    /// Loss of each example when it was last trained (None = never trained)
+    losses: VecDeque<Option<f32>>,
+}
+
+impl ReplayBuffer {
+    pub fn new(policy: ReplayPolicy, capacity: usize) -> Self {
+        Self {
+            policy,
+            capacity,
+            examples: VecDeque::new(),
+            losses: VecDeque::new(),
+        }
+    }
+
+    /// Buffer holding `examples` with their last losses, e.g. from a checkpoint
+    ///
+    /// Missing losses count as never trained.
+    pub fn restore(policy: ReplayPolicy, capacity: usize, examples: Vec<TrainingExample>, losses: Vec<Option<f32>>) -> Self {
+        let mut losses: VecDeque<_> = losses.into();
+        losses.resize(examples.len(), None);
+        Self {
+            policy,
+            capacity,
+            examples: examples.into(),
+            losses,
+        }
+    }
+
+    pub fn policy(&self) -> ReplayPolicy {
+        self.policy
+    }
+
+    pub fn len(&self) -> usize {
+        self.examples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.examples.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.examples.clear();
+        self.losses.clear();
+    }
+
+    /// Buffered examples, oldest first (reservoir order for `Reservoir`)
+    pub fn examples(&self) -> impl Iterator<Item = &TrainingExample> {
+        self.examples.iter()
+    }
+
+    /// Last loss of each buffered example, in `examples` order
+    pub fn losses(&self) -> impl Iterator<Item = Option<f32>> + '_ {
+        self.losses.iter().copied()
+    }
+
+    /// Add an example; `seen` counts every example added so far, this one included
+    pub fn push(&mut self, example: TrainingExample, seen: usize, rng: &mut impl Rng) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if let ReplayPolicy::Reservoir = self.policy {
+            if self.examples.len() < self.capacity {
+                self.examples.push_back(example);
+                self.losses.push_back(None);
+            } else {
+                let slot = rng.gen_range(0..seen.max(1));
+                if slot < self.capacity {
+                    self.examples[slot] = example;
+                    self.losses[slot] = None;
+                }
+            }
+            return;
+        }
+
+        self.examples.push_back(example);
+        self.losses.push_back(None);
+        if self.examples.len() > self.capacity {
+            self.examples.pop_front();
+            self.losses.pop_front();
+        }
+    }
+
+    /// Indices of up to `batch_size` examples for the next update
+    pub fn sample(&self, batch_size: usize, rng: &mut impl Rng) -> Vec<usize> {
+        let len = self.examples.len();
+        let batch_size = batch_size.min(len);
+
+        match self.policy {
+            ReplayPolicy::Fifo | ReplayPolicy::WeightDecay { .. } => (len - batch_size..len).rev().collect(),
+            ReplayPolicy::Reservoir => index::sample(rng, len, batch_size).into_vec(),
+            ReplayPolicy::Prioritized { alpha } => {
+                // Untrained examples first, newest first
+                let mut picked: Vec<usize> = (0..len).rev().filter(|&i| self.losses[i].is_none()).take(batch_size).collect();
+
+                let mut priorities: Vec<f32> = self
+                    .losses
+                    .iter()
+                    .map(|loss| loss.map_or(0.0, |l| (l.max(0.0) + 1e-3).powf(alpha)))
+                    .collect();
+                while picked.len() < batch_size {
+                    let total: f32 = priorities.iter().sum();
+                    if total <= 0.0 || !total.is_finite() {
+                        break;
+                    }
+                    let mut target = rng.gen::<f32>() * total;
+                    let mut choice = len - 1;
+                    for (i, &p) in priorities.iter().enumerate() {
+                        if p > 0.0 && target < p {
+                            choice = i;
+                            break;
+                        }
+                        target -= p;
+                    }
+                    if priorities[choice] <= 0.0 {
+                        // Rounding ran past the end; take the last candidate left
+                        match priorities.iter().rposition(|&p| p > 0.0) {
+                            Some(i) => choice = i,
+                            None => break,
+                        }
+                    }
+                    priorities[choice] = 0.0;
+                    picked.push(choice);
+                }
+                picked
+            }
+        }
+    }
+
+    /// The examples at `indices`
+    pub fn batch(&self, indices: &[usize]) -> Vec<TrainingExample> {
+        indices.iter().map(|&i| self.examples[i].clone()).collect()
+    }
+
+    /// Record the per-example losses of a trained batch, then apply the
+    /// policy's decay and eviction
+    pub fn record(&mut self, indices: &[usize], losses: &[f32]) {
+        for (&i, &loss) in indices.iter().zip(losses) {
+            self.losses[i] = Some(loss);
+        }
+
+        if let ReplayPolicy::WeightDecay { decay, min_weight } = self.policy {
+            for &i in indices {
+                self.examples[i].weight *= decay;
+            }
+            (self.examples, self.losses) = std::mem::take(&mut self.examples)
+                .into_iter()
+                .zip(std::mem::take(&mut self.losses))
+                .filter(|(example, _)| example.weight >= min_weight)
+                .unzip();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn example(i: usize) -> TrainingExample {
+        TrainingExample::new(format!("example {}", i), None)
+    }
+
+    fn inputs(buffer: &ReplayBuffer, indices: &[usize]) -> Vec<String> {
+        buffer.batch(indices).into_iter().map(|e| e.input).collect()
+    }
+
+    #[test]
+    fn test_fifo_keeps_and_trains_newest() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut buffer = ReplayBuffer::new(ReplayPolicy::Fifo, 3);
+        for i in 0..5 {
+            buffer.push(example(i), i + 1, &mut rng);
+        }
+        assert_eq!(buffer.len(), 3);
+        let batch = buffer.sample(2, &mut rng);
+        assert_eq!(inputs(&buffer, &batch), ["example 4", "example 3"]);
+    }
+
+    #[test]
+    fn test_reservoir_is_a_uniform_sample_of_everything_seen() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut counts = [0usize; 20];
+        for _ in 0..500 {
+            let mut buffer = ReplayBuffer::new(ReplayPolicy::Reservoir, 5);
+            for i in 0..20 {
+                buffer.push(example(i), i + 1, &mut rng);
+            }
+            assert_eq!(buffer.len(), 5);
+            for e in buffer.examples() {
+                let i: usize = e.input.trim_start_matches("example ").parse().unwrap();
+                counts[i] += 1;
+            }
+        }
+        // Each example survives with probability 5/20: 125 of 500 runs on average
+        assert!(counts.iter().all(|&c| (75..175).contains(&c)), "{:?}", counts);
+    }
+
+    #[test]
+    fn test_prioritized_prefers_untrained_then_high_loss() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let mut buffer = ReplayBuffer::new(ReplayPolicy::Prioritized { alpha: 1.0 }, 10);
+        for i in 0..4 {
+            buffer.push(example(i), i + 1, &mut rng);
+        }
+        buffer.record(&[0, 1, 2], &[0.0, 0.0, 50.0]);
+        assert_eq!(buffer.sample(1, &mut rng), vec![3]);
+
+        buffer.record(&[3], &[0.0]);
+        let high = (0..100).filter(|_| buffer.sample(1, &mut rng) == vec![2]).count();
+        assert!(high > 90, "{}", high);
+
+        let mut all = buffer.sample(10, &mut rng);
+        all.sort();
+        assert_eq!(all, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_weight_decay_retires_trained_examples() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let policy = ReplayPolicy::WeightDecay {
+            decay: 0.5,
+            min_weight: 0.3,
+        };
+        let mut buffer = ReplayBuffer::new(policy, 10);
+        for i in 0..3 {
+            buffer.push(example(i), i + 1, &mut rng);
+        }
+
+        let batch = buffer.sample(2, &mut rng);
+        buffer.record(&batch, &[1.0, 1.0]);
+        assert_eq!(buffer.examples().map(|e| e.weight).collect::<Vec<_>>(), [1.0, 0.5, 0.5]);
+
+        buffer.record(&[1, 2], &[1.0, 1.0]);
+        assert_eq!(buffer.len(), 1);
+        assert_eq!(buffer.losses().collect::<Vec<_>>(), [None]);
+        assert_eq!(ReplayPolicy::parse("weight_decay").unwrap().name(), "weight_decay");
+    }
+}
@@ -1,12 +1,17 @@
 //! Weight loading from various model formats
 
 use anyhow::{Context, Result};
-use safetensors::SafeTensors;
+use safetensors::tensor::TensorView;
+use safetensors::{Dtype, SafeTensors};
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::Read;
 use std::path::Path;
 use memmap2::Mmap;
+use tokio_util::sync::CancellationToken;
+
+use super::gguf::{self, GgmlType, GgufFile, GgufMetadata, GgufValue, GgufWriter};
+use crate::inference::storage::quantize_rows;
 
 /// Supported weight formats
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -38,6 +43,76 @@ impl WeightFormat {
     }
 }
 
+/// Element type written by `WeightLoader::save_with_options`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SaveDtype {
+    #[default]
+    F32,
+    F16,
+    BF16,
+    /// Int8 matrices with per-row scales in `{name}_scale` (SafeTensors only)
+    Int8,
+}
+
+impl SaveDtype {
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.to_lowercase().as_str() {
+            "f32" | "float32" => Ok(SaveDtype::F32),
+            "f16" | "float16" | "half" => Ok(SaveDtype::F16),
+            "bf16" | "bfloat16" => Ok(SaveDtype::BF16),
+            "int8" | "i8" | "q8" => Ok(SaveDtype::Int8),
+            _ => anyhow::bail!("Unknown dtype: {} (use f32, f16, bf16 or int8)", name),
+        }
+    }
+
+    /// Encode `values` of the given shape; int8 also returns the row scales
+    ///
+    /// 1-D tensors (norms, biases) stay F32 when saving int8.
+    fn encode(self, values: &[f32], shape: &[usize]) -> (Dtype, Vec<u8>, Option<Vec<f32>>) {
+        match self {
+            SaveDtype::F32 => (Dtype::F32, f32_bytes(values), None),
+            SaveDtype::F16 => (
+                Dtype::F16,
+                values.iter().flat_map(|&v| half::f16::from_f32(v).to_le_bytes()).collect(),
+                None,
+            ),
+            SaveDtype::BF16 => (
+                Dtype::BF16,
+                values.iter().flat_map(|&v| half::bf16::from_f32(v).to_le_bytes()).collect(),
+                None,
+            ),
+            SaveDtype::Int8 if shape.len() < 2 => (Dtype::F32, f32_bytes(values), None),
+            SaveDtype::Int8 => {
+                let cols = shape[1..].iter().product();
+                let (quantized, scales) = quantize_rows(values, cols);
+                (Dtype::I8, quantized.into_iter().map(|q| q as u8).collect(), Some(scales))
+            }
+        }
+    }
+}
+
+fn f32_bytes(values: &[f32]) -> Vec<u8> {
+    values.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+/// Names under which `get_embedding_weights` looks for the embedding table
+pub const EMBEDDING_TENSOR_NAMES: &[&str] = &[
+    "transformer.wte.weight",
+    "model.embed_tokens.weight",
+    "embeddings.word_embeddings.weight",
+    "token_embedding.weight",
+    "wte",
+];
+
+/// Options for saving SafeTensors and GGUF files
+#[derive(Debug, Clone, Default)]
+pub struct SaveOptions {
+    /// Down-convert tensors on write (GGUF keeps 1-D tensors such as norms in F32)
+    pub dtype: SaveDtype,
+    /// Free-form string metadata: `__metadata__` in SafeTensors, string keys in GGUF
+    pub metadata: HashMap<String, String>,
+}
+
 /// Weight tensor metadata
 #[derive(Debug, Clone)]
 pub struct TensorInfo {
@@ -54,6 +129,10 @@ pub struct WeightLoader {
     format: WeightFormat,
     tensors: HashMap<String, Vec<f32>>,
     metadata: HashMap<String, TensorInfo>,
+    /// Key/value metadata of a loaded GGUF file
+    gguf_metadata: Option<GgufMetadata>,
+    /// `__metadata__` of a loaded SafeTensors file
+    safetensors_metadata: HashMap<String, String>,
 }
 
 impl WeightLoader {
@@ -63,39 +142,93 @@ impl WeightLoader {
             format,
             tensors: HashMap::new(),
             metadata: HashMap::new(),
+            gguf_metadata: None,
+            safetensors_metadata: HashMap::new(),
         }
     }
 
     /// Load weights from a file
     pub fn load_from_file<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        self.load_from_file_with_progress(path, &CancellationToken::new(), |_, _| {})
+    }
+
+    /// `load_from_file`, calling `on_tensor(done, total)` after each tensor is
+    /// read; once `cancel` fires loading stops with an error
+    pub fn load_from_file_with_progress<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        cancel: &CancellationToken,
+        mut on_tensor: impl FnMut(usize, usize),
+    ) -> Result<()> {
         let path = path.as_ref();
         let format = WeightFormat::from_path(path)?;
 
         match format {
-            WeightFormat::SafeTensors => self.load_safetensors(path),
-            WeightFormat::GGUF => self.load_gguf(path),
+            WeightFormat::SafeTensors => self.load_safetensors(path, cancel, &mut on_tensor),
+            WeightFormat::GGUF => self.load_gguf(path, cancel, &mut on_tensor),
             WeightFormat::Binary => self.load_binary(path),
             WeightFormat::Custom => self.load_custom(path),
+        }?;
+
+        // Single-blob formats are one step
+        if matches!(format, WeightFormat::Binary | WeightFormat::Custom) {
+            on_tensor(1, 1);
         }
+        Ok(())
+    }
+
+    fn check_cancelled(cancel: &CancellationToken, done: usize, total: usize) -> Result<()> {
+        if cancel.is_cancelled() {
+            anyhow::bail!("Weight loading cancelled after {} of {} tensors", done, total);
+        }
+        Ok(())
     }
 
     /// Load from SafeTensors format
-    fn load_safetensors(&mut self, path: &Path) -> Result<()> {
+    fn load_safetensors(
+        &mut self,
+        path: &Path,
+        cancel: &CancellationToken,
+        on_tensor: &mut dyn FnMut(usize, usize),
+    ) -> Result<()> {
         let file = File::open(path)
             .context("Failed to open safetensors file")?;
 
         let mmap = unsafe { Mmap::map(&file)? };
         let tensors = SafeTensors::deserialize(&mmap)?;
+        let (_, header) = SafeTensors::read_metadata(&mmap)?;
+        self.safetensors_metadata = header.metadata().clone().unwrap_or_default();
 
-        for tensor_name in tensors.names() {
+        let names = tensors.names();
+        for (i, tensor_name) in names.iter().enumerate() {
+            Self::check_cancelled(cancel, i, names.len())?;
             let tensor_view = tensors.tensor(tensor_name)?;
 
+            // Row scales of int8 tensors are applied below, not stored
+            if let Some(base) = tensor_name.strip_suffix("_scale") {
+                if tensors.tensor(base).is_ok_and(|t| t.dtype() == Dtype::I8) {
+                    on_tensor(i + 1, names.len());
+                    continue;
+                }
+            }
+
             // Get tensor metadata
             let shape = tensor_view.shape().to_vec();
             let dtype = format!("{:?}", tensor_view.dtype());
 
             // Convert to f32
-            let data = self.convert_to_f32(tensor_view.data(), &dtype)?;
+            let mut data = self.convert_to_f32(tensor_view.data(), &dtype)?;
+            if tensor_view.dtype() == Dtype::I8 {
+                let scale_name = format!("{}_scale", tensor_name);
+                let scales = tensors
+                    .tensor(&scale_name)
+                    .with_context(|| format!("Int8 tensor {} has no {}", tensor_name, scale_name))?;
+                let scales = self.convert_to_f32(scales.data(), &format!("{:?}", scales.dtype()))?;
+                let cols = data.len() / scales.len().max(1);
+                for (row, scale) in data.chunks_mut(cols.max(1)).zip(&scales) {
+                    row.iter_mut().for_each(|v| *v *= scale);
+                }
+            }
 
             // Store tensor
             self.tensors.insert(tensor_name.to_string(), data);
@@ -111,31 +244,67 @@ impl WeightLoader {
                     size: tensor_view.data().len(),
                 },
             );
+            on_tensor(i + 1, names.len());
         }
 
         Ok(())
     }
 
     /// Load from GGUF format (llama.cpp)
-    fn load_gguf(&mut self, path: &Path) -> Result<()> {
-        // GGUF format parsing
-        // This is a simplified version - full GGUF parsing is complex
-        let mut file = File::open(path)?;
-        let mut header = [0u8; 4];
-        file.read_exact(&mut header)?;
+    ///
+    /// Tensors are dequantized to f32 and renamed to HuggingFace names, and
+    /// the Q/K permutation llama.cpp applies to llama models is undone, so
+    /// `Transformer::from_loader` can consume the result directly.
+    fn load_gguf(
+        &mut self,
+        path: &Path,
+        cancel: &CancellationToken,
+        on_tensor: &mut dyn FnMut(usize, usize),
+    ) -> Result<()> {
+        let file = GgufFile::open(path)?;
+
+        // llama.cpp interleaves rotary pairs in Q/K for these architectures
+        let rope_permuted = matches!(file.metadata.architecture(), Some("llama" | "mistral"));
+        let n_heads = file.metadata.arch_u64("attention.head_count").unwrap_or(1) as usize;
+        let n_kv_heads = file
+            .metadata
+            .arch_u64("attention.head_count_kv")
+            .map_or(n_heads, |v| v as usize);
+
+        let total = file.tensors.len();
+        for (i, info) in file.tensors.iter().enumerate() {
+            Self::check_cancelled(cancel, i, total)?;
+            let mut data = file
+                .dequantize(info)
+                .with_context(|| format!("Failed to read GGUF tensor {}", info.name))?;
+            let name = gguf::hf_tensor_name(&info.name);
+            let shape = info.shape();
+
+            if rope_permuted && shape.len() == 2 {
+                if name.ends_with("self_attn.q_proj.weight") {
+                    data = gguf::unpermute_rope_rows(&data, n_heads, shape[1]);
+                } else if name.ends_with("self_attn.k_proj.weight") {
+                    data = gguf::unpermute_rope_rows(&data, n_kv_heads, shape[1]);
+                }
+            }
 
-        // Verify GGUF magic
-        if &header != b"GGUF" {
-            anyhow::bail!("Invalid GGUF file: wrong magic bytes");
+            self.metadata.insert(
+                name.clone(),
+                TensorInfo {
+                    name: name.clone(),
+                    shape,
+                    dtype: format!("{:?}", info.ggml_type),
+                    offset: info.offset as usize,
+                    size: info.ggml_type.byte_size(info.num_elements()).unwrap_or(0),
+                },
+            );
+            self.tensors.insert(name, data);
+            on_tensor(i + 1, total);
         }
 
-        // For now, return error suggesting conversion
-        anyhow::bail!(
-            "GGUF format not yet fully implemented. \
-             Please convert to SafeTensors using: \
-             python -m safetensors.convert --from gguf {}",
-            path.display()
-        )
+        self.gguf_metadata = Some(file.metadata);
+
+        Ok(())
     }
 
     /// Load from raw binary format
@@ -201,6 +370,8 @@ impl WeightLoader {
                 }
                 Ok(result)
             }
+            // Int8 values; `load_safetensors` applies the row scales
+            "I8" => Ok(data.iter().map(|&b| b as i8 as f32).collect()),
             _ => anyhow::bail!("Unsupported dtype: {}", dtype),
         }
     }
@@ -215,6 +386,21 @@ impl WeightLoader {
         self.metadata.get(name)
     }
 
+    /// Architecture, context length, tokenizer etc. of a loaded GGUF file
+    pub fn gguf_metadata(&self) -> Option<&GgufMetadata> {
+        self.gguf_metadata.as_ref()
+    }
+
+    /// Free-form `__metadata__` of a loaded SafeTensors file
+    pub fn safetensors_metadata(&self) -> &HashMap<String, String> {
+        &self.safetensors_metadata
+    }
+
+    /// Metadata to write when saving as GGUF
+    pub fn set_gguf_metadata(&mut self, metadata: GgufMetadata) {
+        self.gguf_metadata = Some(metadata);
+    }
+
     /// Get all tensor names
     pub fn tensor_names(&self) -> Vec<String> {
         self.tensors.keys().cloned().collect()
@@ -223,13 +409,7 @@ impl WeightLoader {
     /// Get embedding weights if they exist
     pub fn get_embedding_weights(&self) -> Option<&Vec<f32>> {
         // Try common embedding layer names
-        for name in &[
-            "transformer.wte.weight",
-            "model.embed_tokens.weight",
-            "embeddings.word_embeddings.weight",
-            "token_embedding.weight",
-            "wte",
-        ] {
+        for name in EMBEDDING_TENSOR_NAMES {
             if let Some(tensor) = self.tensors.get(*name) {
                 return Some(tensor);
             }
@@ -239,6 +419,19 @@ impl WeightLoader {
 
     /// Save weights to file
     pub fn save_to_file<P: AsRef<Path>>(&self, path: P, format: WeightFormat) -> Result<()> {
+        self.save_with_options(path, format, &SaveOptions::default())
+    }
+
+    /// Save weights to file with dtype conversion and metadata
+    ///
+    /// SafeTensors and GGUF files keep the shapes recorded in `TensorInfo`;
+    /// tensors without one are written as 1-D.
+    pub fn save_with_options<P: AsRef<Path>>(
+        &self,
+        path: P,
+        format: WeightFormat,
+        options: &SaveOptions,
+    ) -> Result<()> {
         let path = path.as_ref();
 
         match format {
@@ -256,7 +449,117 @@ impl WeightLoader {
                 }
                 Ok(())
             }
-            _ => anyhow::bail!("Saving to {} format not yet implemented", format.name()),
+            WeightFormat::SafeTensors => self.save_safetensors(path, options),
+            WeightFormat::GGUF => self.save_gguf(path, options),
+        }
+    }
+
+    /// Write a SafeTensors file
+    ///
+    /// The file is written next to `path` and renamed over it, so a
+    /// `MappedSafeTensors` still reading the old file is not disturbed.
+    fn save_safetensors(&self, path: &Path, options: &SaveOptions) -> Result<()> {
+        let mut encoded = Vec::new();
+        for (name, data) in self.sorted_tensors() {
+            let shape = self.shape_of(name, data)?;
+            let (dtype, bytes, scales) = options.dtype.encode(data, &shape);
+            if let Some(scales) = scales {
+                encoded.push((format!("{}_scale", name), Dtype::F32, vec![scales.len()], f32_bytes(&scales)));
+            }
+            encoded.push((name.clone(), dtype, shape, bytes));
+        }
+
+        let views = encoded
+            .iter()
+            .map(|(name, dtype, shape, bytes)| Ok((name.as_str(), TensorView::new(*dtype, shape.clone(), bytes)?)))
+            .collect::<Result<Vec<_>>>()?;
+
+        let metadata = (!options.metadata.is_empty()).then(|| options.metadata.clone());
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        safetensors::serialize_to_file(views, &metadata, Path::new(&tmp))
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        std::fs::rename(&tmp, path).with_context(|| format!("Failed to write {}", path.display()))?;
+
+        Ok(())
+    }
+
+    /// Write a GGUF file with llama.cpp tensor names
+    ///
+    /// Metadata from a loaded (or `set_gguf_metadata`) GGUF file is written
+    /// back, and for llama/mistral the Q/K rows are permuted the way
+    /// llama.cpp expects.
+    fn save_gguf(&self, path: &Path, options: &SaveOptions) -> Result<()> {
+        let mut writer = GgufWriter::new();
+        let mut n_heads = 1;
+        let mut n_kv_heads = 1;
+        let mut rope_permuted = false;
+
+        if let Some(meta) = &self.gguf_metadata {
+            let mut keys: Vec<&String> = meta.keys().collect();
+            keys.sort();
+            for key in keys {
+                writer.add_metadata(key.clone(), meta.get(key).unwrap().clone());
+            }
+            rope_permuted = matches!(meta.architecture(), Some("llama" | "mistral"));
+            n_heads = meta.arch_u64("attention.head_count").unwrap_or(1) as usize;
+            n_kv_heads = meta.arch_u64("attention.head_count_kv").map_or(n_heads, |v| v as usize);
+        }
+
+        let mut extra: Vec<_> = options.metadata.iter().collect();
+        extra.sort();
+        for (key, value) in extra {
+            writer.add_metadata(key.clone(), GgufValue::String(value.clone()));
+        }
+
+        for (name, data) in self.sorted_tensors() {
+            let shape = self.shape_of(name, data)?;
+            let ggml_type = match (options.dtype, shape.len()) {
+                (_, 1) | (SaveDtype::F32, _) => GgmlType::F32,
+                (SaveDtype::F16, _) => GgmlType::F16,
+                (SaveDtype::BF16, _) => GgmlType::BF16,
+                (SaveDtype::Int8, _) => anyhow::bail!("int8 is only supported for SafeTensors"),
+            };
+
+            let permuted;
+            let values = if rope_permuted && shape.len() == 2 && name.ends_with("self_attn.q_proj.weight") {
+                permuted = gguf::permute_rope_rows(data, n_heads, shape[1]);
+                &permuted
+            } else if rope_permuted && shape.len() == 2 && name.ends_with("self_attn.k_proj.weight") {
+                permuted = gguf::permute_rope_rows(data, n_kv_heads, shape[1]);
+                &permuted
+            } else {
+                data
+            };
+
+            writer.add_tensor(gguf::gguf_tensor_name(name), &shape, values, ggml_type)?;
+        }
+
+        writer.write_to_file(path)
+    }
+
+    /// Tensors in name order, so saved files are deterministic
+    fn sorted_tensors(&self) -> Vec<(&String, &Vec<f32>)> {
+        let mut tensors: Vec<_> = self.tensors.iter().collect();
+        tensors.sort_by_key(|(name, _)| *name);
+        tensors
+    }
+
+    /// Recorded shape of a tensor, or 1-D if none was recorded
+    fn shape_of(&self, name: &str, data: &[f32]) -> Result<Vec<usize>> {
+        match self.metadata.get(name) {
+            Some(info) if !info.shape.is_empty() => {
+                if info.shape.iter().product::<usize>() != data.len() {
+                    anyhow::bail!(
+                        "Tensor {} has {} values but recorded shape {:?}",
+                        name,
+                        data.len(),
+                        info.shape
+                    );
+                }
+                Ok(info.shape.clone())
+            }
+            _ => Ok(vec![data.len()]),
         }
     }
 
@@ -275,6 +578,27 @@ impl WeightLoader {
         self.tensors.insert(name, weights);
     }
 
+    /// Insert tensor and record its row-major shape (kept when saving)
+    pub fn insert_tensor_with_shape(&mut self, name: String, shape: Vec<usize>, weights: Vec<f32>) -> Result<()> {
+        if shape.iter().product::<usize>() != weights.len() {
+            anyhow::bail!("Tensor {} has {} values but shape {:?}", name, weights.len(), shape);
+        }
+
+        self.metadata.insert(
+            name.clone(),
+            TensorInfo {
+                name: name.clone(),
+                shape,
+                dtype: "F32".to_string(),
+                offset: 0,
+                size: weights.len() * std::mem::size_of::<f32>(),
+            },
+        );
+        self.tensors.insert(name, weights);
+
+        Ok(())
+    }
+
     /// Load embedding weights from a flat array
     pub fn load_embedding_weights(&mut self, vocab_size: usize, embed_dim: usize, weights: Vec<f32>) -> Result<()> {
         if weights.len() != vocab_size * embed_dim {
@@ -291,57 +615,6 @@ impl WeightLoader {
     }
 }
 
-impl WeightFormat {
-    fn name(&self) -> &'static str {
-        match self {
-            WeightFormat::SafeTensors => "SafeTensors",
-            WeightFormat::GGUF => "GGUF",
-            WeightFormat::Binary => "Binary",
-            WeightFormat::Custom => "Custom",
-        }
-    }
-}
-
-// Add half dependency for f16 conversion
-mod half {
-    #[allow(non_camel_case_types)]
-    pub struct f16 {
-        bits: u16,
-    }
-
-    impl f16 {
-        pub fn from_bits(bits: u16) -> Self {
-            Self { bits }
-        }
-
-        pub fn to_f32(self) -> f32 {
-            // Simple f16 to f32 conversion
-            let sign = (self.bits >> 15) & 0x1;
-            let exp = (self.bits >> 10) & 0x1F;
-            let frac = self.bits & 0x3FF;
-
-            if exp == 0 {
-                // Denormalized or zero
-                let value = (frac as f32) / 1024.0 / 16384.0;
-                if sign == 1 { -value } else { value }
-            } else if exp == 31 {
-                // Inf or NaN
-                if frac == 0 {
-                    if sign == 1 { f32::NEG_INFINITY } else { f32::INFINITY }
-                } else {
-                    f32::NAN
-                }
-            } else {
-                // Normalized
-                let exp_f32 = (exp as i32) - 15 + 127;
-                let frac_f32 = (frac as u32) << 13;
-                let bits = ((sign as u32) << 31) | ((exp_f32 as u32) << 23) | frac_f32;
-                f32::from_bits(bits)
-            }
-        }
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -371,4 +644,136 @@ mod tests {
 
         assert_eq!(loader2.get_tensor("test").unwrap(), &vec![1.0, 2.0, 3.0]);
     }
+
+    #[test]
+    fn test_safetensors_save_keeps_shapes_and_metadata() {
+        let mut loader = WeightLoader::new(WeightFormat::SafeTensors);
+        loader
+            .insert_tensor_with_shape("w".to_string(), vec![2, 3], vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0])
+            .unwrap();
+        loader.insert_tensor("flat".to_string(), vec![0.1, 0.2]);
+        assert!(loader.insert_tensor_with_shape("bad".to_string(), vec![3], vec![1.0]).is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.safetensors");
+        let options = SaveOptions {
+            dtype: SaveDtype::BF16,
+            metadata: HashMap::from([("format".to_string(), "pt".to_string())]),
+        };
+        loader.save_with_options(&path, WeightFormat::SafeTensors, &options).unwrap();
+
+        let bytes = std::fs::read(&path).unwrap();
+        let (_, header) = SafeTensors::read_metadata(&bytes).unwrap();
+        assert_eq!(header.metadata().as_ref().unwrap()["format"], "pt");
+
+        let mut reloaded = WeightLoader::new(WeightFormat::SafeTensors);
+        reloaded.load_from_file(&path).unwrap();
+        assert_eq!(reloaded.safetensors_metadata()["format"], "pt");
+        let info = reloaded.get_metadata("w").unwrap();
+        assert_eq!(info.shape, vec![2, 3]);
+        assert_eq!(info.dtype, "BF16");
+        assert_eq!(reloaded.get_tensor("w").unwrap(), &vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+        assert_eq!(reloaded.get_metadata("flat").unwrap().shape, vec![2]);
+        let flat = reloaded.get_tensor("flat").unwrap();
+        assert!((flat[0] - 0.1).abs() < 1e-3 && (flat[1] - 0.2).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_load_reports_tensors_and_stops_when_cancelled() {
+        let mut loader = WeightLoader::new(WeightFormat::SafeTensors);
+        for name in ["a", "b", "c"] {
+            loader.insert_tensor(name.to_string(), vec![1.0, 2.0]);
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("three.safetensors");
+        loader.save_to_file(&path, WeightFormat::SafeTensors).unwrap();
+
+        let mut progress = Vec::new();
+        let mut reloaded = WeightLoader::new(WeightFormat::SafeTensors);
+        reloaded
+            .load_from_file_with_progress(&path, &CancellationToken::new(), |done, total| progress.push((done, total)))
+            .unwrap();
+        assert_eq!(progress, vec![(1, 3), (2, 3), (3, 3)]);
+
+        let cancel = CancellationToken::new();
+        let mut partial = WeightLoader::new(WeightFormat::SafeTensors);
+        let error = partial
+            .load_from_file_with_progress(&path, &cancel, |done, _| {
+                if done == 2 {
+                    cancel.cancel();
+                }
+            })
+            .unwrap_err();
+        assert!(error.to_string().contains("cancelled after 2 of 3"));
+    }
+
+    #[test]
+    fn test_gguf_save_f16_roundtrip() {
+        let mut loader = WeightLoader::new(WeightFormat::GGUF);
+        loader
+            .insert_tensor_with_shape("model.embed_tokens.weight".to_string(), vec![2, 2], vec![0.5, 1.0, -2.0, 4.0])
+            .unwrap();
+        loader
+            .insert_tensor_with_shape("model.norm.weight".to_string(), vec![2], vec![0.1, 0.3])
+            .unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.gguf");
+        let options = SaveOptions {
+            dtype: SaveDtype::F16,
+            metadata: HashMap::from([("general.name".to_string(), "tiny".to_string())]),
+        };
+        loader.save_with_options(&path, WeightFormat::GGUF, &options).unwrap();
+
+        let gguf = GgufFile::open(&path).unwrap();
+        assert_eq!(gguf.metadata.get("general.name").and_then(GgufValue::as_str), Some("tiny"));
+        let types: HashMap<_, _> = gguf.tensors.iter().map(|t| (t.name.as_str(), t.ggml_type)).collect();
+        assert_eq!(types["token_embd.weight"], GgmlType::F16);
+        // Norms stay F32
+        assert_eq!(types["output_norm.weight"], GgmlType::F32);
+
+        let mut reloaded = WeightLoader::new(WeightFormat::GGUF);
+        reloaded.load_from_file(&path).unwrap();
+        assert_eq!(reloaded.get_embedding_weights().unwrap(), &vec![0.5, 1.0, -2.0, 4.0]);
+        assert_eq!(reloaded.get_tensor("model.norm.weight").unwrap(), &vec![0.1, 0.3]);
+        assert_eq!(reloaded.get_metadata("model.embed_tokens.weight").unwrap().shape, vec![2, 2]);
+    }
+
+    #[test]
+    fn test_gguf_load_renames_and_unpermutes() {
+        use super::super::gguf::{tests::build_gguf, GgmlType, GgufValue};
+
+        // Q projection with 4 rows of length 1, stored in llama.cpp order
+        let q: Vec<u8> = [0.0f32, 1.0, 2.0, 3.0].iter().flat_map(|v| v.to_le_bytes()).collect();
+        let mut q8 = half::f16::from_f32(0.25).to_le_bytes().to_vec();
+        q8.extend((0..32).map(|i| i as u8));
+
+        let bytes = build_gguf(
+            &[
+                ("general.architecture", GgufValue::String("llama".into())),
+                ("llama.attention.head_count", GgufValue::U32(1)),
+                ("llama.context_length", GgufValue::U32(128)),
+            ],
+            &[
+                ("blk.0.attn_q.weight", vec![1, 4], GgmlType::F32, q),
+                ("token_embd.weight", vec![32], GgmlType::Q8_0, q8),
+            ],
+        );
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tiny.gguf");
+        std::fs::write(&path, bytes).unwrap();
+
+        let mut loader = WeightLoader::new(WeightFormat::GGUF);
+        loader.load_from_file(&path).unwrap();
+
+        // One head of head_dim 4: GGUF rows [0, 1, 2, 3] are HF rows [0, 2, 1, 3]
+        assert_eq!(
+            loader.get_tensor("model.layers.0.self_attn.q_proj.weight").unwrap(),
+            &vec![0.0, 2.0, 1.0, 3.0]
+        );
+        let embd = loader.get_embedding_weights().unwrap();
+        assert_eq!(embd[4], 1.0);
+        assert_eq!(loader.get_metadata("model.embed_tokens.weight").unwrap().dtype, "Q8_0");
+        assert_eq!(loader.gguf_metadata().unwrap().context_length(), Some(128));
+    }
 }